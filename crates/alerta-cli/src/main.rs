@@ -1,6 +1,18 @@
-use std::process;
+#[cfg(test)]
+mod tests;
 
-use alerta::{Answer, ButtonPreset, Error, Icon, Theme};
+use std::{
+    fmt::Write as _,
+    io::{self, IsTerminal as _},
+    path::PathBuf,
+    process,
+    time::Duration,
+};
+
+use alerta::{
+    AnsiMode, Answer, Builder, ButtonOrder, ButtonPreset, DragMode, Error, ErrorKind, Icon,
+    TextDirection, Theme, WindowType, WrapMode,
+};
 use larpa::{
     Command,
     types::{Color, PrintVersion},
@@ -11,33 +23,211 @@ use larpa::{
 ///
 /// Alerta will use the following exit status codes to communicate the result of the dialog:
 /// - 0-N: Indicates that the button with this index was clicked to close the dialog (0 being the
-///   leftmost button, etc.).
+///   leftmost button, etc.). For buttons added with `--button`, the label is also printed.
 /// - 64: Indicates an error in the provided command-line arguments.
 /// - 50: The dialog window was closed by other means than the displayed buttons (for example,
 ///   Alt+F4 or ESC).
+/// - 254: The dialog was closed automatically because the `--timeout` expired.
+/// - 253: The message was copied to the clipboard with Ctrl+Shift+C, which also closed the dialog.
 /// - 100: An error occurred while displaying the dialog.
 /// - 101: A panic occurred (this is a bug in Alerta, please file an issue).
+/// - 102: No X server is available (`DISPLAY` is unset, or connecting to the X server failed).
+///
+/// The answer is also printed as the last line of the output: `button=N` for the button with index
+/// N, `closed`, `timed-out` or `copied`.
 #[derive(Command)]
 struct Args {
-    /// The message to display in the dialog.
-    message: String,
+    /// The message to display in the dialog. If it is `-` or omitted, the message is read from
+    /// stdin instead (when omitted, only if stdin isn't a terminal).
+    message: Option<String>,
 
     /// The window title.
     #[larpa(name = "--title")]
     title: Option<String>,
 
-    /// The icon to display next to the message. [choices: info, warning, error, question]
+    /// A secondary text to display below the message, in a smaller font.
+    #[larpa(name = "--detail")]
+    detail: Option<String>,
+
+    /// A longer text, like a traceback, to display in a collapsible "Show details" section.
+    #[larpa(name = "--details")]
+    details: Option<String>,
+
+    /// The icon to display next to the message. [choices: info, warning, error, question, none]
     #[larpa(name = "--icon", default = "info")]
     icon: Icon,
 
-    /// The set of buttons to display. [choices: close, ok, okcancel, retrycancel, yesno, yesnocancel]
+    /// Path to a PNG or QOI image to display instead of the built-in icon.
+    #[larpa(name = "--icon-file")]
+    icon_file: Option<PathBuf>,
+
+    /// The set of buttons to display.
+    /// [choices: abortretryignore, close, ok, okcancel, retrycancel, savedontsavecancel, yesno,
+    /// yesnocancel]
     #[larpa(name = "--buttons", default = "close")]
     buttons: ButtonPreset,
 
-    /// The theme to use. [choices: light, dark]
+    /// Add a button with a custom label, instead of the `--buttons` preset. Can be repeated to
+    /// add more buttons, from left to right.
+    #[larpa(name = "--button")]
+    button: Vec<String>,
+
+    /// The theme to use. [choices: light, dark, system]
     #[larpa(name = "--theme")]
     theme: Option<Theme>,
 
+    /// Switch between light and dark theme when the desktop's preference changes.
+    #[larpa(name = "--follow-system-theme", flag)]
+    follow_system_theme: bool,
+
+    /// Display a text input field with this placeholder text. The entered text is printed to
+    /// stdout.
+    #[larpa(name = "--input")]
+    input: Option<String>,
+
+    /// Mask the characters typed into the `--input` field.
+    #[larpa(name = "--password", flag)]
+    password: bool,
+
+    /// The base direction of the message text. [choices: auto, ltr, rtl]
+    #[larpa(name = "--text-direction", default = "auto")]
+    text_direction: TextDirection,
+
+    /// Lay out the buttons from right to left.
+    #[larpa(name = "--mirror-buttons", flag)]
+    mirror_buttons: bool,
+
+    /// Where to display affirmative buttons like "OK".
+    /// [choices: platform, affirmative-left, affirmative-right]
+    #[larpa(name = "--button-order", default = "affirmative-left")]
+    button_order: ButtonOrder,
+
+    /// The minimum width of each button, in pixels.
+    #[larpa(name = "--min-button-width", default = "64")]
+    min_button_width: u32,
+
+    /// Make all buttons as wide as the widest one, instead of filling the row.
+    #[larpa(name = "--equal-button-widths", flag)]
+    equal_button_widths: bool,
+
+    /// Where to break the lines of the message. [choices: word, char, none]
+    #[larpa(name = "--wrap", default = "word")]
+    wrap: WrapMode,
+
+    /// Display the message in a monospace font, without wrapping it.
+    #[larpa(name = "--monospace", flag)]
+    monospace: bool,
+
+    /// The distance between tab stops in the message, in spaces.
+    #[larpa(name = "--tab-width", default = "4")]
+    tab_width: u32,
+
+    /// How to display ANSI escape sequences in the message. [choices: strip, render]
+    #[larpa(name = "--ansi", default = "strip")]
+    ansi: AnsiMode,
+
+    /// Display `**bold**` and `*italic*` markup in the message.
+    #[larpa(name = "--markup", flag)]
+    markup: bool,
+
+    /// Make the URLs in the message clickable.
+    #[larpa(name = "--detect-links", flag)]
+    detect_links: bool,
+
+    /// Add a "Copy" button that copies the message to the clipboard.
+    #[larpa(name = "--copy-button", flag)]
+    copy_button: bool,
+
+    /// Don't let Ctrl+Shift+C copy the message and close the dialog.
+    #[larpa(name = "--no-copy-shortcut", flag)]
+    no_copy_shortcut: bool,
+
+    /// Limit the dialog height to this many pixels, making long messages scrollable.
+    #[larpa(name = "--max-height")]
+    max_height: Option<u32>,
+
+    /// Show at most this many lines of the message, cutting off the rest.
+    #[larpa(name = "--max-lines")]
+    max_lines: Option<usize>,
+
+    /// Allow resizing the dialog window.
+    #[larpa(name = "--resizable", flag)]
+    resizable: bool,
+
+    /// Where the dialog can be grabbed to move it. [choices: anywhere, title, disabled]
+    #[larpa(name = "--drag", default = "anywhere")]
+    drag: DragMode,
+
+    /// Window size and position in pixels, as `WxH+X+Y`. Either part may be left out, and `-`
+    /// offsets are measured from the right or bottom edge of the screen.
+    #[larpa(name = "--geometry")]
+    geometry: Option<String>,
+
+    /// Show the dialog where the last dialog with the same key was closed.
+    #[larpa(name = "--remember-position")]
+    remember_position: Option<String>,
+
+    /// Ask the window manager not to draw a title bar and border around the dialog.
+    #[larpa(name = "--undecorated", flag)]
+    undecorated: bool,
+
+    /// What kind of window to tell the window manager the dialog is.
+    /// [choices: dialog, normal, utility, notification]
+    #[larpa(name = "--window-type", default = "dialog")]
+    window_type: WindowType,
+
+    /// Display the title as a header in the dialog (the default with `--undecorated`).
+    #[larpa(name = "--title-in-body", flag)]
+    title_in_body: bool,
+
+    /// Round the corners of the dialog window with this radius in pixels.
+    #[larpa(name = "--corner-radius")]
+    corner_radius: Option<u32>,
+
+    /// Make the dialog translucent, from 0 (invisible) to 1 (opaque). Needs a compositing manager.
+    #[larpa(name = "--opacity")]
+    opacity: Option<f32>,
+
+    /// Don't ask the window manager to focus the dialog when it appears.
+    #[larpa(name = "--no-focus", flag)]
+    no_focus: bool,
+
+    /// Don't give the keyboard focus back to the previously focused window when closing.
+    #[larpa(name = "--no-restore-focus", flag)]
+    no_restore_focus: bool,
+
+    /// The X server to display the dialog on, instead of the one in `DISPLAY`.
+    #[larpa(name = "--display")]
+    display: Option<String>,
+
+    /// Play the alert sound matching the `--icon` when the dialog is displayed.
+    #[larpa(name = "--sound", flag)]
+    sound: bool,
+
+    /// Highlight the dialog's taskbar entry until it is focused.
+    #[larpa(name = "--urgent", flag)]
+    urgent: bool,
+
+    /// Limit the dialog width to this many pixels (600 by default), wrapping the message to fit.
+    #[larpa(name = "--max-width")]
+    max_width: Option<u32>,
+
+    /// Use this system font family instead of the built-in font.
+    #[larpa(name = "--font")]
+    font: Option<String>,
+
+    /// Font size in pixels, for use with `--font`.
+    #[larpa(name = "--font-size", default = "18")]
+    font_size: f32,
+
+    /// Scale the dialog by this factor (like 1.5), instead of using the `Xft.dpi` setting.
+    #[larpa(name = "--scale")]
+    scale: Option<f32>,
+
+    /// Close the dialog automatically after this many seconds.
+    #[larpa(name = "--timeout")]
+    timeout: Option<u64>,
+
     /// Whether to use ANSI colors in console output. [choices: always, auto, never]
     #[larpa(name = "--color", default)]
     _color: Color,
@@ -52,30 +242,205 @@ fn main() {
         Ok(code) => process::exit(code),
         Err(e) => {
             eprintln!("error: {e}");
-            process::exit(100);
+            match e.kind() {
+                ErrorKind::NoDisplay | ErrorKind::ConnectionFailed => process::exit(102),
+                _ => process::exit(100),
+            }
         }
     }
 }
 
 fn run() -> Result<i32, Error> {
     let args = Args::from_args();
+    let custom_buttons = !args.button.is_empty();
+    let Some(b) = builder(args)? else {
+        return Ok(64);
+    };
+    let res = b.show_result()?;
+    let label = res.button_label.filter(|_| custom_buttons);
+    let (stdout, exit_status) = output(res.answer, label);
+    print!("{stdout}");
+    Ok(exit_status)
+}
 
+/// Creates the dialog described by `args`.
+///
+/// Returns `None` after printing an error if the arguments are invalid.
+fn builder(args: Args) -> Result<Option<Builder>, Error> {
     let mut b = alerta::alerta()
-        .message(args.message)
         .icon(args.icon)
-        .button_preset(args.buttons);
+        .button_preset(args.buttons)
+        .text_direction(args.text_direction)
+        .wrap(args.wrap)
+        .tab_width(args.tab_width)
+        .ansi(args.ansi)
+        .button_order(args.button_order)
+        .min_button_width(args.min_button_width)
+        .draggable(args.drag)
+        .window_type(args.window_type);
+    b = match args.message.as_deref() {
+        Some("-") => b.message_from_file("/dev/stdin")?,
+        Some(message) => b.message(message),
+        None if !io::stdin().is_terminal() => b.message_from_file("/dev/stdin")?,
+        None => {
+            eprintln!("error: no message given (pass it as an argument, or pipe it to stdin)");
+            return Ok(None);
+        }
+    };
+    if !args.button.is_empty() {
+        b = b.buttons(args.button);
+    }
     if let Some(title) = args.title {
         b = b.title(title);
     }
+    if let Some(text) = args.detail {
+        b = b.detail(text);
+    }
+    if let Some(text) = args.details {
+        b = b.expandable_details(text);
+    }
+    if let Some(path) = args.icon_file {
+        b = b.icon_file(path);
+    }
     if let Some(theme) = args.theme {
         b = b.theme(theme);
     }
+    if args.follow_system_theme {
+        b = b.follow_system_theme(true);
+    }
+    if let Some(placeholder) = args.input {
+        b = b.input(placeholder);
+    }
+    if args.password {
+        b = b.password();
+    }
+    if args.mirror_buttons {
+        b = b.mirror_buttons(true);
+    }
+    if args.equal_button_widths {
+        b = b.equal_button_widths(true);
+    }
+    if args.markup {
+        b = b.markup(true);
+    }
+    if args.detect_links {
+        b = b.detect_links(true);
+    }
+    if args.monospace {
+        b = b.monospace(true);
+    }
+    if args.copy_button {
+        b = b.add_copy_button();
+    }
+    if args.no_copy_shortcut {
+        b = b.copy_shortcut(false);
+    }
+    if let Some(px) = args.max_height {
+        b = b.max_height(px);
+    }
+    if let Some(n) = args.max_lines {
+        b = b.max_lines(n);
+    }
+    if let Some(px) = args.max_width {
+        b = b.max_width(px);
+    }
+    if args.resizable {
+        b = b.resizable(true);
+    }
+    if let Some(spec) = args.geometry {
+        b = match b.geometry(&spec) {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("error: {e}");
+                return Ok(None);
+            }
+        };
+    }
+    if let Some(key) = args.remember_position {
+        b = b.remember_position(key);
+    }
+    if args.undecorated {
+        b = b.decorated(false);
+    }
+    if args.title_in_body {
+        b = b.show_title_in_body(true);
+    }
+    if let Some(px) = args.corner_radius {
+        b = b.corner_radius(px);
+    }
+    if let Some(alpha) = args.opacity {
+        b = b.opacity(alpha);
+    }
+    if args.no_focus {
+        b = b.grab_focus(false);
+    }
+    if args.no_restore_focus {
+        b = b.restore_focus(false);
+    }
+    if args.sound {
+        b = b.sound(true);
+    }
+    if args.urgent {
+        b = b.urgent(true);
+    }
+    if let Some(display) = args.display {
+        b = b.display(display);
+    }
+    if let Some(family) = args.font {
+        b = b.font(family, args.font_size);
+    }
+    if let Some(factor) = args.scale {
+        b = b.scale(factor);
+    }
+    if let Some(secs) = args.timeout {
+        b = b.timeout(Duration::from_secs(secs));
+    }
+
+    Ok(Some(b))
+}
 
-    let ans = b.show()?;
+/// Returns what to print to stdout for the answer, and the exit status.
+///
+/// `button_label` is the label of the pressed button, if it is to be printed.
+fn output(ans: Answer, button_label: Option<String>) -> (String, i32) {
+    let mut stdout = String::new();
+    let summary = match &ans {
+        Answer::Closed(_) => "closed".to_string(),
+        Answer::TimedOut => "timed-out".to_string(),
+        Answer::CopiedAndClosed => "copied".to_string(),
+        Answer::Button(button)
+        | Answer::Input { button, .. }
+        | Answer::Selected { button, .. }
+        | Answer::MultiSelected { button, .. } => format!("button={button}"),
+        _ => "closed".to_string(),
+    };
     let exit_status = match ans {
-        Answer::Closed => 50,
-        Answer::Button(i) => i as i32,
+        Answer::Closed(_) => 50,
+        Answer::TimedOut => 254,
+        Answer::CopiedAndClosed => 253,
+        Answer::Input { button, text } => {
+            writeln!(stdout, "{text}").unwrap();
+            button as i32
+        }
+        Answer::Button(i) => {
+            if let Some(label) = button_label {
+                writeln!(stdout, "{label}").unwrap();
+            }
+            i as i32
+        }
+        Answer::Selected { index, button } => {
+            writeln!(stdout, "{index}").unwrap();
+            button as i32
+        }
+        Answer::MultiSelected { indices, button } => {
+            for index in indices {
+                writeln!(stdout, "{index}").unwrap();
+            }
+            button as i32
+        }
+        _ => 50,
     };
+    writeln!(stdout, "{summary}").unwrap();
 
-    Ok(exit_status)
+    (stdout, exit_status)
 }
@@ -0,0 +1,67 @@
+use alerta::HeadlessEvent;
+use larpa::Command as _;
+
+use crate::{Args, builder, output};
+
+/// Runs the dialog described by `args` with the simulated user actions of `script`, and returns
+/// what the CLI prints to stdout and its exit status.
+fn run_headless(args: &[&str], script: &[HeadlessEvent]) -> (String, i32) {
+    let args = Args::try_from_iter(["alerta"].iter().chain(args)).unwrap();
+    let buttons = args.button.clone();
+    let answer = builder(args)
+        .unwrap()
+        .unwrap()
+        .show_headless(script)
+        .unwrap();
+    let label = answer.label(&buttons);
+    output(answer, label)
+}
+
+#[test]
+fn custom_button_label() {
+    let args = ["--button", "&Save", "--button", "&Discard", "Save changes?"];
+    // The label is printed as displayed, without the `&` that marks the mnemonic.
+    assert_eq!(
+        run_headless(&args, &[HeadlessEvent::Mnemonic('d')]),
+        ("Discard\nbutton=1\n".to_string(), 1)
+    );
+    assert_eq!(
+        run_headless(&args, &[HeadlessEvent::ClickButton(0)]),
+        ("Save\nbutton=0\n".to_string(), 0)
+    );
+}
+
+#[test]
+fn exit_status() {
+    use alerta::{Answer, HeadlessKey};
+
+    assert_eq!(
+        run_headless(&["Hello"], &[HeadlessEvent::Key(HeadlessKey::Escape)]),
+        ("closed\n".to_string(), 50)
+    );
+    assert_eq!(
+        run_headless(
+            &["--input", "Name", "Hello"],
+            &[
+                HeadlessEvent::Type("Alerta".into()),
+                HeadlessEvent::Key(HeadlessKey::Enter)
+            ]
+        ),
+        ("Alerta\nbutton=0\n".to_string(), 0)
+    );
+    assert_eq!(
+        output(Answer::TimedOut, None),
+        ("timed-out\n".to_string(), 254)
+    );
+    assert_eq!(
+        output(Answer::CopiedAndClosed, None),
+        ("copied\n".to_string(), 253)
+    );
+}
+
+#[test]
+fn invalid_geometry() {
+    // Like other invalid arguments, this exits with status 64 before displaying anything.
+    let args = Args::try_from_iter(["alerta", "--geometry", "big", "Hello"]).unwrap();
+    assert!(builder(args).unwrap().is_none());
+}
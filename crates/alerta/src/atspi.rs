@@ -0,0 +1,899 @@
+//! A minimal AT-SPI implementation, so that screen readers like Orca can announce the dialog.
+//!
+//! The dialog is exposed on the accessibility bus as an application with a single alert window,
+//! which contains the message as a label and a push button for every dialog button. Only the
+//! parts of the `Accessible` interface that screen readers query to announce a window are
+//! implemented, and the exposed tree doesn't follow later changes of the dialog.
+//!
+//! None of the dependencies speaks D-Bus, so the wire protocol is implemented here, as far as it
+//! is needed for this.
+
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    mem,
+    net::Shutdown,
+    os::{
+        linux::net::SocketAddrExt as _,
+        unix::{
+            ffi::OsStrExt as _,
+            fs::MetadataExt as _,
+            net::{SocketAddr, UnixStream},
+        },
+    },
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use log::{debug, warn};
+
+use crate::{Error, error::err};
+
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+
+/// Message flag telling the recipient not to reply.
+const NO_REPLY_EXPECTED: u8 = 1;
+
+const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+const REGISTRY: &str = "org.a11y.atspi.Registry";
+const ACCESSIBLE: &str = "org.a11y.atspi.Accessible";
+const APPLICATION: &str = "org.a11y.atspi.Application";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+
+// Values of `AtspiRole`.
+const ROLE_ALERT: u32 = 2;
+const ROLE_LABEL: u32 = 29;
+const ROLE_PUSH_BUTTON: u32 = 43;
+const ROLE_APPLICATION: u32 = 75;
+
+// Bit indices of `AtspiStateType`.
+const STATE_ACTIVE: u32 = 1;
+const STATE_ENABLED: u32 = 8;
+const STATE_FOCUSABLE: u32 = 11;
+const STATE_FOCUSED: u32 = 12;
+const STATE_SENSITIVE: u32 = 24;
+const STATE_SHOWING: u32 = 25;
+const STATE_VISIBLE: u32 = 30;
+
+/// What the dialog is announced as.
+pub(crate) struct Dialog {
+    pub(crate) title: String,
+    pub(crate) message: String,
+    /// Button labels, without mnemonic markers.
+    pub(crate) buttons: Vec<String>,
+    /// Index of the button that has the keyboard focus at first.
+    pub(crate) focused: Option<usize>,
+}
+
+/// The registration of a dialog on the accessibility bus, which is removed when this is dropped.
+pub(crate) struct Registration {
+    state: Arc<Mutex<State>>,
+}
+
+/// The connection of a [`Registration`], shared with the thread that serves it.
+enum State {
+    Connecting,
+    Connected(UnixStream),
+    /// The registration was dropped, so a connection established later isn't used.
+    Closed,
+}
+
+impl Registration {
+    /// Registers `dialog` on the accessibility bus, and announces it.
+    ///
+    /// This happens in the background, and failures are only logged, since the dialog works
+    /// without it.
+    pub(crate) fn start(dialog: Dialog) -> Self {
+        let state = Arc::new(Mutex::new(State::Connecting));
+        let shared = state.clone();
+        let res = thread::Builder::new()
+            .name("alerta-atspi".into())
+            .spawn(move || {
+                let res = connect().and_then(|stream| {
+                    let writer = stream.try_clone().map_err(err)?;
+                    {
+                        let mut state = shared.lock().unwrap();
+                        if let State::Closed = *state {
+                            return Ok(());
+                        }
+                        *state = State::Connected(writer);
+                    }
+                    serve(&stream, &dialog).map_err(err)
+                });
+                if let Err(e) = res {
+                    warn!("failed to register the dialog for screen readers: {e}");
+                }
+            });
+        if let Err(e) = res {
+            warn!("failed to start the accessibility thread: {e}");
+        }
+        Self { state }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        // Disconnecting removes the dialog from the registry, and ends the thread.
+        let state = mem::replace(&mut *self.state.lock().unwrap(), State::Closed);
+        if let State::Connected(stream) = state {
+            stream.shutdown(Shutdown::Both).ok();
+        }
+    }
+}
+
+/// Connects and authenticates to the accessibility bus.
+fn connect() -> Result<UnixStream, Error> {
+    let address = match env::var("AT_SPI_BUS_ADDRESS") {
+        Ok(address) if !address.is_empty() => address,
+        _ => bus_address()?,
+    };
+    debug!("accessibility bus address: {address}");
+    let mut stream = address
+        .split(';')
+        .find_map(|address| {
+            let params = address.strip_prefix("unix:")?;
+            params.split(',').find_map(|param| {
+                let addr = match param.split_once('=')? {
+                    ("path", path) => {
+                        SocketAddr::from_pathname(OsStr::from_bytes(&unescape(path))).ok()?
+                    }
+                    ("abstract", name) => SocketAddr::from_abstract_name(unescape(name)).ok()?,
+                    _ => return None,
+                };
+                UnixStream::connect_addr(&addr).ok()
+            })
+        })
+        .ok_or_else(|| Error::new(format!("failed to connect to the bus at '{address}'")))?;
+
+    // The EXTERNAL mechanism authenticates with the credentials of the socket, and takes the
+    // user ID as hex-encoded ASCII digits.
+    let uid = fs::metadata("/proc/self").map_err(err)?.uid().to_string();
+    let hex = uid.bytes().map(|b| format!("{b:02x}")).collect::<String>();
+    write!(stream, "\0AUTH EXTERNAL {hex}\r\n").map_err(err)?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).map_err(err)?;
+    if !line.starts_with("OK ") {
+        return Err(Error::new(format!(
+            "the bus rejected the authentication: {}",
+            line.trim_end()
+        )));
+    }
+    stream.write_all(b"BEGIN\r\n").map_err(err)?;
+    Ok(stream)
+}
+
+/// Asks the session bus for the address of the accessibility bus.
+fn bus_address() -> Result<String, Error> {
+    let output = Command::new("dbus-send")
+        .args([
+            "--session",
+            "--print-reply=literal",
+            "--dest=org.a11y.Bus",
+            "/org/a11y/bus",
+            "org.a11y.Bus.GetAddress",
+        ])
+        .output()
+        .map_err(|e| Error::new(format!("failed to run dbus-send: {e}")))?;
+    if !output.status.success() {
+        return Err(Error::new(format!(
+            "the accessibility bus isn't running: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Decodes the `%xx` escapes of a D-Bus address value.
+fn unescape(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        let escaped = (b == b'%')
+            .then(|| rest.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(b) => {
+                bytes.push(b);
+                rest = &rest[2..];
+            }
+            None => bytes.push(b),
+        }
+    }
+    bytes
+}
+
+/// Registers `dialog` with the registry, and then answers the requests of screen readers until
+/// the connection is closed.
+pub(crate) fn serve(stream: &UnixStream, dialog: &Dialog) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut conn = Connection {
+        stream,
+        serial: 0,
+        name: String::new(),
+    };
+    let hello = conn.call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "Hello",
+        "",
+        &[],
+    )?;
+    let mut embed = None;
+    loop {
+        let msg = match Message::read(&mut reader) {
+            Ok(msg) => msg,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match msg.kind {
+            METHOD_RETURN if msg.reply_serial == Some(hello) => {
+                conn.name = msg.body().str()?.to_string();
+                let mut body = Writer::default();
+                body.reference(&conn.name, ROOT_PATH);
+                embed = Some(conn.call(
+                    REGISTRY,
+                    ROOT_PATH,
+                    "org.a11y.atspi.Socket",
+                    "Embed",
+                    "(so)",
+                    &body.buf,
+                )?);
+            }
+            METHOD_RETURN if msg.reply_serial.is_some() && msg.reply_serial == embed => {
+                let (name, path) = msg.body().reference()?;
+                debug!("registered for screen readers, with the desktop {name} {path}");
+                announce(&mut conn, dialog)?;
+            }
+            ERROR if msg.reply_serial == Some(hello) || msg.reply_serial == embed => {
+                let text = msg.body().str().unwrap_or_default().to_string();
+                return Err(io::Error::other(format!(
+                    "{}: {text}",
+                    msg.error_name.unwrap_or_default()
+                )));
+            }
+            METHOD_CALL => handle(&mut conn, dialog, &msg)?,
+            _ => {}
+        }
+    }
+}
+
+/// Sends the events that make screen readers announce the dialog.
+fn announce(conn: &mut Connection<'_>, dialog: &Dialog) -> io::Result<()> {
+    let mut body = Writer::default();
+    body.event("", 0, |w| {
+        w.sig("s");
+        w.str(&dialog.title);
+    });
+    conn.signal(
+        &Node::Dialog.path(),
+        "org.a11y.atspi.Event.Window",
+        "Activate",
+        &body.buf,
+    )?;
+    if let Some(i) = dialog.focused {
+        let mut body = Writer::default();
+        body.event("focused", 1, |w| {
+            w.sig("i");
+            w.i32(0);
+        });
+        conn.signal(
+            &Node::Button(i).path(),
+            "org.a11y.atspi.Event.Object",
+            "StateChanged",
+            &body.buf,
+        )?;
+    }
+    Ok(())
+}
+
+/// An accessible object of the dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Node {
+    /// The application, which is the root of the tree.
+    App,
+    Dialog,
+    Message,
+    Button(usize),
+}
+
+impl Node {
+    fn parse(path: &str, dialog: &Dialog) -> Option<Self> {
+        let name = match path {
+            ROOT_PATH => return Some(Node::App),
+            _ => path.strip_prefix("/org/a11y/atspi/accessible/")?,
+        };
+        match name {
+            "dialog" => Some(Node::Dialog),
+            "message" => Some(Node::Message),
+            _ => {
+                let i = name.strip_prefix("button")?.parse().ok()?;
+                (i < dialog.buttons.len()).then_some(Node::Button(i))
+            }
+        }
+    }
+
+    fn path(self) -> String {
+        match self {
+            Node::App => ROOT_PATH.into(),
+            Node::Dialog => "/org/a11y/atspi/accessible/dialog".into(),
+            Node::Message => "/org/a11y/atspi/accessible/message".into(),
+            Node::Button(i) => format!("/org/a11y/atspi/accessible/button{i}"),
+        }
+    }
+
+    fn children(self, dialog: &Dialog) -> Vec<Node> {
+        match self {
+            Node::App => vec![Node::Dialog],
+            Node::Dialog => [Node::Message]
+                .into_iter()
+                .chain((0..dialog.buttons.len()).map(Node::Button))
+                .collect(),
+            Node::Message | Node::Button(_) => Vec::new(),
+        }
+    }
+
+    fn parent(self) -> Option<Node> {
+        match self {
+            Node::App => None,
+            Node::Dialog => Some(Node::App),
+            Node::Message | Node::Button(_) => Some(Node::Dialog),
+        }
+    }
+
+    fn index_in_parent(self) -> i32 {
+        match self {
+            Node::App => -1,
+            Node::Dialog | Node::Message => 0,
+            Node::Button(i) => i as i32 + 1,
+        }
+    }
+
+    fn role(self) -> (u32, &'static str) {
+        match self {
+            Node::App => (ROLE_APPLICATION, "application"),
+            Node::Dialog => (ROLE_ALERT, "alert"),
+            Node::Message => (ROLE_LABEL, "label"),
+            Node::Button(_) => (ROLE_PUSH_BUTTON, "push button"),
+        }
+    }
+
+    fn name(self, dialog: &Dialog) -> String {
+        match self {
+            Node::App => env::args()
+                .next()
+                .and_then(|arg0| Some(arg0.rsplit('/').next()?.to_string()))
+                .unwrap_or_else(|| "alerta".into()),
+            Node::Dialog => dialog.title.clone(),
+            Node::Message => dialog.message.clone(),
+            Node::Button(i) => dialog.buttons[i].clone(),
+        }
+    }
+
+    fn states(self, dialog: &Dialog) -> u64 {
+        let visible = [STATE_ENABLED, STATE_SENSITIVE, STATE_SHOWING, STATE_VISIBLE];
+        let states: &[u32] = match self {
+            Node::App => &[],
+            Node::Dialog => &[STATE_ACTIVE],
+            Node::Message => &[],
+            Node::Button(i) if dialog.focused == Some(i) => &[STATE_FOCUSABLE, STATE_FOCUSED],
+            Node::Button(_) => &[STATE_FOCUSABLE],
+        };
+        let visible = if self == Node::App { &[][..] } else { &visible };
+        states
+            .iter()
+            .chain(visible)
+            .fold(0, |bits, state| bits | 1 << state)
+    }
+
+    fn interfaces(self) -> &'static [&'static str] {
+        match self {
+            Node::App => &[ACCESSIBLE, APPLICATION],
+            _ => &[ACCESSIBLE],
+        }
+    }
+}
+
+/// Answers the method call `msg`.
+fn handle(conn: &mut Connection<'_>, dialog: &Dialog, msg: &Message) -> io::Result<()> {
+    let Some(node) = msg
+        .path
+        .as_deref()
+        .and_then(|path| Node::parse(path, dialog))
+    else {
+        return conn.error(msg, "org.freedesktop.DBus.Error.UnknownObject");
+    };
+    let name = conn.name.clone();
+    let mut w = Writer::default();
+    let interface = msg.interface.as_deref().unwrap_or_default();
+    let signature = match (interface, msg.member.as_deref().unwrap_or_default()) {
+        (PROPERTIES, "Get") => {
+            let mut body = msg.body();
+            let (interface, property) = (body.str()?, body.str()?);
+            if !property_value(&mut w, &name, dialog, node, interface, property) {
+                return conn.error(msg, "org.freedesktop.DBus.Error.UnknownProperty");
+            }
+            "v"
+        }
+        (PROPERTIES, "GetAll") => {
+            let interface = msg.body().str()?.to_string();
+            let properties: &[&str] = match interface.as_str() {
+                ACCESSIBLE => &["Name", "Description", "Parent", "ChildCount", "Locale"],
+                APPLICATION if node == Node::App => &["ToolkitName", "Version", "AtspiVersion"],
+                _ => &[],
+            };
+            w.array(8, |w| {
+                for property in properties {
+                    w.align(8);
+                    w.str(property);
+                    property_value(w, &name, dialog, node, &interface, property);
+                }
+            });
+            "a{sv}"
+        }
+        // The registry assigns the application an ID, which isn't needed.
+        (PROPERTIES, "Set") => "",
+        (ACCESSIBLE, "GetChildAtIndex") => {
+            let i = msg.body().i32()?;
+            let children = node.children(dialog);
+            match usize::try_from(i).ok().and_then(|i| children.get(i)) {
+                Some(child) => w.reference(&name, &child.path()),
+                None => w.reference("", "/org/a11y/atspi/null"),
+            }
+            "(so)"
+        }
+        (ACCESSIBLE, "GetChildren") => {
+            w.array(8, |w| {
+                for child in node.children(dialog) {
+                    w.reference(&name, &child.path());
+                }
+            });
+            "a(so)"
+        }
+        (ACCESSIBLE, "GetIndexInParent") => {
+            w.i32(node.index_in_parent());
+            "i"
+        }
+        (ACCESSIBLE, "GetRelationSet") => {
+            w.array(8, |_| {});
+            "a(ua(so))"
+        }
+        (ACCESSIBLE, "GetRole") => {
+            w.u32(node.role().0);
+            "u"
+        }
+        (ACCESSIBLE, "GetRoleName" | "GetLocalizedRoleName") => {
+            w.str(node.role().1);
+            "s"
+        }
+        (ACCESSIBLE, "GetState") => {
+            let states = node.states(dialog);
+            w.array(4, |w| {
+                w.u32(states as u32);
+                w.u32((states >> 32) as u32);
+            });
+            "au"
+        }
+        (ACCESSIBLE, "GetAttributes") => {
+            w.array(8, |_| {});
+            "a{ss}"
+        }
+        (ACCESSIBLE, "GetApplication") => {
+            w.reference(&name, ROOT_PATH);
+            "(so)"
+        }
+        (ACCESSIBLE, "GetInterfaces") => {
+            w.array(4, |w| {
+                for interface in node.interfaces() {
+                    w.str(interface);
+                }
+            });
+            "as"
+        }
+        (APPLICATION, "GetLocale") if node == Node::App => {
+            w.str("");
+            "s"
+        }
+        ("org.freedesktop.DBus.Peer", "Ping") => "",
+        _ => return conn.error(msg, "org.freedesktop.DBus.Error.UnknownMethod"),
+    };
+    conn.reply(msg, signature, &w.buf)
+}
+
+/// Writes the value of `property` of `node` as a variant, or returns `false` if it doesn't have
+/// that property.
+fn property_value(
+    w: &mut Writer,
+    name: &str,
+    dialog: &Dialog,
+    node: Node,
+    interface: &str,
+    property: &str,
+) -> bool {
+    match (interface, property) {
+        (ACCESSIBLE, "Name") => {
+            w.sig("s");
+            w.str(&node.name(dialog));
+        }
+        (ACCESSIBLE, "Description" | "Locale" | "AccessibleId" | "HelpText") => {
+            w.sig("s");
+            w.str("");
+        }
+        (ACCESSIBLE, "Parent") => {
+            w.sig("(so)");
+            match node.parent() {
+                Some(parent) => w.reference(name, &parent.path()),
+                None => w.reference(REGISTRY, ROOT_PATH),
+            }
+        }
+        (ACCESSIBLE, "ChildCount") => {
+            w.sig("i");
+            w.i32(node.children(dialog).len() as i32);
+        }
+        (APPLICATION, "ToolkitName") if node == Node::App => {
+            w.sig("s");
+            w.str("alerta");
+        }
+        (APPLICATION, "Version") if node == Node::App => {
+            w.sig("s");
+            w.str(env!("CARGO_PKG_VERSION"));
+        }
+        (APPLICATION, "AtspiVersion") if node == Node::App => {
+            w.sig("s");
+            w.str("2.1");
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// The sending half of a bus connection.
+struct Connection<'a> {
+    stream: &'a UnixStream,
+    /// Serial number of the last message sent.
+    serial: u32,
+    /// The unique bus name of the connection, once the bus has assigned it.
+    name: String,
+}
+
+impl Connection<'_> {
+    fn send(
+        &mut self,
+        kind: u8,
+        flags: u8,
+        fields: &[Field<'_>],
+        signature: &str,
+        body: &[u8],
+    ) -> io::Result<u32> {
+        self.serial += 1;
+        let msg = encode(kind, flags, self.serial, fields, signature, body);
+        (&mut &*self.stream).write_all(&msg)?;
+        Ok(self.serial)
+    }
+
+    /// Calls a method, and returns the serial number that its reply refers to.
+    fn call(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        signature: &str,
+        body: &[u8],
+    ) -> io::Result<u32> {
+        let fields = [
+            Field::Destination(destination),
+            Field::Path(path),
+            Field::Interface(interface),
+            Field::Member(member),
+        ];
+        self.send(METHOD_CALL, 0, &fields, signature, body)
+    }
+
+    fn signal(&mut self, path: &str, interface: &str, member: &str, body: &[u8]) -> io::Result<()> {
+        let fields = [
+            Field::Path(path),
+            Field::Interface(interface),
+            Field::Member(member),
+        ];
+        self.send(SIGNAL, NO_REPLY_EXPECTED, &fields, "siiva{sv}", body)?;
+        Ok(())
+    }
+
+    fn reply(&mut self, call: &Message, signature: &str, body: &[u8]) -> io::Result<()> {
+        let mut fields = vec![Field::ReplySerial(call.serial)];
+        fields.extend(call.sender.as_deref().map(Field::Destination));
+        self.send(METHOD_RETURN, NO_REPLY_EXPECTED, &fields, signature, body)?;
+        Ok(())
+    }
+
+    fn error(&mut self, call: &Message, name: &str) -> io::Result<()> {
+        let mut fields = vec![Field::ErrorName(name), Field::ReplySerial(call.serial)];
+        fields.extend(call.sender.as_deref().map(Field::Destination));
+        self.send(ERROR, NO_REPLY_EXPECTED, &fields, "", &[])?;
+        Ok(())
+    }
+}
+
+/// A header field of an outgoing message.
+pub(crate) enum Field<'a> {
+    Path(&'a str),
+    Interface(&'a str),
+    Member(&'a str),
+    ErrorName(&'a str),
+    ReplySerial(u32),
+    Destination(&'a str),
+}
+
+/// Encodes a message in little-endian byte order.
+pub(crate) fn encode(
+    kind: u8,
+    flags: u8,
+    serial: u32,
+    fields: &[Field<'_>],
+    signature: &str,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.buf.extend([b'l', kind, flags, 1]);
+    w.u32(body.len() as u32);
+    w.u32(serial);
+    w.array(8, |w| {
+        for field in fields {
+            w.align(8);
+            match *field {
+                Field::Path(path) => {
+                    w.buf.push(1);
+                    w.sig("o");
+                    w.str(path);
+                }
+                Field::Interface(s) | Field::Member(s) | Field::ErrorName(s) => {
+                    let code = match field {
+                        Field::Interface(_) => 2,
+                        Field::Member(_) => 3,
+                        _ => 4,
+                    };
+                    w.buf.push(code);
+                    w.sig("s");
+                    w.str(s);
+                }
+                Field::ReplySerial(serial) => {
+                    w.buf.push(5);
+                    w.sig("u");
+                    w.u32(serial);
+                }
+                Field::Destination(name) => {
+                    w.buf.push(6);
+                    w.sig("s");
+                    w.str(name);
+                }
+            }
+        }
+        if !signature.is_empty() {
+            w.align(8);
+            w.buf.push(8);
+            w.sig("g");
+            w.sig(signature);
+        }
+    });
+    w.align(8);
+    w.buf.extend_from_slice(body);
+    w.buf
+}
+
+/// Writes values in the D-Bus wire format, in little-endian byte order.
+///
+/// Alignment is relative to the start of the buffer, so a body has to be written into its own
+/// writer.
+#[derive(Default)]
+pub(crate) struct Writer {
+    pub(crate) buf: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn align(&mut self, n: usize) {
+        self.buf.resize(self.buf.len().next_multiple_of(n), 0);
+    }
+
+    pub(crate) fn u32(&mut self, v: u32) {
+        self.align(4);
+        self.buf.extend(v.to_le_bytes());
+    }
+
+    pub(crate) fn i32(&mut self, v: i32) {
+        self.u32(v as u32);
+    }
+
+    /// Writes a string or an object path.
+    pub(crate) fn str(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.buf.extend(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    pub(crate) fn sig(&mut self, s: &str) {
+        self.buf.push(s.len() as u8);
+        self.buf.extend(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    /// Writes an array whose elements are written by `f`, and are aligned to `align` bytes.
+    pub(crate) fn array(&mut self, align: usize, f: impl FnOnce(&mut Self)) {
+        self.u32(0);
+        let len_pos = self.buf.len() - 4;
+        self.align(align);
+        let start = self.buf.len();
+        f(self);
+        let len = (self.buf.len() - start) as u32;
+        self.buf[len_pos..len_pos + 4].copy_from_slice(&len.to_le_bytes());
+    }
+
+    /// Writes a reference to an accessible object, as a `(so)` struct of its bus name and path.
+    pub(crate) fn reference(&mut self, name: &str, path: &str) {
+        self.align(8);
+        self.str(name);
+        self.str(path);
+    }
+
+    /// Writes the body of an event signal, with the extra data written by `data` as a variant.
+    fn event(&mut self, detail: &str, detail1: i32, data: impl FnOnce(&mut Self)) {
+        self.str(detail);
+        self.i32(detail1);
+        self.i32(0);
+        data(self);
+        self.array(8, |_| {});
+    }
+}
+
+/// Reads values in the D-Bus wire format.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn invalid() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "invalid D-Bus message")
+    }
+
+    fn align(&mut self, n: usize) {
+        self.pos = self.pos.next_multiple_of(n);
+    }
+
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(Self::invalid)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> io::Result<u32> {
+        self.align(4);
+        let bytes = self.bytes(4)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    pub(crate) fn i32(&mut self) -> io::Result<i32> {
+        Ok(self.u32()? as i32)
+    }
+
+    /// Reads a string or an object path.
+    pub(crate) fn str(&mut self) -> io::Result<&'a str> {
+        let len = self.u32()? as usize;
+        let s = self.bytes(len + 1)?;
+        std::str::from_utf8(&s[..len]).map_err(|_| Self::invalid())
+    }
+
+    pub(crate) fn sig(&mut self) -> io::Result<&'a str> {
+        let len = usize::from(self.u8()?);
+        let s = self.bytes(len + 1)?;
+        std::str::from_utf8(&s[..len]).map_err(|_| Self::invalid())
+    }
+
+    /// Reads a `(so)` reference to an accessible object.
+    pub(crate) fn reference(&mut self) -> io::Result<(&'a str, &'a str)> {
+        self.align(8);
+        Ok((self.str()?, self.str()?))
+    }
+}
+
+/// A received message.
+pub(crate) struct Message {
+    pub(crate) kind: u8,
+    pub(crate) serial: u32,
+    pub(crate) path: Option<String>,
+    pub(crate) interface: Option<String>,
+    pub(crate) member: Option<String>,
+    pub(crate) error_name: Option<String>,
+    pub(crate) reply_serial: Option<u32>,
+    pub(crate) sender: Option<String>,
+    pub(crate) signature: String,
+    big_endian: bool,
+    body: Vec<u8>,
+}
+
+impl Message {
+    pub(crate) fn read(r: &mut impl Read) -> io::Result<Message> {
+        let mut data = vec![0; 16];
+        r.read_exact(&mut data)?;
+        let big_endian = match data[0] {
+            b'l' => false,
+            b'B' => true,
+            _ => return Err(Reader::invalid()),
+        };
+        let mut header = Reader {
+            data: &data,
+            pos: 4,
+            big_endian,
+        };
+        let (body_len, serial, fields_len) = (header.u32()?, header.u32()?, header.u32()?);
+        let body_start = (16 + fields_len as usize).next_multiple_of(8);
+        data.resize(body_start + body_len as usize, 0);
+        r.read_exact(&mut data[16..])?;
+
+        let mut msg = Message {
+            kind: data[1],
+            serial,
+            path: None,
+            interface: None,
+            member: None,
+            error_name: None,
+            reply_serial: None,
+            sender: None,
+            signature: String::new(),
+            big_endian,
+            body: data[body_start..].to_vec(),
+        };
+        let mut fields = Reader {
+            data: &data[..16 + fields_len as usize],
+            pos: 16,
+            big_endian,
+        };
+        while fields.pos < fields.data.len() {
+            fields.align(8);
+            let code = fields.u8()?;
+            match (code, fields.sig()?) {
+                (1, "o") => msg.path = Some(fields.str()?.into()),
+                (2, "s") => msg.interface = Some(fields.str()?.into()),
+                (3, "s") => msg.member = Some(fields.str()?.into()),
+                (4, "s") => msg.error_name = Some(fields.str()?.into()),
+                (5, "u") => msg.reply_serial = Some(fields.u32()?),
+                (7, "s") => msg.sender = Some(fields.str()?.into()),
+                (8, "g") => msg.signature = fields.sig()?.into(),
+                (_, "s" | "o") => drop(fields.str()?),
+                (_, "g") => drop(fields.sig()?),
+                (_, "u") => drop(fields.u32()?),
+                _ => return Err(Reader::invalid()),
+            }
+        }
+        Ok(msg)
+    }
+
+    /// Returns a reader for the body, which starts aligned to 8 bytes like in the message.
+    pub(crate) fn body(&self) -> Reader<'_> {
+        Reader {
+            data: &self.body,
+            pos: 0,
+            big_endian: self.big_endian,
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Keeping track of open dialogs for [`Builder::dedup_key`], so that identical dialogs aren't
+//! stacked on top of each other.
+//!
+//! [`Builder::dedup_key`]: crate::Builder::dedup_key
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+};
+
+use crate::x11;
+
+/// The dialogs of this process that are open, with their keys.
+static OPEN: Mutex<Vec<(String, Arc<Entry>)>> = Mutex::new(Vec::new());
+
+#[derive(Default)]
+struct Entry {
+    /// Set when another dialog with the same key was shown.
+    raise: AtomicBool,
+    /// Wakes up the event loop, once the dialog window exists.
+    waker: Mutex<Option<x11::Waker>>,
+}
+
+/// An open dialog with a key, which is unregistered when this is dropped.
+pub(crate) struct Registration {
+    entry: Arc<Entry>,
+}
+
+impl Registration {
+    /// Registers an open dialog with `key`.
+    ///
+    /// If a dialog with the same key is open already, it is asked to raise its window, and `None`
+    /// is returned.
+    pub(crate) fn new(key: &str) -> Option<Self> {
+        let mut open = OPEN.lock().unwrap();
+        if let Some((_, entry)) = open.iter().find(|(k, _)| k == key) {
+            entry.raise.store(true, Ordering::Relaxed);
+            if let Some(waker) = &*entry.waker.lock().unwrap() {
+                waker.wake();
+            }
+            return None;
+        }
+        let entry = Arc::new(Entry::default());
+        open.push((key.to_string(), entry.clone()));
+        Some(Self { entry })
+    }
+
+    /// Sets the waker that is used when another dialog with the same key is shown.
+    pub(crate) fn set_waker(&self, waker: x11::Waker) {
+        *self.entry.waker.lock().unwrap() = Some(waker);
+    }
+
+    /// Returns whether another dialog with the same key was shown since the last call, so that
+    /// this one should raise its window.
+    pub(crate) fn take_raise(&self) -> bool {
+        self.entry.raise.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        OPEN.lock()
+            .unwrap()
+            .retain(|(_, entry)| !Arc::ptr_eq(entry, &self.entry));
+        // The waker keeps the connection to the X server open.
+        *self.entry.waker.lock().unwrap() = None;
+    }
+}
@@ -1,31 +1,102 @@
 use std::fmt;
 
+use x11rb::errors::{ConnectionError, ReplyError, ReplyOrIdError};
+
 /// The main error type of this library.
-pub struct Error(Box<dyn std::error::Error + Send + Sync>);
+pub struct Error {
+    kind: ErrorKind,
+    inner: Box<dyn std::error::Error + Send + Sync>,
+}
+
+/// The category of an [`Error`], for reacting to specific kinds of failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// No X server is configured, because the `DISPLAY` environment variable is unset or invalid.
+    NoDisplay,
+    /// Connecting to the X server failed, for example because it isn't running or refused the
+    /// connection.
+    ConnectionFailed,
+    /// Communicating with the X server failed after the connection was established.
+    Protocol,
+    /// The connection to the X server was lost, for example because the server exited or the
+    /// network connection to it broke.
+    Disconnected,
+    /// Any other error, like invalid settings or an icon that couldn't be loaded.
+    Other,
+}
 
 impl Error {
     pub(crate) fn new(inner: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
-        Self(inner.into())
+        Self::with_kind(ErrorKind::Other, inner)
+    }
+
+    pub(crate) fn with_kind(
+        kind: ErrorKind,
+        inner: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Self {
+            kind,
+            inner: inner.into(),
+        }
+    }
+
+    /// Returns whether the connection to the X server was lost.
+    pub(crate) fn is_disconnect(&self) -> bool {
+        self.kind == ErrorKind::Disconnected
+    }
+
+    /// Returns the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
     }
 }
 
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        self.0.source()
+        self.inner.source()
     }
 }
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        self.inner.fmt(f)
     }
 }
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        self.inner.fmt(f)
     }
 }
 
+/// Wraps `inner`, classifying errors of the X11 connection as [`ErrorKind::Protocol`], or as
+/// [`ErrorKind::Disconnected`] if reading from or writing to the connection failed.
 pub(crate) fn err(inner: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Error {
-    Error::new(inner)
+    let inner = inner.into();
+    let connection_error = if let Some(e) = inner.downcast_ref::<ConnectionError>() {
+        Some(e)
+    } else if let Some(e) = inner.downcast_ref::<ReplyError>() {
+        match e {
+            ReplyError::ConnectionError(e) => Some(e),
+            ReplyError::X11Error(_) => None,
+        }
+    } else if let Some(e) = inner.downcast_ref::<ReplyOrIdError>() {
+        match e {
+            ReplyOrIdError::ConnectionError(e) => Some(e),
+            ReplyOrIdError::IdsExhausted | ReplyOrIdError::X11Error(_) => None,
+        }
+    } else {
+        None
+    };
+    let kind = match connection_error {
+        Some(ConnectionError::IoError(_)) => ErrorKind::Disconnected,
+        _ if inner.is::<ConnectionError>()
+            || inner.is::<ReplyError>()
+            || inner.is::<ReplyOrIdError>() =>
+        {
+            ErrorKind::Protocol
+        }
+        _ => ErrorKind::Other,
+    };
+    Error::with_kind(kind, inner)
 }
@@ -0,0 +1,256 @@
+//! Support for displaying dialogs without blocking the calling thread.
+
+use std::{
+    panic,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{Answer, Builder, Error, error::err, x11};
+
+/// The background thread running the event loop of a dialog.
+type DialogThread = JoinHandle<Result<Answer, Error>>;
+
+/// A handle to a dialog running on a background thread.
+///
+/// Returned by [`Builder::spawn`].
+///
+/// Dropping the handle does *not* close the dialog; it will keep running until the user closes it.
+pub struct DialogHandle {
+    thread: DialogThread,
+    shared: Arc<Shared>,
+}
+
+/// State shared between the event loop of a dialog and the handle controlling it.
+#[derive(Default)]
+pub(crate) struct Shared {
+    /// Set to close the dialog with [`Answer::Closed`]
+    /// (for [`CloseReason::External`][crate::CloseReason::External]).
+    pub(crate) close: AtomicBool,
+    /// Changes to the dialog that haven't been displayed yet.
+    pub(crate) update: Mutex<Update>,
+    answer: Mutex<Option<Answer>>,
+    /// Wakes up the event loop, once the dialog window exists.
+    pub(crate) waker: Mutex<Option<x11::Waker>>,
+    /// The X11 window ID of the dialog, while its window is mapped.
+    pub(crate) window_id: Mutex<Option<u32>>,
+}
+
+impl Shared {
+    /// Sets the `close` flag, and wakes up the event loop to close the dialog.
+    fn request_close(&self) {
+        self.close.store(true, Ordering::Relaxed);
+        self.wake();
+    }
+
+    /// Changes the pending update, and wakes up the event loop to display it.
+    fn update(&self, f: impl FnOnce(&mut Update)) {
+        f(&mut self.update.lock().unwrap());
+        self.wake();
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = &*self.waker.lock().unwrap() {
+            waker.wake();
+        }
+    }
+}
+
+/// Changes requested through a [`ProgressDialog`].
+#[derive(Default)]
+pub(crate) struct Update {
+    pub(crate) progress: Option<f32>,
+    pub(crate) message: Option<String>,
+}
+
+/// Runs the event loop of the dialog on a new thread.
+fn spawn(builder: Builder) -> Result<(DialogThread, Arc<Shared>), Error> {
+    let shared = Arc::new(Shared::default());
+    let thread = thread::Builder::new()
+        .name("alerta".into())
+        .spawn({
+            let shared = shared.clone();
+            move || {
+                let outcome = builder.run(Some(&shared), false);
+                // The waker keeps the connection to the X server open.
+                *shared.waker.lock().unwrap() = None;
+                *shared.window_id.lock().unwrap() = None;
+                let answer = outcome?.result.answer;
+                *shared.answer.lock().unwrap() = Some(answer.clone());
+                Ok(answer)
+            }
+        })
+        .map_err(err)?;
+    Ok((thread, shared))
+}
+
+fn join(thread: DialogThread) -> Result<Answer, Error> {
+    thread
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+impl DialogHandle {
+    pub(crate) fn spawn(builder: Builder) -> Result<Self, Error> {
+        let (thread, shared) = spawn(builder)?;
+        Ok(Self { thread, shared })
+    }
+
+    /// Returns the [`Answer`] if the dialog has been closed, or `None` if it is still open.
+    ///
+    /// If displaying the dialog failed, this will also return `None`.
+    /// Use [`DialogHandle::is_finished`] to detect that case, and [`DialogHandle::answer`] to
+    /// retrieve the error.
+    pub fn try_answer(&self) -> Option<Answer> {
+        self.shared.answer.lock().unwrap().clone()
+    }
+
+    /// Returns whether the dialog has been closed (or failed to be displayed).
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Returns the X11 window ID (XID) of the dialog window, for use with tools like `xdotool`
+    /// or `wmctrl`.
+    ///
+    /// Returns `None` until the window has been mapped, and again once the dialog is closed.
+    pub fn window_id(&self) -> Option<u32> {
+        *self.shared.window_id.lock().unwrap()
+    }
+
+    /// Blocks until the dialog is closed, and returns the [`Answer`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error that occurred while displaying the dialog.
+    pub fn answer(self) -> Result<Answer, Error> {
+        join(self.thread)
+    }
+
+    /// Requests the dialog to be closed.
+    ///
+    /// The dialog will resolve with [`Answer::Closed`]
+    /// (for [`CloseReason::External`][crate::CloseReason::External]), unless the user already
+    /// answered it. This can be called from any thread, and takes effect right away.
+    pub fn close(&self) {
+        self.shared.request_close();
+    }
+}
+
+/// A handle to a dialog with a progress bar, running on a background thread.
+///
+/// Returned by [`Builder::progress`].
+///
+/// Dropping the handle closes the dialog.
+pub struct ProgressDialog {
+    /// `None` once the dialog has been [finished][Self::finish].
+    thread: Option<DialogThread>,
+    shared: Arc<Shared>,
+}
+
+impl ProgressDialog {
+    pub(crate) fn spawn(builder: Builder) -> Result<Self, Error> {
+        let (thread, shared) = spawn(builder)?;
+        Ok(Self {
+            thread: Some(thread),
+            shared,
+        })
+    }
+
+    /// Sets the completed fraction of the progress bar, from 0.0 (empty) to 1.0 (full).
+    ///
+    /// Values outside of that range are clamped.
+    pub fn set_progress(&self, fraction: f32) {
+        self.shared
+            .update(|update| update.progress = Some(fraction));
+    }
+
+    /// Replaces the message displayed above the progress bar.
+    ///
+    /// The window keeps its size; a message that doesn't fit anymore becomes scrollable.
+    pub fn set_message(&self, text: &str) {
+        self.shared
+            .update(|update| update.message = Some(text.to_string()));
+    }
+
+    /// Returns the [`Answer`] if the dialog has been closed, or `None` if it is still open.
+    ///
+    /// This can be used to detect when the user closes the dialog or clicks one of its buttons,
+    /// for example to cancel the operation whose progress is displayed.
+    pub fn try_answer(&self) -> Option<Answer> {
+        self.shared.answer.lock().unwrap().clone()
+    }
+
+    /// Returns whether the dialog has been closed (or failed to be displayed).
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Closes the dialog, and returns its [`Answer`].
+    ///
+    /// The answer is [`Answer::Closed`]
+    /// (for [`CloseReason::External`][crate::CloseReason::External]), unless the user already
+    /// answered the dialog.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error that occurred while displaying the dialog.
+    pub fn finish(mut self) -> Result<Answer, Error> {
+        self.shared.request_close();
+        join(self.thread.take().unwrap())
+    }
+}
+
+impl Drop for ProgressDialog {
+    fn drop(&mut self) {
+        self.shared.request_close();
+    }
+}
+
+/// A handle to a dialog with a busy spinner, running on a background thread.
+///
+/// Returned by [`Builder::busy`].
+///
+/// Dropping the handle closes the dialog.
+pub struct BusyDialog {
+    inner: ProgressDialog,
+}
+
+impl BusyDialog {
+    pub(crate) fn spawn(builder: Builder) -> Result<Self, Error> {
+        ProgressDialog::spawn(builder).map(|inner| Self { inner })
+    }
+
+    /// Replaces the message displayed above the spinner.
+    ///
+    /// The window keeps its size; a message that doesn't fit anymore becomes scrollable.
+    pub fn set_message(&self, text: &str) {
+        self.inner.set_message(text);
+    }
+
+    /// Returns the [`Answer`] if the dialog has been closed, or `None` if it is still open.
+    pub fn try_answer(&self) -> Option<Answer> {
+        self.inner.try_answer()
+    }
+
+    /// Returns whether the dialog has been closed (or failed to be displayed).
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+
+    /// Closes the dialog, and returns its [`Answer`].
+    ///
+    /// The answer is [`Answer::Closed`]
+    /// (for [`CloseReason::External`][crate::CloseReason::External]), unless the user already
+    /// answered the dialog.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error that occurred while displaying the dialog.
+    pub fn close(self) -> Result<Answer, Error> {
+        self.inner.finish()
+    }
+}
@@ -0,0 +1,129 @@
+//! Running dialogs without an X server, driven by scripted input.
+
+use crate::{
+    Answer, Builder, CursorPos, Error, Keysym, Modifiers, MouseButton, WindowEvent, ui::Ui,
+};
+
+/// A simulated user action, for [`Builder::show_headless`].
+///
+/// Positions are in pixels, relative to the top-left corner of the dialog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeadlessEvent {
+    /// Moves the mouse cursor to the given position.
+    MoveCursor { x: i16, y: i16 },
+    /// Presses and releases the left mouse button at the current cursor position.
+    Click,
+    /// Moves the mouse cursor to the center of the dialog button with the given index, then
+    /// clicks it.
+    ///
+    /// The index is the one reported in [`Answer::Button`]. The
+    /// [copy button][Builder::add_copy_button] comes after the other buttons.
+    ClickButton(usize),
+    /// Presses a key.
+    Key(HeadlessKey),
+    /// Presses a key for every character of the text.
+    Type(String),
+    /// Presses the given letter while holding Alt, to activate a button by its mnemonic (see
+    /// [`Builder::buttons`]).
+    Mnemonic(char),
+    /// Closes the window, like clicking the close button in its title bar.
+    Close,
+}
+
+/// A key to press with [`HeadlessEvent::Key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeadlessKey {
+    Enter,
+    Escape,
+    Tab,
+    /// Shift+Tab.
+    BackTab,
+    Backspace,
+    Up,
+    Down,
+    /// A key that types a character.
+    Char(char),
+}
+
+/// Runs the dialog of `builder` without displaying it, feeding the [`Ui`] the events of `script`.
+pub(crate) fn run(builder: Builder, script: &[HeadlessEvent]) -> Result<Answer, Error> {
+    let buttons = builder.button_labels();
+    let options = builder.ui_options()?;
+    let mut ui = Ui::new(
+        builder.resolved_icon(),
+        // There is no desktop to detect the theme of, so `Theme::System` looks like the default.
+        builder.explicit_theme().unwrap_or_default(),
+        builder.message.as_deref().unwrap_or_default(),
+        &buttons,
+        options,
+    );
+
+    let key = |sym| WindowEvent::KeyPress(sym, Modifiers::default());
+    let click = || {
+        [
+            WindowEvent::ButtonPress(MouseButton::Left),
+            WindowEvent::ButtonRelease(MouseButton::Left),
+        ]
+    };
+    for event in script {
+        let events = match event {
+            HeadlessEvent::MoveCursor { x, y } => {
+                vec![WindowEvent::CursorMove(CursorPos { x: *x, y: *y })]
+            }
+            HeadlessEvent::Click => click().into(),
+            HeadlessEvent::ClickButton(index) => {
+                let bounds = ui.button_bounds();
+                let rect = bounds.get(*index).ok_or_else(|| {
+                    Error::new(format!(
+                        "button index {index} is out of range for {} buttons",
+                        bounds.len()
+                    ))
+                })?;
+                let center = rect.center();
+                let pos = CursorPos {
+                    x: center.x as i16,
+                    y: center.y as i16,
+                };
+                let mut events = vec![WindowEvent::CursorMove(pos)];
+                events.extend(click());
+                events
+            }
+            HeadlessEvent::Key(k) => vec![key(match *k {
+                HeadlessKey::Enter => Keysym::RETURN,
+                HeadlessKey::Escape => Keysym::ESCAPE,
+                HeadlessKey::Tab => Keysym::TAB,
+                HeadlessKey::BackTab => Keysym::ISO_LEFT_TAB,
+                HeadlessKey::Backspace => Keysym::BACKSPACE,
+                HeadlessKey::Up => Keysym::UP,
+                HeadlessKey::Down => Keysym::DOWN,
+                HeadlessKey::Char(c) => Keysym::from_char(c),
+            })],
+            HeadlessEvent::Type(text) => text.chars().map(|c| key(Keysym::from_char(c))).collect(),
+            HeadlessEvent::Mnemonic(c) => {
+                let alt = Modifiers {
+                    bits: Modifiers::ALT,
+                };
+                vec![
+                    key(Keysym::ALT_L),
+                    WindowEvent::KeyPress(Keysym::from_char(*c), alt),
+                ]
+            }
+            HeadlessEvent::Close => vec![WindowEvent::CloseRequested],
+        };
+        for event in events {
+            builder.observe_event(&event);
+            let answer = ui.process_event(event);
+            if let Some(index) = ui.take_callback_button() {
+                builder.run_callback(index);
+            }
+            if let Some(answer) = answer {
+                return Ok(answer);
+            }
+        }
+    }
+    Err(Error::new(
+        "the script ended before the dialog was answered",
+    ))
+}
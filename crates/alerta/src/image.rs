@@ -0,0 +1,159 @@
+//! Decoding of icon images.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use png::{ColorType, Transformations};
+use rapid_qoi::Qoi;
+use raqote::DrawTarget;
+
+use crate::{Error, error::err};
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+const QOI_MAGIC: &[u8] = b"qoif";
+
+/// A user-provided image.
+#[derive(Clone)]
+pub(crate) enum Source {
+    /// A PNG or QOI file on disk.
+    File(PathBuf),
+    /// Non-premultiplied RGBA pixels.
+    Rgba {
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    },
+    /// Premultiplied ARGB pixels, taken from a [`DrawTarget`].
+    Argb {
+        width: i32,
+        height: i32,
+        data: Vec<u32>,
+    },
+}
+
+impl Source {
+    pub(crate) fn load(&self) -> Result<DrawTarget, Error> {
+        match self {
+            Source::File(path) => load(path),
+            Source::Rgba {
+                width,
+                height,
+                data,
+            } => {
+                self.check()?;
+                Ok(from_rgba(*width, *height, data))
+            }
+            Source::Argb {
+                width,
+                height,
+                data,
+            } => Ok(DrawTarget::from_vec(*width, *height, data.clone())),
+        }
+    }
+
+    /// Checks that the file exists, or that the pixel data has the right size, without decoding
+    /// the image.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        match self {
+            Source::File(path) => fs::metadata(path)
+                .map(|_| ())
+                .map_err(|e| Error::new(format!("failed to load icon '{}': {e}", path.display()))),
+            Source::Rgba {
+                width,
+                height,
+                data,
+            } => {
+                let expected = u64::from(*width) * u64::from(*height) * 4;
+                if data.len() as u64 != expected {
+                    return Err(Error::new(format!(
+                        "invalid RGBA icon data: expected {expected} bytes for a {width}x{height} \
+                         image, got {}",
+                        data.len()
+                    )));
+                }
+                Ok(())
+            }
+            Source::Argb { .. } => Ok(()),
+        }
+    }
+}
+
+/// Reads and decodes the PNG or QOI image at `path`.
+pub(crate) fn load(path: &Path) -> Result<DrawTarget, Error> {
+    fs::read(path)
+        .map_err(err)
+        .and_then(|src| decode(&src))
+        .map_err(|e| Error::new(format!("failed to load icon '{}': {e}", path.display())))
+}
+
+/// Decodes a PNG or QOI image, detecting the format from its magic bytes.
+pub(crate) fn decode(src: &[u8]) -> Result<DrawTarget, Error> {
+    if src.starts_with(PNG_MAGIC) {
+        decode_png(src)
+    } else if src.starts_with(QOI_MAGIC) {
+        decode_qoi(src)
+    } else {
+        Err(Error::new("unsupported image format (expected PNG or QOI)"))
+    }
+}
+
+fn decode_qoi(src: &[u8]) -> Result<DrawTarget, Error> {
+    let mut qoi = Qoi::decode_header(src).map_err(|e| Error::new(format!("{e:?}")))?;
+    qoi.colors = rapid_qoi::Colors::Rgba;
+
+    let mut rgba = vec![0; qoi.decoded_size()];
+    Qoi::decode(src, &mut rgba).map_err(|e| Error::new(format!("{e:?}")))?;
+
+    Ok(from_rgba(qoi.width, qoi.height, &rgba))
+}
+
+fn decode_png(src: &[u8]) -> Result<DrawTarget, Error> {
+    let mut decoder = png::Decoder::new(src);
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(err)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(err)?;
+    buf.truncate(info.buffer_size());
+
+    // Bring everything into RGBA format.
+    let rgba = match info.color_type {
+        ColorType::Rgba => buf,
+        ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        ColorType::Grayscale => buf.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        ColorType::Indexed => {
+            return Err(Error::new("indexed PNG was not expanded by the decoder"));
+        }
+    };
+
+    Ok(from_rgba(info.width, info.height, &rgba))
+}
+
+/// Creates a [`DrawTarget`] from non-premultiplied RGBA pixels.
+///
+/// `rgba` must contain exactly `width * height * 4` bytes.
+pub(crate) fn from_rgba(width: u32, height: u32, rgba: &[u8]) -> DrawTarget {
+    assert_eq!(rgba.len() as u64, u64::from(width) * u64::from(height) * 4);
+
+    // RGBA -> ARGB and premultiply.
+    let pixels = rgba
+        .chunks_exact(4)
+        .map(|p| {
+            let [mut r, mut g, mut b, a] = [p[0], p[1], p[2], p[3]].map(u32::from);
+            r = r * a / 255;
+            g = g * a / 255;
+            b = b * a / 255;
+            (a << 24) | (r << 16) | (g << 8) | b
+        })
+        .collect();
+
+    DrawTarget::from_vec(width as i32, height as i32, pixels)
+}
@@ -20,21 +20,65 @@
 //! println!("{answer:?}");
 //! # Ok::<_, alerta::Error>(())
 //! ```
+//!
+//! # Mouse Buttons
+//!
+//! The dialog handles all three mouse buttons itself:
+//!
+//! - The left button activates buttons, selects text in the message, and drags the window (see
+//!   [`Builder::draggable`]).
+//! - The middle button pastes the primary selection into the [input field][Builder::input].
+//! - The right button opens a menu for copying the message or selecting all of it.
+//!
+//! # Features
+//!
+//! - `serde`: implements `Serialize` and `Deserialize` for [`Answer`], [`ButtonPreset`], [`Icon`]
+//!   and [`Theme`].
+//! - `atspi`: adds `Builder::accessible`, which makes dialogs known to screen readers over the
+//!   AT-SPI accessibility bus.
 
+#[cfg(feature = "atspi")]
+mod atspi;
+mod dedup;
 mod error;
+mod handle;
+mod headless;
+mod image;
+mod monitor;
+mod positions;
+mod session;
 mod ui;
 mod x11;
 
 #[cfg(test)]
 mod tests;
 
-use std::{fmt, process::Command, str::FromStr};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    cmp, env, fmt, fs,
+    io::{self, Read, Write as _},
+    iter, mem, panic,
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    str::FromStr,
+    sync::{Arc, Mutex, atomic::Ordering, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
 
-pub use error::Error;
-use rapid_qoi::Qoi;
+pub use error::{Error, ErrorKind};
+pub use handle::{BusyDialog, DialogHandle, ProgressDialog};
+pub use headless::{HeadlessEvent, HeadlessKey};
+use log::{debug, trace, warn};
+/// The 2D graphics library that [`Builder::custom_draw`] draws with.
+///
+/// Its version is part of the public API: updating it to an incompatible version is a breaking
+/// change of this crate.
+pub use raqote;
 use raqote::DrawTarget;
+pub use session::Session;
 
-use crate::{error::err, ui::Ui, x11::X11Window};
+use crate::{error::err, handle::Shared, monitor::ThemeMonitor, ui::Ui, x11::X11Window};
 
 /// Returns a [`Builder`] for creating dialogs.
 ///
@@ -43,21 +87,253 @@ pub fn alerta() -> Builder {
     Builder {
         title: None,
         message: None,
+        detail: None,
+        expandable_details: None,
+        footer: None,
         theme: None,
-        icon: Default::default(),
+        accent_color: None,
+        follow_system_theme: false,
+        theme_cache: true,
+        icon: None,
+        auto_icon: false,
+        custom_icon: None,
+        icon_frames: Vec::new(),
+        icon_position: IconPosition::default(),
+        window_icon: None,
         button_preset: ButtonPreset::default(),
+        buttons: None,
+        default_button: None,
+        confirm_buttons: Vec::new(),
+        button_icons: Vec::new(),
+        timeout: None,
+        input: None,
+        password: false,
+        input_default: None,
+        select_input_default: false,
+        input_rows: None,
+        input_max_len: None,
+        input_filter: None,
+        custom_draw: None,
+        custom_draw_size: None,
+        input_validate: None,
+        checkbox: None,
+        choices: Vec::new(),
+        multi_select: false,
+        progress: None,
+        copy_button: false,
+        copy_shortcut: true,
+        scale: None,
+        font: None,
+        markup: false,
+        ansi: AnsiMode::Strip,
+        detect_links: false,
+        monospace: false,
+        tab_width: None,
+        text_direction: TextDirection::Auto,
+        wrap: WrapMode::Word,
+        mirror_buttons: false,
+        button_order: ButtonOrder::AffirmativeLeft,
+        min_button_width: None,
+        padding: None,
+        equal_button_widths: false,
+        max_height: None,
+        max_lines: None,
+        max_width: None,
+        position: None,
+        size: None,
+        remember_position: None,
+        dedup_key: None,
+        center: false,
+        always_on_top: false,
+        urgent: false,
+        resizable: false,
+        drag_mode: DragMode::Anywhere,
+        decorated: true,
+        title_in_body: None,
+        corner_radius: 0,
+        opacity: None,
+        grab_focus: true,
+        restore_focus: true,
+        transient_for: 0,
+        app_id: None,
+        window_role: None,
+        window_type: WindowType::Dialog,
+        modal: false,
+        sound: false,
+        #[cfg(feature = "atspi")]
+        accessible: false,
+        display: None,
+        session: None,
+        callbacks: Vec::new(),
+        on_event: None,
+        on_answer: None,
+        on_error: None,
+    }
+}
+
+/// Breaks `text` into lines like the dialog does when wrapping its message at `max_width_px`
+/// pixels, using the built-in font at a size of `font_size` pixels.
+///
+/// This is meant for deciding whether a message is too long before displaying it. The dialog
+/// uses a size of 18 pixels, multiplied by the [scale factor][Builder::scale], unless
+/// [`Builder::font`] is set. Lines are broken between words, and words wider than
+/// `max_width_px` are broken between characters. The text is treated as plain text, without
+/// [markup][Builder::markup] or escape sequences, and whitespace at the end of the lines is
+/// removed.
+///
+/// ```
+/// let lines = alerta::wrap_text("The document could not be saved.", 120, 18.0);
+/// assert!(lines.len() > 1);
+/// assert_eq!(lines.concat().replace(' ', ""), "Thedocumentcouldnotbesaved.");
+/// ```
+pub fn wrap_text(text: &str, max_width_px: u32, font_size: f32) -> Vec<String> {
+    ui::wrap_text(text, max_width_px as f32, font_size)
+}
+
+/// Installs a panic hook that displays the panic message in an error dialog.
+///
+/// The dialog displays the location of the panic, and the backtrace as
+/// [expandable details][Builder::expandable_details] if one is captured (see
+/// [`Backtrace::capture`]). Once the dialog is closed, the hook that was installed before runs, so
+/// the panic is still printed to stderr with the default hook. If the dialog can't be displayed,
+/// for example because no X server is available, the reason is printed to stderr instead.
+///
+/// The dialog is displayed on the thread that panicked, which is blocked until it is closed.
+pub fn set_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let message = info.payload_as_str().unwrap_or("Box<dyn Any>");
+        let thread = thread::current();
+        let location = info
+            .location()
+            .map_or(String::new(), |loc| format!(" at {loc}"));
+        let detail = format!(
+            "Thread '{}' panicked{location}.",
+            thread.name().unwrap_or("<unnamed>"),
+        );
+        if let Err(e) = panic_dialog(message, detail, &Backtrace::capture()).show() {
+            let _ = writeln!(io::stderr(), "failed to display the panic message: {e}");
+        }
+        previous(info);
+    }));
+}
+
+pub(crate) fn panic_dialog(message: &str, detail: String, backtrace: &Backtrace) -> Builder {
+    let builder = alerta()
+        .icon(Icon::Error)
+        .title("Panic")
+        .message(message)
+        .detail(detail);
+    match backtrace.status() {
+        BacktraceStatus::Captured => builder.expandable_details(backtrace.to_string()),
+        _ => builder,
     }
 }
 
 /// A message dialog builder.
+///
+/// Builders can be cloned to create several similar dialogs from a common template.
+#[derive(Clone)]
 pub struct Builder {
     title: Option<String>,
     message: Option<String>,
+    detail: Option<String>,
+    expandable_details: Option<String>,
+    footer: Option<String>,
     theme: Option<Theme>,
-    icon: Icon,
+    accent_color: Option<u32>,
+    follow_system_theme: bool,
+    theme_cache: bool,
+    icon: Option<Icon>,
+    auto_icon: bool,
+    custom_icon: Option<image::Source>,
+    icon_frames: Vec<(Duration, image::Source)>,
+    icon_position: IconPosition,
+    window_icon: Option<Icon>,
     button_preset: ButtonPreset,
+    buttons: Option<Vec<String>>,
+    default_button: Option<usize>,
+    confirm_buttons: Vec<(usize, String)>,
+    button_icons: Vec<(usize, ButtonIcon)>,
+    timeout: Option<Duration>,
+    input: Option<String>,
+    password: bool,
+    input_default: Option<String>,
+    select_input_default: bool,
+    input_rows: Option<u32>,
+    input_max_len: Option<usize>,
+    input_filter: Option<ui::InputFilter>,
+    custom_draw: Option<ui::CustomDraw>,
+    custom_draw_size: Option<(u32, u32)>,
+    input_validate: Option<ui::InputValidator>,
+    checkbox: Option<String>,
+    choices: Vec<String>,
+    multi_select: bool,
+    progress: Option<ui::Indicator>,
+    copy_button: bool,
+    copy_shortcut: bool,
+    max_height: Option<u32>,
+    max_lines: Option<usize>,
+    max_width: Option<u32>,
+    scale: Option<f32>,
+    font: Option<(String, f32)>,
+    markup: bool,
+    ansi: AnsiMode,
+    detect_links: bool,
+    monospace: bool,
+    tab_width: Option<u32>,
+    text_direction: TextDirection,
+    wrap: WrapMode,
+    mirror_buttons: bool,
+    button_order: ButtonOrder,
+    min_button_width: Option<u32>,
+    padding: Option<(u32, u32)>,
+    equal_button_widths: bool,
+    position: Option<(x11::Offset, x11::Offset)>,
+    size: Option<(u32, u32)>,
+    remember_position: Option<String>,
+    dedup_key: Option<String>,
+    center: bool,
+    always_on_top: bool,
+    urgent: bool,
+    resizable: bool,
+    drag_mode: DragMode,
+    decorated: bool,
+    title_in_body: Option<bool>,
+    corner_radius: u32,
+    opacity: Option<f32>,
+    grab_focus: bool,
+    restore_focus: bool,
+    transient_for: u32,
+    app_id: Option<(String, String)>,
+    window_role: Option<String>,
+    window_type: WindowType,
+    modal: bool,
+    sound: bool,
+    #[cfg(feature = "atspi")]
+    accessible: bool,
+    display: Option<String>,
+    session: Option<Session>,
+    /// Buttons that call a function instead of closing the dialog.
+    callbacks: Vec<(usize, Callback)>,
+    on_event: Option<EventCallback>,
+    on_answer: Option<AnswerCallback>,
+    on_error: Option<ErrorCallback>,
 }
 
+type Callback = Arc<Mutex<dyn FnMut() + Send>>;
+
+/// An image displayed on a button (see [`Builder::button_icon`]).
+#[derive(Clone)]
+enum ButtonIcon {
+    Icon(Icon),
+    Image(image::Source),
+}
+type EventCallback = Arc<Mutex<dyn FnMut(&WindowEvent) + Send>>;
+/// Taken when it is called, so that clones of the builder only call it once.
+type AnswerCallback = Arc<Mutex<Option<Box<dyn FnOnce(Answer) + Send>>>>;
+type ErrorCallback = Arc<Mutex<Option<Box<dyn FnOnce(&Error) + Send>>>>;
+
 impl Builder {
     /// Sets the window title of the dialog.
     ///
@@ -70,27 +346,391 @@ impl Builder {
     /// Sets the message body.
     ///
     /// The body can contain line breaks, and will be line-wrapped to fit into the dialog window.
+    /// See [`Builder::markup`] for emphasizing parts of it.
     pub fn message(mut self, message: impl Into<String>) -> Self {
         self.message = Some(message.into());
         self
     }
 
+    /// Sets the message body to `template`, with its `{name}` placeholders replaced by the values
+    /// of the matching `args`.
+    ///
+    /// Literal braces are written as `{{` and `}}`. Placeholders without a matching argument, and
+    /// braces that don't form a placeholder, are kept verbatim, so a mistake in a translated
+    /// template still shows up in the dialog rather than failing it. The substituted values
+    /// aren't searched for placeholders themselves.
+    ///
+    /// ```
+    /// let dialog = alerta::alerta().message_template(
+    ///     "{file} could not be saved: {reason}",
+    ///     &[("file", "notes.txt"), ("reason", "the disk is full")],
+    /// );
+    /// ```
+    pub fn message_template(self, template: impl Into<String>, args: &[(&str, &str)]) -> Self {
+        let message = fill_template(&template.into(), args);
+        self.message(message)
+    }
+
+    /// Sets up the dialog for reporting `err` to the user.
+    ///
+    /// The error's message becomes the title, and its sources (the errors that caused it, from
+    /// [`Error::source`][std::error::Error::source]) the message body, one per line. If it has no
+    /// source, the body repeats the error's message. The icon is set to [`Icon::Error`].
+    ///
+    /// An icon or title that was set before is kept.
+    ///
+    /// ```
+    /// # fn load() -> std::io::Result<String> { Err(std::io::ErrorKind::NotFound.into()) }
+    /// if let Err(e) = load() {
+    ///     let dialog = alerta::alerta().from_error(&e);
+    /// }
+    /// ```
+    pub fn from_error(mut self, err: &dyn std::error::Error) -> Self {
+        let causes = iter::successors(err.source(), |e| e.source())
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        let message = if causes.is_empty() {
+            err.to_string()
+        } else {
+            causes.join("\n")
+        };
+        self.icon = self.icon.or(Some(Icon::Error));
+        self.title = self.title.or_else(|| Some(err.to_string()));
+        self.message(message)
+    }
+
+    /// Sets the message body to the contents of a text file.
+    ///
+    /// Invalid UTF-8 in the file is replaced with `U+FFFD`. Combine this with
+    /// [`Builder::monospace`] for displaying logs or other preformatted text.
+    pub fn message_from_file(self, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|e| Error::new(format!("failed to read '{}': {e}", path.display())))?;
+        Ok(self.message(String::from_utf8_lossy(&bytes)))
+    }
+
+    /// Sets a secondary text, which is displayed below the message in a smaller and dimmer font.
+    ///
+    /// This is meant for explaining the message, like "The disk is full. Free up space and try
+    /// again." below "The document could not be saved." The detail text is always wrapped
+    /// between words, and isn't affected by [`Builder::markup`] or [`Builder::monospace`].
+    pub fn detail(mut self, text: impl Into<String>) -> Self {
+        self.detail = Some(text.into());
+        self
+    }
+
+    /// Adds a collapsible section with a longer text, like a traceback or a log excerpt.
+    ///
+    /// The section is collapsed behind a "Show details" toggle at first. Clicking it displays the
+    /// text in the system monospace font, and grows the window to make room for it. Texts longer
+    /// than 8 lines scroll with the mouse wheel.
+    pub fn expandable_details(mut self, text: impl Into<String>) -> Self {
+        self.expandable_details = Some(text.into());
+        self
+    }
+
+    /// Sets a line of text displayed at the bottom of the dialog, below the buttons, in a small
+    /// and dim font.
+    ///
+    /// This is meant for credits like "MyApp v1.2.3", which are unrelated to the message. The
+    /// footer is displayed on a single line, and ends with an ellipsis if it is wider than the
+    /// dialog.
+    pub fn footer(mut self, text: impl Into<String>) -> Self {
+        self.footer = Some(text.into());
+        self
+    }
+
+    /// Enables inline markup in the [message][Self::message].
+    ///
+    /// With markup enabled, `**text**` is displayed in bold and `*text*` in italics. Literal
+    /// asterisks and backslashes can be written as `\*` and `\\`. Markers without a matching
+    /// closing marker are displayed as-is.
+    ///
+    /// By default, the message is displayed as plain text.
+    pub fn markup(mut self, yes: bool) -> Self {
+        self.markup = yes;
+        self
+    }
+
+    /// Sets how ANSI escape sequences in the [message][Self::message] are handled.
+    ///
+    /// Program output often contains escape sequences for colors, which a terminal interprets but
+    /// would otherwise be displayed as garbage. By default, they are removed
+    /// ([`AnsiMode::Strip`]). With [`AnsiMode::Render`], the bold, italic and foreground colors
+    /// they select are displayed. They are applied on top of [`Builder::markup`].
+    pub fn ansi(mut self, mode: AnsiMode) -> Self {
+        self.ansi = mode;
+        self
+    }
+
+    /// Makes the `http://` and `https://` URLs in the [message][Self::message] clickable.
+    ///
+    /// Links are underlined and displayed in a different color. Clicking one opens it with
+    /// `xdg-open`, without closing the dialog. If `xdg-open` isn't available, nothing happens.
+    ///
+    /// By default, URLs are displayed as plain text.
+    pub fn detect_links(mut self, yes: bool) -> Self {
+        self.detect_links = yes;
+        self
+    }
+
+    /// Displays the message in the system's monospace font, for aligned tables and code.
+    ///
+    /// The message is not wrapped, so its whitespace is preserved exactly (like with
+    /// [`WrapMode::None`]). Buttons and other text keep using the regular font.
+    ///
+    /// The monospace font is looked up with `fc-match`. If there is none, the message is displayed
+    /// in the built-in font, which isn't monospace, so columns won't line up; a warning is logged
+    /// then.
+    pub fn monospace(mut self, yes: bool) -> Self {
+        self.monospace = yes;
+        self
+    }
+
+    /// Sets the distance between tab stops in the message, in widths of a space.
+    ///
+    /// Tab characters advance the text to the next tab stop, which lines up tab-separated columns
+    /// (best combined with [`Builder::monospace`]). The default is 4, and 0 is treated as 1.
+    pub fn tab_width(mut self, spaces: u32) -> Self {
+        self.tab_width = Some(spaces);
+        self
+    }
+
+    /// Sets the base direction of the message text.
+    ///
+    /// Right-to-left text (like Arabic or Hebrew) is reordered for display, and a message with a
+    /// right-to-left base direction is aligned to the right. By default, the direction is inferred
+    /// from the message ([`TextDirection::Auto`]).
+    pub fn text_direction(mut self, direction: TextDirection) -> Self {
+        self.text_direction = direction;
+        self
+    }
+
+    /// Sets how the message is broken into lines.
+    ///
+    /// By default, lines are broken between words ([`WrapMode::Word`]). With [`WrapMode::None`],
+    /// preformatted text like a stack trace keeps its layout, and the message can be scrolled
+    /// horizontally if it is too wide for the dialog.
+    pub fn wrap(mut self, mode: WrapMode) -> Self {
+        self.wrap = mode;
+        self
+    }
+
+    /// Lays out the buttons from right to left, as is customary in right-to-left user interfaces.
+    ///
+    /// This only affects where the buttons are displayed; [`Answer::Button`] still refers to the
+    /// buttons by their index in the list.
+    pub fn mirror_buttons(mut self, yes: bool) -> Self {
+        self.mirror_buttons = yes;
+        self
+    }
+
+    /// Sets whether affirmative buttons like "OK" are displayed on the left or on the right.
+    ///
+    /// By default, the buttons are displayed in the order they are listed in
+    /// ([`ButtonOrder::AffirmativeLeft`]). Like [`Builder::mirror_buttons`], this only affects
+    /// where the buttons are displayed; [`Answer::Button`] still refers to the buttons by their
+    /// index in the list. The order is mirrored once more if `mirror_buttons` is also enabled.
+    pub fn button_order(mut self, order: ButtonOrder) -> Self {
+        self.button_order = order;
+        self
+    }
+
+    /// Makes every button at least `px` pixels wide, so that short labels like "OK" and "No" don't
+    /// look cramped next to longer ones.
+    ///
+    /// Buttons with longer labels still grow to fit them. The width is scaled like all other sizes
+    /// (see [`Builder::scale`]). The default is 64 pixels.
+    pub fn min_button_width(mut self, px: u32) -> Self {
+        self.min_button_width = Some(px);
+        self
+    }
+
+    /// Sets the distance between the window edges and its contents to `content` pixels, and the
+    /// distance between adjacent buttons to `button_gap` pixels.
+    ///
+    /// Both are scaled like all other sizes (see [`Builder::scale`]). The default is 10 pixels for
+    /// both.
+    pub fn padding(mut self, content: u32, button_gap: u32) -> Self {
+        self.padding = Some((content, button_gap));
+        self
+    }
+
+    /// Makes every button as wide as the one with the widest label, and aligns the buttons to the
+    /// end of the row.
+    ///
+    /// By default, the buttons share the width of the window equally. That also makes them equally
+    /// wide, but stretches them in wide dialogs.
+    pub fn equal_button_widths(mut self, yes: bool) -> Self {
+        self.equal_button_widths = yes;
+        self
+    }
+
     /// Sets the icon to display in the dialog.
     ///
-    /// By default, [`Icon::Info`] is used.
+    /// By default, [`Icon::Info`] is used, unless [`Builder::auto_icon`] is enabled.
     pub fn icon(mut self, icon: Icon) -> Self {
-        self.icon = icon;
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Picks the [`Icon`] based on keywords in the title and message.
+    ///
+    /// Text mentioning an error or failure gets [`Icon::Error`], text mentioning a warning gets
+    /// [`Icon::Warning`], and text containing a question mark gets [`Icon::Question`]. Everything
+    /// else gets [`Icon::Info`].
+    ///
+    /// This has no effect if an icon is set explicitly via [`Builder::icon`].
+    pub fn auto_icon(mut self) -> Self {
+        self.auto_icon = true;
+        self
+    }
+
+    /// Displays the PNG or QOI image at `path` as the dialog icon.
+    ///
+    /// This replaces the image of the [`Icon`] set via [`Builder::icon`], but that [`Icon`] is
+    /// still used to derive the default window title.
+    ///
+    /// The file is loaded when the dialog is shown. If it cannot be read or decoded,
+    /// [`Builder::show`] will return an error.
+    pub fn icon_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.custom_icon = Some(image::Source::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Displays an in-memory image as the dialog icon.
+    ///
+    /// `data` must contain `width * height` pixels in RGBA order, with 8 bits per channel and
+    /// without premultiplied alpha. Otherwise, [`Builder::show`] will return an error.
+    ///
+    /// Like [`Builder::icon_file`], this only replaces the image of the [`Icon`].
+    pub fn icon_rgba(mut self, width: u32, height: u32, data: Vec<u8>) -> Self {
+        self.custom_icon = Some(image::Source::Rgba {
+            width,
+            height,
+            data,
+        });
+        self
+    }
+
+    /// Displays an animation as the dialog icon, for example to show that an operation is in
+    /// progress.
+    ///
+    /// Each of the `frames` is displayed for the given duration before the next frame replaces
+    /// it. After the last frame, the animation starts over. The icon is redrawn at most every
+    /// 60 ms, so shorter frames may be skipped. The frames should all have the same size, since
+    /// the dialog is laid out for the first one.
+    ///
+    /// This replaces the image set via [`Builder::icon_file`] or [`Builder::icon_rgba`].
+    pub fn animated_icon(mut self, frames: Vec<(Duration, DrawTarget)>) -> Self {
+        self.icon_frames = frames
+            .into_iter()
+            .map(|(duration, frame)| {
+                let frame = image::Source::Argb {
+                    width: frame.width(),
+                    height: frame.height(),
+                    data: frame.into_vec(),
+                };
+                (duration, frame)
+            })
+            .collect();
+        self
+    }
+
+    /// Sets where the icon is placed relative to the message.
+    ///
+    /// By default, [`IconPosition::Left`] is used.
+    pub fn icon_position(mut self, position: IconPosition) -> Self {
+        self.icon_position = position;
+        self
+    }
+
+    /// Sets the icon displayed for the dialog window in the taskbar and window switcher.
+    ///
+    /// By default, the [`Icon`] of the dialog is used.
+    pub fn window_icon(mut self, icon: Icon) -> Self {
+        self.window_icon = Some(icon);
+        self
+    }
+
+    /// Sets the instance and class name of the dialog window (its `WM_CLASS` property).
+    ///
+    /// Window managers use these names in window rules, and taskbars use them to group windows by
+    /// application. By default, both names are `alerta`.
+    pub fn app_id(mut self, instance: impl Into<String>, class: impl Into<String>) -> Self {
+        self.app_id = Some((instance.into(), class.into()));
+        self
+    }
+
+    /// Sets the role of the dialog window (its `WM_WINDOW_ROLE` property).
+    ///
+    /// The role tells apart the different kinds of windows of an application, so that window
+    /// rules and session managers can match a specific dialog. By default, no role is set.
+    pub fn window_role(mut self, role: impl Into<String>) -> Self {
+        self.window_role = Some(role.into());
+        self
+    }
+
+    /// Sets the kind of window the dialog tells the window manager it is (its
+    /// `_NET_WM_WINDOW_TYPE` property).
+    ///
+    /// Window managers use the type to decide how to place and decorate the window; tiling window
+    /// managers, for example, let dialogs float above the tiled windows. By default, the dialog
+    /// is a [`WindowType::Dialog`].
+    pub fn window_type(mut self, window_type: WindowType) -> Self {
+        self.window_type = window_type;
         self
     }
 
     /// Sets the dialog's color theme.
     ///
-    /// By default, the OS theme is used.
+    /// By default, or with [`Theme::System`], the OS theme is used. Detecting it can take a moment,
+    /// so the dialog appears with [`Theme::Light`] and switches to the detected theme once it is
+    /// known (unless it was [cached][Builder::theme_cache]).
     pub fn theme(mut self, theme: Theme) -> Self {
         self.theme = Some(theme);
         self
     }
 
+    /// Sets whether the dialog switches between [`Theme::Light`] and [`Theme::Dark`] when the
+    /// desktop's color scheme preference changes while it is open.
+    ///
+    /// This runs `dbus-monitor` in the background to listen for changes of the XDG desktop portal
+    /// setting. It has no effect if a theme other than [`Theme::System`] was set with
+    /// [`Builder::theme`].
+    ///
+    /// By default, the theme is only detected once, when the dialog is created.
+    pub fn follow_system_theme(mut self, yes: bool) -> Self {
+        self.follow_system_theme = yes;
+        self
+    }
+
+    /// Sets whether the dialog may reuse the theme that was detected for an earlier dialog.
+    ///
+    /// Detecting the theme runs external commands, which is slow when showing many dialogs in a
+    /// row. So a detected theme is reused by all dialogs created within the next 5 seconds. Turn
+    /// this off if the color scheme preference is expected to have changed since then. It has no
+    /// effect if a theme other than [`Theme::System`] was set with [`Builder::theme`].
+    ///
+    /// By default, the cache is used.
+    pub fn theme_cache(mut self, yes: bool) -> Self {
+        self.theme_cache = yes;
+        self
+    }
+
+    /// Fills the [default button][Builder::default_button] with the given `0xAARRGGBB` color.
+    ///
+    /// The color is also used for the outline of the [input field][Builder::input]. The alpha
+    /// channel is ignored, and the button label is drawn in black or white, whichever is more
+    /// readable. This overrides the accent color of a [`Theme::Custom`].
+    ///
+    /// By default, the default button is only marked with a focus ring.
+    pub fn accent_color(mut self, argb: u32) -> Self {
+        self.accent_color = Some(argb);
+        self
+    }
+
     /// Sets the button preset to use.
     ///
     /// By default, [`ButtonPreset::Close`] is used.
@@ -99,89 +739,1807 @@ impl Builder {
         self
     }
 
-    /// Displays the dialog and blocks until the dialog is closed.
+    /// Makes the button at `index` call `f` when it is pressed, instead of closing the dialog.
     ///
-    /// Returns an [`Answer`] indicating which dialog button was clicked.
+    /// This is meant for buttons like "Apply" in a settings dialog. The dialog still closes when
+    /// one of the other buttons is pressed, or when the window is closed. `f` is called on the
+    /// thread that displays the dialog, which doesn't react to input until `f` returns.
     ///
-    /// # Errors
+    /// Clones of the builder share `f`.
+    pub fn on_button(mut self, index: usize, f: impl FnMut() + Send + 'static) -> Self {
+        self.callbacks.retain(|(i, _)| *i != index);
+        self.callbacks.push((index, Arc::new(Mutex::new(f))));
+        self
+    }
+
+    /// Makes the button at `index` ask for confirmation, for destructive actions like "Delete".
     ///
-    /// An error may occur when communicating with the X server.
-    pub fn show(self) -> Result<Answer, Error> {
-        let title = match self.title {
-            Some(title) => title,
-            None => match self.icon {
-                Icon::Error => "Error\0".into(),
-                Icon::Warning => "Warning\0".into(),
-                Icon::Info => "Info\0".into(),
-                Icon::Question => "Question\0".into(),
-            },
+    /// The first click on the button only replaces its label with `confirm_label` (like "Click
+    /// again to confirm"). The button takes effect if it is clicked again within 3 seconds;
+    /// otherwise, or when the cursor moves on to another button, its label is restored. Activating
+    /// the button with the keyboard works the same way. The button is made wide enough for both
+    /// labels.
+    pub fn confirm_button(mut self, index: usize, confirm_label: impl Into<String>) -> Self {
+        self.confirm_buttons.retain(|(i, _)| *i != index);
+        self.confirm_buttons.push((index, confirm_label.into()));
+        self
+    }
+
+    /// Displays the image of `icon` left of the label of the button at `index`.
+    ///
+    /// The image is scaled to the height of the label, and the button is made wider to fit it.
+    /// [`Icon::None`] removes the image again.
+    pub fn button_icon(mut self, index: usize, icon: Icon) -> Self {
+        self.button_icons.retain(|(i, _)| *i != index);
+        if icon != Icon::None {
+            self.button_icons.push((index, ButtonIcon::Icon(icon)));
+        }
+        self
+    }
+
+    /// Displays an in-memory image left of the label of the button at `index`, like a trash can
+    /// on a "Delete" button.
+    ///
+    /// `data` is in the format of [`Builder::icon_rgba`]. Like with [`Builder::button_icon`], the
+    /// image is scaled to the height of the label, so it looks best if it is small and square.
+    pub fn button_icon_rgba(
+        mut self,
+        index: usize,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    ) -> Self {
+        self.button_icons.retain(|(i, _)| *i != index);
+        let image = image::Source::Rgba {
+            width,
+            height,
+            data,
         };
+        self.button_icons.push((index, ButtonIcon::Image(image)));
+        self
+    }
 
-        let mut ui = Ui::new(
-            self.icon,
-            self.theme.unwrap_or_else(Theme::detect),
-            &self.message.unwrap_or_default(),
-            self.button_preset.strings(),
-        );
+    /// Calls `f` with every event the dialog window receives, before the dialog handles it.
+    ///
+    /// This is meant for observing the dialog, for example for logging; `f` can't change how the
+    /// dialog reacts to the event. Like with [`Builder::on_button`], `f` is called on the thread
+    /// that displays the dialog, and clones of the builder share it.
+    pub fn on_event(mut self, f: impl FnMut(&WindowEvent) + Send + 'static) -> Self {
+        self.on_event = Some(Arc::new(Mutex::new(f)));
+        self
+    }
 
-        let conn = x11::Connection::connect()?;
+    /// Calls `f` with the [`Answer`] once the dialog is answered, however that happens.
+    ///
+    /// This is meant for dialogs displayed with [`Builder::spawn`] (or [`Builder::progress`]),
+    /// whose answer nobody waits for: `f` is called on the thread that displays the dialog, right
+    /// before the answer becomes available through the handle. For [`Builder::show_retry`], `f`
+    /// is called with the answer from the last time the dialog was displayed.
+    ///
+    /// If displaying the dialog fails, the function set with [`Builder::on_error`] is called
+    /// instead. Clones of the builder share `f`, and only the first of their dialogs to finish
+    /// calls it (or the function set with [`Builder::on_error`]).
+    pub fn on_answer(mut self, f: impl FnOnce(Answer) + Send + 'static) -> Self {
+        self.on_answer = Some(Arc::new(Mutex::new(Some(Box::new(f)))));
+        self
+    }
 
-        let win = X11Window::create(
-            conn.clone(),
-            ui.canvas.width() as u16,
-            ui.canvas.height() as u16,
-        )?
-        .with_title(title)?;
+    /// Calls `f` with the error if displaying the dialog fails, for example because the settings
+    /// are invalid or the connection to the X server is lost.
+    ///
+    /// This is the counterpart of [`Builder::on_answer`]: once the dialog finishes, exactly one
+    /// of the two functions is called.
+    pub fn on_error(mut self, f: impl FnOnce(&Error) + Send + 'static) -> Self {
+        self.on_error = Some(Arc::new(Mutex::new(Some(Box::new(f)))));
+        self
+    }
 
-        win.set_contents(&ui.canvas)?;
+    /// Sets custom button labels, overriding the [`ButtonPreset`].
+    ///
+    /// Buttons are displayed in the order they're yielded by `labels`, and the index in
+    /// [`Answer::Button`] refers to the position in this list.
+    ///
+    /// If `labels` is empty, the default [`ButtonPreset::Close`] is used instead, so that the
+    /// dialog can always be dismissed.
+    ///
+    /// An `&` in a label marks the following letter as the *mnemonic* of the button: it is
+    /// underlined while Alt is held, and pressing Alt together with it activates the button. Write
+    /// `&&` for a literal ampersand. The buttons of a [`ButtonPreset`] get the first letter of
+    /// their label that isn't used by another button.
+    pub fn buttons(mut self, labels: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.buttons = Some(labels.into_iter().map(Into::into).collect());
+        self
+    }
 
-        win.show()?;
+    /// Marks the button at `index` as the default button.
+    ///
+    /// The default button is highlighted, and pressing Enter or Space will activate it. It also
+    /// has keyboard focus initially, which can be moved to the other buttons with Tab and
+    /// Shift+Tab.
+    ///
+    /// By default, no button is the default button, and Enter does nothing until the focus has
+    /// been moved with Tab.
+    pub fn default_button(mut self, index: usize) -> Self {
+        self.default_button = Some(index);
+        self
+    }
+
+    /// Closes the dialog automatically after `timeout` has elapsed.
+    ///
+    /// When the timeout expires, the dialog returns [`Answer::TimedOut`].
+    /// While the dialog is open, the remaining number of seconds is displayed on the
+    /// [default button][Self::default_button] (or the first button, if there is no default).
+    ///
+    /// By default, the dialog stays open until the user closes it.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Adds a single-line text input field below the message.
+    ///
+    /// `placeholder` is displayed in the field while it is empty (and may also be empty).
+    ///
+    /// When a button is pressed, the dialog will return [`Answer::Input`], containing the entered
+    /// text. Pressing Escape returns [`Answer::Closed`] as usual. Ctrl+V pastes the clipboard into
+    /// the field, and the middle mouse button the primary selection. The caret is moved with the
+    /// Left and Right arrow keys, Home and End, or by clicking into the field.
+    pub fn input(mut self, placeholder: impl Into<String>) -> Self {
+        self.input = Some(placeholder.into());
+        self
+    }
+
+    /// Turns the [input field][Builder::input] into a password field.
+    ///
+    /// Typed characters are displayed as `•`, and the entered text is erased from memory once the
+    /// dialog has returned it. Has no effect unless [`Builder::input`] is also used.
+    pub fn password(mut self) -> Self {
+        self.password = true;
+        self
+    }
+
+    /// Fills the [input field][Builder::input] with `text` at first, for editing an existing value.
+    ///
+    /// The caret is placed at the end of the text. If the dialog is answered without editing the
+    /// text, [`Answer::Input`] contains `text` unchanged. The text isn't checked against
+    /// [`Builder::input_max_len`] or [`Builder::input_filter`].
+    pub fn input_default(mut self, text: impl Into<String>) -> Self {
+        self.input_default = Some(text.into());
+        self
+    }
+
+    /// Sets whether the text set with [`Builder::input_default`] is selected at first, so that
+    /// typing replaces it instead of adding to it.
+    ///
+    /// Moving the caret deselects the text. By default, the text isn't selected.
+    pub fn select_input_default(mut self, yes: bool) -> Self {
+        self.select_input_default = yes;
+        self
+    }
+
+    /// Turns the [input field][Builder::input] into a text area with `rows` visible lines, for
+    /// longer answers like a description.
+    ///
+    /// Enter inserts a line break, and Ctrl+Enter answers the dialog. The text scrolls once it
+    /// has more lines than fit, and [`Answer::Input`] contains all of them, separated by `\n`.
+    /// Has no effect on [password fields][Builder::password].
+    pub fn input_multiline(mut self, rows: u32) -> Self {
+        self.input_rows = Some(rows.max(1));
+        self
+    }
+
+    /// Limits the [input field][Builder::input] to `n` characters.
+    ///
+    /// Typing more characters rings the bell instead, and pasted text is cut off.
+    pub fn input_max_len(mut self, n: usize) -> Self {
+        self.input_max_len = Some(n);
+        self
+    }
+
+    /// Only lets characters for which `f` returns `true` be typed into the
+    /// [input field][Builder::input].
+    ///
+    /// Rejected characters ring the bell, and are left out of pasted text.
+    ///
+    /// ```no_run
+    /// let answer = alerta::alerta()
+    ///     .message("Enter the PIN:")
+    ///     .input("PIN")
+    ///     .input_filter(|c| c.is_ascii_digit())
+    ///     .input_max_len(4)
+    ///     .show()?;
+    /// # Ok::<_, alerta::Error>(())
+    /// ```
+    pub fn input_filter(mut self, f: impl Fn(char) -> bool + Send + Sync + 'static) -> Self {
+        self.input_filter = Some(Arc::new(f));
+        self
+    }
+
+    /// Disables the [default button][Builder::default_button] (or the first button, if none is
+    /// set) while `f` returns `false` for the text of the [input field][Builder::input].
+    ///
+    /// The disabled button is displayed faded, and can't be clicked or activated with Enter. The
+    /// other buttons, and closing the dialog, still work.
+    pub fn input_validate(mut self, f: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.input_validate = Some(Arc::new(f));
+        self
+    }
+
+    /// Reserves an area between the message and the buttons, and draws into it with `f`.
+    ///
+    /// `f` is called whenever the dialog is redrawn, with the window contents and the [`Rect`]
+    /// reserved for the custom content. Drawing is clipped to that area, which is in physical
+    /// pixels and already has the dialog background. Its size is set with
+    /// [`Builder::custom_draw_size`].
+    ///
+    /// ```no_run
+    /// use alerta::raqote::{DrawOptions, SolidSource};
+    ///
+    /// let answer = alerta::alerta()
+    ///     .message("Disk usage:")
+    ///     .custom_draw(|canvas, rect| {
+    ///         let used = rect.width as f32 * 0.7;
+    ///         let color = SolidSource::from_unpremultiplied_argb(255, 200, 60, 60);
+    ///         let (x, y) = (rect.x as f32, rect.y as f32);
+    ///         canvas.fill_rect(x, y, used, rect.height as f32, &color.into(), &DrawOptions::new());
+    ///     })
+    ///     .custom_draw_size(200, 24)
+    ///     .show()?;
+    /// # Ok::<_, alerta::Error>(())
+    /// ```
+    pub fn custom_draw(
+        mut self,
+        f: impl Fn(&mut DrawTarget, Rect) + Send + Sync + 'static,
+    ) -> Self {
+        self.custom_draw = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets the size of the area reserved for [`Builder::custom_draw`], in logical pixels.
+    ///
+    /// The area is at least `width` wide, and otherwise spans the width of the window. By
+    /// default, it is 120 pixels high.
+    pub fn custom_draw_size(mut self, width: u32, height: u32) -> Self {
+        self.custom_draw_size = Some((width, height));
+        self
+    }
+
+    /// Adds a checkbox with the given label above the buttons, like "Don't show this again".
+    ///
+    /// The checkbox starts out unchecked. Its final state is reported in
+    /// [`DialogResult::checkbox`].
+    pub fn checkbox(mut self, label: impl Into<String>) -> Self {
+        self.checkbox = Some(label.into());
+        self
+    }
+
+    /// Displays a list of options below the message, of which the user selects one.
+    ///
+    /// The options are displayed as radio buttons, and the first one is selected initially. The
+    /// selection can be changed by clicking an option, or with the Up and Down arrow keys. When a
+    /// dialog button is pressed, the dialog returns [`Answer::Selected`].
+    ///
+    /// A dialog can't have both choices and an [input field][Self::input].
+    pub fn choices(mut self, items: Vec<String>) -> Self {
+        self.choices = items;
+        self.multi_select = false;
+        self
+    }
+
+    /// Displays a list of options below the message, of which the user can select any number.
+    ///
+    /// The options are displayed as checkboxes, which are all unchecked initially. They are toggled
+    /// by clicking them, or by moving to them with the Up and Down arrow keys and pressing Space.
+    /// If there are many options, the list scrolls. When a dialog button is pressed, the dialog
+    /// returns [`Answer::MultiSelected`].
+    ///
+    /// This replaces the options set with [`Builder::choices`], and vice versa. A dialog can't
+    /// have both choices and an [input field][Self::input].
+    pub fn multi_choices(mut self, items: Vec<String>) -> Self {
+        self.choices = items;
+        self.multi_select = true;
+        self
+    }
+
+    /// Adds a "Copy" button after the dialog buttons, which copies the message to the clipboard.
+    ///
+    /// Clicking it doesn't close the dialog. The copied text is only available while the dialog is
+    /// open, unless a clipboard manager is running, which takes it over when the dialog closes.
+    pub fn add_copy_button(mut self) -> Self {
+        self.copy_button = true;
+        self
+    }
+
+    /// Sets whether Ctrl+Shift+C copies the whole message to the clipboard and closes the dialog,
+    /// which then returns [`Answer::CopiedAndClosed`].
+    ///
+    /// The shortcut is enabled by default. Like with the [copy button][Self::add_copy_button], the
+    /// text stays available after the dialog is closed only if a clipboard manager takes it over.
+    pub fn copy_shortcut(mut self, yes: bool) -> Self {
+        self.copy_shortcut = yes;
+        self
+    }
+
+    /// Limits the height of the dialog window to `px` pixels.
+    ///
+    /// If the message doesn't fit, it is displayed in a scrollable area that can be scrolled with
+    /// the mouse wheel, while the icon and buttons stay in place.
+    ///
+    /// By default, the window grows as tall as needed to fit the message.
+    pub fn max_height(mut self, px: u32) -> Self {
+        self.max_height = Some(px);
+        self
+    }
+
+    /// Displays at most `n` lines of the message (but at least one).
+    ///
+    /// If the wrapped message has more lines, the rest is cut off and the last visible line ends
+    /// with "…". Clicking the [copy button][Self::add_copy_button] still copies the full message.
+    pub fn max_lines(mut self, n: usize) -> Self {
+        self.max_lines = Some(n);
+        self
+    }
+
+    /// Limits the width of the dialog window to `px` pixels.
+    ///
+    /// The message is wrapped to fit, breaking words between characters if they are too long for
+    /// a line. The buttons are not shrunk, so a dialog with many buttons may still be wider.
+    ///
+    /// By default, the limit is 600 pixels (multiplied by the [scale factor][Self::scale]).
+    pub fn max_width(mut self, px: u32) -> Self {
+        self.max_width = Some(px);
+        self
+    }
+
+    /// Sets the size of the dialog window to `width` by `height` pixels, instead of fitting the
+    /// window to its contents.
+    ///
+    /// The message is wrapped to the width, and becomes scrollable if it doesn't fit the height.
+    /// The window is still made larger if the buttons and other elements don't fit. This
+    /// overrides [`Builder::max_width`] and [`Builder::max_height`].
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    /// Scales the dialog contents by `factor`, for HiDPI displays.
+    ///
+    /// By default, the scale factor is derived from the `Xft.dpi` X resource (96 DPI corresponding
+    /// to a factor of 1), which is where desktop environments store their scaling setting. Sizes
+    /// are rounded to whole pixels, so fractional factors like 1.5 don't produce blurry edges.
+    ///
+    /// Without an explicit factor, the dialog also follows the pixel density of the monitors: when
+    /// it is moved to a monitor that is denser than the one it was shown on (as reported through
+    /// RandR), it is laid out again at a correspondingly larger scale, and vice versa.
+    ///
+    /// `factor` must be positive; otherwise, displaying the dialog fails.
+    pub fn scale(mut self, factor: f32) -> Self {
+        self.scale = Some(factor);
+        self
+    }
+
+    /// Uses the system font `family` at a size of `size_px` pixels for all text in the dialog.
+    ///
+    /// The font is looked up with fontconfig's `fc-match`, so `family` may also be a pattern like
+    /// `"Noto Sans:bold"` or a generic family like `"serif"`. If the family can't be found, the
+    /// built-in font is used at the given size, and a warning is logged. The size is multiplied
+    /// by the [scale factor][Self::scale].
+    ///
+    /// `size_px` must be positive; otherwise, displaying the dialog fails.
+    pub fn font(mut self, family: impl Into<String>, size_px: f32) -> Self {
+        self.font = Some((family.into(), size_px));
+        self
+    }
+
+    /// Places the top-left corner of the dialog window at the given screen coordinates.
+    ///
+    /// This takes precedence over [`Builder::center`].
+    ///
+    /// By default, the window manager decides where to place the dialog. Note that some window
+    /// managers (particularly tiling ones) may not honor the requested position.
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.position = Some((x11::Offset::Start(x), x11::Offset::Start(y)));
+        self
+    }
+
+    /// Sets the window size and position from an X11 geometry specification, like the
+    /// `-geometry` option of `xterm` and `xmessage`.
+    ///
+    /// The specification has the form `<width>x<height>{+-}<x>{+-}<y>`, where either the size or
+    /// the position may be left out. A `+` offset is the distance of the left or top edge of the
+    /// window from that of the screen, and a `-` offset that of the right or bottom edge. For
+    /// example, `400x200-0+0` places a 400 by 200 pixel window in the top-right corner.
+    ///
+    /// The size is set like with [`Builder::size`]. The position takes precedence over
+    /// [`Builder::center`] and [`Builder::remember_position`], like [`Builder::position`].
+    pub fn geometry(mut self, spec: &str) -> Result<Self, Error> {
+        let (size, position) =
+            parse_geometry(spec).ok_or_else(|| Error::new(format!("invalid geometry '{spec}'")))?;
+        self.size = size.or(self.size);
+        self.position = position.or(self.position);
+        Ok(self)
+    }
+
+    /// Remembers where the dialog was when it closed, and shows it there the next time a dialog
+    /// with the same `key` is shown.
+    ///
+    /// The positions are stored in `$XDG_CACHE_HOME/alerta/positions`. A remembered position
+    /// that is no longer on any monitor is moved back onto the screen. [`Builder::position`]
+    /// takes precedence over the remembered position, which takes precedence over
+    /// [`Builder::center`].
+    ///
+    /// By default, positions are not remembered.
+    pub fn remember_position(mut self, key: impl Into<String>) -> Self {
+        self.remember_position = Some(key.into());
+        self
+    }
+
+    /// Shows at most one dialog with the same `key` at a time.
+    ///
+    /// If a dialog with this key is open already (in this process), the new dialog isn't
+    /// displayed. Instead, the window of the open dialog is raised and focused, and the new one
+    /// is answered with [`Answer::Closed`] right away (for [`CloseReason::Duplicate`]). This keeps
+    /// an error that occurs over and over again from filling the screen with identical dialogs.
+    ///
+    /// By default, dialogs are not deduplicated.
+    pub fn dedup_key(mut self, key: impl Into<String>) -> Self {
+        self.dedup_key = Some(key.into());
+        self
+    }
+
+    /// Centers the dialog window on the screen.
+    ///
+    /// If an explicit [`Builder::position`] is set, it takes precedence.
+    pub fn center(mut self) -> Self {
+        self.center = true;
+        self
+    }
+
+    /// Sets whether the dialog should stay on top of other windows.
+    ///
+    /// This is a request to the window manager, which may not honor it.
+    ///
+    /// By default, the dialog is stacked like any other window.
+    pub fn always_on_top(mut self, yes: bool) -> Self {
+        self.always_on_top = yes;
+        self
+    }
+
+    /// Sets whether the dialog should ask for the user's attention.
+    ///
+    /// This sets the `_NET_WM_STATE_DEMANDS_ATTENTION` state and the urgency hint of the window,
+    /// so that most taskbars and panels highlight its entry until it is focused. This is useful
+    /// for dialogs that open while the application is in the background, or when the window
+    /// manager doesn't let them take the focus.
+    ///
+    /// By default, the dialog doesn't ask for attention.
+    pub fn urgent(mut self, yes: bool) -> Self {
+        self.urgent = yes;
+        self
+    }
+
+    /// Allows the user to resize the dialog window.
+    ///
+    /// The message is wrapped again to fit the new width, and becomes scrollable if it doesn't fit
+    /// the new height. The window can't be made smaller than needed for the buttons.
+    ///
+    /// By default, the dialog has a fixed size.
+    pub fn resizable(mut self, yes: bool) -> Self {
+        self.resizable = yes;
+        self
+    }
+
+    /// Sets where the dialog can be grabbed to move it with the mouse.
+    ///
+    /// By default, the dialog can be dragged anywhere outside of the message text
+    /// ([`DragMode::Anywhere`]).
+    pub fn draggable(mut self, mode: DragMode) -> Self {
+        self.drag_mode = mode;
+        self
+    }
+
+    /// Sets whether the window manager draws a title bar and border around the dialog.
+    ///
+    /// Without them, the dialog can still be moved by dragging it, and closed with Escape or its
+    /// buttons. Window managers may ignore this request.
+    ///
+    /// By default, the dialog is decorated.
+    pub fn decorated(mut self, yes: bool) -> Self {
+        self.decorated = yes;
+        self
+    }
+
+    /// Sets whether the title is displayed as a bold header at the top of the dialog.
+    ///
+    /// This presents the title the same way regardless of the window manager, which may cut off
+    /// long titles in its title bar. Titles that don't fit are wrapped to a second line, and end
+    /// with an ellipsis if they don't fit on that either.
+    ///
+    /// By default, the title is displayed in the dialog if it is not
+    /// [decorated][Builder::decorated], since there is no title bar then.
+    pub fn show_title_in_body(mut self, yes: bool) -> Self {
+        self.title_in_body = Some(yes);
+        self
+    }
+
+    /// Gives the dialog window rounded corners with the given radius in pixels.
+    ///
+    /// The radius is scaled like all other sizes (see [`Builder::scale`]). The corners are cut out
+    /// of the window with the X Shape extension; if the X server doesn't support it, the corners
+    /// stay square. Since window managers draw their frame around the whole window, this looks
+    /// best together with [`Builder::decorated`]`(false)`.
+    ///
+    /// By default, the corners are square.
+    pub fn corner_radius(mut self, px: u32) -> Self {
+        self.corner_radius = px;
+        self
+    }
+
+    /// Makes the dialog window translucent, from 0.0 (invisible) to 1.0 (opaque).
+    ///
+    /// Values outside of that range are clamped. This needs a compositing manager, without which
+    /// the dialog stays opaque. Note that a nearly transparent dialog can still be clicked, even
+    /// though it is hard to see.
+    ///
+    /// By default, the dialog is opaque.
+    pub fn opacity(mut self, alpha: f32) -> Self {
+        // NaN would make the window invisible.
+        self.opacity = Some(if alpha.is_nan() {
+            1.0
+        } else {
+            alpha.clamp(0.0, 1.0)
+        });
+        self
+    }
+
+    /// Sets whether the dialog asks the window manager for the keyboard focus when it appears.
+    ///
+    /// Window managers with focus stealing prevention may still refuse to focus the dialog, for
+    /// example while the user is typing in another window.
+    ///
+    /// By default, the dialog asks for the focus.
+    pub fn grab_focus(mut self, yes: bool) -> Self {
+        self.grab_focus = yes;
+        self
+    }
+
+    /// Sets whether the keyboard focus returns to the previously focused window when the dialog
+    /// closes.
+    ///
+    /// The focus is only given back if the dialog still has it and the previous window is still
+    /// visible, so that closing the dialog doesn't take the focus away from a window the user has
+    /// switched to in the meantime. Some window managers move the focus themselves.
+    ///
+    /// By default, the focus is restored.
+    pub fn restore_focus(mut self, yes: bool) -> Self {
+        self.restore_focus = yes;
+        self
+    }
+
+    /// Marks the dialog as belonging to the X11 window `parent_window_id`.
+    ///
+    /// Window managers typically keep the dialog above its parent and center it over the parent.
+    /// This takes a raw X11 window ID so that it can be used with any GUI framework.
+    ///
+    /// An ID of 0 is ignored. Invalid window IDs are ignored by the window manager.
+    pub fn transient_for(mut self, parent_window_id: u32) -> Self {
+        self.transient_for = parent_window_id;
+        self
+    }
+
+    /// Sets whether the dialog should be modal for its [parent
+    /// window][Self::transient_for].
+    ///
+    /// This is a request to the window manager, which may block interaction with the parent window
+    /// while the dialog is open.
+    ///
+    /// By default, the dialog is not modal.
+    pub fn modal(mut self, yes: bool) -> Self {
+        self.modal = yes;
+        self
+    }
+
+    /// Plays the alert sound matching the [`Icon`] when the dialog is displayed.
+    ///
+    /// The sound is played from the desktop's sound theme with `canberra-gtk-play` (for example,
+    /// the `dialog-error` sound for [`Icon::Error`]). If that isn't installed, the X11 bell is rung
+    /// instead. Failing to play the sound doesn't prevent the dialog from being displayed.
+    ///
+    /// By default, no sound is played.
+    pub fn sound(mut self, yes: bool) -> Self {
+        self.sound = yes;
+        self
+    }
+
+    /// Registers the dialog on the AT-SPI accessibility bus, so that screen readers like Orca
+    /// announce its title, message and buttons when it is displayed.
+    ///
+    /// The dialog is exposed as an alert containing a label and push buttons. Changes made while
+    /// it is displayed (with a [`DialogHandle`]) are not reflected. If the accessibility bus
+    /// isn't running, this is ignored.
+    ///
+    /// By default, the dialog isn't registered. Requires the `atspi` feature.
+    #[cfg(feature = "atspi")]
+    pub fn accessible(mut self, yes: bool) -> Self {
+        self.accessible = yes;
+        self
+    }
+
+    /// Displays the dialog on the X server `display` (like `:1` or `remote-host:0`), instead of
+    /// the one named by the `DISPLAY` environment variable.
+    ///
+    /// This has no effect on dialogs created with [`Session::alerta`], which use the X server of
+    /// their session.
+    pub fn display(mut self, display: impl Into<String>) -> Self {
+        self.display = Some(display.into());
+        self
+    }
+
+    /// Returns the title of the dialog, which defaults to one matching the icon.
+    fn resolved_title(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
+        match self.resolved_icon() {
+            Icon::Error => "Error",
+            Icon::Warning => "Warning",
+            Icon::Info => "Info",
+            Icon::Question => "Question",
+            Icon::None => "Message",
+        }
+        .into()
+    }
+
+    /// Returns the icon that will be displayed, which also determines the default title.
+    fn resolved_icon(&self) -> Icon {
+        if let Some(icon) = self.icon {
+            return icon;
+        }
+        if !self.auto_icon {
+            return Icon::Info;
+        }
+
+        let text = [&self.title, &self.message]
+            .into_iter()
+            .flatten()
+            .map(|s| s.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if text.contains("error") || text.contains("fail") {
+            Icon::Error
+        } else if text.contains("warn") {
+            Icon::Warning
+        } else if text.contains('?') {
+            Icon::Question
+        } else {
+            Icon::Info
+        }
+    }
+
+    /// Calls the function set with [`Builder::on_button`] for the button at `index`.
+    fn run_callback(&self, index: usize) {
+        if let Some((_, f)) = self.callbacks.iter().find(|(i, _)| *i == index) {
+            (f.lock().unwrap())();
+        }
+    }
+
+    /// Calls the function set with [`Builder::on_event`].
+    fn observe_event(&self, event: &WindowEvent) {
+        if let Some(f) = &self.on_event {
+            (f.lock().unwrap())(event);
+        }
+    }
+
+    /// Returns the labels of the buttons that will be displayed.
+    fn button_labels(&self) -> Vec<&str> {
+        match &self.buttons {
+            Some(labels) if !labels.is_empty() => labels.iter().map(|s| &**s).collect(),
+            Some(_) => ButtonPreset::Close.strings().to_vec(),
+            None => self.button_preset.strings().to_vec(),
+        }
+    }
+
+    /// Checks the settings of the dialog, without displaying it or connecting to the X server.
+    ///
+    /// Displaying the dialog performs the same checks first, so this is only needed to find
+    /// mistakes early. An error is returned if:
+    ///
+    /// - the index passed to [`default_button`][Self::default_button],
+    ///   [`on_button`][Self::on_button], [`button_icon`][Self::button_icon] or
+    ///   [`confirm_button`][Self::confirm_button] is out of range for the buttons. An empty list
+    ///   of [custom buttons][Self::buttons] displays a single "Close" button, so only index 0 is
+    ///   valid then.
+    /// - the dialog has both an [input field][Self::input] and [choices][Self::choices].
+    /// - the [scale factor][Self::scale] or the [font size][Self::font] isn't positive.
+    /// - an image file for the icon, a button or an animation frame doesn't exist, or RGBA pixel
+    ///   data doesn't match its size. The images aren't decoded here, so a file that exists but
+    ///   isn't a valid image is only reported when the dialog is displayed.
+    pub fn validate(&self) -> Result<(), Error> {
+        let buttons = self.button_labels();
+        if let Some(i) = self.default_button
+            && i >= buttons.len()
+        {
+            return Err(Error::new(format!(
+                "default button index {i} is out of range (the dialog has {} buttons)",
+                buttons.len()
+            )));
+        }
+        if let Some((i, _)) = self.callbacks.iter().find(|(i, _)| *i >= buttons.len()) {
+            return Err(Error::new(format!(
+                "callback button index {i} is out of range (the dialog has {} buttons)",
+                buttons.len()
+            )));
+        }
+        if let Some((i, _)) = self.button_icons.iter().find(|(i, _)| *i >= buttons.len()) {
+            return Err(Error::new(format!(
+                "button icon index {i} is out of range (the dialog has {} buttons)",
+                buttons.len()
+            )));
+        }
+        if let Some((i, _)) = self
+            .confirm_buttons
+            .iter()
+            .find(|(i, _)| *i >= buttons.len())
+        {
+            return Err(Error::new(format!(
+                "confirm button index {i} is out of range (the dialog has {} buttons)",
+                buttons.len()
+            )));
+        }
+        if self.input.is_some() && !self.choices.is_empty() {
+            return Err(Error::new(
+                "a dialog can't have both an input field and choices",
+            ));
+        }
+        if let Some(scale) = self.scale
+            && !(scale.is_finite() && scale > 0.0)
+        {
+            return Err(Error::new(format!("invalid scale factor {scale}")));
+        }
+        if let Some((_, size)) = &self.font
+            && !(size.is_finite() && *size > 0.0)
+        {
+            return Err(Error::new(format!("invalid font size {size}")));
+        }
+        let images = self.custom_icon.iter().chain(
+            self.button_icons
+                .iter()
+                .filter_map(|(_, icon)| match icon {
+                    ButtonIcon::Image(source) => Some(source),
+                    ButtonIcon::Icon(_) => None,
+                })
+                .chain(self.icon_frames.iter().map(|(_, frame)| frame)),
+        );
+        for image in images {
+            image.check()?;
+        }
+        Ok(())
+    }
+
+    /// Displays the dialog and blocks until the dialog is closed.
+    ///
+    /// Returns an [`Answer`] indicating which dialog button was clicked.
+    ///
+    /// # Errors
+    ///
+    /// See [`Builder::show_result`].
+    pub fn show(self) -> Result<Answer, Error> {
+        self.show_result().map(|res| res.answer)
+    }
+
+    /// Displays the dialog and blocks until the dialog is closed.
+    ///
+    /// This works like [`Builder::show`], but returns a [`DialogResult`] that contains the full
+    /// state of the dialog at the time it was closed, instead of just the [`Answer`].
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the settings are invalid (see [`Builder::validate`]), or if an
+    /// image can't be loaded.
+    /// An error may also occur when communicating with the X server.
+    pub fn show_result(self) -> Result<DialogResult, Error> {
+        self.run(None, false).map(|outcome| outcome.result)
+    }
+
+    /// Displays the dialog and blocks until the dialog is closed.
+    ///
+    /// This works like [`Builder::show`], but also reports where the dialog window was when it
+    /// was closed, for logging purposes.
+    ///
+    /// # Errors
+    ///
+    /// See [`Builder::show_result`].
+    pub fn show_with_report(self) -> Result<ShowReport, Error> {
+        let outcome = self.run(None, true)?;
+        Ok(ShowReport {
+            answer: outcome.result.answer,
+            geometry: outcome.geometry,
+            monitor: outcome.monitor,
+            theme: outcome.theme,
+        })
+    }
+
+    /// Displays the dialog and blocks until the dialog is closed.
+    ///
+    /// This works like [`Builder::show`], but also returns how long the dialog was visible before
+    /// it was answered. The time is measured from when the window is first drawn on screen, so it
+    /// doesn't include the time spent connecting to the X server and creating the window.
+    ///
+    /// If the dialog was answered before it was ever drawn, the duration is zero.
+    ///
+    /// # Errors
+    ///
+    /// See [`Builder::show_result`].
+    pub fn show_timed(self) -> Result<(Answer, Duration), Error> {
+        self.run(None, false)
+            .map(|outcome| (outcome.result.answer, outcome.visible_for))
+    }
+
+    /// Displays the dialog again and again, for as long as the user chooses to retry an operation
+    /// that keeps failing.
+    ///
+    /// This is meant for dialogs with [`ButtonPreset::RetryCancel`]. When the first button (Retry)
+    /// is pressed, `op` is called to retry the operation, and should return whether it succeeded.
+    /// If it didn't, the dialog is displayed again.
+    ///
+    /// Returns the [`Answer`] from the last time the dialog was displayed. It is
+    /// [`Answer::Button(0)`][Answer::Button] if the operation eventually succeeded.
+    ///
+    /// # Errors
+    ///
+    /// See [`Builder::show_result`].
+    pub fn show_retry(self, op: impl FnMut() -> bool) -> Result<Answer, Error> {
+        self.retry_with(Builder::show, op)
+    }
+
+    /// Runs the loop of [`Builder::show_retry`], displaying the dialog with `show`.
+    pub(crate) fn retry_with(
+        mut self,
+        mut show: impl FnMut(Builder) -> Result<Answer, Error>,
+        mut op: impl FnMut() -> bool,
+    ) -> Result<Answer, Error> {
+        // Only the final answer is reported.
+        let (on_answer, on_error) = (self.on_answer.take(), self.on_error.take());
+        let res = loop {
+            match show(self.clone()) {
+                Ok(answer) if answer.button() == Some(0) && !op() => {}
+                res => break res,
+            }
+        };
+        report(on_answer, on_error, res.as_ref());
+        res
+    }
+
+    /// Runs the dialog without displaying it, simulating the user actions in `script`.
+    ///
+    /// This doesn't need an X server, and is meant for testing code that creates dialogs. The
+    /// dialog is laid out like [`Builder::show`] would, but without any scale factor or theme from
+    /// the desktop, and the events of `script` are processed in order until one of them answers
+    /// it. The [timeout][Self::timeout] is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Like [`Builder::show`], this returns an error if the settings are invalid, or if the
+    /// custom icon can't be loaded. An error is also returned if the script clicks a button that
+    /// doesn't exist, or ends before the dialog is answered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alerta::{Answer, ButtonPreset, HeadlessEvent};
+    ///
+    /// let answer = alerta::alerta()
+    ///     .message("Delete the file?")
+    ///     .button_preset(ButtonPreset::YesNo)
+    ///     .show_headless(&[HeadlessEvent::ClickButton(1)])?;
+    /// assert_eq!(answer, Answer::Button(1));
+    /// # Ok::<_, alerta::Error>(())
+    /// ```
+    pub fn show_headless(self, script: &[HeadlessEvent]) -> Result<Answer, Error> {
+        let (on_answer, on_error) = (self.on_answer.clone(), self.on_error.clone());
+        let res = headless::run(self, script);
+        report(on_answer, on_error, res.as_ref());
+        res
+    }
+
+    /// Displays the dialog on a background thread, without blocking the calling thread.
+    ///
+    /// The returned [`DialogHandle`] can be used to wait for or poll the [`Answer`], or to close
+    /// the dialog.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the background thread could not be spawned.
+    /// Errors that occur while the dialog is displayed are reported by [`DialogHandle::answer`].
+    pub fn spawn(self) -> Result<DialogHandle, Error> {
+        DialogHandle::spawn(self)
+    }
+
+    /// Displays the dialog with a progress bar below the message, on a background thread.
+    ///
+    /// The returned [`ProgressDialog`] is used to update the progress bar and the message while
+    /// the operation is running. The dialog is closed once the handle is dropped or
+    /// [finished][ProgressDialog::finish].
+    ///
+    /// The dialog buttons stay active, so a button like "Cancel" can be used to let the user
+    /// abort the operation (see [`ProgressDialog::try_answer`]).
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the background thread could not be spawned.
+    /// Errors that occur while the dialog is displayed are reported by [`ProgressDialog::finish`].
+    pub fn progress(mut self) -> Result<ProgressDialog, Error> {
+        self.progress = Some(ui::Indicator::Bar(0.0));
+        ProgressDialog::spawn(self)
+    }
+
+    /// Displays the dialog with an animated spinner below the message, on a background thread.
+    ///
+    /// This is meant for operations whose duration isn't known; otherwise, use
+    /// [`Builder::progress`]. The dialog is closed once the returned [`BusyDialog`] is dropped or
+    /// [closed][BusyDialog::close].
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the background thread could not be spawned.
+    /// Errors that occur while the dialog is displayed are reported by [`BusyDialog::close`].
+    pub fn busy(mut self) -> Result<BusyDialog, Error> {
+        self.progress = Some(ui::Indicator::Spinner(0.0));
+        BusyDialog::spawn(self)
+    }
+
+    /// Computes the size of the dialog window in pixels, as `(width, height)`, without displaying
+    /// it.
+    ///
+    /// This doesn't need an X server. Since the scale factor of the display is not known without
+    /// one, only the [scale factor][Self::scale] set on the builder affects the size.
+    ///
+    /// # Errors
+    ///
+    /// Like [`Builder::show`], this returns an error if the settings are invalid, or if the
+    /// custom icon can't be loaded.
+    pub fn measure(&self) -> Result<(u32, u32), Error> {
+        let buttons = self.button_labels();
+        let options = self.ui_options()?;
+        // The theme doesn't affect the layout.
+        let ui = Ui::new(
+            self.resolved_icon(),
+            Theme::Light,
+            self.message.as_deref().unwrap_or_default(),
+            &buttons,
+            options,
+        );
+        Ok((ui.canvas.width() as u32, ui.canvas.height() as u32))
+    }
+
+    /// Replaces `ui` with one laid out at the scale factor `scale`, which keeps what the user
+    /// entered and the rest of its state.
+    fn rescale_ui(
+        &self,
+        ui: &mut Ui,
+        buttons: &[&str],
+        scale: f32,
+        shape: bool,
+    ) -> Result<(), Error> {
+        let mut options = self.ui_options()?;
+        options.scale = Some(scale);
+        if !shape {
+            options.corner_radius = 0;
+        }
+        let mut new = Ui::new(self.resolved_icon(), ui.theme(), "", buttons, options);
+        mem::swap(ui, &mut new);
+        ui.carry_over(new, self.resizable);
+        Ok(())
+    }
+
+    /// Checks the settings of the dialog (see [`Builder::validate`]), and returns the options for
+    /// its [`Ui`].
+    ///
+    /// The custom icon is loaded here, so this may fail if the icon can't be read. The scale
+    /// factor of the display is not taken into account.
+    fn ui_options(&self) -> Result<ui::Options, Error> {
+        self.validate()?;
+        let custom_icon = self
+            .custom_icon
+            .as_ref()
+            .map(image::Source::load)
+            .transpose()?;
+        let button_icons = self
+            .button_icons
+            .iter()
+            .filter_map(|(i, icon)| {
+                let image = match icon {
+                    ButtonIcon::Icon(icon) => Ok(icon.get()?),
+                    ButtonIcon::Image(source) => source.load(),
+                };
+                Some(image.map(|image| (*i, image)))
+            })
+            .collect::<Result<_, Error>>()?;
+        let icon_frames = self
+            .icon_frames
+            .iter()
+            .map(|(duration, frame)| Ok((*duration, frame.load()?)))
+            .collect::<Result<_, Error>>()?;
+
+        Ok(ui::Options {
+            custom_icon,
+            icon_frames,
+            icon_position: self.icon_position,
+            button_icons,
+            default_button: self.default_button,
+            title: self
+                .title_in_body
+                .unwrap_or(!self.decorated)
+                .then(|| self.resolved_title()),
+            detail: self.detail.clone(),
+            expandable_details: self.expandable_details.clone(),
+            footer: self.footer.clone(),
+            input: self.input.clone(),
+            password: self.password,
+            input_default: self.input_default.clone(),
+            select_input_default: self.select_input_default,
+            input_rows: self.input_rows.filter(|_| !self.password),
+            input_max_len: self.input_max_len,
+            input_filter: self.input_filter.clone(),
+            custom_draw: self.custom_draw.clone(),
+            custom_draw_size: self.custom_draw_size,
+            input_validate: self.input_validate.clone(),
+            checkbox: self.checkbox.clone(),
+            choices: self.choices.clone(),
+            multi_select: self.multi_select,
+            progress: self.progress,
+            copy_button: self.copy_button,
+            copy_shortcut: self.copy_shortcut,
+            callback_buttons: self.callbacks.iter().map(|(i, _)| *i).collect(),
+            confirm_buttons: self.confirm_buttons.clone(),
+            auto_mnemonics: !self
+                .buttons
+                .as_ref()
+                .is_some_and(|labels| !labels.is_empty()),
+            accent_color: self.accent_color,
+            max_height: self
+                .max_height
+                .map(|px| i32::try_from(px).unwrap_or(i32::MAX)),
+            max_lines: self.max_lines,
+            size: self.size,
+            max_width: self
+                .max_width
+                .map(|px| i32::try_from(px).unwrap_or(i32::MAX)),
+            scale: self.scale,
+            font_family: self.font.as_ref().map(|(family, _)| family.clone()),
+            font_size: self.font.as_ref().map(|(_, size)| *size),
+            markup: self.markup,
+            ansi: self.ansi,
+            detect_links: self.detect_links,
+            monospace: self.monospace,
+            tab_width: self.tab_width,
+            text_direction: self.text_direction,
+            wrap: self.wrap,
+            mirror_buttons: self.mirror_buttons != self.button_order.is_reversed(),
+            min_button_width: self.min_button_width,
+            padding: self.padding,
+            equal_button_widths: self.equal_button_widths,
+            corner_radius: self.corner_radius,
+        })
+    }
+
+    /// Runs the dialog with [`Builder::run_dialog`], and reports how it finished to the functions
+    /// set with [`Builder::on_answer`] and [`Builder::on_error`].
+    fn run(self, shared: Option<&Shared>, report_location: bool) -> Result<Outcome, Error> {
+        let (on_answer, on_error) = (self.on_answer.clone(), self.on_error.clone());
+        let res = self.run_dialog(shared, report_location);
+        let answer = res.as_ref().map(|outcome| &outcome.result.answer);
+        report(on_answer, on_error, answer);
+        res
+    }
+
+    /// Displays the dialog and runs its event loop until it is closed.
+    ///
+    /// If `shared` is provided, the dialog is closed with [`Answer::Closed`] once its `close` flag
+    /// is set, and its updates are applied as they come in. Its `waker` is set to wake up the
+    /// event loop when that happens.
+    ///
+    /// The geometry and the monitor of the window are only looked up for the outcome if
+    /// `report_location` is set, and are zero and `None` otherwise.
+    fn run_dialog(self, shared: Option<&Shared>, report_location: bool) -> Result<Outcome, Error> {
+        /// How often animations are redrawn.
+        const FRAME_INTERVAL: Duration = Duration::from_millis(60);
+        /// How long a confirm button waits for the second click.
+        const CONFIRM_TIMEOUT: Duration = Duration::from_secs(3);
+        /// How long to wait for the clipboard manager to take over the clipboard.
+        const CLIPBOARD_TIMEOUT: Duration = Duration::from_secs(1);
+
+        let buttons = self.button_labels();
+        let mut options = self.ui_options()?;
+
+        // Kept until the dialog closes, so that later dialogs with the same key raise this one.
+        let dedup = match &self.dedup_key {
+            Some(key) => match dedup::Registration::new(key) {
+                Some(registration) => Some(registration),
+                None => {
+                    debug!("a dialog with the key '{key}' is open already");
+                    return Ok(Outcome {
+                        result: DialogResult {
+                            answer: Answer::Closed(CloseReason::Duplicate),
+                            button_label: None,
+                            modifiers: Modifiers::default(),
+                            input: None,
+                            checkbox: None,
+                        },
+                        geometry: (0, 0, 0, 0),
+                        monitor: None,
+                        theme: self.explicit_theme().unwrap_or_default(),
+                        visible_for: Duration::ZERO,
+                    });
+                }
+            },
+            None => None,
+        };
+
+        let icon = self.resolved_icon();
+        let title = self.resolved_title();
+        #[cfg(feature = "atspi")]
+        let accessible_title = title.clone();
+
+        let conn = match &self.session {
+            Some(session) => session.conn.clone(),
+            None => x11::Connection::connect(self.display.as_deref())?,
+        };
+        // Sessions have waited for the X server for earlier dialogs already.
+        let round_trips = self
+            .session
+            .as_ref()
+            .map_or(0, |session| session.conn.round_trips());
+        options.scale = options.scale.or_else(|| conn.scale_factor());
+        if !conn.shape {
+            options.corner_radius = 0;
+        }
+        debug!("scale factor: {:?}", options.scale);
+        let mut scale = options.scale.unwrap_or(1.0);
+        let base_scale = scale;
+
+        // Detecting the theme runs external commands. Unless the theme is known already, the window
+        // is shown with the default theme, which is replaced once the detection has finished.
+        let known_theme = self
+            .explicit_theme()
+            .or_else(|| Theme::cached(self.theme_cache));
+        let mut ui = Ui::new(
+            icon,
+            known_theme.unwrap_or_default(),
+            self.message.as_deref().unwrap_or_default(),
+            &buttons,
+            options,
+        );
+
+        // The point followed across monitors is at a fixed offset from the window position, so
+        // that resizing the window for a new scale factor doesn't move it back to the old monitor.
+        let center = (ui.canvas.width() / 2, ui.canvas.height() / 2);
+        let mut monitors = None;
+
+        let remembered = self.remember_position.as_deref().and_then(positions::load);
+        let position = match (self.position, remembered, self.center) {
+            (Some((x, y)), _, _) => x11::Position::At(x, y),
+            (None, Some((x, y)), _) => x11::Position::Restored(x, y),
+            (None, None, true) => x11::Position::Centered,
+            (None, None, false) => x11::Position::Auto,
+        };
+        let win = X11Window::create(
+            conn.clone(),
+            ui.canvas.width() as u16,
+            ui.canvas.height() as u16,
+            &x11::WindowOptions {
+                position,
+                always_on_top: self.always_on_top,
+                resizable: self.resizable.then(|| {
+                    let min = ui.min_size();
+                    (min.width as u16, min.height as u16)
+                }),
+                transient_for: self.transient_for,
+                modal: self.modal,
+                urgent: self.urgent,
+                decorated: self.decorated,
+                class: self.app_id.clone(),
+                role: self.window_role.clone(),
+                window_type: self.window_type,
+                grab_focus: self.grab_focus,
+                opacity: self.opacity,
+                icons: self.window_icon.unwrap_or(icon).window_icons(),
+            },
+        )?
+        .with_title(title)?;
+
+        if let Some(shape) = ui.window_shape() {
+            win.set_shape(&shape)?;
+        }
+        present(&mut ui, &win)?;
+        if let Some(shared) = shared {
+            *shared.waker.lock().unwrap() = Some(win.waker());
+        }
+        if let Some(dedup) = &dedup {
+            dedup.set_waker(win.waker());
+        }
+
+        let previous_focus = if self.restore_focus {
+            win.previous_focus()
+        } else {
+            None
+        };
+        win.show()?;
+        debug!(
+            "dialog shown after {} round-trips to the X server",
+            conn.round_trips() - round_trips
+        );
+        if let Some(shared) = shared {
+            *shared.window_id.lock().unwrap() = Some(win.id());
+        }
+        if self.sound {
+            play_sound(icon, &win);
+        }
+        // Kept until the dialog closes, which removes it from the accessibility bus.
+        #[cfg(feature = "atspi")]
+        let _accessible = self.accessible.then(|| {
+            atspi::Registration::start(atspi::Dialog {
+                title: accessible_title,
+                message: ui.message_text().to_string(),
+                buttons: buttons
+                    .iter()
+                    .map(|label| ui::mnemonic::parse(label).text)
+                    .collect(),
+                focused: ui.focused_button(),
+            })
+        });
+
+        let detected_theme = if known_theme.is_none() {
+            let (sender, receiver) = mpsc::channel();
+            let (waker, use_cache) = (win.waker(), self.theme_cache);
+            let res = thread::Builder::new()
+                .name("alerta-theme".into())
+                .spawn(move || {
+                    sender.send(Theme::detect_cached(use_cache)).ok();
+                    waker.wake();
+                });
+            match res {
+                Ok(_) => Some(receiver),
+                Err(e) => {
+                    warn!("failed to detect the theme in the background: {e}");
+                    ui.set_theme(Theme::detect_cached(self.theme_cache));
+                    present(&mut ui, &win)?;
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // The monitor is best-effort: if `dbus-monitor` isn't available, the theme just stays
+        // fixed.
+        let theme_monitor = (self.explicit_theme().is_none() && self.follow_system_theme)
+            .then(|| {
+                ThemeMonitor::spawn(win.waker())
+                    .inspect_err(|e| warn!("failed to watch for theme changes: {e}"))
+                    .ok()
+            })
+            .flatten();
+
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let countdown_button = self.default_button.unwrap_or(0);
+        // Number of seconds currently displayed on the countdown button.
+        let mut countdown = None;
+
+        // The armed confirm button, and when it reverts to its original label.
+        let mut confirm_deadline: Option<(usize, Instant)> = None;
+        // When the caret of the input field is shown or hidden next.
+        let mut caret_blink = None;
+
+        let mut last_frame = Instant::now();
+        // When the window was first drawn, which is when it became visible.
+        let mut shown_at = None;
+
+        let mut pressed = false;
+        let result = (|| -> Result<Answer, Error> {
+            Ok('outer: loop {
+                if let Some(shared) = shared {
+                    if shared.close.load(Ordering::Relaxed) {
+                        break Answer::Closed(CloseReason::External);
+                    }
+                    let update = mem::take(&mut *shared.update.lock().unwrap());
+                    if let Some(text) = &update.message {
+                        ui.set_message(text);
+                    }
+                    if let Some(fraction) = update.progress {
+                        ui.set_progress(fraction);
+                    }
+                    if update.message.is_some() || update.progress.is_some() {
+                        ui.redraw();
+                        present(&mut ui, &win)?;
+                    }
+                }
+                if dedup.as_ref().is_some_and(dedup::Registration::take_raise) {
+                    win.activate()?;
+                }
+                if let Some(theme) = detected_theme.as_ref().and_then(|r| r.try_recv().ok()) {
+                    ui.set_theme(theme);
+                    present(&mut ui, &win)?;
+                }
+                if let Some(theme) = theme_monitor.as_ref().and_then(ThemeMonitor::poll) {
+                    ui.set_theme(theme);
+                    present(&mut ui, &win)?;
+                }
+                if let Some(deadline) = deadline {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        break Answer::TimedOut;
+                    }
+                    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+                    if countdown != Some(secs) {
+                        countdown = Some(secs);
+                        let label = format!("{} ({secs})", buttons[countdown_button]);
+                        ui.set_button_label(countdown_button, &label);
+                        ui.redraw();
+                        present(&mut ui, &win)?;
+                    }
+                }
+                if ui.is_animated() {
+                    let now = Instant::now();
+                    if now >= last_frame + FRAME_INTERVAL {
+                        ui.animate(now - last_frame);
+                        last_frame = now;
+                        ui.redraw();
+                        present(&mut ui, &win)?;
+                    }
+                }
+                if let Some((_, at)) = confirm_deadline
+                    && Instant::now() >= at
+                {
+                    confirm_deadline = None;
+                    ui.disarm();
+                    ui.redraw();
+                    present(&mut ui, &win)?;
+                }
+                if ui.take_caret_reset() {
+                    caret_blink = Some(Instant::now() + ui::CARET_BLINK_INTERVAL);
+                } else if let Some(at) = caret_blink
+                    && Instant::now() >= at
+                {
+                    caret_blink = Some(at + ui::CARET_BLINK_INTERVAL);
+                    ui.blink_caret();
+                    ui.redraw();
+                    present(&mut ui, &win)?;
+                }
+                // Wake up when the displayed number of seconds has to change.
+                let mut wakeup = deadline
+                    .zip(countdown)
+                    .map(|(deadline, secs)| deadline - Duration::from_secs(secs - 1));
+                if ui.is_animated() {
+                    let frame = last_frame + FRAME_INTERVAL;
+                    wakeup = Some(wakeup.map_or(frame, |wakeup| cmp::min(wakeup, frame)));
+                }
+                if let Some((_, at)) = confirm_deadline {
+                    wakeup = Some(wakeup.map_or(at, |wakeup| cmp::min(wakeup, at)));
+                }
+                if let Some(at) = caret_blink {
+                    wakeup = Some(wakeup.map_or(at, |wakeup| cmp::min(wakeup, at)));
+                }
+
+                let mut process_event = |event| {
+                    trace!("{event:?}");
+                    self.observe_event(&event);
+                    let left_press = matches!(event, WindowEvent::ButtonPress(MouseButton::Left));
+                    match &event {
+                        WindowEvent::CursorMove(..) if pressed => {
+                            win.start_drag().ok();
+                        }
+                        WindowEvent::ButtonRelease(MouseButton::Left) => pressed = false,
+                        WindowEvent::SelectionRequest(SelectionRequest(req)) => {
+                            win.answer_selection_request(req).ok();
+                        }
+                        WindowEvent::RedrawRequested => {
+                            shown_at.get_or_insert_with(Instant::now);
+                        }
+                        WindowEvent::Moved { x, y } if self.scale.is_none() => {
+                            let monitors = monitors.get_or_insert_with(|| win.monitor_scales());
+                            let new_scale = monitors
+                                .relative_scale((x + center.0, y + center.1))
+                                .map(|factor| base_scale * factor);
+                            // Only an actual change lays out the dialog again, not every move.
+                            if let Some(new_scale) = new_scale
+                                && new_scale != scale
+                            {
+                                debug!("scale factor of the new monitor: {new_scale}");
+                                match self.rescale_ui(&mut ui, &buttons, new_scale, conn.shape) {
+                                    Ok(()) => {
+                                        scale = new_scale;
+                                        // The countdown label is set again.
+                                        countdown = None;
+                                    }
+                                    Err(e) => warn!("failed to adapt to the scale factor: {e}"),
+                                }
+                            }
+                        }
+                        // Fixed-size dialogs keep their layout, even if the window manager resizes
+                        // them.
+                        WindowEvent::Resized { .. } if !self.resizable => return None,
+                        _ => {}
+                    }
+                    let mut resized = matches!(event, WindowEvent::Resized { .. });
+                    let answer = ui.process_event(event);
+                    if let Some(size) = ui.take_resize_request() {
+                        let min = ui.min_size();
+                        win.resize(
+                            size.width as u16,
+                            size.height as u16,
+                            self.resizable
+                                .then_some((min.width as u16, min.height as u16)),
+                        )
+                        .ok();
+                        resized = true;
+                    }
+                    if resized && let Some(shape) = ui.window_shape() {
+                        win.set_shape(&shape).ok();
+                    }
+                    if left_press {
+                        // Pressing the mouse on the message selects text instead of dragging the
+                        // window.
+                        pressed = !ui.is_selecting()
+                            && match self.drag_mode {
+                                DragMode::Anywhere => true,
+                                DragMode::TitleAreaOnly => ui.cursor_in_title_area(),
+                                DragMode::Disabled => false,
+                            };
+                    }
+                    win.set_cursor(ui.cursor_shape()).ok();
+                    if let Some(text) = ui.take_copied_text() {
+                        win.set_clipboard(text).ok();
+                    }
+                    if let Some(selection) = ui.take_paste_request() {
+                        win.request_selection(selection).ok();
+                    }
+                    if ui.take_bell_request() {
+                        win.bell().ok();
+                    }
+                    if let Some(url) = ui.take_opened_link() {
+                        open_link(&url);
+                    }
+                    if let Some(index) = ui.take_callback_button() {
+                        self.run_callback(index);
+                    }
+                    answer
+                };
+
+                let Some(event) = win.wait_for_event_until(wakeup)? else {
+                    continue;
+                };
+                if let Some(answer) = process_event(event) {
+                    break answer;
+                }
+                // Batch all pending events together to limit the number of redraws.
+                while let Some(event) = win.poll_for_event()? {
+                    if let Some(answer) = process_event(event) {
+                        break 'outer answer;
+                    }
+                }
+
+                let armed = ui.armed_button();
+                if armed != confirm_deadline.map(|(i, _)| i) {
+                    confirm_deadline = armed.map(|i| (i, Instant::now() + CONFIRM_TIMEOUT));
+                }
+
+                if ui.needs_redraw() {
+                    ui.redraw();
+                }
+                present(&mut ui, &win)?;
+            })
+        })();
+        // An X server that goes away (for example because the session ended) closes the dialog.
+        let disconnected = result.as_ref().is_err_and(Error::is_disconnect);
+        let answer = match result {
+            Ok(answer) => answer,
+            Err(_) if disconnected => Answer::Closed(CloseReason::External),
+            Err(e) => return Err(e),
+        };
+        // A theme detected after the last redraw is still the one the dialog was meant to have.
+        let theme = match detected_theme.as_ref().and_then(|r| r.try_recv().ok()) {
+            Some(theme) => theme,
+            None => ui.theme(),
+        };
+        let visible_for = shown_at.map_or(Duration::ZERO, |shown_at| shown_at.elapsed());
+
+        let input = match &answer {
+            Answer::Input { text, .. } => Some(text.clone()),
+            _ => None,
+        };
+        let (geometry, monitor) = if disconnected {
+            ((0, 0, 0, 0), None)
+        } else {
+            // The dialog has been answered already, so failing to look up where it was only
+            // leaves the location out.
+            let geometry = (report_location || self.remember_position.is_some())
+                .then(|| {
+                    win.geometry()
+                        .inspect_err(|e| warn!("failed to query the dialog geometry: {e}"))
+                        .ok()
+                })
+                .flatten();
+            if let Some(key) = &self.remember_position
+                && let Some((x, y, ..)) = geometry
+                && let Err(e) = positions::store(key, (x, y))
+            {
+                warn!("failed to remember the dialog position: {e}");
+            }
+            if let Err(e) = win.save_clipboard(CLIPBOARD_TIMEOUT) {
+                warn!("failed to hand the clipboard over to the clipboard manager: {e}");
+            }
+            if let Some(window) = previous_focus {
+                win.restore_focus(window).ok();
+            }
+            let monitor = geometry
+                .filter(|_| report_location)
+                .and_then(|(x, y, w, h)| win.output_at(x + w as i32 / 2, y + h as i32 / 2));
+            (
+                geometry.filter(|_| report_location).unwrap_or_default(),
+                monitor,
+            )
+        };
+        Ok(Outcome {
+            result: DialogResult {
+                button_label: answer.label(&buttons),
+                answer,
+                modifiers: win.modifiers(),
+                input,
+                checkbox: ui.checkbox_checked(),
+            },
+            geometry,
+            monitor,
+            theme,
+            visible_for,
+        })
+    }
+
+    /// Returns the theme set with [`Builder::theme`], unless it is to be detected.
+    fn explicit_theme(&self) -> Option<Theme> {
+        self.theme.filter(|theme| *theme != Theme::System)
+    }
+}
+
+/// Calls `on_answer` or `on_error` with the result of a dialog, unless one of them has been
+/// called already.
+fn report(
+    on_answer: Option<AnswerCallback>,
+    on_error: Option<ErrorCallback>,
+    res: Result<&Answer, &Error>,
+) {
+    fn take<T>(f: Option<Arc<Mutex<Option<T>>>>) -> Option<T> {
+        f.and_then(|f| f.lock().unwrap().take())
+    }
+    let (on_answer, on_error) = (take(on_answer), take(on_error));
+    match res {
+        Ok(answer) => {
+            if let Some(f) = on_answer {
+                f(answer.clone());
+            }
+        }
+        Err(e) => {
+            if let Some(f) = on_error {
+                f(e);
+            }
+        }
+    }
+}
+
+/// Copies the parts of the canvas of `ui` that changed since the last call to the window.
+fn present(ui: &mut Ui, win: &X11Window) -> Result<(), Error> {
+    if let Some(rect) = ui.take_damage() {
+        win.set_contents_region(&ui.canvas, rect)?;
+    }
+    Ok(())
+}
+
+/// Everything [`Builder::run`] found out about the dialog.
+struct Outcome {
+    result: DialogResult,
+    geometry: (i32, i32, u32, u32),
+    monitor: Option<String>,
+    theme: Theme,
+    /// Time from when the window was first drawn until it was answered.
+    visible_for: Duration,
+}
+
+/// The answer to a dialog and where it was displayed.
+///
+/// Returned by [`Builder::show_with_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ShowReport {
+    /// The button that was pressed, or [`Answer::Closed`] if the dialog was closed by other means.
+    pub answer: Answer,
+
+    /// Position and size of the dialog window on the screen when it was closed, as
+    /// `(x, y, width, height)` in pixels.
+    ///
+    /// The position is that of the window contents, without the frame drawn by the window manager.
+    /// If the connection to the X server was lost, or the geometry couldn't be queried, this is
+    /// all zeros.
+    pub geometry: (i32, i32, u32, u32),
+
+    /// Name of the RandR output (like `eDP-1` or `HDMI-1`) showing the center of the dialog.
+    ///
+    /// This is `None` if the X server doesn't support RandR, or the center of the dialog wasn't
+    /// on any monitor.
+    pub monitor: Option<String>,
+
+    /// The theme the dialog was displayed in when it was closed.
+    ///
+    /// This is never [`Theme::System`]: if the theme was detected, this is the detected theme. A
+    /// dialog closed before the detection has finished reports the [`Theme::Light`] it was
+    /// displayed in until then.
+    pub theme: Theme,
+}
+
+/// The full state of a dialog at the time it was closed.
+///
+/// Returned by [`Builder::show_result`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DialogResult {
+    /// The button that was pressed, or [`Answer::Closed`] if the dialog was closed by other means.
+    pub answer: Answer,
+
+    /// The label of the button that was pressed, or `None` if the dialog was closed without
+    /// pressing a button.
+    pub button_label: Option<String>,
+
+    /// The keyboard modifiers that were held down when the dialog was closed.
+    ///
+    /// This allows treating Shift+Click or Ctrl+Click on a button differently from a plain click.
+    pub modifiers: Modifiers,
+
+    /// The contents of the [input field][Builder::input].
+    ///
+    /// This is `None` if the dialog has no input field, or if it was closed without pressing a
+    /// button.
+    pub input: Option<String>,
+
+    /// Whether the [checkbox][Builder::checkbox] was checked when the dialog was closed.
+    ///
+    /// This is `None` if the dialog has no checkbox.
+    pub checkbox: Option<bool>,
+}
+
+/// Set of keyboard modifier keys.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    bits: u8,
+}
+
+impl Modifiers {
+    const SHIFT: u8 = 1 << 0;
+    const CONTROL: u8 = 1 << 1;
+    const ALT: u8 = 1 << 2;
+    const LOGO: u8 = 1 << 3;
+
+    /// Returns whether a Shift key is held down.
+    pub fn shift(&self) -> bool {
+        self.bits & Self::SHIFT != 0
+    }
 
-        let mut pressed = false;
-        loop {
-            let mut process_event = |event| {
-                match event {
-                    WindowEvent::CursorMove(..) if pressed => {
-                        win.start_drag().ok();
-                    }
-                    WindowEvent::ButtonPress(MouseButton::Left) => pressed = true,
-                    WindowEvent::ButtonRelease(MouseButton::Left) => pressed = false,
-                    _ => {}
-                }
-                ui.process_event(event)
-            };
+    /// Returns whether a Control key is held down.
+    pub fn control(&self) -> bool {
+        self.bits & Self::CONTROL != 0
+    }
 
-            let event = win.wait_for_event()?;
-            if let Some(answer) = process_event(event) {
-                return Ok(answer);
-            }
-            // Batch all pending events together to limit the number of redraws.
-            while let Some(event) = win.poll_for_event()? {
-                if let Some(answer) = process_event(event) {
-                    return Ok(answer);
-                }
-            }
+    /// Returns whether an Alt key is held down.
+    pub fn alt(&self) -> bool {
+        self.bits & Self::ALT != 0
+    }
 
-            ui.redraw();
-            win.set_contents(&ui.canvas)?;
-        }
+    /// Returns whether a "logo" key (also called Super, Windows or Command key) is held down.
+    pub fn logo(&self) -> bool {
+        self.bits & Self::LOGO != 0
     }
 }
 
 /// A user response to a dialog.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Answer {
-    /// The dialog window was closed by the OS.
+    /// The dialog window was closed without clicking one of the dialog buttons.
     ///
-    /// This happens when the user clicks the close button in the window frame, presses Alt+F4
-    /// (if the desktop environment is configured that way), or when some other mechanism causes the
-    /// window manager or compositor to close the window.
-    Closed,
+    /// The payload tells how the window was closed.
+    Closed(CloseReason),
 
     /// One of the dialog buttons was pressed.
     ///
     /// The 0-based button index is provided in the payload.
     Button(usize),
+
+    /// The dialog was closed automatically because its [timeout][Builder::timeout] expired.
+    TimedOut,
+
+    /// One of the dialog buttons was pressed in a dialog with an [input field][Builder::input].
+    ///
+    /// Pressing Enter in the input field activates the [default button][Builder::default_button],
+    /// or the first button if there is no default button.
+    Input {
+        /// The 0-based index of the button that was pressed.
+        button: usize,
+        /// The text that was entered into the input field.
+        text: String,
+    },
+
+    /// One of the dialog buttons was pressed in a dialog with [choices][Builder::choices].
+    Selected {
+        /// The 0-based index of the option that was selected.
+        index: usize,
+        /// The 0-based index of the button that was pressed.
+        button: usize,
+    },
+
+    /// One of the dialog buttons was pressed in a dialog with
+    /// [multiple choices][Builder::multi_choices].
+    MultiSelected {
+        /// The 0-based indices of the options that were checked, in ascending order.
+        indices: Vec<usize>,
+        /// The 0-based index of the button that was pressed.
+        button: usize,
+    },
+
+    /// The message was copied to the clipboard with the [copy shortcut][Builder::copy_shortcut],
+    /// which also closed the dialog.
+    CopiedAndClosed,
+}
+
+impl Answer {
+    /// Returns the 0-based index of the button that was pressed, if any.
+    pub fn button(&self) -> Option<usize> {
+        match self {
+            Answer::Button(i)
+            | Answer::Input { button: i, .. }
+            | Answer::Selected { button: i, .. }
+            | Answer::MultiSelected { button: i, .. } => Some(*i),
+            Answer::Closed(_) | Answer::TimedOut | Answer::CopiedAndClosed => None,
+        }
+    }
+
+    /// Returns the label of the button that was pressed, looked up in `buttons`.
+    ///
+    /// `buttons` should be the list of labels passed to [`Builder::buttons`]. The label is returned
+    /// as displayed, without the `&` that marks its mnemonic, like in
+    /// [`DialogResult::button_label`]. Returns `None` if no button was pressed.
+    ///
+    /// ```
+    /// use alerta::{Answer, CloseReason};
+    ///
+    /// let buttons = ["&Save", "Discard"];
+    /// assert_eq!(Answer::Button(0).label(&buttons).as_deref(), Some("Save"));
+    /// assert_eq!(Answer::Button(1).label(&buttons).as_deref(), Some("Discard"));
+    /// assert_eq!(Answer::Closed(CloseReason::Keyboard).label(&buttons), None);
+    /// ```
+    pub fn label(&self, buttons: &[impl AsRef<str>]) -> Option<String> {
+        let label = buttons.get(self.button()?)?;
+        Some(ui::mnemonic::parse(label.as_ref()).text)
+    }
+}
+
+/// How a dialog was closed without pressing one of its buttons (see [`Answer::Closed`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum CloseReason {
+    /// The window manager asked the window to close.
+    ///
+    /// This happens when the user clicks the close button in the window frame, presses Alt+F4 (if
+    /// the desktop environment is configured that way), or when some other mechanism causes the
+    /// window manager or compositor to close the window.
+    WmCloseButton,
+    /// The user pressed Escape.
+    Keyboard,
+    /// The dialog was closed by the application through its [`DialogHandle`] (or another handle
+    /// type), or the connection to the X server was lost.
+    External,
+    /// The dialog wasn't displayed, because a dialog with the same [dedup
+    /// key][Builder::dedup_key] was open already, which was raised instead.
+    Duplicate,
 }
 
 /// Presets of button groups.
@@ -189,13 +2547,16 @@ pub enum Answer {
 /// These presets define a couple of well-established button combinations, in the order that users
 /// expect.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum ButtonPreset {
+    AbortRetryIgnore,
     #[default]
     Close,
     Ok,
     OkCancel,
     RetryCancel,
+    SaveDontSaveCancel,
     YesNo,
     YesNoCancel,
 }
@@ -203,10 +2564,12 @@ pub enum ButtonPreset {
 impl ButtonPreset {
     fn strings(&self) -> &[&str] {
         match self {
+            ButtonPreset::AbortRetryIgnore => &["Abort", "Retry", "Ignore"],
             ButtonPreset::Close => &["Close"],
             ButtonPreset::Ok => &["OK"],
             ButtonPreset::OkCancel => &["OK", "Cancel"],
             ButtonPreset::RetryCancel => &["Retry", "Cancel"],
+            ButtonPreset::SaveDontSaveCancel => &["Save", "Don't Save", "Cancel"],
             ButtonPreset::YesNo => &["Yes", "No"],
             ButtonPreset::YesNoCancel => &["Yes", "No", "Cancel"],
         }
@@ -218,10 +2581,12 @@ impl FromStr for ButtonPreset {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
+            "abortretryignore" => Self::AbortRetryIgnore,
             "close" => Self::Close,
             "ok" => Self::Ok,
             "okcancel" => Self::OkCancel,
             "retrycancel" => Self::RetryCancel,
+            "savedontsavecancel" => Self::SaveDontSaveCancel,
             "yesno" => Self::YesNo,
             "yesnocancel" => Self::YesNoCancel,
             _ => return Err(InvalidValue { _p: () }),
@@ -231,6 +2596,7 @@ impl FromStr for ButtonPreset {
 
 /// The icon to display in the dialog.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Icon {
     Error,
@@ -238,6 +2604,8 @@ pub enum Icon {
     #[default]
     Info,
     Question,
+    /// No icon. The message takes up the space of the icon.
+    None,
 }
 
 impl FromStr for Icon {
@@ -249,39 +2617,373 @@ impl FromStr for Icon {
             "warning" => Self::Warning,
             "info" => Self::Info,
             "question" => Self::Question,
+            "none" => Self::None,
             _ => return Err(InvalidValue { _p: () }),
         })
     }
 }
 
+/// Replaces the `{name}` placeholders in `template` (see [`Builder::message_template`]).
+pub(crate) fn fill_template(template: &str, args: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        filled.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            filled.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        if rest.starts_with('{')
+            && let Some(end) = rest.find('}')
+            && let Some((_, value)) = args.iter().find(|(name, _)| *name == &rest[1..end])
+        {
+            filled.push_str(value);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        filled.push_str(&rest[..1]);
+        rest = &rest[1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Window size and position parsed from an X11 geometry specification.
+type Geometry = (Option<(u32, u32)>, Option<(x11::Offset, x11::Offset)>);
+
+/// Parses an X11 geometry specification (see [`Builder::geometry`]), returning `None` if it is
+/// invalid.
+pub(crate) fn parse_geometry(spec: &str) -> Option<Geometry> {
+    let (size, offsets) = spec.split_at(spec.find(['+', '-']).unwrap_or(spec.len()));
+    let size = if size.is_empty() {
+        None
+    } else {
+        let (width, height) = size.split_once(['x', 'X'])?;
+        let px = |s: &str| s.parse::<u32>().ok().filter(|&px| px > 0);
+        Some((px(width)?, px(height)?))
+    };
+    /// Returns the offset at the start of `s`, and the rest of `s`.
+    fn offset(s: &str) -> Option<(x11::Offset, &str)> {
+        let end = s.get(1..)?.find(['+', '-']).map_or(s.len(), |i| i + 1);
+        let value = s[1..end].parse::<i32>().ok()?;
+        let offset = match s.as_bytes()[0] {
+            b'+' => x11::Offset::Start(value),
+            _ => x11::Offset::End(value),
+        };
+        Some((offset, &s[end..]))
+    }
+    let position = if offsets.is_empty() {
+        None
+    } else {
+        let (x, rest) = offset(offsets)?;
+        let (y, rest) = offset(rest)?;
+        if !rest.is_empty() {
+            return None;
+        }
+        Some((x, y))
+    };
+    (size.is_some() || position.is_some()).then_some((size, position))
+}
+
+/// Opens `url` in the default browser, without waiting for it.
+fn open_link(url: &str) {
+    let opener = Command::new("xdg-open")
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match opener {
+        Ok(mut opener) => {
+            thread::spawn(move || opener.wait());
+        }
+        Err(e) => warn!("failed to run xdg-open: {e}"),
+    }
+}
+
+/// Plays the sound-theme event for `icon`, falling back to the X11 bell.
+///
+/// This is best-effort, so errors are ignored.
+fn play_sound(icon: Icon, win: &X11Window) {
+    let player = Command::new("canberra-gtk-play")
+        .args(["--id", icon.sound_name()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match player {
+        // Reap the player in the background, so that it doesn't delay the dialog.
+        Ok(mut player) => {
+            thread::spawn(move || player.wait());
+        }
+        Err(e) => {
+            warn!("failed to run canberra-gtk-play, ringing the bell instead: {e}");
+            win.bell().ok();
+        }
+    }
+}
+
 impl Icon {
-    fn get(self) -> DrawTarget {
+    /// Returns the name of the matching event in the freedesktop.org sound theme.
+    fn sound_name(self) -> &'static str {
+        match self {
+            Icon::Error => "dialog-error",
+            Icon::Warning => "dialog-warning",
+            Icon::Info => "dialog-information",
+            Icon::Question => "dialog-question",
+            Icon::None => "bell",
+        }
+    }
+
+    /// Returns the image at the sizes offered to the window manager for the window icon.
+    ///
+    /// [`Icon::None`] has no images, which leaves the choice of the icon to the window manager.
+    fn window_icons(self) -> Vec<DrawTarget> {
+        [1.0, 0.5, 0.25]
+            .into_iter()
+            .filter_map(|factor| Some(ui::scale_image(self.get()?, factor)))
+            .collect()
+    }
+
+    /// Returns the image of the icon, or `None` for [`Icon::None`].
+    fn get(self) -> Option<DrawTarget> {
         let src: &[u8] = match self {
             Icon::Error => include_bytes!("../3rdparty/icons/dialog-error.qoi"),
             Icon::Warning => include_bytes!("../3rdparty/icons/dialog-warning.qoi"),
             Icon::Info => include_bytes!("../3rdparty/icons/dialog-information.qoi"),
             Icon::Question => include_bytes!("../3rdparty/icons/dialog-question.qoi"),
+            Icon::None => return None,
         };
 
-        let mut qoi = Qoi::decode_header(src).unwrap();
-        qoi.colors = rapid_qoi::Colors::Rgba;
+        Some(image::decode(src).unwrap())
+    }
+}
+
+/// Base direction of the message text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TextDirection {
+    /// The direction is inferred from the first character with a strong direction (like a letter).
+    #[default]
+    Auto,
+    /// Left-to-right.
+    Ltr,
+    /// Right-to-left, like Arabic or Hebrew. The message is aligned to the right.
+    Rtl,
+}
+
+impl FromStr for TextDirection {
+    type Err = InvalidValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "auto" => Self::Auto,
+            "ltr" => Self::Ltr,
+            "rtl" => Self::Rtl,
+            _ => return Err(InvalidValue { _p: () }),
+        })
+    }
+}
+
+/// How the message is broken into lines that fit the dialog (see [`Builder::wrap`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WrapMode {
+    /// Lines are broken between words. A word that is too long for the
+    /// [maximum width][Builder::max_width] of the dialog is broken between characters.
+    #[default]
+    Word,
+    /// Lines are broken between any two characters.
+    Char,
+    /// Lines are only broken at the line breaks in the message.
+    ///
+    /// The dialog grows to fit the longest line, up to its [maximum width][Builder::max_width].
+    /// Beyond that, the message can be scrolled horizontally.
+    None,
+}
+
+impl FromStr for WrapMode {
+    type Err = InvalidValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "word" => Self::Word,
+            "char" => Self::Char,
+            "none" => Self::None,
+            _ => return Err(InvalidValue { _p: () }),
+        })
+    }
+}
+
+/// How ANSI escape sequences in the message are handled (see [`Builder::ansi`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnsiMode {
+    /// The escape sequences are removed, and the message is displayed as plain text.
+    #[default]
+    Strip,
+    /// The escape sequences are removed, and the bold, italic and foreground colors selected by
+    /// SGR sequences are applied to the text. Background colors and other attributes are ignored.
+    Render,
+}
+
+impl FromStr for AnsiMode {
+    type Err = InvalidValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "strip" => Self::Strip,
+            "render" => Self::Render,
+            _ => return Err(InvalidValue { _p: () }),
+        })
+    }
+}
+
+/// Where affirmative buttons are displayed (see [`Builder::button_order`]).
+///
+/// The affirmative buttons are the ones that come first in a [`ButtonPreset`], like "OK" and
+/// "Yes".
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ButtonOrder {
+    /// The order that is customary on the desktop environment: [`ButtonOrder::AffirmativeRight`]
+    /// on GNOME and other GTK-based desktops, [`ButtonOrder::AffirmativeLeft`] elsewhere.
+    ///
+    /// The desktop environment is taken from the `XDG_CURRENT_DESKTOP` environment variable.
+    Platform,
+    /// The buttons are displayed from left to right, in the order they are listed in.
+    #[default]
+    AffirmativeLeft,
+    /// The buttons are displayed from right to left, so that the affirmative button is last.
+    AffirmativeRight,
+}
 
-        let mut target = DrawTarget::new(qoi.width as _, qoi.height as _);
-        Qoi::decode(src, target.get_data_u8_mut()).unwrap();
+impl ButtonOrder {
+    /// Returns whether the buttons are displayed in reverse order.
+    fn is_reversed(self) -> bool {
+        match self {
+            ButtonOrder::Platform => {
+                let desktops = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+                let order = Self::for_desktop(&desktops);
+                debug!("using {order:?} button order for desktop '{desktops}'");
+                order.is_reversed()
+            }
+            ButtonOrder::AffirmativeLeft => false,
+            ButtonOrder::AffirmativeRight => true,
+        }
+    }
 
-        // RGBA -> ARGB and premultiply.
-        for p in target.get_data_mut() {
-            let [mut r, mut g, mut b, a] = p.to_ne_bytes().map(u32::from);
-            r = r * a / 255;
-            g = g * a / 255;
-            b = b * a / 255;
-            *p = (a << 24) | (r << 16) | (g << 8) | b;
+    /// Returns the customary button order on the desktop environments in `desktops`, which is a
+    /// colon-separated list in the format of `XDG_CURRENT_DESKTOP`.
+    pub(crate) fn for_desktop(desktops: &str) -> ButtonOrder {
+        const GTK_DESKTOPS: &[&str] = &[
+            "GNOME",
+            "Unity",
+            "Pantheon",
+            "Budgie",
+            "Cinnamon",
+            "X-Cinnamon",
+            "MATE",
+            "XFCE",
+        ];
+        if desktops
+            .split(':')
+            .any(|desktop| GTK_DESKTOPS.iter().any(|d| d.eq_ignore_ascii_case(desktop)))
+        {
+            ButtonOrder::AffirmativeRight
+        } else {
+            ButtonOrder::AffirmativeLeft
         }
+    }
+}
+
+impl FromStr for ButtonOrder {
+    type Err = InvalidValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "platform" => Self::Platform,
+            "affirmative-left" => Self::AffirmativeLeft,
+            "affirmative-right" => Self::AffirmativeRight,
+            _ => return Err(InvalidValue { _p: () }),
+        })
+    }
+}
+
+/// Where the dialog can be grabbed to move it with the mouse (see [`Builder::draggable`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DragMode {
+    /// The dialog can be dragged anywhere, except on the message, which is used for selecting
+    /// text.
+    #[default]
+    Anywhere,
+    /// The dialog can only be dragged by the strip above the message.
+    TitleAreaOnly,
+    /// The dialog can't be moved by dragging it (only with the window manager).
+    Disabled,
+}
+
+impl FromStr for DragMode {
+    type Err = InvalidValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "anywhere" => Self::Anywhere,
+            "title" => Self::TitleAreaOnly,
+            "disabled" => Self::Disabled,
+            _ => return Err(InvalidValue { _p: () }),
+        })
+    }
+}
+
+/// The kind of window the dialog is displayed as (see [`Builder::window_type`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WindowType {
+    /// A dialog window, which floats above other windows and is centered on its parent, if it has
+    /// one.
+    #[default]
+    Dialog,
+    /// A regular top-level window.
+    Normal,
+    /// A small persistent window, like a toolbox.
+    Utility,
+    /// A notification bubble, which window managers usually don't decorate or put in the taskbar.
+    Notification,
+}
+
+impl FromStr for WindowType {
+    type Err = InvalidValue;
 
-        target
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "dialog" => Self::Dialog,
+            "normal" => Self::Normal,
+            "utility" => Self::Utility,
+            "notification" => Self::Notification,
+            _ => return Err(InvalidValue { _p: () }),
+        })
     }
 }
 
+/// Placement of the dialog [`Icon`] relative to the message.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IconPosition {
+    /// The icon is drawn to the left of the message.
+    #[default]
+    Left,
+    /// The icon is drawn centered above the message (a "banner" layout).
+    Top,
+    /// The icon is drawn to the right of the message.
+    Right,
+    /// The icon is not drawn, and the message takes up the full width of the dialog.
+    None,
+}
+
 /// Error returned by [`FromStr`] implementations.
 #[derive(Debug)]
 pub struct InvalidValue {
@@ -297,11 +2999,34 @@ impl std::error::Error for InvalidValue {}
 
 /// Color theme selection.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Theme {
     #[default]
     Light,
     Dark,
+    /// The theme of the desktop environment, detected when the dialog is displayed.
+    ///
+    /// This is what is used if no theme is set (see [`Builder::theme`]). While the detection is
+    /// running, it looks like [`Theme::Light`].
+    System,
+    /// A custom color palette.
+    ///
+    /// All colors are given as `0xAARRGGBB`. The alpha channel is ignored, since the dialog window
+    /// is always opaque. The remaining colors (like button outlines) are derived from these.
+    Custom {
+        /// Window background color.
+        background: u32,
+        /// Color of the message and button labels.
+        text: u32,
+        /// Button background color.
+        button: u32,
+        /// Button background color while the mouse cursor is over the button.
+        button_hover: u32,
+        /// Background color of the [default button][Builder::default_button], and color of the
+        /// input field outline.
+        accent: u32,
+    },
 }
 
 impl FromStr for Theme {
@@ -311,107 +3036,449 @@ impl FromStr for Theme {
         Ok(match s {
             "light" => Self::Light,
             "dark" => Self::Dark,
+            "system" => Self::System,
             _ => return Err(InvalidValue { _p: () }),
         })
     }
 }
 
+/// How long a detected theme is reused by later dialogs.
+const THEME_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// The most recently detected theme, and when it was detected.
+static DETECTED_THEME: Mutex<Option<(Theme, Instant)>> = Mutex::new(None);
+
 impl Theme {
+    /// Returns the theme detected within the last [`THEME_CACHE_TTL`], if `use_cache` is set.
+    fn cached(use_cache: bool) -> Option<Theme> {
+        let cached = *DETECTED_THEME.lock().unwrap();
+        cached
+            .filter(|(_, detected_at)| use_cache && detected_at.elapsed() < THEME_CACHE_TTL)
+            .map(|(theme, _)| theme)
+    }
+
+    /// Returns the theme detected within the last [`THEME_CACHE_TTL`] if `use_cache` is set, or
+    /// detects it again.
+    fn detect_cached(use_cache: bool) -> Theme {
+        // Holding the lock makes concurrently created dialogs wait for a single detection.
+        let mut cached = DETECTED_THEME.lock().unwrap();
+        if use_cache
+            && let Some((theme, detected_at)) = *cached
+            && detected_at.elapsed() < THEME_CACHE_TTL
+        {
+            debug!("using cached {theme:?} theme");
+            return theme;
+        }
+        let theme = Self::detect();
+        *cached = Some((theme, Instant::now()));
+        theme
+    }
+
     /// Attempts to detect the preferred color scheme of the desktop environment.
     ///
-    /// X11 doesn't have a built-in mechanism to do this, so we run these external commands in order
-    /// (using the first one that works):
+    /// X11 doesn't have a built-in mechanism to do this, so we run these external commands
+    /// concurrently (using the first one in this list that works):
     ///
     /// - `dbus-send org.freedesktop.portal.Settings.Read org.freedesktop.appearance color-scheme`
     /// - `gsettings get org.gnome.desktop.interface color-scheme`
     ///
     /// The latter doesn't properly update on KDE when changing themes, so the first option is
-    /// preferred (and is also generally newer and less vendor-specific).
-    fn detect() -> Theme {
-        Self::detect_dbus()
-            .or_else(|_| Self::detect_gsettings())
-            .unwrap_or(Theme::Light)
-    }
-
-    fn detect_dbus() -> Result<Theme, Error> {
-        // https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.Settings.html
-        // Invocation taken from `sctk-adwaita`.
-        let out = Command::new("dbus-send")
-            .args([
-                "--reply-timeout=100",
-                "--print-reply=literal",
-                "--dest=org.freedesktop.portal.Desktop",
-                "/org/freedesktop/portal/desktop",
-                "org.freedesktop.portal.Settings.Read",
-                "string:org.freedesktop.appearance",
-                "string:color-scheme",
-            ])
-            .output()
-            .map_err(err)?;
-        if !out.status.success() {
-            return Err(Error::new(format!(
-                "failed to query DBus: {}",
-                String::from_utf8_lossy(&out.stderr)
-            )));
+    /// preferred (and is also generally newer and less vendor-specific). The commands get 150
+    /// milliseconds in total to respond, so that a hanging command doesn't delay the dialog.
+    ///
+    /// If neither works, which is common with standalone window managers, the theme is guessed
+    /// from the `GTK_THEME` and `QT_STYLE_OVERRIDE` environment variables and from
+    /// `gtk-application-prefer-dark-theme` in `~/.config/gtk-3.0/settings.ini`. If that fails too,
+    /// [`Theme::Light`] is returned.
+    ///
+    /// This is what dialogs do without a [theme][Builder::theme], except that it always runs the
+    /// detection again instead of using the [cache][Builder::theme_cache].
+    pub fn detect() -> Theme {
+        Self::try_detect().unwrap_or_else(|e| {
+            warn!("{e}, using the light theme");
+            Theme::Light
+        })
+    }
+
+    /// Attempts to detect the preferred color scheme of the desktop environment, like
+    /// [`Theme::detect`], but returns an error instead of falling back to [`Theme::Light`].
+    ///
+    /// The error describes why the guess from the GTK and Qt settings failed. Why the external
+    /// commands failed is logged.
+    pub fn try_detect() -> Result<Theme, Error> {
+        if let Some(theme) = ThemeProbe::run_all() {
+            return Ok(theme);
         }
+        let theme = Self::detect_env().map_err(|e| {
+            Error::new(format!(
+                "failed to detect the theme via GTK/Qt settings: {e}"
+            ))
+        })?;
+        debug!("detected {theme:?} theme via GTK/Qt settings");
+        Ok(theme)
+    }
 
-        let stdout = str::from_utf8(&out.stdout).map_err(err)?.trim();
-        if stdout.ends_with("uint32 1") {
-            Ok(Theme::Dark)
-        } else if stdout.ends_with("uint32 2") {
-            Ok(Theme::Light)
-        } else {
-            Err(Error::new(format!(
-                "unknown color scheme preference: {stdout}"
-            )))
+    fn detect_env() -> Result<Theme, Error> {
+        // Theme names like `Adwaita:dark` or `Breeze-Dark` mark their dark variant.
+        for var in ["GTK_THEME", "QT_STYLE_OVERRIDE"] {
+            if let Ok(name) = env::var(var)
+                && !name.is_empty()
+            {
+                return Ok(if name.to_lowercase().contains("dark") {
+                    Theme::Dark
+                } else {
+                    Theme::Light
+                });
+            }
+        }
+
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .ok_or_else(|| Error::new("neither `XDG_CONFIG_HOME` nor `HOME` is set"))?;
+        let path = config.join("gtk-3.0/settings.ini");
+        let settings = fs::read_to_string(&path)
+            .map_err(|e| Error::new(format!("failed to read {}: {e}", path.display())))?;
+        Self::from_gtk_settings(&settings).ok_or_else(|| {
+            Error::new(format!(
+                "{} doesn't set `gtk-application-prefer-dark-theme`",
+                path.display()
+            ))
+        })
+    }
+
+    /// Reads `gtk-application-prefer-dark-theme` from the contents of a GTK `settings.ini`.
+    fn from_gtk_settings(settings: &str) -> Option<Theme> {
+        let mut in_settings = false;
+        for line in settings.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_settings = line == "[Settings]";
+            } else if in_settings
+                && let Some((key, value)) = line.split_once('=')
+                && key.trim() == "gtk-application-prefer-dark-theme"
+            {
+                return match value.trim() {
+                    "1" | "true" => Some(Theme::Dark),
+                    "0" | "false" => Some(Theme::Light),
+                    _ => None,
+                };
+            }
+        }
+        None
+    }
+}
+
+/// Total time that theme detection waits for the external commands to respond.
+const DETECT_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// An external command that reports the desktop's color scheme preference.
+#[derive(Debug, Clone, Copy)]
+enum ThemeProbe {
+    /// The XDG desktop portal, queried with `dbus-send`.
+    Portal,
+    Gsettings,
+}
+
+impl ThemeProbe {
+    /// All probes, in order of preference.
+    const ALL: [Self; 2] = [Self::Portal, Self::Gsettings];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Portal => "the settings portal",
+            Self::Gsettings => "gsettings",
+        }
+    }
+
+    fn command(self) -> Command {
+        match self {
+            Self::Portal => {
+                // https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.Settings.html
+                // Invocation taken from `sctk-adwaita`.
+                let mut cmd = Command::new("dbus-send");
+                cmd.args([
+                    "--reply-timeout=100",
+                    "--print-reply=literal",
+                    "--dest=org.freedesktop.portal.Desktop",
+                    "/org/freedesktop/portal/desktop",
+                    "org.freedesktop.portal.Settings.Read",
+                    "string:org.freedesktop.appearance",
+                    "string:color-scheme",
+                ]);
+                cmd
+            }
+            Self::Gsettings => {
+                let mut cmd = Command::new("gsettings");
+                cmd.args(["get", "org.gnome.desktop.interface", "color-scheme"]);
+                cmd
+            }
         }
     }
 
-    fn detect_gsettings() -> Result<Theme, Error> {
-        let out = Command::new("gsettings")
-            .args(["get", "org.gnome.desktop.interface", "color-scheme"])
-            .output()
+    /// Starts the command, and sends its output to `sender`, tagged with `index`, once it exits.
+    fn spawn(
+        self,
+        index: usize,
+        sender: mpsc::Sender<(usize, Vec<u8>, Vec<u8>)>,
+    ) -> Result<Child, Error> {
+        let mut child = self
+            .command()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
             .map_err(err)?;
+        let mut stdout = child.stdout.take().expect("stdout should be piped");
+        let mut stderr = child.stderr.take().expect("stderr should be piped");
+        let res = thread::Builder::new()
+            .name("alerta-theme".into())
+            .spawn(move || {
+                // The output is short, so reading the pipes one after the other can't deadlock.
+                let (mut out, mut errors) = (Vec::new(), Vec::new());
+                stdout.read_to_end(&mut out).ok();
+                stderr.read_to_end(&mut errors).ok();
+                sender.send((index, out, errors)).ok();
+            });
+        if let Err(e) = res {
+            child.kill().ok();
+            child.wait().ok();
+            return Err(err(e));
+        }
+        Ok(child)
+    }
+
+    /// Interprets the output of the command.
+    fn parse(self, out: &Output) -> Result<Theme, Error> {
         if !out.status.success() {
             return Err(Error::new(format!(
-                "failed to query gsettings: {}",
+                "failed to query {}: {}",
+                self.name(),
                 String::from_utf8_lossy(&out.stderr)
             )));
         }
-        let stdout = str::from_utf8(&out.stdout)
-            .map_err(err)?
-            .trim()
-            .trim_matches('\'');
-        match stdout {
-            "prefer-dark" => Ok(Theme::Dark),
-            "prefer-light" => Ok(Theme::Light),
-            _ => Err(Error::new(format!(
-                "unknown color scheme preference: {stdout}"
-            ))),
+        let stdout = str::from_utf8(&out.stdout).map_err(err)?.trim();
+        let theme = match self {
+            Self::Portal if stdout.ends_with("uint32 1") => Some(Theme::Dark),
+            Self::Portal if stdout.ends_with("uint32 2") => Some(Theme::Light),
+            Self::Portal => None,
+            Self::Gsettings => match stdout.trim_matches('\'') {
+                "prefer-dark" => Some(Theme::Dark),
+                "prefer-light" => Some(Theme::Light),
+                _ => None,
+            },
+        };
+        theme.ok_or_else(|| Error::new(format!("unknown color scheme preference: {stdout}")))
+    }
+
+    /// Runs all probes concurrently, and returns the theme reported by the most preferred one
+    /// that works within [`DETECT_TIMEOUT`].
+    ///
+    /// Commands that are still running when the theme is known, or when the time is up, are
+    /// killed.
+    fn run_all() -> Option<Theme> {
+        let deadline = Instant::now() + DETECT_TIMEOUT;
+        let (sender, receiver) = mpsc::channel();
+        let mut children = Self::ALL.map(|_| None::<Child>);
+        let mut results = Self::ALL.map(|_| None::<Result<Theme, Error>>);
+        for (i, probe) in Self::ALL.into_iter().enumerate() {
+            match probe.spawn(i, sender.clone()) {
+                Ok(child) => children[i] = Some(child),
+                Err(e) => results[i] = Some(Err(e)),
+            }
+        }
+
+        let found = loop {
+            // Wait for the most preferred probe that hasn't failed yet.
+            let Some(i) = results.iter().position(|res| !matches!(res, Some(Err(_)))) else {
+                break None;
+            };
+            if let Some(Ok(theme)) = results[i] {
+                break Some((i, theme));
+            }
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok((j, stdout, stderr)) => {
+                    let mut child = children[j].take().expect("each probe reports only once");
+                    results[j] = Some(child.wait().map_err(err).and_then(|status| {
+                        Self::ALL[j].parse(&Output {
+                            status,
+                            stdout,
+                            stderr,
+                        })
+                    }));
+                }
+                // Out of time, so fall back to a less preferred probe that already worked.
+                Err(_) => {
+                    break results.iter().enumerate().find_map(|(i, res)| match res {
+                        Some(Ok(theme)) => Some((i, *theme)),
+                        _ => None,
+                    });
+                }
+            }
+        };
+
+        let preferred = found.map_or(Self::ALL.len(), |(i, _)| i);
+        for (probe, res) in Self::ALL.iter().zip(&results).take(preferred) {
+            match res {
+                Some(Err(e)) => warn!("failed to detect the theme via {}: {e}", probe.name()),
+                _ => warn!("{} didn't respond within {DETECT_TIMEOUT:?}", probe.name()),
+            }
+        }
+        for child in children.iter_mut().flatten() {
+            child.kill().ok();
+            child.wait().ok();
         }
+
+        let (i, theme) = found?;
+        debug!("detected {theme:?} theme via {}", Self::ALL[i].name());
+        Some(theme)
     }
 }
 
+/// An event received by the dialog window (see [`Builder::on_event`]).
+///
+/// New variants may be added in future versions, as the dialog learns to react to more events.
 #[derive(Debug)]
-enum WindowEvent {
+#[non_exhaustive]
+pub enum WindowEvent {
+    /// The window manager asked to close the window, for example because its close button was
+    /// clicked.
     CloseRequested,
+    /// Parts of the window were uncovered, and its contents have to be displayed again.
     RedrawRequested,
+    /// The mouse cursor entered the window at the given position.
     CursorEnter(CursorPos),
+    /// The mouse cursor moved to the given position within the window.
     CursorMove(CursorPos),
+    /// The mouse cursor left the window.
     CursorLeave,
+    /// A mouse button was pressed at the current cursor position.
     ButtonPress(MouseButton),
+    /// A mouse button was released.
     ButtonRelease(MouseButton),
+    /// The button was pressed twice in quick succession, at about the same position.
+    ///
+    /// This follows the [`WindowEvent::ButtonPress`] of the second press.
+    DoubleClick(MouseButton),
+    /// Text from the primary selection or the clipboard arrived, after it was requested for
+    /// pasting.
+    Paste(String),
+    /// The event loop was woken up from another thread, because a dialog running in the
+    /// background was updated or closed, or the theme was detected.
+    Wakeup,
+    /// The mouse wheel was scrolled by the given number of steps.
+    ///
+    /// Positive values scroll up and to the right.
+    Scroll { delta_x: i16, delta_y: i16 },
+    /// A key was pressed while the given modifiers were held down.
+    ///
+    /// Keys that repeat while held down send a `KeyPress` for every repetition.
+    KeyPress(Keysym, Modifiers),
+    /// A key was released.
+    KeyRelease(Keysym),
+    /// The window was resized to the given size in pixels.
+    Resized { width: u16, height: u16 },
+    /// The window was moved, so that its top left corner is at the given position on the screen.
+    Moved { x: i32, y: i32 },
+    /// Another client asked for the contents of the clipboard, after text was copied from the
+    /// dialog.
+    SelectionRequest(SelectionRequest),
+}
+
+/// A request from another client for the contents of the clipboard, which the dialog answers.
+#[derive(Debug)]
+pub struct SelectionRequest(x11rb::protocol::xproto::SelectionRequestEvent);
+
+/// An X11 keysym, identifying the symbol on a key (rather than its physical location).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keysym(u32);
+
+impl Keysym {
+    const RETURN: Self = Self(0xff0d);
+    const KP_ENTER: Self = Self(0xff8d);
+    const ESCAPE: Self = Self(0xff1b);
+    const BACKSPACE: Self = Self(0xff08);
+    const TAB: Self = Self(0xff09);
+    /// Produced by Shift+Tab.
+    const ISO_LEFT_TAB: Self = Self(0xfe20);
+    const SPACE: Self = Self(0x20);
+    const ALT_L: Self = Self(0xffe9);
+    const ALT_R: Self = Self(0xffea);
+    const HOME: Self = Self(0xff50);
+    const LEFT: Self = Self(0xff51);
+    const UP: Self = Self(0xff52);
+    const RIGHT: Self = Self(0xff53);
+    const DOWN: Self = Self(0xff54);
+    const END: Self = Self(0xff57);
+    const DELETE: Self = Self(0xffff);
+    const KP_HOME: Self = Self(0xff95);
+    const KP_LEFT: Self = Self(0xff96);
+    const KP_UP: Self = Self(0xff97);
+    const KP_RIGHT: Self = Self(0xff98);
+    const KP_DOWN: Self = Self(0xff99);
+    const KP_END: Self = Self(0xff9c);
+    const KP_DELETE: Self = Self(0xff9f);
+
+    /// Returns the keysym that types `c`.
+    fn from_char(c: char) -> Self {
+        match u32::from(c) {
+            code @ (0x20..=0x7e | 0xa0..=0xff) => Self(code),
+            code => Self(0x0100_0000 + code),
+        }
+    }
+
+    /// Returns the numeric value of the keysym, as defined in `X11/keysymdef.h`.
+    pub fn raw(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the character this keysym types, if any.
+    pub fn to_char(self) -> Option<char> {
+        match self.0 {
+            // Latin-1 keysyms are identical to their code points.
+            0x20..=0x7e | 0xa0..=0xff => char::from_u32(self.0),
+            // Unicode keysyms.
+            0x0100_0000..=0x0110_ffff => char::from_u32(self.0 - 0x0100_0000),
+            // Keypad digits.
+            0xffb0..=0xffb9 => char::from_u32(self.0 - 0xffb0 + '0' as u32),
+            _ => None,
+        }
+    }
 }
 
+/// Position of the mouse cursor in pixels, relative to the top-left corner of the dialog.
 #[derive(Debug, Clone, Copy)]
-struct CursorPos {
-    x: i16,
-    y: i16,
+#[non_exhaustive]
+pub struct CursorPos {
+    /// Distance from the left edge of the dialog. Right-to-left layouts don't change this.
+    pub x: i16,
+    /// Distance from the top edge of the dialog.
+    pub y: i16,
 }
 
-#[derive(Debug)]
-enum MouseButton {
+/// A rectangle in the dialog window, in physical pixels (see [`Builder::custom_draw`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    /// Distance of the left edge from the left edge of the window.
+    pub x: i32,
+    /// Distance of the top edge from the top edge of the window.
+    pub y: i32,
+    /// Width of the rectangle, in physical pixels.
+    pub width: u32,
+    /// Height of the rectangle, in physical pixels.
+    pub height: u32,
+}
+
+/// A mouse button.
+///
+/// Other buttons are ignored by the dialog, and don't produce events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MouseButton {
+    /// The primary button, which clicks buttons and selects text.
     Left,
+    /// The middle button (or wheel click), which pastes the primary selection.
     Middle,
+    /// The secondary button, which opens the context menu.
     Right,
 }
@@ -0,0 +1,104 @@
+//! Watches the desktop environment's color scheme preference for changes.
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{Error, Theme, error::err, x11::Waker};
+
+/// Runs `dbus-monitor` in the background and reports changes of the XDG portal's `color-scheme`
+/// setting.
+///
+/// The monitor process is killed when this is dropped.
+pub(crate) struct ThemeMonitor {
+    child: Child,
+    changes: Receiver<Theme>,
+}
+
+impl ThemeMonitor {
+    /// Starts the monitor, which wakes up `waker` whenever it reports a change.
+    pub(crate) fn spawn(waker: Waker) -> Result<Self, Error> {
+        // https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.Settings.html
+        let mut child = Command::new("dbus-monitor")
+            .args([
+                "--session",
+                "type='signal',interface='org.freedesktop.portal.Settings',member='SettingChanged'",
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(err)?;
+
+        let stdout = child.stdout.take().expect("stdout should be piped");
+        let (sender, changes) = mpsc::channel();
+        let res = thread::Builder::new()
+            .name("alerta-theme".into())
+            .spawn(move || {
+                let mut parser = Parser::default();
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else { break };
+                    if let Some(theme) = parser.feed(&line) {
+                        if sender.send(theme).is_err() {
+                            break;
+                        }
+                        waker.wake();
+                    }
+                }
+            });
+        if let Err(e) = res {
+            child.kill().ok();
+            child.wait().ok();
+            return Err(err(e));
+        }
+
+        Ok(Self { child, changes })
+    }
+
+    /// Returns the most recently reported theme, if it has changed since the last call.
+    pub(crate) fn poll(&self) -> Option<Theme> {
+        self.changes.try_iter().last()
+    }
+}
+
+impl Drop for ThemeMonitor {
+    fn drop(&mut self) {
+        // This also makes the reader thread exit, since it will see the end of the output.
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Extracts color scheme changes from the output of `dbus-monitor`.
+#[derive(Default)]
+pub(crate) struct Parser {
+    /// The string arguments of the signal that is currently being printed.
+    strings: Vec<String>,
+}
+
+impl Parser {
+    /// Processes a single line of output, returning the new theme if the line completes a
+    /// `color-scheme` change.
+    pub(crate) fn feed(&mut self, line: &str) -> Option<Theme> {
+        let line = line.trim();
+        if line.starts_with("signal ") {
+            self.strings.clear();
+        } else if let Some(s) = line.strip_prefix("string \"") {
+            self.strings.push(s.trim_end_matches('"').to_string());
+        } else if let Some(value) = line.strip_prefix("variant") {
+            let is_color_scheme = self.strings == ["org.freedesktop.appearance", "color-scheme"];
+            self.strings.clear();
+            if is_color_scheme {
+                // 1 means "prefer dark", everything else is treated as light.
+                return Some(match value.trim() {
+                    "uint32 1" => Theme::Dark,
+                    _ => Theme::Light,
+                });
+            }
+        }
+        None
+    }
+}
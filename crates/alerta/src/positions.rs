@@ -0,0 +1,72 @@
+//! Remembering where dialogs were, for [`Builder::remember_position`].
+//!
+//! The positions are stored in `$XDG_CACHE_HOME/alerta/positions`, one per line as the x and y
+//! coordinates followed by the key, separated by spaces.
+//!
+//! [`Builder::remember_position`]: crate::Builder::remember_position
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// Returns the position stored for `key`, if any.
+pub(crate) fn load(key: &str) -> Option<(i32, i32)> {
+    let contents = fs::read_to_string(path().ok()?).ok()?;
+    lookup(&contents, key)
+}
+
+/// Stores `pos` as the position for `key`, replacing any previous one.
+pub(crate) fn store(key: &str, pos: (i32, i32)) -> Result<(), Error> {
+    let path = path()?;
+    let contents = fs::read_to_string(&path).unwrap_or_default();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| Error::new(format!("failed to create {}: {e}", dir.display())))?;
+    }
+    fs::write(&path, update(&contents, key, pos))
+        .map_err(|e| Error::new(format!("failed to write {}: {e}", path.display())))
+}
+
+fn path() -> Result<PathBuf, Error> {
+    let cache = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .ok_or_else(|| Error::new("neither `XDG_CACHE_HOME` nor `HOME` is set"))?;
+    Ok(cache.join("alerta/positions"))
+}
+
+/// Keys are stored until the end of the line, so line breaks in them are replaced.
+fn sanitize(key: &str) -> String {
+    key.replace(['\n', '\r'], " ")
+}
+
+/// Finds the position stored for `key` in the contents of the positions file.
+pub(crate) fn lookup(contents: &str, key: &str) -> Option<(i32, i32)> {
+    let key = sanitize(key);
+    contents.lines().find_map(|line| {
+        let mut fields = line.splitn(3, ' ');
+        let (x, y) = (fields.next()?, fields.next()?);
+        if fields.next()? != key {
+            return None;
+        }
+        Some((x.parse().ok()?, y.parse().ok()?))
+    })
+}
+
+/// Returns the contents of the positions file with the position for `key` set to `pos`.
+pub(crate) fn update(contents: &str, key: &str, (x, y): (i32, i32)) -> String {
+    let key = sanitize(key);
+    let mut updated = String::new();
+    for line in contents.lines() {
+        if line.splitn(3, ' ').nth(2) != Some(key.as_str()) {
+            updated.push_str(line);
+            updated.push('\n');
+        }
+    }
+    updated.push_str(&format!("{x} {y} {key}\n"));
+    updated
+}
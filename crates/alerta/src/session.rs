@@ -0,0 +1,53 @@
+//! Reusing one X server connection for several dialogs.
+
+use crate::{Builder, Error, alerta, x11};
+
+/// A connection to the X server that can be shared by several dialogs.
+///
+/// By default, every dialog opens its own connection to the X server. Applications that display
+/// many dialogs can connect once with [`Session::connect`], and create the dialogs with
+/// [`Session::alerta`] instead of [`alerta()`].
+///
+/// Cloning a session is cheap, and the clones share the connection. It is closed when the last
+/// clone and the last dialog using it are dropped.
+///
+/// The dialogs of a session should be displayed one at a time. If several of them are open at
+/// once (for example via [`Builder::spawn`]), user input may be delivered to the wrong dialog.
+#[derive(Clone)]
+pub struct Session {
+    pub(crate) conn: x11::Connection,
+}
+
+impl Session {
+    /// Connects to the X server in the `DISPLAY` environment variable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails. Its [`kind`][Error::kind] tells apart a missing
+    /// `DISPLAY` from an X server that couldn't be reached.
+    pub fn connect() -> Result<Self, Error> {
+        Ok(Self {
+            conn: x11::Connection::connect(None)?,
+        })
+    }
+
+    /// Connects to the X server `display` (like `:1`), instead of the one in `DISPLAY`.
+    ///
+    /// # Errors
+    ///
+    /// See [`Session::connect`].
+    pub fn connect_to(display: &str) -> Result<Self, Error> {
+        Ok(Self {
+            conn: x11::Connection::connect(Some(display))?,
+        })
+    }
+
+    /// Returns a [`Builder`] for a dialog that uses this session's connection.
+    ///
+    /// [`Builder::display`] has no effect on the returned builder.
+    pub fn alerta(&self) -> Builder {
+        let mut builder = alerta();
+        builder.session = Some(self.clone());
+        builder
+    }
+}
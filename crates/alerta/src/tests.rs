@@ -1,8 +1,30 @@
-use std::fs;
+use std::{
+    backtrace::Backtrace,
+    fs,
+    io::{Read as _, Write as _},
+    os::{
+        linux::net::SocketAddrExt as _,
+        unix::net::{SocketAddr, UnixListener},
+    },
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU16, Ordering::Relaxed},
+    },
+    thread,
+    time::Duration,
+};
 
+use euclid::{point2, size2};
 use raqote::DrawTarget;
+use x11rb::{protocol::xproto, x11_utils::Serialize as _};
 
-use crate::{ButtonPreset, Icon, Theme, ui::Ui};
+use crate::{
+    AnsiMode, Answer, ButtonPreset, CloseReason, CursorPos, Error, ErrorKind, Icon, IconPosition,
+    Keysym, Modifiers, MouseButton, Rect, TextDirection, Theme, WindowEvent, WrapMode, dedup,
+    error, image, monitor, positions,
+    ui::{self, CursorShape, Indicator, Selection, Ui},
+    x11,
+};
 
 fn snap(name: &str, image: &DrawTarget) {
     let path = format!("src/snap/{name}.png");
@@ -17,6 +39,18 @@ fn snap(name: &str, image: &DrawTarget) {
     );
 }
 
+/// Returns whether fontconfig finds a system font for the `monospace` family.
+///
+/// Without one, monospace text is displayed in the built-in font, so tests that rely on it being
+/// monospace are skipped.
+fn has_monospace_font() -> bool {
+    let found = ui::font::Font::load(Some("monospace"), 18.0).is_monospace();
+    if !found {
+        eprintln!("no monospace font found, skipping test");
+    }
+    found
+}
+
 const IPSUM: &str = "Lorem ipsum is a dummy or placeholder text commonly used in graphic design, publishing, and web development. Its purpose is to permit a page layout to be designed, independently of the copy that will subsequently populate it, or to demonstrate various fonts of a typeface without meaningful text that could be distracting.";
 const NBSP: &str = "\u{a0}";
 const ZWSP: &str = "\u{200b}";
@@ -25,7 +59,7 @@ const ZWSP: &str = "\u{200b}";
 fn textwrap() {
     snap(
         "textwrap",
-        &Ui::new(Icon::Info, Theme::Light, IPSUM, &["OK"]).canvas,
+        &Ui::new(Icon::Info, Theme::Light, IPSUM, &["OK"], Default::default()).canvas,
     );
     snap(
         "nbsp",
@@ -38,12 +72,20 @@ fn textwrap() {
                 .unwrap()
                 .replace(' ', NBSP),
             &["OK"],
+            Default::default(),
         )
         .canvas,
     );
     snap(
         "zwsp",
-        &Ui::new(Icon::Info, Theme::Light, &IPSUM.replace(' ', ZWSP), &["OK"]).canvas,
+        &Ui::new(
+            Icon::Info,
+            Theme::Light,
+            &IPSUM.replace(' ', ZWSP),
+            &["OK"],
+            Default::default(),
+        )
+        .canvas,
     );
 }
 
@@ -56,6 +98,7 @@ fn buttons() {
             Theme::Light,
             "Buttons",
             ButtonPreset::YesNoCancel.strings(),
+            Default::default(),
         )
         .canvas,
     );
@@ -66,6 +109,60 @@ fn buttons() {
             Theme::Light,
             "Buttons",
             ButtonPreset::RetryCancel.strings(),
+            Default::default(),
+        )
+        .canvas,
+    );
+    snap(
+        "buttons-abortretryignore",
+        &Ui::new(
+            Icon::Error,
+            Theme::Light,
+            "Buttons",
+            ButtonPreset::AbortRetryIgnore.strings(),
+            Default::default(),
+        )
+        .canvas,
+    );
+    snap(
+        "buttons-savedontsavecancel",
+        &Ui::new(
+            Icon::Warning,
+            Theme::Light,
+            "Buttons",
+            ButtonPreset::SaveDontSaveCancel.strings(),
+            Default::default(),
+        )
+        .canvas,
+    );
+    assert_eq!(
+        "abortretryignore".parse::<ButtonPreset>().ok(),
+        Some(ButtonPreset::AbortRetryIgnore)
+    );
+    assert_eq!(
+        "savedontsavecancel".parse::<ButtonPreset>().ok(),
+        Some(ButtonPreset::SaveDontSaveCancel)
+    );
+}
+
+#[test]
+fn custom_buttons() {
+    let labels = crate::alerta().buttons(["Save", "Discard", "Keep Editing"]);
+    assert_eq!(labels.button_labels(), ["Save", "Discard", "Keep Editing"]);
+
+    let empty = crate::alerta()
+        .button_preset(ButtonPreset::YesNo)
+        .buttons(Vec::<String>::new());
+    assert_eq!(empty.button_labels(), ["Close"]);
+
+    snap(
+        "buttons-custom",
+        &Ui::new(
+            Icon::Warning,
+            Theme::Light,
+            "Buttons",
+            &labels.button_labels(),
+            Default::default(),
         )
         .canvas,
     );
@@ -75,7 +172,14 @@ fn buttons() {
 fn dark_theme() {
     snap(
         "dark-theme",
-        &Ui::new(Icon::Question, Theme::Dark, IPSUM, &["Yes", "No", "Cancel"]).canvas,
+        &Ui::new(
+            Icon::Question,
+            Theme::Dark,
+            IPSUM,
+            &["Yes", "No", "Cancel"],
+            Default::default(),
+        )
+        .canvas,
     );
 }
 
@@ -88,6 +192,7 @@ fn icons() {
             Theme::Light,
             "Error",
             ButtonPreset::RetryCancel.strings(),
+            Default::default(),
         )
         .canvas,
     );
@@ -98,7 +203,3720 @@ fn icons() {
             Theme::Light,
             "Huh? Wha?",
             ButtonPreset::YesNo.strings(),
+            Default::default(),
         )
         .canvas,
     );
 }
+
+#[test]
+fn icon_position() {
+    for (name, icon_position) in [
+        ("icon-left", IconPosition::Left),
+        ("icon-top", IconPosition::Top),
+        ("icon-right", IconPosition::Right),
+        ("icon-none", IconPosition::None),
+    ] {
+        snap(
+            name,
+            &Ui::new(
+                Icon::Info,
+                Theme::Light,
+                "The icon can be placed in different spots.",
+                ButtonPreset::OkCancel.strings(),
+                ui::Options {
+                    icon_position,
+                    ..Default::default()
+                },
+            )
+            .canvas,
+        );
+    }
+
+    // `Icon::None` looks the same as hiding the icon, wherever it would have been placed.
+    let no_icon = Ui::new(
+        Icon::None,
+        Theme::Light,
+        "The icon can be placed in different spots.",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            icon_position: IconPosition::Top,
+            ..Default::default()
+        },
+    );
+    let expected = image::load("src/snap/icon-none.png".as_ref()).unwrap();
+    assert!(no_icon.canvas.get_data() == expected.get_data());
+}
+
+#[test]
+fn button_states() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Save changes before closing?",
+        ButtonPreset::YesNoCancel.strings(),
+        ui::Options::default(),
+    );
+    let no = ui.button_bounds()[1].center();
+    let no = CursorPos {
+        x: no.x as i16,
+        y: no.y as i16,
+    };
+    ui.process_event(WindowEvent::CursorEnter(no));
+    assert!(ui.needs_redraw());
+    ui.redraw();
+    let hovered = ui.canvas.get_data().to_vec();
+
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert!(ui.needs_redraw());
+    ui.redraw();
+    snap("button-pressed", &ui.canvas);
+
+    // Moving off the button while the mouse button is held releases it visually.
+    ui.process_event(WindowEvent::CursorMove(CursorPos { x: 5, y: 5 }));
+    assert!(ui.needs_redraw());
+    ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left));
+
+    // A press that started elsewhere only highlights the button.
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    ui.process_event(WindowEvent::CursorMove(no));
+    ui.redraw();
+    assert!(ui.canvas.get_data() == hovered);
+}
+
+#[test]
+fn default_button() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Press Enter to pick the default.",
+        ButtonPreset::YesNoCancel.strings(),
+        ui::Options {
+            default_button: Some(1),
+            ..Default::default()
+        },
+    );
+    snap("default-button", &ui.canvas);
+
+    let enter = WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default());
+    assert_eq!(ui.process_event(enter), Some(Answer::Button(1)));
+
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Enter does nothing without a default button.",
+        ButtonPreset::YesNoCancel.strings(),
+        Default::default(),
+    );
+    let enter = WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default());
+    assert_eq!(ui.process_event(enter), None);
+}
+
+#[test]
+fn relabel_button() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "This dialog will close by itself.",
+        ButtonPreset::Ok.strings(),
+        Default::default(),
+    );
+    ui.set_button_label(0, "OK (3)");
+    ui.redraw();
+    snap("countdown", &ui.canvas);
+}
+
+#[test]
+fn wrap_text() {
+    let font = ui::font::Font::load(None, 18.0);
+    let lines = crate::wrap_text(IPSUM, 300, 18.0);
+    assert!(lines.len() > 5, "{lines:?}");
+    for line in &lines {
+        assert!(font.advance(line) <= 300.0, "{line:?} is too wide");
+        assert_eq!(line.trim(), line);
+    }
+    assert_eq!(lines.join(" "), IPSUM);
+
+    // Line breaks and empty lines are kept, and long words are broken up.
+    assert_eq!(crate::wrap_text("a\n\nb\r\n", 300, 18.0), ["a", "", "b"]);
+    let lines = crate::wrap_text(&"x".repeat(100), 300, 18.0);
+    assert!(lines.len() > 1);
+    assert_eq!(lines.concat(), "x".repeat(100));
+    assert!(crate::wrap_text("", 300, 18.0).is_empty());
+}
+
+#[test]
+fn from_error() {
+    #[derive(Debug)]
+    struct Failed(&'static str, Option<Box<Failed>>);
+    impl std::fmt::Display for Failed {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+    impl std::error::Error for Failed {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.1.as_deref().map(|e| e as _)
+        }
+    }
+
+    let err = Failed(
+        "failed to save the document",
+        Some(Box::new(Failed(
+            "failed to write 'notes.txt'",
+            Some(Box::new(Failed("the disk is full", None))),
+        ))),
+    );
+    let builder = crate::alerta().from_error(&err);
+    assert_eq!(builder.icon, Some(Icon::Error));
+    assert_eq!(
+        builder.title.as_deref(),
+        Some("failed to save the document")
+    );
+    assert_eq!(
+        builder.message.as_deref(),
+        Some("failed to write 'notes.txt'\nthe disk is full")
+    );
+
+    let builder = crate::alerta()
+        .icon(Icon::Warning)
+        .title("Saving")
+        .from_error(&Failed("the disk is full", None));
+    assert_eq!(builder.icon, Some(Icon::Warning));
+    assert_eq!(builder.title.as_deref(), Some("Saving"));
+    assert_eq!(builder.message.as_deref(), Some("the disk is full"));
+}
+
+#[test]
+fn panic_dialog() {
+    let builder = crate::panic_dialog(
+        "index out of bounds",
+        "Thread 'main' panicked at src/main.rs:3:7.".into(),
+        &Backtrace::disabled(),
+    );
+    assert_eq!(builder.icon, Some(Icon::Error));
+    assert_eq!(builder.message.as_deref(), Some("index out of bounds"));
+    assert_eq!(
+        builder.detail.as_deref(),
+        Some("Thread 'main' panicked at src/main.rs:3:7.")
+    );
+    assert_eq!(builder.expandable_details, None);
+
+    let builder = crate::panic_dialog("oops", String::new(), &Backtrace::force_capture());
+    assert!(builder.expandable_details.is_some_and(|bt| !bt.is_empty()));
+}
+
+#[test]
+fn geometry() {
+    use x11::Offset::{End, Start};
+    let parse = crate::parse_geometry;
+    assert_eq!(
+        parse("400x200+100+50"),
+        Some((Some((400, 200)), Some((Start(100), Start(50)))))
+    );
+    assert_eq!(parse("400X200"), Some((Some((400, 200)), None)));
+    assert_eq!(parse("-0+20"), Some((None, Some((End(0), Start(20))))));
+    assert_eq!(
+        parse("300x100-10-10"),
+        Some((Some((300, 100)), Some((End(10), End(10)))))
+    );
+    for invalid in [
+        "",
+        "400",
+        "400x",
+        "0x200",
+        "400x200+10",
+        "+10+20+30",
+        "+x+1",
+        "400x200 ",
+    ] {
+        assert_eq!(parse(invalid), None, "{invalid:?}");
+    }
+    assert!(crate::alerta().geometry("big").is_err());
+
+    // The dialog is laid out for the size, instead of fitting the window to its contents.
+    let ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Short message.",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            size: Some((500, 300)),
+            ..Default::default()
+        },
+    );
+    assert_eq!((ui.canvas.width(), ui.canvas.height()), (500, 300));
+    assert_eq!(ui.button_bounds()[0].max.y, 300 - 10);
+}
+
+#[test]
+fn fixed_size() {
+    let ui = |message: &str| {
+        Ui::new(
+            Icon::Warning,
+            Theme::Light,
+            message,
+            ButtonPreset::OkCancel.strings(),
+            ui::Options {
+                size: Some((420, 220)),
+                max_width: Some(300),
+                ..Default::default()
+            },
+        )
+    };
+    // Messages of any length get the same window, and long ones scroll.
+    let short = ui("Delete the file?");
+    let long = ui(&"The following files will be deleted permanently. ".repeat(12));
+    for ui in [&short, &long] {
+        assert_eq!((ui.canvas.width(), ui.canvas.height()), (420, 220));
+    }
+    snap("fixed-size", &long.canvas);
+}
+
+#[test]
+fn message_template() {
+    let args = [("name", "Alerta"), ("count", "3")];
+    let fill = |template| crate::fill_template(template, &args);
+    assert_eq!(
+        fill("{name} has {count} new messages"),
+        "Alerta has 3 new messages"
+    );
+    assert_eq!(fill("{{name}} is {name}"), "{name} is Alerta");
+    assert_eq!(fill("}}{{"), "}{");
+    // Unknown placeholders and stray braces are kept.
+    assert_eq!(fill("{unknown} {name"), "{unknown} {name");
+    assert_eq!(fill("{ {name} }"), "{ Alerta }");
+    // Values aren't filled in again.
+    assert_eq!(
+        crate::fill_template("{a}", &[("a", "{b}"), ("b", "oops")]),
+        "{b}"
+    );
+}
+
+#[test]
+fn default_button_out_of_range() {
+    let res = crate::alerta()
+        .button_preset(ButtonPreset::OkCancel)
+        .default_button(2)
+        .show();
+    assert!(res.is_err());
+}
+
+#[test]
+fn validate() {
+    assert!(crate::alerta().validate().is_ok());
+    assert!(crate::alerta().default_button(1).validate().is_err());
+    // An empty list of buttons displays a single "Close" button.
+    assert!(
+        crate::alerta()
+            .buttons(Vec::<String>::new())
+            .default_button(0)
+            .validate()
+            .is_ok()
+    );
+    assert!(
+        crate::alerta()
+            .buttons(Vec::<String>::new())
+            .default_button(1)
+            .validate()
+            .is_err()
+    );
+    assert!(
+        crate::alerta()
+            .input("")
+            .choices(vec!["A".into()])
+            .validate()
+            .is_err()
+    );
+    assert!(crate::alerta().scale(0.0).validate().is_err());
+
+    let missing = crate::alerta().icon_file("/nonexistent/icon.png");
+    let e = missing.validate().unwrap_err();
+    assert!(e.to_string().contains("/nonexistent/icon.png"), "{e}");
+    // The settings are checked before connecting to the X server.
+    let e = missing.display(":9999").show().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Other);
+}
+
+#[test]
+fn measure() {
+    let ui = Ui::new(
+        Icon::Warning,
+        Theme::Dark,
+        IPSUM,
+        ButtonPreset::YesNo.strings(),
+        Default::default(),
+    );
+    let builder = crate::alerta()
+        .icon(Icon::Warning)
+        .message(IPSUM)
+        .button_preset(ButtonPreset::YesNo);
+    assert_eq!(
+        builder.measure().unwrap(),
+        (ui.canvas.width() as u32, ui.canvas.height() as u32)
+    );
+
+    let (width, height) = builder.measure().unwrap();
+    let scaled = crate::alerta()
+        .icon(Icon::Warning)
+        .message(IPSUM)
+        .button_preset(ButtonPreset::YesNo)
+        .scale(2.0)
+        .measure()
+        .unwrap();
+    assert!(scaled.0 > width && scaled.1 > height);
+
+    assert!(crate::alerta().default_button(5).measure().is_err());
+}
+
+#[test]
+fn escape_closes() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Escape",
+        ButtonPreset::OkCancel.strings(),
+        Default::default(),
+    );
+    assert_eq!(
+        ui.process_event(WindowEvent::KeyPress(Keysym::ESCAPE, Modifiers::default())),
+        Some(Answer::Closed(CloseReason::Keyboard))
+    );
+    assert_eq!(
+        ui.process_event(WindowEvent::CloseRequested),
+        Some(Answer::Closed(CloseReason::WmCloseButton))
+    );
+}
+
+#[test]
+fn spawn_reports_errors() {
+    let handle = crate::alerta().default_button(5).spawn().unwrap();
+    handle.close();
+    assert!(handle.answer().is_err());
+}
+
+#[test]
+fn icon_file() {
+    // The PNG versions of the built-in icons should decode to the same pixels as the QOI versions.
+    let png = image::load("3rdparty/icons/dialog-warning.png".as_ref()).unwrap();
+    let qoi = Icon::Warning.get().unwrap();
+    assert_eq!(png.width(), qoi.width());
+    assert_eq!(png.height(), qoi.height());
+    assert!(png.get_data() == qoi.get_data());
+
+    let qoi = image::load("3rdparty/icons/dialog-warning.qoi".as_ref()).unwrap();
+    assert!(png.get_data() == qoi.get_data());
+
+    for path in ["does/not/exist.png", "Cargo.toml"] {
+        let Err(e) = image::load(path.as_ref()) else {
+            panic!("loading '{path}' should fail");
+        };
+        assert!(e.to_string().contains(path), "{e}");
+    }
+}
+
+#[test]
+fn icon_rgba() {
+    // A 2x1 image with an opaque red and a half-transparent white pixel.
+    let rgba = vec![255, 0, 0, 255, 255, 255, 255, 128];
+    let source = image::Source::Rgba {
+        width: 2,
+        height: 1,
+        data: rgba.clone(),
+    };
+    let Ok(icon) = source.load() else {
+        panic!("valid RGBA data should load");
+    };
+    assert_eq!(icon.get_data(), [0xffff0000, 0x80808080]);
+
+    let source = image::Source::Rgba {
+        width: 2,
+        height: 2,
+        data: rgba,
+    };
+    assert!(source.load().is_err());
+}
+
+#[test]
+fn input() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "What is your name?",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some("Name".into()),
+            ..Default::default()
+        },
+    );
+    snap("input-empty", &ui.canvas);
+
+    let key = |c: char| WindowEvent::KeyPress(Keysym(c as u32), Modifiers::default());
+    for c in "Alertx".chars() {
+        assert_eq!(ui.process_event(key(c)), None);
+    }
+    let backspace = WindowEvent::KeyPress(Keysym::BACKSPACE, Modifiers::default());
+    assert_eq!(ui.process_event(backspace), None);
+    assert_eq!(ui.process_event(key('a')), None);
+    ui.redraw();
+    snap("input", &ui.canvas);
+
+    let enter = WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default());
+    assert_eq!(
+        ui.process_event(enter),
+        Some(Answer::Input {
+            button: 0,
+            text: "Alerta".into(),
+        })
+    );
+}
+
+#[test]
+fn input_caret() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "What is your name?",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some("Name".into()),
+            ..Default::default()
+        },
+    );
+    let press =
+        |ui: &mut Ui, sym| ui.process_event(WindowEvent::KeyPress(sym, Modifiers::default()));
+    let text = |ui: &mut Ui| match press(ui, Keysym::RETURN) {
+        Some(Answer::Input { text, .. }) => text,
+        answer => panic!("unexpected answer {answer:?}"),
+    };
+    for c in "Alrta".chars() {
+        press(&mut ui, Keysym::from_char(c));
+    }
+    for _ in 0..3 {
+        press(&mut ui, Keysym::LEFT);
+    }
+    press(&mut ui, Keysym::from_char('e'));
+    ui.redraw();
+    snap("input-caret", &ui.canvas);
+    assert_eq!(text(&mut ui), "Alerta");
+
+    press(&mut ui, Keysym::HOME);
+    press(&mut ui, Keysym::DELETE);
+    press(&mut ui, Keysym::RIGHT);
+    press(&mut ui, Keysym::BACKSPACE);
+    assert_eq!(text(&mut ui), "erta");
+    press(&mut ui, Keysym::END);
+    press(&mut ui, Keysym::from_char('!'));
+    assert_eq!(text(&mut ui), "erta!");
+
+    // The caret moves over a grapheme cluster at once.
+    ui.process_event(WindowEvent::Paste(" 🇩🇪".into()));
+    press(&mut ui, Keysym::LEFT);
+    press(&mut ui, Keysym::DELETE);
+    assert_eq!(text(&mut ui), "erta! ");
+
+    // Clicking the start of the field moves the caret there.
+    let field = ui.input_rect().unwrap();
+    ui.process_event(WindowEvent::CursorEnter(CursorPos {
+        x: field.min.x as i16 + 1,
+        y: field.center().y as i16,
+    }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left));
+    press(&mut ui, Keysym::from_char('H'));
+    assert_eq!(text(&mut ui), "Herta! ");
+
+    // The caret blinks, and is visible again whenever it moves.
+    assert!(ui.take_caret_reset());
+    assert!(!ui.take_caret_reset());
+    ui.redraw();
+    let visible = ui.canvas.get_data().to_vec();
+    ui.blink_caret();
+    ui.redraw();
+    assert!(ui.canvas.get_data() != visible);
+    press(&mut ui, Keysym::END);
+    assert!(ui.take_caret_reset());
+    press(&mut ui, Keysym::HOME);
+    press(&mut ui, Keysym::RIGHT);
+    ui.redraw();
+    assert!(ui.canvas.get_data() == visible);
+}
+
+#[test]
+fn input_default() {
+    let ui = |select| {
+        Ui::new(
+            Icon::Question,
+            Theme::Light,
+            "Rename the file:",
+            ButtonPreset::OkCancel.strings(),
+            ui::Options {
+                input: Some("Name".into()),
+                input_default: Some("notes.txt".into()),
+                select_input_default: select,
+                ..Default::default()
+            },
+        )
+    };
+    let key = |c| WindowEvent::KeyPress(Keysym::from_char(c), Modifiers::default());
+    let enter = || WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default());
+    let input = |text: &str| {
+        Some(Answer::Input {
+            button: 0,
+            text: text.into(),
+        })
+    };
+
+    assert_eq!(ui(false).process_event(enter()), input("notes.txt"));
+    let mut appended = ui(false);
+    appended.process_event(key('~'));
+    assert_eq!(appended.process_event(enter()), input("notes.txt~"));
+
+    // Typing replaces selected text, and moving the caret deselects it.
+    let mut selected = ui(true);
+    selected.redraw();
+    snap("input-default-selected", &selected.canvas);
+    assert_eq!(selected.process_event(enter()), input("notes.txt"));
+    let mut replaced = ui(true);
+    replaced.process_event(key('a'));
+    assert_eq!(replaced.process_event(enter()), input("a"));
+    let mut kept = ui(true);
+    kept.process_event(WindowEvent::KeyPress(Keysym::HOME, Modifiers::default()));
+    kept.process_event(key('_'));
+    assert_eq!(kept.process_event(enter()), input("_notes.txt"));
+}
+
+#[test]
+fn input_multiline() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Describe the bug:",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some("What happened?".into()),
+            input_rows: Some(3),
+            ..Default::default()
+        },
+    );
+    let single_line = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Describe the bug:",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some("What happened?".into()),
+            ..Default::default()
+        },
+    );
+    assert!(ui.canvas.height() > single_line.canvas.height());
+
+    let press =
+        |ui: &mut Ui, sym| ui.process_event(WindowEvent::KeyPress(sym, Modifiers::default()));
+    let submit = |ui: &mut Ui| {
+        let ctrl = Modifiers {
+            bits: Modifiers::CONTROL,
+        };
+        match ui.process_event(WindowEvent::KeyPress(Keysym::RETURN, ctrl)) {
+            Some(Answer::Input { text, .. }) => text,
+            answer => panic!("unexpected answer {answer:?}"),
+        }
+    };
+    for c in "It crashed.".chars() {
+        press(&mut ui, Keysym::from_char(c));
+    }
+    // Enter inserts a line break instead of answering the dialog.
+    assert_eq!(press(&mut ui, Keysym::RETURN), None);
+    ui.process_event(WindowEvent::Paste(
+        "Twice.\nThen it hung.\r\nAnd again.".into(),
+    ));
+    ui.redraw();
+    snap("input-multiline", &ui.canvas);
+    assert_eq!(
+        submit(&mut ui),
+        "It crashed.\nTwice.\nThen it hung.\nAnd again."
+    );
+
+    // Up and Down keep the caret at about the same horizontal position.
+    press(&mut ui, Keysym::UP);
+    press(&mut ui, Keysym::UP);
+    press(&mut ui, Keysym::HOME);
+    press(&mut ui, Keysym::UP);
+    press(&mut ui, Keysym::from_char('1'));
+    press(&mut ui, Keysym::DOWN);
+    press(&mut ui, Keysym::DOWN);
+    press(&mut ui, Keysym::END);
+    press(&mut ui, Keysym::from_char('2'));
+    press(&mut ui, Keysym::DOWN);
+    press(&mut ui, Keysym::DOWN);
+    press(&mut ui, Keysym::from_char('3'));
+    assert_eq!(
+        submit(&mut ui),
+        "1It crashed.\nTwice.\nThen it hung.2\nAnd again.3"
+    );
+    // Scrolling followed the caret, so the first line is out of view.
+    ui.redraw();
+    snap("input-multiline-scrolled", &ui.canvas);
+}
+
+#[test]
+fn input_constraints() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Which port should the server listen on?",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some("Port".into()),
+            input_max_len: Some(5),
+            input_filter: Some(Arc::new(|c| c.is_ascii_digit())),
+            input_validate: Some(Arc::new(|text| text.parse::<u16>().is_ok_and(|p| p > 0))),
+            ..Default::default()
+        },
+    );
+    let press = |ui: &mut Ui, c| {
+        ui.process_event(WindowEvent::KeyPress(
+            Keysym::from_char(c),
+            Modifiers::default(),
+        ))
+    };
+    let enter = || WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default());
+
+    // OK is disabled while the field is empty.
+    assert_eq!(ui.process_event(enter()), None);
+    ui.redraw();
+    snap("input-invalid", &ui.canvas);
+
+    // Rejected characters ring the bell.
+    press(&mut ui, 'x');
+    assert!(ui.take_bell_request());
+    for c in "80800".chars() {
+        press(&mut ui, c);
+    }
+    assert!(!ui.take_bell_request());
+    assert_eq!(ui.process_event(enter()), None, "80800 is out of range");
+    press(&mut ui, '1');
+    assert!(ui.take_bell_request(), "the field is full");
+
+    let backspace = || WindowEvent::KeyPress(Keysym::BACKSPACE, Modifiers::default());
+    ui.process_event(backspace());
+    ui.process_event(backspace());
+    ui.process_event(WindowEvent::Paste("a 0".into()));
+    assert!(ui.take_bell_request());
+    assert_eq!(
+        ui.process_event(enter()),
+        Some(Answer::Input {
+            button: 0,
+            text: "8080".into()
+        })
+    );
+
+    // Other buttons still work.
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Name:",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some(String::new()),
+            input_validate: Some(Arc::new(|text| !text.is_empty())),
+            ..Default::default()
+        },
+    );
+    let cancel = ui.button_bounds()[1].center();
+    ui.process_event(WindowEvent::CursorEnter(CursorPos {
+        x: cancel.x as i16,
+        y: cancel.y as i16,
+    }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert_eq!(
+        ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left)),
+        Some(Answer::Input {
+            button: 1,
+            text: String::new()
+        })
+    );
+}
+
+#[test]
+fn held_backspace() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "What is your name?",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some("Name".into()),
+            ..Default::default()
+        },
+    );
+    for c in "Alerta".chars() {
+        ui.process_event(WindowEvent::KeyPress(
+            Keysym(c as u32),
+            Modifiers::default(),
+        ));
+    }
+
+    // The X server repeats a held key by sending a release and a press for every repetition.
+    let backspace = || WindowEvent::KeyPress(Keysym::BACKSPACE, Modifiers::default());
+    assert_eq!(ui.process_event(backspace()), None);
+    for _ in 0..3 {
+        assert_eq!(
+            ui.process_event(WindowEvent::KeyRelease(Keysym::BACKSPACE)),
+            None
+        );
+        assert_eq!(ui.process_event(backspace()), None);
+    }
+    ui.process_event(WindowEvent::KeyRelease(Keysym::BACKSPACE));
+
+    let enter = WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default());
+    assert_eq!(
+        ui.process_event(enter),
+        Some(Answer::Input {
+            button: 0,
+            text: "Al".into(),
+        })
+    );
+}
+
+#[test]
+fn password() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Enter your password.",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some("Password".into()),
+            password: true,
+            ..Default::default()
+        },
+    );
+    let key = |c: char| WindowEvent::KeyPress(Keysym(c as u32), Modifiers::default());
+    for c in "hunter2".chars() {
+        ui.process_event(key(c));
+    }
+    ui.redraw();
+    snap("password", &ui.canvas);
+
+    let enter = WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default());
+    assert_eq!(
+        ui.process_event(enter),
+        Some(Answer::Input {
+            button: 0,
+            text: "hunter2".into(),
+        })
+    );
+}
+
+#[test]
+fn checkbox() {
+    let mut ui = Ui::new(
+        Icon::Warning,
+        Theme::Light,
+        "This operation may take a while.",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            checkbox: Some("Don't show this again".into()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(ui.checkbox_checked(), Some(false));
+
+    // Click on the label, which also toggles the checkbox.
+    let y = ui.canvas.height() - 70;
+    ui.process_event(WindowEvent::CursorEnter(CursorPos { x: 60, y: y as i16 }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert_eq!(
+        ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left)),
+        None
+    );
+    assert_eq!(ui.checkbox_checked(), Some(true));
+    ui.process_event(WindowEvent::CursorLeave);
+    ui.redraw();
+    snap("checkbox", &ui.canvas);
+
+    let ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "No checkbox.",
+        ButtonPreset::Ok.strings(),
+        Default::default(),
+    );
+    assert_eq!(ui.checkbox_checked(), None);
+}
+
+#[test]
+fn scrollable_message() {
+    let text = (1..=200)
+        .map(|i| format!("Line {i}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        &text,
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            max_height: Some(300),
+            ..Default::default()
+        },
+    );
+    assert_eq!(ui.canvas.height(), 300);
+    snap("scroll-top", &ui.canvas);
+
+    // Scrolling up at the top does nothing.
+    let scroll = |delta_y| WindowEvent::Scroll {
+        delta_x: 0,
+        delta_y,
+    };
+    ui.process_event(scroll(1));
+    ui.process_event(scroll(-2));
+    ui.redraw();
+    snap("scroll-down", &ui.canvas);
+}
+
+#[test]
+fn max_width() {
+    let ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Downloaded https://example.com/releases/download/v1.2.3/alerta-x86_64-unknown-linux-musl.tar.gz",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            max_width: Some(300),
+            ..Default::default()
+        },
+    );
+    assert_eq!(ui.canvas.width(), 300);
+    snap("max-width", &ui.canvas);
+}
+
+#[test]
+fn min_button_width() {
+    let ui = |min_button_width| {
+        Ui::new(
+            Icon::Question,
+            Theme::Light,
+            "Save changes?",
+            ButtonPreset::YesNoCancel.strings(),
+            ui::Options {
+                max_width: Some(100),
+                min_button_width,
+                ..Default::default()
+            },
+        )
+    };
+
+    // The buttons are at least 64 pixels wide, and make the window wider than `max_width`.
+    let default = ui(None);
+    let bounds = default.button_bounds();
+    assert!(bounds.iter().all(|rect| rect.width() >= 64));
+    assert!(default.canvas.width() > 100);
+    assert_eq!(default.canvas.width(), bounds[2].max.x + bounds[0].min.x);
+
+    // With the buttons exactly as wide as the minimum, all of "No" is clickable.
+    let bounds = ui(Some(100)).button_bounds();
+    assert!(bounds.iter().all(|rect| rect.width() == 100));
+    assert_eq!(
+        ui(Some(100)).canvas.width(),
+        bounds[2].max.x + bounds[0].min.x
+    );
+    let no = bounds[1];
+    for x in [no.min.x + 1, no.max.x - 1] {
+        let mut ui = ui(Some(100));
+        let y = no.center().y as i16;
+        ui.process_event(WindowEvent::CursorEnter(CursorPos { x: x as i16, y }));
+        ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+        assert_eq!(
+            ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left)),
+            Some(Answer::Button(1))
+        );
+    }
+}
+
+#[test]
+fn padding() {
+    let ui = |padding| {
+        Ui::new(
+            Icon::Question,
+            Theme::Light,
+            "Save changes?",
+            ButtonPreset::YesNoCancel.strings(),
+            ui::Options {
+                max_width: Some(100),
+                min_button_width: Some(100),
+                padding,
+                icon_position: IconPosition::None,
+                ..Default::default()
+            },
+        )
+    };
+    let default = ui(None);
+    assert_eq!(default.canvas.width(), 3 * 100 + 2 * 10 + 2 * 10);
+    assert_eq!(
+        ui(Some((10, 10))).canvas.get_data(),
+        default.canvas.get_data()
+    );
+
+    // The window size follows the padding, and the buttons are the same size.
+    let ui = ui(Some((20, 4)));
+    assert_eq!(ui.canvas.width(), 3 * 100 + 2 * 4 + 2 * 20);
+    assert!(ui.canvas.height() > default.canvas.height());
+    let bounds = ui.button_bounds();
+    assert_eq!(bounds[0].min.x, 20);
+    assert_eq!(bounds[1].min.x, bounds[0].max.x + 4);
+    assert_eq!(bounds[2].max.x, ui.canvas.width() - 20);
+    assert_eq!(ui.canvas.height() - bounds[0].max.y, 20);
+    snap("padding", &ui.canvas);
+}
+
+#[test]
+fn equal_button_widths() {
+    let ui = |equal_button_widths, max_width, min_button_width| {
+        Ui::new(
+            Icon::Question,
+            Theme::Light,
+            "Save changes before closing?",
+            ButtonPreset::YesNoCancel.strings(),
+            ui::Options {
+                equal_button_widths,
+                max_width,
+                min_button_width,
+                ..Default::default()
+            },
+        )
+    };
+
+    // In a narrow dialog, the short labels get as much room as "Cancel".
+    let narrow = |equal| ui(equal, Some(100), Some(0)).canvas.width();
+    assert!(narrow(true) > narrow(false));
+
+    let mut ui = ui(true, None, None);
+    ui.redraw();
+    snap("equal-button-widths", &ui.canvas);
+}
+
+#[test]
+fn resize() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        IPSUM,
+        ButtonPreset::YesNo.strings(),
+        ui::Options {
+            checkbox: Some("Remember my choice".into()),
+            ..Default::default()
+        },
+    );
+    let min = ui.min_size();
+    assert!(min.width <= ui.canvas.width() && min.height <= ui.canvas.height());
+
+    // At the minimum height, the message becomes scrollable.
+    ui.process_event(WindowEvent::Resized {
+        width: 700,
+        height: min.height as u16,
+    });
+    ui.redraw();
+    assert_eq!((ui.canvas.width(), ui.canvas.height()), (700, min.height));
+    snap("resize", &ui.canvas);
+
+    // The window is never laid out smaller than its minimum size.
+    ui.process_event(WindowEvent::Resized {
+        width: 10,
+        height: 10,
+    });
+    assert_eq!(
+        (ui.canvas.width(), ui.canvas.height()),
+        (min.width, min.height)
+    );
+}
+
+#[test]
+fn wrap_modes() {
+    let text = "thread 'main' panicked at src/main.rs:42:5:\n\
+                called `Option::unwrap()` on a `None` value, which is a very long line of text\n\
+                note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace, \
+                 or `RUST_BACKTRACE=full` for a verbose one";
+    let ui = |wrap| {
+        Ui::new(
+            Icon::Error,
+            Theme::Light,
+            text,
+            ButtonPreset::Ok.strings(),
+            ui::Options {
+                wrap,
+                ..Default::default()
+            },
+        )
+    };
+    snap("wrap-char", &ui(WrapMode::Char).canvas);
+
+    let mut ui = ui(WrapMode::None);
+    assert!(
+        ui.canvas.width()
+            > Ui::new(Icon::Error, Theme::Light, text, &["OK"], Default::default())
+                .canvas
+                .width()
+    );
+    snap("wrap-none", &ui.canvas);
+
+    // The message can only be scrolled horizontally, so the vertical wheel does that.
+    ui.process_event(WindowEvent::Scroll {
+        delta_x: 0,
+        delta_y: -1,
+    });
+    ui.redraw();
+    snap("wrap-none-scrolled", &ui.canvas);
+}
+
+#[test]
+fn wheel_events() {
+    let press = |detail| {
+        x11::button_press(&xproto::ButtonPressEvent {
+            detail,
+            ..Default::default()
+        })
+    };
+    match press(4) {
+        Some(WindowEvent::Scroll {
+            delta_x: 0,
+            delta_y,
+        }) => assert!(delta_y > 0),
+        ev => panic!("unexpected event {ev:?}"),
+    }
+    match press(5) {
+        Some(WindowEvent::Scroll {
+            delta_x: 0,
+            delta_y,
+        }) => assert!(delta_y < 0),
+        ev => panic!("unexpected event {ev:?}"),
+    }
+    match press(7) {
+        Some(WindowEvent::Scroll {
+            delta_x,
+            delta_y: 0,
+        }) => assert!(delta_x > 0),
+        ev => panic!("unexpected event {ev:?}"),
+    }
+    assert!(matches!(
+        press(1),
+        Some(WindowEvent::ButtonPress(MouseButton::Left))
+    ));
+}
+
+#[test]
+fn custom_theme() {
+    let theme = Theme::Custom {
+        background: 0xff_fdf6e3,
+        text: 0xff_586e75,
+        button: 0xff_eee8d5,
+        button_hover: 0xff_e4dcc4,
+        accent: 0xff_268bd2,
+    };
+    let ui = Ui::new(
+        Icon::Warning,
+        theme,
+        "Custom colors.",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            default_button: Some(0),
+            ..Default::default()
+        },
+    );
+    snap("theme-custom", &ui.canvas);
+
+    let ui = Ui::new(
+        Icon::Question,
+        Theme::Dark,
+        "Accent color on the default button.",
+        ButtonPreset::YesNoCancel.strings(),
+        ui::Options {
+            default_button: Some(0),
+            accent_color: Some(0xff_e66100),
+            ..Default::default()
+        },
+    );
+    snap("accent-color", &ui.canvas);
+}
+
+#[test]
+fn theme_monitor_parser() {
+    let mut parser = monitor::Parser::default();
+    let output = r#"
+signal time=1700000000.1 sender=:1.5 -> destination=(null destination) serial=90 path=/org/freedesktop/portal/desktop; interface=org.freedesktop.portal.Settings; member=SettingChanged
+   string "org.gnome.desktop.interface"
+   string "color-scheme"
+   variant       string "prefer-dark"
+signal time=1700000000.2 sender=:1.5 -> destination=(null destination) serial=91 path=/org/freedesktop/portal/desktop; interface=org.freedesktop.portal.Settings; member=SettingChanged
+   string "org.freedesktop.appearance"
+   string "color-scheme"
+   variant       uint32 1
+signal time=1700000005.3 sender=:1.5 -> destination=(null destination) serial=92 path=/org/freedesktop/portal/desktop; interface=org.freedesktop.portal.Settings; member=SettingChanged
+   string "org.freedesktop.appearance"
+   string "color-scheme"
+   variant       uint32 2
+"#;
+    let themes = output
+        .lines()
+        .filter_map(|line| parser.feed(line))
+        .collect::<Vec<_>>();
+    assert_eq!(themes, [Theme::Dark, Theme::Light]);
+}
+
+#[test]
+fn gtk_settings() {
+    let settings = "
+[Settings]
+gtk-theme-name=Adwaita
+gtk-application-prefer-dark-theme = 1
+";
+    assert_eq!(Theme::from_gtk_settings(settings), Some(Theme::Dark));
+    assert_eq!(
+        Theme::from_gtk_settings("[Settings]\ngtk-application-prefer-dark-theme=false"),
+        Some(Theme::Light)
+    );
+    // Keys in other sections don't count.
+    assert_eq!(
+        Theme::from_gtk_settings("[Other]\ngtk-application-prefer-dark-theme=1"),
+        None
+    );
+    assert_eq!(
+        Theme::from_gtk_settings("[Settings]\ngtk-theme-name=Adwaita"),
+        None
+    );
+}
+
+#[test]
+fn remembered_positions() {
+    let contents = positions::update("", "first", (10, 20));
+    let contents = positions::update(&contents, "second dialog", (-5, 0));
+    assert_eq!(positions::lookup(&contents, "first"), Some((10, 20)));
+    assert_eq!(positions::lookup(&contents, "second dialog"), Some((-5, 0)));
+    assert_eq!(positions::lookup(&contents, "second"), None);
+
+    // Updating a position replaces the old line.
+    let contents = positions::update(&contents, "first", (30, 40));
+    assert_eq!(positions::lookup(&contents, "first"), Some((30, 40)));
+    assert_eq!(contents.lines().count(), 2);
+
+    // Line breaks can't sneak in another entry.
+    let contents = positions::update("", "a\n1 2 b", (0, 0));
+    assert_eq!(positions::lookup(&contents, "b"), None);
+    assert_eq!(positions::lookup(&contents, "a\n1 2 b"), Some((0, 0)));
+}
+
+#[test]
+fn clamp_to_monitors() {
+    let monitors = [(0, 0, 1920, 1080), (1920, 0, 1280, 1024)];
+    // Windows that are on a monitor stay where they are.
+    assert_eq!(
+        x11::clamp_to_monitors(&monitors, (100, 100), (300, 200)),
+        (100, 100)
+    );
+    assert_eq!(
+        x11::clamp_to_monitors(&monitors, (2000, 500), (300, 200)),
+        (2000, 500)
+    );
+    // Windows sticking out are moved onto the monitor with their center.
+    assert_eq!(
+        x11::clamp_to_monitors(&monitors, (3000, 900), (300, 200)),
+        (2900, 824)
+    );
+    // Windows on a disconnected monitor are moved onto the first one.
+    assert_eq!(
+        x11::clamp_to_monitors(&monitors, (4000, 100), (300, 200)),
+        (1620, 100)
+    );
+    assert_eq!(
+        x11::clamp_to_monitors(&monitors, (-1000, -1000), (300, 200)),
+        (0, 0)
+    );
+}
+
+#[test]
+fn set_theme() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "The theme was changed after creating the dialog.",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            default_button: Some(0),
+            ..Default::default()
+        },
+    );
+    ui.set_theme(Theme::Dark);
+    snap("theme-changed", &ui.canvas);
+}
+
+#[test]
+fn select_message() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Hello, world! Select me.",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            icon_position: IconPosition::None,
+            ..Default::default()
+        },
+    );
+    let ctrl = Modifiers {
+        bits: Modifiers::CONTROL,
+    };
+    let ctrl_c = || WindowEvent::KeyPress(Keysym(u32::from('c')), ctrl);
+
+    // Without a selection, the whole message is copied.
+    ui.process_event(ctrl_c());
+    assert_eq!(
+        ui.take_copied_text().as_deref(),
+        Some("Hello, world! Select me.")
+    );
+    assert_eq!(ui.take_copied_text(), None);
+
+    // Drag from the end of the message towards its start.
+    ui.process_event(WindowEvent::CursorEnter(CursorPos { x: 151, y: 26 }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert!(ui.is_selecting());
+    ui.process_event(WindowEvent::CursorMove(CursorPos { x: 93, y: 26 }));
+    ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left));
+    assert!(!ui.is_selecting());
+    ui.process_event(ctrl_c());
+    assert_eq!(ui.take_copied_text().as_deref(), Some("Select me."));
+    ui.process_event(WindowEvent::CursorLeave);
+    ui.redraw();
+    snap("selection", &ui.canvas);
+
+    // Clicking outside of the message clears the selection.
+    ui.process_event(WindowEvent::CursorEnter(CursorPos { x: 5, y: 5 }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert!(!ui.is_selecting());
+    ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left));
+    ui.process_event(ctrl_c());
+    assert_eq!(
+        ui.take_copied_text().as_deref(),
+        Some("Hello, world! Select me.")
+    );
+}
+
+#[test]
+fn copy_button() {
+    let message = "Something went wrong:\n\nerror code 42";
+    let mut ui = Ui::new(
+        Icon::Error,
+        Theme::Light,
+        message,
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            copy_button: true,
+            ..Default::default()
+        },
+    );
+    ui.redraw();
+    snap("copy-button", &ui.canvas);
+
+    // The "Copy" button is on the right, and doesn't close the dialog.
+    let y = ui.canvas.height() - 20;
+    let x = ui.canvas.width() - 20;
+    ui.process_event(WindowEvent::CursorEnter(CursorPos {
+        x: x as i16,
+        y: y as i16,
+    }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert_eq!(
+        ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left)),
+        None
+    );
+    assert_eq!(ui.take_copied_text().as_deref(), Some(message));
+
+    ui.process_event(WindowEvent::CursorMove(CursorPos { x: 20, y: y as i16 }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert_eq!(
+        ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left)),
+        Some(Answer::Button(0))
+    );
+    assert_eq!(ui.take_copied_text(), None);
+}
+
+#[test]
+fn copy_shortcut() {
+    let message = "Something went wrong:\n\nerror code 42";
+    let options = |copy_shortcut| ui::Options {
+        copy_shortcut,
+        ..Default::default()
+    };
+    let ctrl_shift_c = || {
+        WindowEvent::KeyPress(
+            Keysym(u32::from('C')),
+            Modifiers {
+                bits: Modifiers::CONTROL | Modifiers::SHIFT,
+            },
+        )
+    };
+
+    let mut ui = Ui::new(Icon::Error, Theme::Light, message, &["OK"], options(true));
+    assert_eq!(
+        ui.process_event(ctrl_shift_c()),
+        Some(Answer::CopiedAndClosed)
+    );
+    assert_eq!(ui.take_copied_text().as_deref(), Some(message));
+
+    // When disabled, it copies like Ctrl+C.
+    let mut ui = Ui::new(Icon::Error, Theme::Light, message, &["OK"], options(false));
+    assert_eq!(ui.process_event(ctrl_shift_c()), None);
+    assert_eq!(ui.take_copied_text().as_deref(), Some(message));
+}
+
+#[test]
+fn tab_focus() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Use Tab to move between buttons.",
+        ButtonPreset::YesNoCancel.strings(),
+        Default::default(),
+    );
+    let key = |sym, bits| WindowEvent::KeyPress(sym, Modifiers { bits });
+
+    // The first button has focus, and Tab moves on to the next one.
+    assert_eq!(ui.process_event(key(Keysym::TAB, 0)), None);
+    ui.redraw();
+    snap("tab-focus", &ui.canvas);
+
+    // Shift+Tab goes backwards, wrapping around.
+    ui.process_event(key(Keysym::TAB, Modifiers::SHIFT));
+    ui.process_event(key(Keysym::ISO_LEFT_TAB, Modifiers::SHIFT));
+    assert_eq!(
+        ui.process_event(key(Keysym::SPACE, 0)),
+        Some(Answer::Button(2))
+    );
+    ui.process_event(key(Keysym::TAB, 0));
+    assert_eq!(
+        ui.process_event(key(Keysym::RETURN, 0)),
+        Some(Answer::Button(0))
+    );
+
+    // Focus starts on the default button, and Space types into the input field.
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Name?",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            default_button: Some(1),
+            input: Some(String::new()),
+            ..Default::default()
+        },
+    );
+    ui.process_event(key(Keysym::SPACE, 0));
+    ui.process_event(key(Keysym::TAB, 0));
+    assert_eq!(
+        ui.process_event(key(Keysym::RETURN, 0)),
+        Some(Answer::Input {
+            button: 0,
+            text: " ".into(),
+        })
+    );
+}
+
+#[test]
+fn scale() {
+    let ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Everything is 1.5 times as large.",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            default_button: Some(0),
+            checkbox: Some("Remember my choice".into()),
+            scale: Some(1.5),
+            ..Default::default()
+        },
+    );
+    assert_eq!(ui.canvas.width(), 600);
+    snap("scale-1.5", &ui.canvas);
+}
+
+#[test]
+fn corner_radius() {
+    let ui = Ui::new(
+        Icon::Info,
+        Theme::Dark,
+        "The corners of this dialog are rounded.",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            corner_radius: 12,
+            ..Default::default()
+        },
+    );
+    snap("corner_radius", &ui.canvas);
+
+    let (width, height) = (ui.canvas.width(), ui.canvas.height());
+    let shape = ui.window_shape().expect("rounded windows need a shape");
+    let covers = |x, y| shape.iter().any(|rect| rect.contains(point2(x, y)));
+    assert!(!covers(0, 0));
+    assert!(!covers(width - 1, height - 1));
+    assert!(covers(12, 0));
+    assert!(covers(0, 12));
+    assert!(covers(width / 2, height / 2));
+    // Every pixel is covered exactly once.
+    let area: i32 = shape.iter().map(|rect| rect.area()).sum();
+    let corners = (0..width)
+        .flat_map(|x| (0..height).map(move |y| (x, y)))
+        .filter(|&(x, y)| !covers(x, y))
+        .count() as i32;
+    assert_eq!(area + corners, width * height);
+
+    let square = Ui::new(Icon::Info, Theme::Dark, "", &["OK"], Default::default());
+    assert!(square.window_shape().is_none());
+}
+
+#[test]
+fn xft_dpi() {
+    let resources = "Xcursor.size:\t24\nXft.antialias:\t1\nXft.dpi:\t144\nXft.hinting:\t1\n";
+    assert_eq!(x11::xft_dpi(resources), Some(144.0));
+    assert_eq!(x11::xft_dpi("Xft.dpi: 96.5"), Some(96.5));
+    assert_eq!(x11::xft_dpi("Xcursor.size:\t24\n"), None);
+    assert_eq!(x11::xft_dpi("Xft.dpi:\tlots\n"), None);
+    assert_eq!(x11::xft_dpi("Xft.dpi:\t0\n"), None);
+}
+
+#[test]
+fn monitor_scale() {
+    // A 24" 1080p monitor, a 27" 4K one, and a 13" laptop screen.
+    assert_eq!(x11::monitor_scale((1920, 1080), (527, 296)), Some(1.0));
+    assert_eq!(x11::monitor_scale((3840, 2160), (600, 340)), Some(1.5));
+    assert_eq!(x11::monitor_scale((2560, 1600), (286, 179)), Some(2.5));
+    // Rotated.
+    assert_eq!(x11::monitor_scale((1600, 2560), (179, 286)), Some(2.5));
+    // Not less than 1, even for large TVs.
+    assert_eq!(x11::monitor_scale((1920, 1080), (1600, 900)), Some(1.0));
+    // Unknown or bogus physical sizes.
+    assert_eq!(x11::monitor_scale((1920, 1080), (0, 0)), None);
+    assert_eq!(x11::monitor_scale((1920, 1080), (16, 9)), None);
+
+    let mut monitors = x11::MonitorScales::new(vec![
+        ((0, 0, 1920, 1080), Some(1.0)),
+        ((1920, 0, 3840, 2160), Some(2.0)),
+        ((5760, 0, 1920, 1080), None),
+    ]);
+    assert_eq!(monitors.relative_scale((100, 100)), Some(1.0));
+    assert_eq!(monitors.relative_scale((2000, 100)), Some(2.0));
+    assert_eq!(monitors.relative_scale((6000, 100)), None);
+    assert_eq!(monitors.relative_scale((-100, -100)), None);
+    assert_eq!(monitors.relative_scale((500, 100)), Some(1.0));
+
+    // Factors are relative to the first monitor.
+    let mut monitors = x11::MonitorScales::new(vec![
+        ((0, 0, 1920, 1080), Some(1.0)),
+        ((1920, 0, 3840, 2160), Some(2.0)),
+    ]);
+    assert_eq!(monitors.relative_scale((2000, 100)), Some(1.0));
+    assert_eq!(monitors.relative_scale((100, 100)), Some(0.5));
+}
+
+#[test]
+fn carry_over() {
+    let options = || ui::Options {
+        input: Some("Name".into()),
+        ..Default::default()
+    };
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Dark,
+        "What is your name?",
+        ButtonPreset::OkCancel.strings(),
+        options(),
+    );
+    ui.set_message("What is your name, again?");
+    let key = |c: char| WindowEvent::KeyPress(Keysym(c as u32), Modifiers::default());
+    for c in "Alerta".chars() {
+        ui.process_event(key(c));
+    }
+
+    let mut scaled = Ui::new(
+        Icon::Question,
+        Theme::Dark,
+        "",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            scale: Some(2.0),
+            ..options()
+        },
+    );
+    scaled.carry_over(ui, false);
+    assert_eq!(
+        scaled.take_resize_request(),
+        Some(size2(scaled.canvas.width(), scaled.canvas.height()))
+    );
+    let reference = Ui::new(
+        Icon::Question,
+        Theme::Dark,
+        "What is your name, again?",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            scale: Some(2.0),
+            ..options()
+        },
+    );
+    assert_eq!(scaled.canvas.width(), reference.canvas.width());
+    assert_eq!(scaled.canvas.height(), reference.canvas.height());
+    assert_eq!(
+        scaled.process_event(WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default())),
+        Some(Answer::Input {
+            button: 0,
+            text: "Alerta".into(),
+        })
+    );
+}
+
+#[test]
+fn font_size() {
+    let ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Larger text makes the dialog larger, too.",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            font_size: Some(24.0),
+            ..Default::default()
+        },
+    );
+    snap("font-size", &ui.canvas);
+}
+
+#[test]
+fn font_fallback() {
+    let ui = |font_family: Option<&str>| {
+        Ui::new(
+            Icon::Info,
+            Theme::Light,
+            "Unknown fonts are replaced with the built-in font.",
+            ButtonPreset::Ok.strings(),
+            ui::Options {
+                font_family: font_family.map(Into::into),
+                ..Default::default()
+            },
+        )
+    };
+    let builtin = ui(None);
+    let fallback = ui(Some("This Font Does Not Exist"));
+    assert!(builtin.canvas.get_data() == fallback.canvas.get_data());
+}
+
+#[test]
+fn glyph_fallback() {
+    use ab_glyph::ScaleFont as _;
+    use ui::font::{DEFAULT_SIZE, Font};
+
+    let font = Font::load(None, DEFAULT_SIZE);
+    assert_eq!(font.face_of('a'), 0);
+    assert_eq!(font.face_of(' '), 0);
+
+    // Which fallback is found depends on the installed fonts, but it must contain the glyph.
+    let face = font.face_of('☺');
+    assert_eq!(font.face_of('☺'), face);
+    let faces = font.faces_for("☺");
+    if face != 0 {
+        assert_ne!(faces[face].glyph_id('☺').0, 0);
+    }
+
+    // Text using the fallback still renders.
+    let canvas = font.render("Smile ☺").finish();
+    assert!(canvas.width() > font.render("Smile").finish().width());
+}
+
+#[test]
+fn monospace() {
+    use ui::font::{DEFAULT_SIZE, Font};
+
+    // The built-in font is what monospace text falls back to, which is detected as not monospace.
+    assert!(!Font::load(None, DEFAULT_SIZE).is_monospace());
+    if !has_monospace_font() {
+        return;
+    }
+    // Generic families are resolved by fontconfig instead of being rejected as unknown.
+    let font = Font::load(Some("monospace"), DEFAULT_SIZE);
+    assert_eq!(font.advance("iii"), font.advance("WWW"));
+
+    let table = "Name     Size  Modified\n\
+                 a.txt      12  yesterday\n\
+                 notes.md  340  last week, while the dialog is narrower than this line";
+    let ui = |monospace| {
+        Ui::new(
+            Icon::Info,
+            Theme::Light,
+            table,
+            ButtonPreset::Ok.strings(),
+            ui::Options {
+                monospace,
+                ..Default::default()
+            },
+        )
+    };
+    // The message isn't wrapped, so the dialog is wider.
+    assert!(ui(true).canvas.width() > ui(false).canvas.width());
+}
+
+#[test]
+fn markup_parser() {
+    use ui::markup::{Style, parse};
+
+    let bold = Style {
+        bold: true,
+        italic: false,
+        link: false,
+        color: None,
+    };
+    let italic = Style {
+        bold: false,
+        italic: true,
+        link: false,
+        color: None,
+    };
+
+    let m = parse("**Error:** the file *could not* be saved.");
+    assert_eq!(m.text, "Error: the file could not be saved.");
+    assert_eq!(m.runs, [(0..6, bold), (16..25, italic)]);
+
+    // Escapes, and markers without a partner.
+    let m = parse(r"2 \* 3 = 6, \\ and *one * and ** and a \n");
+    assert_eq!(m.text, r"2 * 3 = 6, \ and one  and ** and a \n");
+    assert_eq!(m.runs, [(17..21, italic)]);
+
+    let m = parse("***both***");
+    assert_eq!(m.text, "both");
+    assert_eq!(
+        m.runs,
+        [(
+            0..4,
+            Style {
+                bold: true,
+                italic: true,
+                link: false,
+                color: None,
+            }
+        )]
+    );
+}
+
+#[test]
+fn ansi_parser() {
+    use ui::{ansi::apply, markup};
+
+    let plain = |text: &str| markup::Markup {
+        text: text.to_string(),
+        runs: Vec::new(),
+    };
+    let log =
+        "\x1b[1;31merror\x1b[0m: \x1b]8;;https://x.org\x07file\x1b]8;;\x1b\\ not \x1b[2Kfound";
+
+    // Stripping removes every kind of escape sequence, but no other text.
+    let m = apply(plain(log), false);
+    assert_eq!(m.text, "error: file not found");
+    assert!(m.runs.is_empty());
+    let m = apply(plain("50% done\x1b[K\x1b(B\x1b"), false);
+    assert_eq!(m.text, "50% done");
+    assert_eq!(apply(plain("a\x1b[1"), false).text, "a");
+
+    // Rendering turns SGR sequences into styled runs.
+    let m = apply(plain(log), true);
+    assert_eq!(m.text, "error: file not found");
+    assert_eq!(m.runs.len(), 1);
+    let (range, style) = &m.runs[0];
+    assert_eq!(*range, 0..5);
+    assert!(style.bold && !style.italic && style.color.is_some());
+
+    let m = apply(
+        plain("\x1b[32mok\x1b[39m \x1b[38;5;208morange\x1b[38;2;1;2;3m rgb\x1b[48;5;1m\x1b[m"),
+        true,
+    );
+    assert_eq!(m.text, "ok orange rgb");
+    let ranges = m.runs.iter().map(|(r, _)| r.clone()).collect::<Vec<_>>();
+    assert_eq!(ranges, [0..2, 3..9, 9..13]);
+    let colors = m.runs.iter().map(|(_, s)| s.color).collect::<Vec<_>>();
+    assert!(colors.iter().all(Option::is_some));
+    assert!(colors[0] != colors[1] && colors[1] != colors[2]);
+
+    // Markup styles are kept, and moved to where their text ends up.
+    let m = apply(
+        markup::parse("**bold \x1b[3mand\x1b[23m** \x1b[1mafter"),
+        true,
+    );
+    assert_eq!(m.text, "bold and after");
+    let styles = m
+        .runs
+        .iter()
+        .map(|(r, s)| (r.clone(), s.bold, s.italic))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        styles,
+        [
+            (0..5, true, false),
+            (5..8, true, true),
+            (9..14, true, false)
+        ]
+    );
+}
+
+#[test]
+fn ansi_render() {
+    let ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "\x1b[1mBuild finished\x1b[0m\n\
+         \x1b[32m   Compiling\x1b[0m alerta v0.1.2\n\
+         \x1b[33mwarning\x1b[0m: unused variable\n\
+         \x1b[1;31merror\x1b[0m: could not compile",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            ansi: AnsiMode::Render,
+            ..Default::default()
+        },
+    );
+    snap("ansi", &ui.canvas);
+}
+
+#[test]
+fn title_in_body() {
+    let ui = Ui::new(
+        Icon::Warning,
+        Theme::Light,
+        "The backup could not be completed.",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            title: Some(
+                "Backup of “Documents” to the external drive “Archive (2 TB)” failed after \
+                 copying 1,204 of 3,750 files, because the drive was disconnected during the \
+                 verification step that runs after every backup"
+                    .into(),
+            ),
+            ..Default::default()
+        },
+    );
+    snap("title-in-body", &ui.canvas);
+
+    // Undecorated dialogs have no title bar, so the title is displayed in them by default.
+    let size = |b: crate::Builder| b.message("Message").measure().unwrap();
+    let plain = size(crate::alerta());
+    assert_eq!(
+        size(crate::alerta().decorated(false).show_title_in_body(false)),
+        plain
+    );
+    let undecorated = size(crate::alerta().decorated(false));
+    assert!(undecorated.1 > plain.1);
+    assert_eq!(size(crate::alerta().show_title_in_body(true)), undecorated);
+}
+
+#[test]
+fn line_endings() {
+    let ui = |message: &str| {
+        Ui::new(
+            Icon::Info,
+            Theme::Light,
+            message,
+            ButtonPreset::Ok.strings(),
+            Default::default(),
+        )
+        .canvas
+    };
+    let unix = ui("First line\nSecond line\n\nFourth line");
+    for message in [
+        "First line\r\nSecond line\r\n\r\nFourth line",
+        "First line\rSecond line\r\rFourth line",
+        "First line\r\nSecond line\r\rFourth line",
+    ] {
+        let canvas = ui(message);
+        assert_eq!(canvas.height(), unix.height(), "{message:?}");
+        assert!(canvas.get_data() == unix.get_data(), "{message:?}");
+    }
+    assert!(ui("First line Second line Fourth line").height() < unix.height());
+}
+
+#[test]
+fn detail() {
+    let ui = Ui::new(
+        Icon::Error,
+        Theme::Light,
+        "**The document could not be saved.**",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            markup: true,
+            detail: Some(
+                "The disk is full. Free up some space by deleting files you don't need anymore, \
+                 then try again."
+                    .into(),
+            ),
+            ..Default::default()
+        },
+    );
+    snap("detail", &ui.canvas);
+
+    // The window grows to fit the detail text.
+    let height = |detail: Option<&str>| {
+        let options = ui::Options {
+            icon_position: IconPosition::None,
+            detail: detail.map(Into::into),
+            ..Default::default()
+        };
+        Ui::new(
+            Icon::Info,
+            Theme::Light,
+            "A\nlonger\nmessage",
+            &["OK"],
+            options,
+        )
+        .canvas
+        .height()
+    };
+    assert!(height(Some("Detail")) > height(None));
+    assert!(height(Some("Line 1\nLine 2")) > height(Some("Detail")));
+}
+
+#[test]
+fn footer() {
+    let ui = |footer: Option<&str>| {
+        let options = ui::Options {
+            footer: footer.map(Into::into),
+            ..Default::default()
+        };
+        Ui::new(
+            Icon::Info,
+            Theme::Light,
+            "The update was installed.",
+            ButtonPreset::Ok.strings(),
+            options,
+        )
+    };
+    snap("footer", &ui(Some("MyApp v1.2.3")).canvas);
+
+    // The window grows to fit the footer, but a long footer is cut off instead of widening it.
+    let plain = ui(None).canvas;
+    let long = ui(Some(&"MyApp v1.2.3 ".repeat(20))).canvas;
+    assert!(long.height() > plain.height());
+    assert_eq!(long.width(), plain.width());
+    snap("footer-long", &long);
+}
+
+#[test]
+fn custom_draw() {
+    let drawn = Arc::new(Mutex::new(Vec::new()));
+    let draw = {
+        let drawn = drawn.clone();
+        move |canvas: &mut DrawTarget, rect: Rect| {
+            drawn.lock().unwrap().push(rect);
+            let mut bar = |i: i32, height: i32, color: u32| {
+                let [_, r, g, b] = color.to_be_bytes();
+                canvas.fill_rect(
+                    (rect.x + i * 60) as f32,
+                    (rect.y + rect.height as i32 - height) as f32,
+                    40.0,
+                    height as f32,
+                    &raqote::SolidSource::from_unpremultiplied_argb(255, r, g, b).into(),
+                    &Default::default(),
+                );
+            };
+            bar(0, 30, 0x3b8eea);
+            bar(1, 60, 0x23d18b);
+            // Drawing outside of the area is clipped.
+            bar(2, 500, 0xf14c4c);
+        }
+    };
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Requests per day:",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            custom_draw: Some(Arc::new(draw)),
+            custom_draw_size: Some((460, 80)),
+            ..Default::default()
+        },
+    );
+    ui.redraw();
+    snap("custom-draw", &ui.canvas);
+
+    // The area is between the message and the buttons, and widens the window.
+    let rect = drawn.lock().unwrap()[0];
+    assert_eq!((rect.x, rect.width, rect.height), (10, 460, 80));
+    assert_eq!(ui.canvas.width(), 460 + 2 * 10);
+    assert!(rect.y + rect.height as i32 <= ui.button_bounds()[0].min.y);
+}
+
+#[test]
+fn expandable_details() {
+    let traceback = (1..=12)
+        .map(|i| format!("  at frame_{i} (src/main.rs:{})", i * 10))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut ui = Ui::new(
+        Icon::Error,
+        Theme::Light,
+        "The application crashed.",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            expandable_details: Some(traceback),
+            ..Default::default()
+        },
+    );
+    ui.redraw();
+    snap("details-collapsed", &ui.canvas);
+    let collapsed = size2(ui.canvas.width(), ui.canvas.height());
+
+    // Clicking the toggle expands the details, and asks for a taller window.
+    let click = |ui: &mut Ui, p: euclid::default::Point2D<i32>| {
+        let (x, y) = (p.x as i16, p.y as i16);
+        ui.process_event(WindowEvent::CursorMove(CursorPos { x, y }));
+        ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+        ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left))
+    };
+    let (toggle, details) = ui.details_bounds().unwrap();
+    assert_eq!(details, None);
+    assert_eq!(click(&mut ui, toggle.center()), None);
+    let expanded = ui.take_resize_request().unwrap();
+    assert_eq!(expanded.width, collapsed.width);
+    assert!(expanded.height > collapsed.height);
+    assert_eq!(size2(ui.canvas.width(), ui.canvas.height()), expanded);
+    assert_eq!(ui.take_resize_request(), None);
+
+    // The text is longer than the box, and scrolls. It is displayed in the system monospace font,
+    // so it isn't compared to a snapshot.
+    let (_, details) = ui.details_bounds().unwrap();
+    let center = details.unwrap().center();
+    ui.process_event(WindowEvent::CursorMove(CursorPos {
+        x: center.x as i16,
+        y: center.y as i16,
+    }));
+    ui.redraw();
+    let unscrolled = ui.canvas.get_data().to_vec();
+    ui.process_event(WindowEvent::Scroll {
+        delta_x: 0,
+        delta_y: -1,
+    });
+    ui.redraw();
+    assert!(ui.canvas.get_data() != unscrolled);
+
+    // The toggle stays in place, and clicking it again collapses the details.
+    assert_eq!(ui.details_bounds().unwrap().0, toggle);
+    assert_eq!(click(&mut ui, toggle.center()), None);
+    assert_eq!(ui.take_resize_request(), Some(collapsed));
+}
+
+#[test]
+fn markup() {
+    let ui = Ui::new(
+        Icon::Error,
+        Theme::Light,
+        "**The document could not be saved.**\nThe disk is *full*. Literal \\*asterisks\\*.",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            markup: true,
+            ..Default::default()
+        },
+    );
+    snap("markup", &ui.canvas);
+}
+
+#[test]
+fn tab_stops() {
+    use ui::font::{DEFAULT_SIZE, Font};
+
+    if !has_monospace_font() {
+        return;
+    }
+    let font = Font::load(Some("monospace"), DEFAULT_SIZE);
+    let space = font.advance(" ");
+    // Returns the x offset of the character at `index` from the start of the line.
+    let offset = |text: &str, tab_width, index| {
+        let (_, layout) = font
+            .render(text)
+            .with_tab_width(tab_width)
+            .with_line_box()
+            .finish_with_layout();
+        let line = layout.boxes(0..1)[0];
+        layout.boxes(index..index + 1)[0].min.x - line.min.x
+    };
+
+    assert_eq!(offset("\tx", 4, 1), 4.0 * space);
+    assert_eq!(offset("\tx", 2, 1), 2.0 * space);
+    assert_eq!(offset("\t\tx", 4, 2), 8.0 * space);
+    // Text before the tab is padded up to the tab stop, or to the next one if it is longer.
+    assert_eq!(offset("ab\tx", 4, 3), 4.0 * space);
+    assert_eq!(offset("abcde\tx", 4, 6), 8.0 * space);
+    // A tab width of 0 is treated as 1.
+    assert_eq!(offset("\tx", 0, 1), space);
+}
+
+#[test]
+fn grapheme_clusters() {
+    use ui::grapheme::cluster_starts;
+
+    let clusters = |text: &str| {
+        let mut starts = cluster_starts(text).collect::<Vec<_>>();
+        starts.push(text.len());
+        starts
+            .windows(2)
+            .map(|w| text[w[0]..w[1]].to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(clusters("abc"), ["a", "b", "c"]);
+    assert_eq!(
+        clusters("e\u{301}a\u{308}\u{304}"),
+        ["e\u{301}", "a\u{308}\u{304}"]
+    );
+    // Flags are pairs of regional indicators.
+    assert_eq!(clusters("🇩🇪🇫🇷🇮"), ["🇩🇪", "🇫🇷", "🇮"]);
+    // Emoji with a skin tone, and emoji joined with ZWJ.
+    assert_eq!(clusters("👍🏽👩\u{200d}💻x"), ["👍🏽", "👩\u{200d}💻", "x"]);
+    // Hangul syllables made of conjoining jamo.
+    assert_eq!(
+        clusters("\u{1100}\u{1161}\u{11a8}\u{ac00}\u{11a8}"),
+        ["\u{1100}\u{1161}\u{11a8}", "\u{ac00}\u{11a8}"]
+    );
+    // Vowel signs of Indic scripts, and conjuncts joined by a virama.
+    assert_eq!(clusters("কি"), ["কি"]);
+    assert_eq!(clusters("நிலா"), ["நி", "லா"]);
+    assert_eq!(clusters("ગુજ"), ["ગુ", "જ"]);
+    assert_eq!(clusters("क्षि"), ["क्षि"]);
+}
+
+#[test]
+fn wrap_grapheme_clusters() {
+    use ui::font::{DEFAULT_SIZE, Font};
+    use ui::grapheme::cluster_starts;
+
+    let font = Font::load(None, DEFAULT_SIZE);
+    let text = "e\u{301}o\u{308}\u{304}🇩🇪a\u{20dd}".repeat(8);
+    let chars = text.char_indices().map(|(i, _)| i).collect::<Vec<_>>();
+    for width in [20.0, 30.0, 45.0, 60.0] {
+        let (_, layout) = font
+            .render(&text)
+            .with_wrap(WrapMode::Char)
+            .with_max_width(width)
+            .finish_with_layout();
+        let mut starts = cluster_starts(&text)
+            .map(|byte| chars.binary_search(&byte).unwrap())
+            .collect::<Vec<_>>();
+        starts.push(chars.len());
+        // The text is wrapped, but every grapheme cluster stays on a single line.
+        assert!(layout.boxes(0..layout.len()).len() > 1);
+        for cluster in starts.windows(2) {
+            assert_eq!(
+                layout.boxes(cluster[0]..cluster[1]).len(),
+                1,
+                "width {width}"
+            );
+        }
+    }
+}
+
+#[test]
+fn bidi_order() {
+    use ui::bidi::{class, levels, visual_order};
+
+    let visual = |text: &str, rtl: bool| {
+        let chars = text.chars().collect::<Vec<_>>();
+        let classes = chars.iter().map(|&c| class(c)).collect::<Vec<_>>();
+        visual_order(&levels(&classes, rtl))
+            .into_iter()
+            .map(|i| chars[i])
+            .collect::<String>()
+    };
+
+    assert_eq!(visual("abc def", false), "abc def");
+    // Right-to-left runs are reversed, but numbers and left-to-right words in them are not.
+    assert_eq!(visual("abc אבג דהו def", false), "abc והד גבא def");
+    assert_eq!(visual("אבג 123 דהו", true), "והד 123 גבא");
+    assert_eq!(visual("אבג abc def דהו.", true), ".והד abc def גבא");
+}
+
+#[test]
+fn rtl() {
+    // The built-in font has no Hebrew or Arabic glyphs, so this only shows the alignment.
+    let ui = Ui::new(
+        Icon::Warning,
+        Theme::Light,
+        "Right-to-left messages are aligned to the right.\nShort line.",
+        ButtonPreset::RetryCancel.strings(),
+        ui::Options {
+            icon_position: IconPosition::Right,
+            text_direction: TextDirection::Rtl,
+            mirror_buttons: true,
+            ..Default::default()
+        },
+    );
+    snap("rtl", &ui.canvas);
+}
+
+#[test]
+fn progress() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Copying files…",
+        ButtonPreset::Close.strings(),
+        ui::Options {
+            progress: Some(Indicator::Bar(0.0)),
+            ..Default::default()
+        },
+    );
+    let size = (ui.canvas.width(), ui.canvas.height());
+    ui.set_progress(0.4);
+    ui.redraw();
+    snap("progress", &ui.canvas);
+
+    // Updating the message keeps the window size.
+    ui.set_message("Copying files…\nalerta-x86_64-unknown-linux-musl.tar.gz");
+    ui.set_progress(2.0);
+    ui.redraw();
+    assert_eq!((ui.canvas.width(), ui.canvas.height()), size);
+    snap("progress-updated", &ui.canvas);
+}
+
+#[test]
+fn busy() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Connecting to the server…",
+        ButtonPreset::Close.strings(),
+        ui::Options {
+            progress: Some(Indicator::Spinner(0.0)),
+            ..Default::default()
+        },
+    );
+    assert!(ui.is_animated());
+    ui.animate(Duration::from_millis(375));
+    ui.redraw();
+    snap("busy", &ui.canvas);
+}
+
+#[test]
+fn animated_icon() {
+    let frame = |rgba: [u8; 4]| image::from_rgba(32, 32, &rgba.repeat(32 * 32));
+    let new_ui = || {
+        Ui::new(
+            Icon::Info,
+            Theme::Light,
+            "Syncing…",
+            ButtonPreset::Close.strings(),
+            ui::Options {
+                icon_frames: vec![
+                    (Duration::from_millis(100), frame([255, 0, 0, 255])),
+                    (Duration::from_millis(200), frame([0, 0, 255, 255])),
+                ],
+                ..Default::default()
+            },
+        )
+    };
+    let mut ui = new_ui();
+    assert!(ui.is_animated());
+    let first = ui.canvas.get_data().to_vec();
+    snap("animated_icon", &ui.canvas);
+
+    ui.animate(Duration::from_millis(60));
+    ui.redraw();
+    assert!(ui.canvas.get_data() == first);
+    ui.animate(Duration::from_millis(60));
+    ui.redraw();
+    let second = ui.canvas.get_data().to_vec();
+    assert!(second != first);
+
+    // The animation loops, even if the time between redraws skips over frames.
+    ui.animate(Duration::from_millis(400));
+    ui.redraw();
+    assert!(ui.canvas.get_data() == second);
+    ui.animate(Duration::from_millis(100));
+    ui.redraw();
+    assert!(ui.canvas.get_data() == first);
+
+    // A single frame isn't animated.
+    let still = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "",
+        &["OK"],
+        ui::Options {
+            icon_frames: vec![(Duration::ZERO, frame([0, 0, 0, 255]))],
+            ..Default::default()
+        },
+    );
+    assert!(!still.is_animated());
+
+    // The builder passes the frames on unchanged.
+    let options = crate::alerta()
+        .animated_icon(vec![(Duration::ZERO, frame([0, 255, 0, 128]))])
+        .ui_options()
+        .unwrap();
+    assert!(options.icon_frames[0].1.get_data() == frame([0, 255, 0, 128]).get_data());
+}
+
+#[test]
+fn net_wm_icon() {
+    // The pixels are stored premultiplied, but the property wants straight alpha.
+    let icon = image::from_rgba(2, 1, &[255, 0, 0, 128, 0, 0, 0, 0]);
+    assert_eq!(x11::net_wm_icon(&[icon]), [2, 1, 0x80ff0000, 0]);
+}
+
+#[test]
+fn title_area() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Hello",
+        ButtonPreset::Ok.strings(),
+        Default::default(),
+    );
+    ui.process_event(WindowEvent::CursorMove(CursorPos { x: 200, y: 2 }));
+    assert!(ui.cursor_in_title_area());
+    let bottom = ui.canvas.height() as i16 - 2;
+    ui.process_event(WindowEvent::CursorMove(CursorPos { x: 200, y: bottom }));
+    assert!(!ui.cursor_in_title_area());
+    ui.process_event(WindowEvent::CursorLeave);
+    assert!(!ui.cursor_in_title_area());
+}
+
+#[test]
+fn double_click() {
+    let press = |time, x| xproto::ButtonPressEvent {
+        detail: 1,
+        time,
+        event_x: x,
+        ..Default::default()
+    };
+    let mut clicks = x11::Clicks::new(400);
+    assert!(!clicks.press(&press(1000, 10)));
+    assert!(clicks.press(&press(1300, 12)));
+    // A third press doesn't complete another double-click.
+    assert!(!clicks.press(&press(1400, 12)));
+    // Too slow, or too far apart.
+    assert!(!clicks.press(&press(2000, 12)));
+    assert!(!clicks.press(&press(2100, 40)));
+
+    assert_eq!(
+        x11::double_click_time("*doubleClickTime:\t250\n"),
+        Some(250)
+    );
+    assert_eq!(x11::double_click_time("Xt.doubleClickTime: 300"), Some(300));
+    assert_eq!(x11::double_click_time("Xft.dpi:\t96\n"), None);
+
+    // Double-clicking the message selects a word.
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Hello, world! Select me.",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            icon_position: IconPosition::None,
+            ..Default::default()
+        },
+    );
+    ui.process_event(WindowEvent::CursorEnter(CursorPos { x: 100, y: 26 }));
+    for event in [
+        WindowEvent::ButtonPress(MouseButton::Left),
+        WindowEvent::ButtonRelease(MouseButton::Left),
+        WindowEvent::ButtonPress(MouseButton::Left),
+        WindowEvent::DoubleClick(MouseButton::Left),
+        WindowEvent::ButtonRelease(MouseButton::Left),
+    ] {
+        assert_eq!(ui.process_event(event), None);
+    }
+    let ctrl = Modifiers {
+        bits: Modifiers::CONTROL,
+    };
+    ui.process_event(WindowEvent::KeyPress(Keysym(u32::from('c')), ctrl));
+    assert_eq!(ui.take_copied_text().as_deref(), Some("Select"));
+}
+
+#[test]
+fn context_menu() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Hello, world! Select me.",
+        ButtonPreset::Ok.strings(),
+        ui::Options {
+            icon_position: IconPosition::None,
+            ..Default::default()
+        },
+    );
+    let click = |ui: &mut Ui, x, y, button| {
+        ui.process_event(WindowEvent::CursorMove(CursorPos { x, y }));
+        ui.process_event(WindowEvent::ButtonPress(button));
+        ui.process_event(WindowEvent::ButtonRelease(button))
+    };
+
+    // Right-clicking the message opens the menu; its second item is "Select All".
+    click(&mut ui, 60, 26, MouseButton::Right);
+    ui.process_event(WindowEvent::CursorMove(CursorPos { x: 80, y: 70 }));
+    ui.redraw();
+    snap("context-menu", &ui.canvas);
+    assert_eq!(click(&mut ui, 80, 70, MouseButton::Left), None);
+
+    // "Copy" copies the selection.
+    click(&mut ui, 60, 26, MouseButton::Right);
+    assert_eq!(click(&mut ui, 80, 40, MouseButton::Left), None);
+    assert_eq!(
+        ui.take_copied_text().as_deref(),
+        Some("Hello, world! Select me.")
+    );
+
+    // Clicking a button while the menu is open activates it.
+    click(&mut ui, 60, 26, MouseButton::Right);
+    let y = ui.canvas.height() as i16 - 20;
+    assert_eq!(
+        click(&mut ui, 200, y, MouseButton::Left),
+        Some(Answer::Button(0))
+    );
+}
+
+#[test]
+fn middle_click_paste() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Enter your name:",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            input: Some("Name".into()),
+            ..Default::default()
+        },
+    );
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Middle));
+    assert_eq!(ui.take_paste_request(), Some(Selection::Primary));
+    assert_eq!(ui.take_paste_request(), None);
+    ui.process_event(WindowEvent::Paste("Ada\nLovelace".into()));
+
+    let ctrl = Modifiers {
+        bits: Modifiers::CONTROL,
+    };
+    ui.process_event(WindowEvent::KeyPress(Keysym(u32::from('v')), ctrl));
+    assert_eq!(ui.take_paste_request(), Some(Selection::Clipboard));
+    ui.process_event(WindowEvent::Paste(" (1815)".into()));
+    assert_eq!(
+        ui.process_event(WindowEvent::KeyPress(Keysym::RETURN, Modifiers::default())),
+        Some(Answer::Input {
+            button: 0,
+            text: "AdaLovelace (1815)".into()
+        })
+    );
+}
+
+#[test]
+fn error_kind() {
+    assert_eq!(Error::new("invalid").kind(), ErrorKind::Other);
+    let e = error::err(x11rb::errors::ConnectionError::UnknownError);
+    assert_eq!(e.kind(), ErrorKind::Protocol);
+    assert!(!e.is_disconnect());
+
+    let eof = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+    assert!(error::err(x11rb::errors::ConnectionError::IoError(eof)).is_disconnect());
+}
+
+#[test]
+fn disconnect() {
+    use std::{
+        io::{Read as _, Write as _},
+        os::unix::net::UnixStream,
+        thread,
+        time::Instant,
+    };
+    use x11rb::{
+        connection::Connection as _,
+        protocol::xproto::ConnectionExt as _,
+        rust_connection::{DefaultStream, RustConnection},
+        x11_utils::Serialize as _,
+    };
+
+    // A fake X server that accepts the connection, and then goes away.
+    let (client, mut server) = UnixStream::pair().unwrap();
+    let server = thread::spawn(move || {
+        let mut request = [0; 12];
+        server.read_exact(&mut request).unwrap();
+        let mut setup = xproto::Setup {
+            status: 1,
+            protocol_major_version: 11,
+            resource_id_mask: 0x1f_ffff,
+            maximum_request_length: u16::MAX,
+            roots: vec![xproto::Screen::default()],
+            ..Default::default()
+        };
+        setup.length = (setup.serialize().len() as u16 - 8) / 4;
+        server.write_all(&setup.serialize()).unwrap();
+    });
+    let (stream, _) = DefaultStream::from_unix_stream(client).unwrap();
+    let conn = RustConnection::connect_to_stream(stream, 0).unwrap();
+    server.join().unwrap();
+
+    let start = Instant::now();
+    let e = error::err(conn.wait_for_event().unwrap_err());
+    assert_eq!(e.kind(), ErrorKind::Disconnected);
+    assert!(e.is_disconnect());
+    // Replies to requests fail the same way.
+    let e = error::err(conn.get_input_focus().unwrap().reply().unwrap_err());
+    assert_eq!(e.kind(), ErrorKind::Disconnected);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // A dialog whose X server goes away once the window is mapped is closed right away, instead
+    // of failing, or waiting for events that never come. It runs on another thread, so that a
+    // loop that spins or hangs fails the test instead of blocking it.
+    let (display, server) = fake_x_server();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let answer = crate::alerta()
+            .display(display)
+            .theme(Theme::Light)
+            .message("Hello")
+            .grab_focus(false)
+            .show();
+        sender.send(answer).ok();
+    });
+    let answer = receiver
+        .recv_timeout(Duration::from_secs(2))
+        .expect("the dialog should close promptly");
+    server.join().unwrap();
+    assert_eq!(answer.unwrap(), Answer::Closed(CloseReason::External));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    fn round_trip<T>(value: T, json: &str)
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+        assert_eq!(serde_json::from_str::<T>(json).unwrap(), value);
+    }
+
+    round_trip(
+        Answer::Closed(CloseReason::Keyboard),
+        r#"{"Closed":"Keyboard"}"#,
+    );
+    round_trip(Answer::Button(1), r#"{"Button":1}"#);
+    round_trip(
+        Answer::Input {
+            button: 0,
+            text: "Ada".into(),
+        },
+        r#"{"Input":{"button":0,"text":"Ada"}}"#,
+    );
+    round_trip(ButtonPreset::YesNo, r#""YesNo""#);
+    round_trip(Icon::Warning, r#""Warning""#);
+    round_trip(Theme::Dark, r#""Dark""#);
+    round_trip(
+        Theme::Custom {
+            background: 1,
+            text: 2,
+            button: 3,
+            button_hover: 4,
+            accent: 5,
+        },
+        r#"{"Custom":{"background":1,"text":2,"button":3,"button_hover":4,"accent":5}}"#,
+    );
+}
+
+#[test]
+fn builder_clone() {
+    let base = crate::alerta()
+        .title("Backup")
+        .icon(Icon::Warning)
+        .icon_rgba(1, 1, vec![0; 4]);
+    let a = base.clone().message("Disk A is full");
+    let b = base.message("Disk B is full");
+    assert_eq!(a.title, b.title);
+    assert_eq!(a.resolved_icon(), b.resolved_icon());
+    assert!(a.custom_icon.is_some() && b.custom_icon.is_some());
+    assert_eq!(a.message.as_deref(), Some("Disk A is full"));
+    assert_eq!(b.message.as_deref(), Some("Disk B is full"));
+}
+
+#[test]
+fn message_from_file() {
+    let path = std::env::temp_dir().join(format!("alerta-message-{}.txt", std::process::id()));
+    fs::write(&path, b"line 1\nline \xff 2\n").unwrap();
+    let builder = crate::alerta().message_from_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        builder.message.as_deref(),
+        Some("line 1\nline \u{fffd} 2\n")
+    );
+
+    let Err(e) = crate::alerta().message_from_file(&path) else {
+        panic!("reading a missing file should fail");
+    };
+    assert!(e.to_string().contains(&*path.to_string_lossy()));
+}
+
+#[test]
+fn auto_icon() {
+    let icon = |title: Option<&str>, message: &str| {
+        let mut builder = crate::alerta().message(message).auto_icon();
+        if let Some(title) = title {
+            builder = builder.title(title);
+        }
+        builder.resolved_icon()
+    };
+    assert_eq!(icon(None, "Backup FAILED"), Icon::Error);
+    assert_eq!(icon(Some("Errors"), "Disk is full."), Icon::Error);
+    assert_eq!(icon(None, "Warning: disk is almost full"), Icon::Warning);
+    assert_eq!(icon(None, "Delete 3 files?"), Icon::Question);
+    assert_eq!(icon(None, "Backup complete."), Icon::Info);
+
+    // An explicit icon takes precedence.
+    let builder = crate::alerta()
+        .message("Error!")
+        .icon(Icon::Info)
+        .auto_icon();
+    assert_eq!(builder.resolved_icon(), Icon::Info);
+    assert_eq!(
+        crate::alerta().message("Error!").resolved_icon(),
+        Icon::Info
+    );
+}
+
+#[test]
+fn max_lines() {
+    let message = "The backup of your home directory could not be completed, because the \
+        destination drive ran out of space. Free up some space, or choose another drive in the \
+        backup settings, and then try again.";
+    let options = |max_lines| ui::Options {
+        max_lines,
+        max_width: Some(300),
+        ..Default::default()
+    };
+    let full = Ui::new(
+        Icon::Error,
+        Theme::Light,
+        message,
+        ButtonPreset::Ok.strings(),
+        options(None),
+    );
+    let mut ui = Ui::new(
+        Icon::Error,
+        Theme::Light,
+        message,
+        ButtonPreset::Ok.strings(),
+        options(Some(2)),
+    );
+    assert!(ui.canvas.height() < full.canvas.height());
+    snap("max-lines", &ui.canvas);
+
+    // The full message can still be copied.
+    let ctrl = Modifiers {
+        bits: Modifiers::CONTROL,
+    };
+    ui.process_event(WindowEvent::KeyPress(Keysym(u32::from('c')), ctrl));
+    assert_eq!(ui.take_copied_text().as_deref(), Some(message));
+
+    // Messages that fit are not changed.
+    let ui = Ui::new(
+        Icon::Error,
+        Theme::Light,
+        "Backup failed.",
+        ButtonPreset::Ok.strings(),
+        options(Some(2)),
+    );
+    let unlimited = Ui::new(
+        Icon::Error,
+        Theme::Light,
+        "Backup failed.",
+        ButtonPreset::Ok.strings(),
+        options(None),
+    );
+    assert_eq!(ui.canvas.get_data(), unlimited.canvas.get_data());
+}
+
+#[test]
+fn session_connect_error() {
+    let Err(e) = crate::Session::connect_to("not a display") else {
+        panic!("connecting to an invalid display should fail");
+    };
+    assert_eq!(e.kind(), ErrorKind::NoDisplay);
+    assert!(e.to_string().contains("'not a display'"));
+}
+
+/// Starts a fake X server on an abstract Unix socket, for running dialogs without an actual one.
+///
+/// Requests are answered with replies that are all zeros, except that atoms are numbered. The
+/// server disconnects once a window has been mapped. Returns the display name to connect to.
+fn fake_x_server() -> (String, thread::JoinHandle<()>) {
+    static SERVERS: AtomicU16 = AtomicU16::new(0);
+    // A display number that no real X server is likely to use, and that is unique among the
+    // test processes that run at the same time.
+    let display = 20000 + (std::process::id() % 1000) as u16 * 20 + SERVERS.fetch_add(1, Relaxed);
+    let name = format!("/tmp/.X11-unix/X{display}");
+    let addr = SocketAddr::from_abstract_name(name).unwrap();
+    let listener = UnixListener::bind_addr(&addr).unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 12];
+        stream.read_exact(&mut request).unwrap();
+        let padded = |i: usize| {
+            usize::from(u16::from_le_bytes([request[i], request[i + 1]])).next_multiple_of(4)
+        };
+        let mut auth = vec![0; padded(6) + padded(8)];
+        stream.read_exact(&mut auth).unwrap();
+
+        let visual = xproto::Visualtype {
+            visual_id: 0x21,
+            class: xproto::VisualClass::TRUE_COLOR,
+            bits_per_rgb_value: 8,
+            colormap_entries: 256,
+            red_mask: 0xff0000,
+            green_mask: 0xff00,
+            blue_mask: 0xff,
+        };
+        let mut setup = xproto::Setup {
+            status: 1,
+            protocol_major_version: 11,
+            resource_id_base: 0x20_0000,
+            resource_id_mask: 0x1f_ffff,
+            maximum_request_length: u16::MAX,
+            min_keycode: 8,
+            max_keycode: 255,
+            roots: vec![xproto::Screen {
+                root: 0x100,
+                width_in_pixels: 1920,
+                height_in_pixels: 1080,
+                root_depth: 24,
+                root_visual: visual.visual_id,
+                allowed_depths: vec![xproto::Depth {
+                    depth: 24,
+                    visuals: vec![visual],
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        setup.length = (setup.serialize().len() as u16 - 8) / 4;
+        stream.write_all(&setup.serialize()).unwrap();
+
+        let (mut sequence, mut next_atom) = (0u16, 100u32);
+        loop {
+            let mut header = [0; 4];
+            if stream.read_exact(&mut header).is_err() {
+                break;
+            }
+            let len = usize::from(u16::from_le_bytes([header[2], header[3]])) * 4;
+            stream.read_exact(&mut vec![0; len - 4]).unwrap();
+            sequence = sequence.wrapping_add(1);
+            let has_reply = matches!(
+                header[0],
+                xproto::GET_WINDOW_ATTRIBUTES_REQUEST
+                    | xproto::GET_GEOMETRY_REQUEST
+                    | xproto::INTERN_ATOM_REQUEST
+                    | xproto::GET_PROPERTY_REQUEST
+                    | xproto::QUERY_POINTER_REQUEST
+                    | xproto::TRANSLATE_COORDINATES_REQUEST
+                    | xproto::GET_INPUT_FOCUS_REQUEST
+                    | xproto::QUERY_EXTENSION_REQUEST
+                    | xproto::GET_KEYBOARD_MAPPING_REQUEST
+            );
+            if header[0] == xproto::MAP_WINDOW_REQUEST {
+                break;
+            } else if !has_reply {
+                continue;
+            }
+            let mut reply = [0; 32];
+            reply[0] = 1;
+            reply[2..4].copy_from_slice(&sequence.to_le_bytes());
+            if header[0] == xproto::INTERN_ATOM_REQUEST {
+                reply[8..12].copy_from_slice(&next_atom.to_le_bytes());
+                next_atom += 1;
+            }
+            stream.write_all(&reply).unwrap();
+        }
+    });
+    (format!(":{display}"), server)
+}
+
+#[test]
+fn startup_round_trips() {
+    let (display, server) = fake_x_server();
+    let session = crate::Session::connect_to(&display).unwrap();
+    let answer = session
+        .alerta()
+        .theme(Theme::Light)
+        .message("Hello")
+        .grab_focus(false)
+        .show()
+        .unwrap();
+    server.join().unwrap();
+    assert_eq!(answer, Answer::Closed(CloseReason::External));
+    // One for connecting (the extensions and X resources), and one for creating the window (the
+    // atoms, the keymap and the focus).
+    assert_eq!(session.conn.round_trips(), 2);
+}
+
+#[test]
+fn mnemonic_parser() {
+    use ui::mnemonic::{self, Label};
+
+    let label = |text: &str, mnemonic| Label {
+        text: text.into(),
+        mnemonic,
+    };
+    assert_eq!(mnemonic::parse("&Yes"), label("Yes", Some(0)));
+    assert_eq!(mnemonic::parse("Do&n't Save"), label("Don't Save", Some(2)));
+    assert_eq!(mnemonic::parse("Save && Quit"), label("Save & Quit", None));
+    assert_eq!(mnemonic::parse("Save & Quit"), label("Save & Quit", None));
+    assert_eq!(mnemonic::parse("&A&B"), label("AB", Some(0)));
+    assert_eq!(mnemonic::parse("&&&Ok"), label("&Ok", Some(1)));
+    assert_eq!(mnemonic::parse("Trailing&"), label("Trailing&", None));
+
+    let mut labels = ["Cancel", "&Copy", "Close", "Ok"].map(mnemonic::parse);
+    mnemonic::assign(&mut labels);
+    let keys = labels.iter().map(Label::key).collect::<Vec<_>>();
+    assert_eq!(keys, [Some('a'), Some('c'), Some('l'), Some('o')]);
+}
+
+#[test]
+fn mnemonics() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Save changes before closing?",
+        &["&Save", "Do&n't Save", "Cancel"],
+        ui::Options::default(),
+    );
+    let alt = Modifiers {
+        bits: Modifiers::ALT,
+    };
+    ui.process_event(WindowEvent::KeyPress(Keysym::ALT_L, Modifiers::default()));
+    ui.redraw();
+    snap("mnemonics", &ui.canvas);
+
+    // Custom labels only get the mnemonics they mark.
+    assert_eq!(
+        ui.process_event(WindowEvent::KeyPress(Keysym(u32::from('c')), alt)),
+        None
+    );
+    assert_eq!(
+        ui.process_event(WindowEvent::KeyPress(Keysym(u32::from('N')), alt)),
+        Some(Answer::Button(1))
+    );
+
+    // Presets get mnemonics automatically, and typing the letter without Alt does nothing.
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Continue?",
+        ButtonPreset::YesNoCancel.strings(),
+        ui::Options {
+            auto_mnemonics: true,
+            ..Default::default()
+        },
+    );
+    let c = Keysym(u32::from('c'));
+    assert_eq!(
+        ui.process_event(WindowEvent::KeyPress(c, Modifiers::default())),
+        None
+    );
+    assert_eq!(
+        ui.process_event(WindowEvent::KeyPress(c, alt)),
+        Some(Answer::Button(2))
+    );
+}
+
+#[test]
+fn headless() {
+    use crate::{HeadlessEvent as Event, HeadlessKey};
+
+    let dialog = || {
+        crate::alerta()
+            .message("Delete 3 files?")
+            .button_preset(ButtonPreset::YesNoCancel)
+    };
+    assert_eq!(
+        dialog().show_headless(&[Event::ClickButton(2)]).unwrap(),
+        Answer::Button(2)
+    );
+    assert_eq!(
+        dialog().show_headless(&[Event::Mnemonic('N')]).unwrap(),
+        Answer::Button(1)
+    );
+    assert_eq!(
+        dialog()
+            .show_headless(&[Event::Key(HeadlessKey::Escape)])
+            .unwrap(),
+        Answer::Closed(CloseReason::Keyboard)
+    );
+
+    // Clicking next to the buttons does nothing.
+    let script = [Event::MoveCursor { x: 2, y: 2 }, Event::Click, Event::Close];
+    assert_eq!(
+        dialog().show_headless(&script).unwrap(),
+        Answer::Closed(CloseReason::WmCloseButton)
+    );
+
+    let answer = crate::alerta()
+        .input("Name")
+        .button_preset(ButtonPreset::OkCancel)
+        .show_headless(&[
+            Event::Type("Adx".into()),
+            Event::Key(HeadlessKey::Backspace),
+            Event::Key(HeadlessKey::Char('a')),
+            Event::Key(HeadlessKey::Enter),
+        ])
+        .unwrap();
+    assert_eq!(
+        answer,
+        Answer::Input {
+            button: 0,
+            text: "Ada".into()
+        }
+    );
+
+    assert!(dialog().show_headless(&[Event::ClickButton(3)]).is_err());
+    assert!(dialog().show_headless(&[Event::Click]).is_err());
+}
+
+#[test]
+fn button_bounds() {
+    let mut ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Continue?",
+        ButtonPreset::YesNo.strings(),
+        ui::Options::default(),
+    );
+    let bounds = ui.button_bounds();
+    assert_eq!(bounds.len(), 2);
+    assert!(bounds[0].max.x <= bounds[1].min.x);
+
+    let center = bounds[1].center();
+    ui.process_event(WindowEvent::CursorMove(CursorPos {
+        x: center.x as i16,
+        y: center.y as i16,
+    }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert_eq!(
+        ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left)),
+        Some(Answer::Button(1))
+    );
+}
+
+#[test]
+fn on_button() {
+    use crate::HeadlessEvent as Event;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let applied = Arc::new(AtomicUsize::new(0));
+    let dialog = crate::alerta()
+        .message("Use the new settings?")
+        .buttons(["OK", "&Apply", "Cancel"])
+        .on_button(1, {
+            let applied = applied.clone();
+            move || {
+                applied.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+    let script = [
+        Event::ClickButton(1),
+        Event::Mnemonic('a'),
+        Event::ClickButton(0),
+    ];
+    assert_eq!(
+        dialog.clone().show_headless(&script).unwrap(),
+        Answer::Button(0)
+    );
+    assert_eq!(applied.load(Ordering::Relaxed), 2);
+
+    // The dialog stays open after the callback button is pressed.
+    assert!(dialog.show_headless(&[Event::ClickButton(1)]).is_err());
+    assert_eq!(applied.load(Ordering::Relaxed), 3);
+
+    let err = crate::alerta()
+        .on_button(2, || {})
+        .show_headless(&[])
+        .unwrap_err();
+    assert!(err.to_string().contains("out of range"), "{err}");
+}
+
+#[test]
+fn confirm_button() {
+    use crate::HeadlessEvent as Event;
+
+    let dialog = crate::alerta()
+        .message("Delete 3 files permanently?")
+        .buttons(["Delete", "Cancel"])
+        .confirm_button(0, "Click again to confirm");
+    let show = |script: &[Event]| dialog.clone().show_headless(script);
+    assert_eq!(
+        show(&[Event::ClickButton(0), Event::ClickButton(0)]).unwrap(),
+        Answer::Button(0)
+    );
+    // The first click doesn't close the dialog.
+    assert!(show(&[Event::ClickButton(0)]).is_err());
+    // Moving on to another button cancels the confirmation.
+    assert_eq!(
+        show(&[Event::ClickButton(0), Event::ClickButton(1)]).unwrap(),
+        Answer::Button(1)
+    );
+
+    let mut ui = Ui::new(
+        Icon::Warning,
+        Theme::Light,
+        "Delete 3 files permanently?",
+        &["Delete", "Cancel"],
+        ui::Options {
+            confirm_buttons: vec![(0, "Click again to confirm".into())],
+            ..Default::default()
+        },
+    );
+    let center = ui.button_bounds()[0].center();
+    ui.process_event(WindowEvent::CursorEnter(CursorPos {
+        x: center.x as i16,
+        y: center.y as i16,
+    }));
+    ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+    assert_eq!(
+        ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left)),
+        None
+    );
+    assert_eq!(ui.armed_button(), Some(0));
+    ui.redraw();
+    snap("confirm-button", &ui.canvas);
+
+    ui.disarm();
+    assert_eq!(ui.armed_button(), None);
+
+    let err = crate::alerta()
+        .confirm_button(1, "Sure?")
+        .show_headless(&[])
+        .unwrap_err();
+    assert!(err.to_string().contains("out of range"), "{err}");
+}
+
+#[test]
+fn button_icons() {
+    let ui = |button_icons| {
+        Ui::new(
+            Icon::Warning,
+            Theme::Light,
+            "Delete 3 files permanently?",
+            &["Delete", "Cancel"],
+            ui::Options {
+                button_icons,
+                max_width: Some(100),
+                ..Default::default()
+            },
+        )
+    };
+    let icon = || vec![(0, Icon::Error.get().unwrap())];
+    // The button grows to fit the icon.
+    assert!(ui(icon()).min_size().width > ui(Vec::new()).min_size().width);
+
+    let mut ui = Ui::new(
+        Icon::Warning,
+        Theme::Light,
+        "Delete 3 files permanently?",
+        &["Delete", "Cancel"],
+        ui::Options {
+            button_icons: icon(),
+            ..Default::default()
+        },
+    );
+    ui.redraw();
+    snap("button-icon", &ui.canvas);
+
+    let err = crate::alerta()
+        .button_icon(1, Icon::Info)
+        .show_headless(&[])
+        .unwrap_err();
+    assert!(err.to_string().contains("out of range"), "{err}");
+}
+
+#[test]
+fn on_event() {
+    use crate::HeadlessEvent as Event;
+    use std::sync::{Arc, Mutex};
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let answer = crate::alerta()
+        .buttons(["Yes", "No"])
+        .on_event({
+            let seen = seen.clone();
+            move |event| seen.lock().unwrap().push(format!("{event:?}"))
+        })
+        .show_headless(&[Event::Type("x".into()), Event::ClickButton(1)])
+        .unwrap();
+    assert_eq!(answer, Answer::Button(1));
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 4, "{seen:?}");
+    assert!(seen[0].starts_with("KeyPress(Keysym(120)"), "{seen:?}");
+    assert!(seen[1].starts_with("CursorMove("), "{seen:?}");
+    assert_eq!(seen[2..], ["ButtonPress(Left)", "ButtonRelease(Left)"]);
+}
+
+#[test]
+fn on_answer() {
+    use crate::{HeadlessEvent as Event, HeadlessKey};
+    use std::sync::mpsc;
+
+    let (sender, receiver) = mpsc::channel();
+    let dialog = crate::alerta()
+        .buttons(["Yes", "No"])
+        .on_answer({
+            let sender = sender.clone();
+            move |answer| sender.send(Ok(answer)).unwrap()
+        })
+        .on_error(move |e| sender.send(Err(e.to_string())).unwrap());
+    let answer = dialog
+        .clone()
+        .show_headless(&[Event::ClickButton(1)])
+        .unwrap();
+    assert_eq!(answer, Answer::Button(1));
+    assert_eq!(receiver.try_recv(), Ok(Ok(Answer::Button(1))));
+
+    // Clones share the functions, which are only called once.
+    dialog
+        .show_headless(&[Event::Key(HeadlessKey::Escape)])
+        .ok();
+    assert!(receiver.try_recv().is_err());
+
+    // Errors are reported instead of answers.
+    let (sender, receiver) = mpsc::channel();
+    let res = crate::alerta()
+        .on_answer(|_| panic!("the dialog wasn't answered"))
+        .on_error(move |e| sender.send(e.to_string()).unwrap())
+        .show_headless(&[]);
+    assert_eq!(receiver.try_recv(), Ok(res.unwrap_err().to_string()));
+
+    // Retried dialogs report the last answer.
+    let (sender, receiver) = mpsc::channel();
+    let mut attempts = 0;
+    let answer = crate::alerta()
+        .button_preset(ButtonPreset::RetryCancel)
+        .on_answer(move |answer| sender.send(answer).unwrap())
+        .retry_with(
+            |dialog| dialog.show_headless(&[Event::ClickButton(0)]),
+            || {
+                attempts += 1;
+                attempts == 2
+            },
+        )
+        .unwrap();
+    assert_eq!(answer, Answer::Button(0));
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [Answer::Button(0)]);
+}
+
+#[test]
+fn show_retry() {
+    use crate::HeadlessEvent as Event;
+
+    let dialog = crate::alerta()
+        .message("Failed to connect to the server.")
+        .button_preset(ButtonPreset::RetryCancel);
+
+    // The operation succeeds on the third attempt.
+    let mut attempts = 0;
+    let answer = dialog
+        .clone()
+        .retry_with(
+            |builder| builder.show_headless(&[Event::ClickButton(0)]),
+            || {
+                attempts += 1;
+                attempts == 3
+            },
+        )
+        .unwrap();
+    assert_eq!(answer, Answer::Button(0));
+    assert_eq!(attempts, 3);
+
+    let mut shown = 0;
+    let answer = dialog
+        .retry_with(
+            |builder| {
+                shown += 1;
+                builder.show_headless(&[Event::ClickButton(shown - 1)])
+            },
+            || false,
+        )
+        .unwrap();
+    assert_eq!(answer, Answer::Button(1));
+    assert_eq!(shown, 2);
+}
+
+#[test]
+fn button_order() {
+    use crate::ButtonOrder;
+
+    assert_eq!(
+        ButtonOrder::for_desktop("GNOME"),
+        ButtonOrder::AffirmativeRight
+    );
+    assert_eq!(
+        ButtonOrder::for_desktop("ubuntu:GNOME"),
+        ButtonOrder::AffirmativeRight
+    );
+    assert_eq!(
+        ButtonOrder::for_desktop("KDE"),
+        ButtonOrder::AffirmativeLeft
+    );
+    assert_eq!(ButtonOrder::for_desktop(""), ButtonOrder::AffirmativeLeft);
+
+    let bounds = |order, mirror| {
+        let builder = crate::alerta()
+            .button_preset(ButtonPreset::OkCancel)
+            .button_order(order)
+            .mirror_buttons(mirror);
+        let buttons = builder.button_labels();
+        let options = builder.ui_options().unwrap();
+        Ui::new(Icon::Info, Theme::Light, "", &buttons, options).button_bounds()
+    };
+    let left = bounds(ButtonOrder::AffirmativeLeft, false);
+    assert!(left[0].max.x <= left[1].min.x);
+    let right = bounds(ButtonOrder::AffirmativeRight, false);
+    assert!(right[1].max.x <= right[0].min.x);
+    let mirrored = bounds(ButtonOrder::AffirmativeRight, true);
+    assert!(mirrored[0].max.x <= mirrored[1].min.x);
+}
+
+#[test]
+fn link_finder() {
+    use ui::link::{find, style};
+    use ui::markup::Style;
+
+    let text =
+        "See https://example.com/a(b). Or (http://x.org/docs), but not xhttp://no or https://";
+    let links = find(text);
+    let urls = links.iter().map(|l| &text[l.clone()]).collect::<Vec<_>>();
+    assert_eq!(urls, ["https://example.com/a(b)", "http://x.org/docs"]);
+
+    let bold = Style {
+        bold: true,
+        ..Style::default()
+    };
+    let link = Style {
+        link: true,
+        ..Style::default()
+    };
+    let bold_link = Style {
+        bold: true,
+        link: true,
+        ..Style::default()
+    };
+    assert_eq!(
+        style(&[(0..6, bold)], &[4..10, 12..14]),
+        [
+            (0..4, bold),
+            (4..6, bold_link),
+            (6..10, link),
+            (12..14, link)
+        ]
+    );
+}
+
+#[test]
+fn links() {
+    let message = "The manual is at https://example.com/manual.";
+    let dialog = |detect_links| {
+        Ui::new(
+            Icon::Info,
+            Theme::Light,
+            message,
+            ButtonPreset::Close.strings(),
+            ui::Options {
+                detect_links,
+                ..Default::default()
+            },
+        )
+    };
+    let mut ui = dialog(true);
+    snap("links", &ui.canvas);
+
+    // Click every pixel of the message area until one of them opens the link.
+    let click = |ui: &mut Ui| {
+        let (width, height) = (ui.canvas.width() as i16, ui.canvas.height() as i16);
+        let mut opened = Vec::new();
+        for y in (0..height / 2).step_by(2) {
+            for x in (0..width).step_by(2) {
+                ui.process_event(WindowEvent::CursorMove(CursorPos { x, y }));
+                ui.process_event(WindowEvent::ButtonPress(MouseButton::Left));
+                assert_eq!(
+                    ui.process_event(WindowEvent::ButtonRelease(MouseButton::Left)),
+                    None
+                );
+                opened.extend(ui.take_opened_link());
+            }
+        }
+        opened
+    };
+    let opened = click(&mut ui);
+    assert!(!opened.is_empty());
+    assert!(opened.iter().all(|url| url == "https://example.com/manual"));
+
+    assert!(click(&mut dialog(false)).is_empty());
+
+    // Links keep their color when the theme changes.
+    let mut ui = dialog(true);
+    ui.set_theme(Theme::Dark);
+    let dark = Ui::new(
+        Icon::Info,
+        Theme::Dark,
+        message,
+        ButtonPreset::Close.strings(),
+        ui::Options {
+            detect_links: true,
+            ..Default::default()
+        },
+    );
+    assert!(ui.canvas.get_data() == dark.canvas.get_data());
+}
+
+#[test]
+fn cursor_shapes() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "The manual is at https://example.com/manual.",
+        ButtonPreset::Close.strings(),
+        ui::Options {
+            detect_links: true,
+            ..Default::default()
+        },
+    );
+    let width = ui.canvas.width() as i16;
+    let button = ui.button_bounds()[0].center();
+    let message = ui.message_view().center();
+    let mut shape_at = |x, y| {
+        ui.process_event(WindowEvent::CursorMove(CursorPos { x, y }));
+        ui.cursor_shape()
+    };
+    assert_eq!(
+        shape_at(button.x as i16, button.y as i16),
+        CursorShape::Pointer
+    );
+    assert_eq!(shape_at(5, 5), CursorShape::Default);
+
+    // Only the link in the message is clickable, not the text before it.
+    let shapes = (0..width)
+        .step_by(2)
+        .map(|x| shape_at(x, message.y as i16))
+        .collect::<Vec<_>>();
+    let first = shapes
+        .iter()
+        .position(|&shape| shape == CursorShape::Pointer);
+    assert!(first.is_some_and(|first| first > 0));
+
+    ui.process_event(WindowEvent::CursorLeave);
+    assert_eq!(ui.cursor_shape(), CursorShape::Default);
+}
+
+#[test]
+fn choices() {
+    use crate::{HeadlessEvent as Event, HeadlessKey};
+
+    let items = ["Small", "Medium", "Large"].map(String::from).to_vec();
+    let ui = Ui::new(
+        Icon::Question,
+        Theme::Light,
+        "Which size should the image be exported at?",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            choices: items.clone(),
+            ..Default::default()
+        },
+    );
+    snap("choices", &ui.canvas);
+
+    let dialog = || {
+        crate::alerta()
+            .message("Which size?")
+            .button_preset(ButtonPreset::OkCancel)
+            .choices(items.clone())
+    };
+    assert_eq!(
+        dialog().show_headless(&[Event::ClickButton(0)]).unwrap(),
+        Answer::Selected {
+            index: 0,
+            button: 0
+        }
+    );
+    let script = [
+        Event::Key(HeadlessKey::Down),
+        Event::Key(HeadlessKey::Down),
+        Event::Key(HeadlessKey::Down),
+        Event::Key(HeadlessKey::Up),
+        Event::ClickButton(1),
+    ];
+    assert_eq!(
+        dialog().show_headless(&script).unwrap(),
+        Answer::Selected {
+            index: 1,
+            button: 1
+        }
+    );
+    assert_eq!(
+        dialog()
+            .show_headless(&[Event::Key(HeadlessKey::Escape)])
+            .unwrap(),
+        Answer::Closed(CloseReason::Keyboard)
+    );
+
+    let err = dialog().input("Name").show_headless(&[]).unwrap_err();
+    assert!(err.to_string().contains("choices"), "{err}");
+}
+
+#[test]
+fn multi_choices() {
+    use crate::{HeadlessEvent as Event, HeadlessKey};
+
+    let items = (1..=12)
+        .map(|i| format!("report-{i:02}.pdf"))
+        .collect::<Vec<_>>();
+    let mut ui = Ui::new(
+        Icon::Warning,
+        Theme::Light,
+        "Select the files to delete.",
+        ButtonPreset::OkCancel.strings(),
+        ui::Options {
+            choices: items.clone(),
+            multi_select: true,
+            ..Default::default()
+        },
+    );
+    let key = |sym| WindowEvent::KeyPress(sym, Modifiers::default());
+    for _ in 0..9 {
+        ui.process_event(key(Keysym::DOWN));
+    }
+    ui.process_event(key(Keysym::SPACE));
+    // Scrolling the wheel over the list scrolls it, not the message.
+    ui.process_event(WindowEvent::CursorMove(CursorPos { x: 100, y: 200 }));
+    ui.process_event(WindowEvent::Scroll {
+        delta_x: 0,
+        delta_y: -1,
+    });
+    ui.redraw();
+    snap("multi_choices", &ui.canvas);
+
+    let dialog = || {
+        crate::alerta()
+            .message("Select the files to delete.")
+            .button_preset(ButtonPreset::OkCancel)
+            .multi_choices(items.clone())
+    };
+    assert_eq!(
+        dialog().show_headless(&[Event::ClickButton(0)]).unwrap(),
+        Answer::MultiSelected {
+            indices: Vec::new(),
+            button: 0
+        }
+    );
+    let script = [
+        Event::Key(HeadlessKey::Down),
+        Event::Key(HeadlessKey::Char(' ')),
+        Event::Key(HeadlessKey::Up),
+        Event::Key(HeadlessKey::Char(' ')),
+        Event::Key(HeadlessKey::Down),
+        Event::Key(HeadlessKey::Down),
+        Event::Key(HeadlessKey::Char(' ')),
+        Event::Key(HeadlessKey::Down),
+        Event::Key(HeadlessKey::Char(' ')),
+        Event::Key(HeadlessKey::Char(' ')),
+        Event::ClickButton(1),
+    ];
+    assert_eq!(
+        dialog().show_headless(&script).unwrap(),
+        Answer::MultiSelected {
+            indices: vec![0, 1, 2],
+            button: 1
+        }
+    );
+    assert_eq!(
+        dialog()
+            .choices(items.clone())
+            .show_headless(&[Event::ClickButton(0)])
+            .unwrap(),
+        Answer::Selected {
+            index: 0,
+            button: 0
+        }
+    );
+}
+
+#[test]
+fn theme_cache() {
+    let custom = Theme::Custom {
+        background: 0x123456,
+        text: 0xffffff,
+        button: 0x234567,
+        button_hover: 0x345678,
+        accent: 0x456789,
+    };
+    *crate::DETECTED_THEME.lock().unwrap() = Some((custom, std::time::Instant::now()));
+    assert_eq!(Theme::detect_cached(true), custom);
+    assert_eq!(Theme::cached(true), Some(custom));
+    assert_eq!(Theme::cached(false), None);
+}
+
+#[test]
+fn system_theme() {
+    assert_eq!("system".parse::<Theme>().ok(), Some(Theme::System));
+    assert_eq!(crate::alerta().explicit_theme(), None);
+    assert_eq!(crate::alerta().theme(Theme::System).explicit_theme(), None);
+    assert_eq!(
+        crate::alerta().theme(Theme::Dark).explicit_theme(),
+        Some(Theme::Dark)
+    );
+
+    // Until the theme is detected, the dialog looks like the light theme.
+    let ui = |theme| {
+        Ui::new(
+            Icon::Info,
+            theme,
+            "Which theme is this?",
+            ButtonPreset::Ok.strings(),
+            Default::default(),
+        )
+        .canvas
+    };
+    assert!(ui(Theme::System).get_data() == ui(Theme::Light).get_data());
+}
+
+#[test]
+fn theme_probe_output() {
+    use std::{os::unix::process::ExitStatusExt, process};
+
+    use crate::ThemeProbe;
+
+    let output = |code, stdout: &str| process::Output {
+        status: process::ExitStatus::from_raw(code),
+        stdout: stdout.into(),
+        stderr: b"not available".into(),
+    };
+    let portal = "   variant       variant          uint32 1\n";
+    assert_eq!(
+        ThemeProbe::Portal.parse(&output(0, portal)).unwrap(),
+        Theme::Dark
+    );
+    assert_eq!(
+        ThemeProbe::Gsettings
+            .parse(&output(0, "'prefer-light'\n"))
+            .unwrap(),
+        Theme::Light
+    );
+    assert!(
+        ThemeProbe::Gsettings
+            .parse(&output(0, "'default'\n"))
+            .is_err()
+    );
+    let err = ThemeProbe::Portal.parse(&output(1 << 8, "")).unwrap_err();
+    assert!(err.to_string().contains("not available"), "{err}");
+}
+
+#[test]
+fn damage() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Only what changed is presented again.",
+        ButtonPreset::OkCancel.strings(),
+        Default::default(),
+    );
+    let size = (ui.canvas.width(), ui.canvas.height());
+    let full = raqote::IntRect::new(raqote::IntPoint::zero(), size.into());
+    assert_eq!(ui.take_damage(), Some(full));
+    ui.redraw();
+    assert_eq!(ui.take_damage(), None);
+
+    // Hovering a button only changes the button.
+    let button = ui.button_bounds()[1];
+    let center = button.center();
+    ui.process_event(WindowEvent::CursorMove(CursorPos {
+        x: center.x as i16,
+        y: center.y as i16,
+    }));
+    ui.redraw();
+    let damage = ui.take_damage().unwrap();
+    assert!(button.contains_box(&damage), "{damage:?}");
+
+    ui.process_event(WindowEvent::RedrawRequested);
+    assert_eq!(ui.take_damage(), Some(full));
+}
+
+#[test]
+fn needs_redraw() {
+    let mut ui = Ui::new(
+        Icon::Info,
+        Theme::Light,
+        "Moving the mouse over nothing doesn't redraw.",
+        ButtonPreset::OkCancel.strings(),
+        Default::default(),
+    );
+    ui.redraw();
+    assert!(!ui.needs_redraw());
+
+    let cursor_move = |x, y| WindowEvent::CursorMove(CursorPos { x, y });
+    ui.process_event(cursor_move(5, 5));
+    ui.process_event(cursor_move(6, 7));
+    assert!(!ui.needs_redraw());
+
+    let center = ui.button_bounds()[0].center();
+    ui.process_event(cursor_move(center.x as i16, center.y as i16));
+    assert!(ui.needs_redraw());
+    ui.redraw();
+    ui.process_event(cursor_move(center.x as i16 + 1, center.y as i16));
+    assert!(!ui.needs_redraw());
+
+    ui.process_event(WindowEvent::KeyPress(Keysym::TAB, Modifiers::default()));
+    assert!(ui.needs_redraw());
+}
+
+#[test]
+fn dedup_key() {
+    let first = dedup::Registration::new("dedup-test").unwrap();
+    assert!(!first.take_raise());
+
+    // Dialogs with the same key aren't displayed, and ask the open one to raise its window.
+    assert!(dedup::Registration::new("dedup-test").is_none());
+    let answer = crate::alerta()
+        .message("Again")
+        .dedup_key("dedup-test")
+        .show()
+        .unwrap();
+    assert_eq!(answer, Answer::Closed(CloseReason::Duplicate));
+    assert!(first.take_raise());
+    assert!(!first.take_raise());
+
+    let other = dedup::Registration::new("dedup-test-other");
+    assert!(other.is_some());
+
+    drop(first);
+    assert!(dedup::Registration::new("dedup-test").is_some());
+}
+
+#[cfg(feature = "atspi")]
+#[test]
+fn atspi() {
+    use std::os::unix::net::UnixStream;
+
+    use crate::atspi::{self, Field, Message, Writer};
+
+    const ROOT: &str = "/org/a11y/atspi/accessible/root";
+
+    let (client, mut bus) = UnixStream::pair().unwrap();
+    let app = thread::spawn(move || {
+        let dialog = atspi::Dialog {
+            title: "Title".into(),
+            message: "Message".into(),
+            buttons: vec!["Yes".into(), "No".into()],
+            focused: Some(1),
+        };
+        atspi::serve(&client, &dialog).unwrap();
+    });
+    let mut reader = bus.try_clone().unwrap();
+    let mut send = move |kind, fields: &[Field<'_>], signature, body: &Writer| {
+        let msg = atspi::encode(kind, 0, 100, fields, signature, &body.buf);
+        bus.write_all(&msg).unwrap();
+    };
+    let mut read = || Message::read(&mut reader).unwrap();
+
+    // The bus assigns a name, and the registry accepts the application.
+    let hello = read();
+    assert_eq!(hello.member.as_deref(), Some("Hello"));
+    let mut body = Writer::default();
+    body.str(":1.5");
+    send(2, &[Field::ReplySerial(hello.serial)], "s", &body);
+    let embed = read();
+    assert_eq!(embed.member.as_deref(), Some("Embed"));
+    assert_eq!(embed.signature, "(so)");
+    assert_eq!(embed.body().reference().unwrap(), (":1.5", ROOT));
+    let mut body = Writer::default();
+    body.reference("org.a11y.atspi.Registry", ROOT);
+    send(2, &[Field::ReplySerial(embed.serial)], "(so)", &body);
+
+    // The dialog is announced, and then the focused button.
+    let activate = read();
+    assert_eq!(activate.kind, 4);
+    assert_eq!(activate.member.as_deref(), Some("Activate"));
+    assert_eq!(
+        activate.path.as_deref(),
+        Some("/org/a11y/atspi/accessible/dialog")
+    );
+    let mut body = activate.body();
+    assert_eq!(body.str().unwrap(), "");
+    assert_eq!((body.i32().unwrap(), body.i32().unwrap()), (0, 0));
+    assert_eq!(body.sig().unwrap(), "s");
+    assert_eq!(body.str().unwrap(), "Title");
+    let focused = read();
+    assert_eq!(focused.member.as_deref(), Some("StateChanged"));
+    assert_eq!(
+        focused.path.as_deref(),
+        Some("/org/a11y/atspi/accessible/button1")
+    );
+    let mut body = focused.body();
+    assert_eq!(body.str().unwrap(), "focused");
+    assert_eq!(body.i32().unwrap(), 1);
+
+    // Screen readers can walk the tree.
+    let call = |path, interface, member| {
+        [
+            Field::Path(path),
+            Field::Interface(interface),
+            Field::Member(member),
+        ]
+    };
+    let fields = call(
+        "/org/a11y/atspi/accessible/dialog",
+        "org.a11y.atspi.Accessible",
+        "GetChildren",
+    );
+    send(1, &fields, "", &Writer::default());
+    let reply = read();
+    assert_eq!(reply.kind, 2);
+    assert_eq!(reply.reply_serial, Some(100));
+    assert_eq!(reply.signature, "a(so)");
+    let mut body = reply.body();
+    body.u32().unwrap();
+    let children = (0..3)
+        .map(|_| body.reference().unwrap().1)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        children,
+        [
+            "/org/a11y/atspi/accessible/message",
+            "/org/a11y/atspi/accessible/button0",
+            "/org/a11y/atspi/accessible/button1",
+        ]
+    );
+
+    let fields = call(
+        "/org/a11y/atspi/accessible/button1",
+        "org.freedesktop.DBus.Properties",
+        "Get",
+    );
+    let mut body = Writer::default();
+    body.str("org.a11y.atspi.Accessible");
+    body.str("Name");
+    send(1, &fields, "ss", &body);
+    let reply = read();
+    assert_eq!(reply.signature, "v");
+    let mut body = reply.body();
+    assert_eq!(body.sig().unwrap(), "s");
+    assert_eq!(body.str().unwrap(), "No");
+
+    let fields = call(
+        "/org/a11y/atspi/accessible/button2",
+        "org.a11y.atspi.Accessible",
+        "GetRole",
+    );
+    send(1, &fields, "", &Writer::default());
+    let reply = read();
+    assert_eq!(reply.kind, 3);
+    assert_eq!(
+        reply.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.UnknownObject")
+    );
+
+    // Disconnecting ends the registration.
+    drop(send);
+    drop(reader);
+    app.join().unwrap();
+}
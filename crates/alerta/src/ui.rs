@@ -1,19 +1,45 @@
 //! Handles drawing and layouting of the UI, and processes input events for the UI.
 
-mod font;
+pub(crate) mod ansi;
+pub(crate) mod bidi;
+pub(crate) mod font;
+pub(crate) mod grapheme;
+pub(crate) mod link;
+pub(crate) mod markup;
+pub(crate) mod mnemonic;
 
-use std::{cmp, f32::consts::PI};
+use std::{
+    borrow::Cow,
+    cmp,
+    f32::consts::{PI, TAU},
+    hint, iter, mem,
+    ops::Range,
+    sync::Arc,
+    time::Duration,
+};
 
-use euclid::{Size2D, Transform2D, point2, size2};
+use euclid::{
+    Size2D, Transform2D,
+    default::{Box2D, Size2D as IntSize, Vector2D as IntVector},
+    point2, size2, vec2,
+};
 use raqote::{
-    BlendMode, Color, DrawTarget, IntPoint, IntRect, Path, PathBuilder, SolidSource, Source,
-    StrokeStyle,
+    BlendMode, Color, DrawTarget, Image, IntPoint, IntRect, LineCap, LineJoin, Path, PathBuilder,
+    SolidSource, Source, StrokeStyle,
 };
 
-use crate::{Answer, Icon, MouseButton, Theme, WindowEvent, ui::font::Font};
+use crate::{
+    AnsiMode, Answer, CloseReason, Icon, IconPosition, Keysym, MouseButton, TextDirection, Theme,
+    WindowEvent, WrapMode,
+    ui::{
+        font::{Font, TextLayout},
+        markup::Markup,
+        mnemonic::Label,
+    },
+};
 
-#[derive(Debug, Clone, Copy)]
-struct Rgb(u8, u8, u8);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rgb(u8, u8, u8);
 
 impl From<Rgb> for Color {
     fn from(Rgb(r, g, b): Rgb) -> Self {
@@ -35,6 +61,36 @@ const fn rgb(r: u8, g: u8, b: u8) -> Rgb {
     Rgb(r, g, b)
 }
 
+impl Rgb {
+    /// Converts a `0xAARRGGBB` color, ignoring the alpha channel.
+    fn from_argb(argb: u32) -> Self {
+        let [_, r, g, b] = argb.to_be_bytes();
+        rgb(r, g, b)
+    }
+
+    /// Linearly interpolates between `self` (at `t = 0`) and `other` (at `t = 1`).
+    fn mix(self, other: Rgb, t: f32) -> Rgb {
+        let lerp = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8;
+        rgb(
+            lerp(self.0, other.0),
+            lerp(self.1, other.1),
+            lerp(self.2, other.2),
+        )
+    }
+
+    /// Returns black or white, whichever is more readable on top of `self`.
+    fn contrasting(self) -> Rgb {
+        let luma =
+            0.299 * f32::from(self.0) + 0.587 * f32::from(self.1) + 0.114 * f32::from(self.2);
+        if luma > 150.0 {
+            rgb(0, 0, 0)
+        } else {
+            rgb(255, 255, 255)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
 struct Colors {
     window_bg: Rgb,
     text: Rgb,
@@ -43,6 +99,79 @@ struct Colors {
     button_hover: Rgb,
     button_pressed: Rgb,
     button_outline: Rgb,
+    focus_ring: Rgb,
+
+    input_bg: Rgb,
+    placeholder: Rgb,
+    /// Background of selected text.
+    selection: Rgb,
+    link: Rgb,
+
+    /// Fill color of the default button.
+    ///
+    /// If `None`, the default button is marked with the focus ring instead.
+    accent: Option<Rgb>,
+}
+
+impl Colors {
+    fn new(theme: Theme, accent_color: Option<u32>) -> Self {
+        let mut colors = match theme {
+            // The system theme is replaced with the detected theme, once that is known.
+            Theme::Light | Theme::System => THEME_LIGHT,
+            Theme::Dark => THEME_DARK,
+            Theme::Custom {
+                background,
+                text,
+                button,
+                button_hover,
+                accent,
+            } => Colors::custom(background, text, button, button_hover, accent),
+        };
+        if let Some(accent) = accent_color.map(Rgb::from_argb) {
+            colors.accent = Some(accent);
+            colors.focus_ring = accent;
+        }
+        colors
+    }
+
+    fn custom(background: u32, text: u32, button: u32, button_hover: u32, accent: u32) -> Self {
+        let [background, text, button, button_hover, accent] =
+            [background, text, button, button_hover, accent].map(Rgb::from_argb);
+        Self {
+            window_bg: background,
+            text,
+            button,
+            button_hover,
+            button_pressed: button_hover.mix(text, 0.2),
+            button_outline: button.mix(text, 0.8),
+            focus_ring: accent,
+            input_bg: background.mix(button, 0.5),
+            placeholder: text.mix(background, 0.5),
+            selection: accent.mix(background, 0.6),
+            link: accent,
+            accent: Some(accent),
+        }
+    }
+
+    /// Returns the fill colors of the button at `index` in its normal, hovered, and pressed state.
+    fn button_fill(&self, index: usize, default_button: Option<usize>) -> [Rgb; 3] {
+        match self.accent {
+            Some(accent) if default_button == Some(index) => [
+                accent,
+                accent.mix(self.text, 0.15),
+                accent.mix(self.text, 0.3),
+            ],
+            _ => [self.button, self.button_hover, self.button_pressed],
+        }
+    }
+
+    /// Returns the label color of the button at `index`.
+    fn button_text(&self, index: usize, default_button: Option<usize>) -> Rgb {
+        match self.accent {
+            Some(accent) if default_button == Some(index) => accent.contrasting(),
+            _ => self.text,
+        }
+    }
 }
 
 static THEME_LIGHT: Colors = Colors {
@@ -52,6 +181,12 @@ static THEME_LIGHT: Colors = Colors {
     button_hover: rgb(180, 180, 180),
     button_pressed: rgb(150, 150, 150),
     button_outline: rgb(40, 40, 40),
+    focus_ring: rgb(53, 132, 228),
+    input_bg: rgb(255, 255, 255),
+    placeholder: rgb(120, 120, 120),
+    selection: rgb(170, 204, 241),
+    link: rgb(26, 95, 180),
+    accent: None,
 };
 static THEME_DARK: Colors = Colors {
     window_bg: rgb(30, 30, 30),
@@ -60,32 +195,816 @@ static THEME_DARK: Colors = Colors {
     button_hover: rgb(110, 110, 110),
     button_pressed: rgb(160, 160, 160),
     button_outline: rgb(200, 200, 200),
+    focus_ring: rgb(120, 174, 237),
+    input_bg: rgb(45, 45, 45),
+    placeholder: rgb(150, 150, 150),
+    selection: rgb(38, 79, 120),
+    link: rgb(120, 174, 237),
+    accent: None,
 };
 
+/// Distance between the window edges and its contents, unless [`Options::padding`] is set.
 const WINDOW_PADDING: i32 = 10;
 const BTN_PADDING: i32 = 12;
+/// Minimum width of a button, unless [`Options::min_button_width`] is set.
+const MIN_BTN_WIDTH: i32 = 64;
+/// Distance between the icon of a button and its label.
+const BTN_ICON_SPACING: i32 = 6;
 const SPACING: i32 = 10;
+/// Distance between adjacent buttons, unless [`Options::padding`] is set.
+const BTN_GAP: i32 = 10;
 const BTN_RADIUS: f32 = 5.0;
+/// Distance between the button outline and the focus ring drawn inside of it.
+const FOCUS_RING_INSET: i32 = 3;
+/// Distance between the outline of an input field and its text.
+const INPUT_PADDING: i32 = 6;
+const CHECKBOX_SIZE: i32 = 16;
+const PROGRESS_HEIGHT: i32 = 8;
+const SPINNER_SIZE: i32 = 20;
+/// Time the busy spinner takes for a full turn.
+const SPINNER_PERIOD: Duration = Duration::from_secs(1);
+/// Size of the detail text relative to the message.
+const DETAIL_FONT_SCALE: f32 = 0.85;
+/// Size of the footer text relative to the message.
+const FOOTER_FONT_SCALE: f32 = 0.75;
+/// Shortest time a frame of an animated icon is displayed, so that frames without a duration
+/// don't stall the animation.
+const MIN_ICON_FRAME_DURATION: Duration = Duration::from_millis(10);
+const SCROLLBAR_WIDTH: i32 = 6;
+/// Distance between a scrollable message and its scrollbar.
+const SCROLLBAR_SPACING: i32 = 6;
+/// Time the caret of the input field is visible, and then hidden, while it blinks.
+pub(crate) const CARET_BLINK_INTERVAL: Duration = Duration::from_millis(500);
+/// Number of lines scrolled per mouse wheel step.
+const SCROLL_LINES: f32 = 3.0;
+/// Number of choices that are visible at once. Longer lists scroll.
+const MAX_VISIBLE_CHOICES: usize = 8;
+/// Number of lines of the expanded details that are visible at once. Longer texts scroll.
+const MAX_VISIBLE_DETAILS_LINES: usize = 8;
+/// Height of the custom content area, unless [`Options::custom_draw_size`] is set.
+const CUSTOM_DRAW_HEIGHT: i32 = 120;
+const MIN_WIDTH: i32 = 400;
+const MIN_HEIGHT: i32 = 100;
+/// Default maximum window width.
+const MAX_WIDTH: i32 = 600;
+
+/// Sizes of UI elements in physical pixels.
+///
+/// The constants above are the sizes at a scale factor of 1. All sizes are rounded to whole pixels,
+/// so that edges stay sharp at fractional scale factors.
+#[derive(Debug, Clone, Copy)]
+struct Metrics {
+    scale: f32,
+    window_padding: i32,
+    btn_padding: i32,
+    min_btn_width: i32,
+    btn_icon_spacing: i32,
+    spacing: i32,
+    btn_gap: i32,
+    btn_radius: f32,
+    focus_ring_inset: i32,
+    input_padding: i32,
+    checkbox_size: i32,
+    progress_height: i32,
+    spinner_size: i32,
+    scrollbar_width: i32,
+    scrollbar_spacing: i32,
+    min_width: i32,
+    min_height: i32,
+    max_width: i32,
+    /// Width of outlines and of the text caret.
+    line_width: f32,
+    /// Width of focus rings and check marks.
+    thick_line_width: f32,
+}
+
+impl Metrics {
+    fn new(scale: f32) -> Self {
+        let px = |size: i32| (size as f32 * scale).round() as i32;
+        let px_f32 = |size: f32| f32::max(1.0, (size * scale).round());
+        Self {
+            scale,
+            window_padding: px(WINDOW_PADDING),
+            btn_padding: px(BTN_PADDING),
+            min_btn_width: px(MIN_BTN_WIDTH),
+            btn_icon_spacing: px(BTN_ICON_SPACING),
+            spacing: px(SPACING),
+            btn_gap: px(BTN_GAP),
+            btn_radius: px_f32(BTN_RADIUS),
+            focus_ring_inset: px(FOCUS_RING_INSET),
+            input_padding: px(INPUT_PADDING),
+            checkbox_size: px(CHECKBOX_SIZE),
+            progress_height: px(PROGRESS_HEIGHT),
+            spinner_size: px(SPINNER_SIZE),
+            scrollbar_width: px(SCROLLBAR_WIDTH),
+            scrollbar_spacing: px(SCROLLBAR_SPACING),
+            min_width: px(MIN_WIDTH),
+            min_height: px(MIN_HEIGHT),
+            max_width: px(MAX_WIDTH),
+            line_width: px_f32(1.0),
+            thick_line_width: px_f32(2.0),
+        }
+    }
+
+    /// Scales `size` without rounding.
+    fn scale(&self, size: f32) -> f32 {
+        size * self.scale
+    }
+}
+
+/// Decides whether a character may be typed into the input field.
+pub(crate) type InputFilter = Arc<dyn Fn(char) -> bool + Send + Sync>;
+/// Decides whether the text of the input field may be submitted.
+pub(crate) type InputValidator = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+/// Draws the custom content into its area of the canvas.
+pub(crate) type CustomDraw = Arc<dyn Fn(&mut DrawTarget, crate::Rect) + Send + Sync>;
+
+/// Optional layout and rendering settings.
+///
+/// The [`Default`] value matches the look of a dialog with no customizations applied.
+#[derive(Default)]
+pub(crate) struct Options {
+    /// Image to display instead of the built-in image of the [`Icon`].
+    pub(crate) custom_icon: Option<DrawTarget>,
+    /// Images to cycle through instead of the icon, with how long each of them is displayed.
+    /// Empty for a still icon.
+    pub(crate) icon_frames: Vec<(Duration, DrawTarget)>,
+    pub(crate) icon_position: IconPosition,
+    /// Images to display left of the labels of the buttons at the given indices, scaled to the
+    /// height of the text.
+    pub(crate) button_icons: Vec<(usize, DrawTarget)>,
+    /// Index of the button that is activated by pressing Enter.
+    ///
+    /// Must be in range for the button list passed to [`Ui::new`].
+    pub(crate) default_button: Option<usize>,
+    /// Title to display as a bold header at the top of the dialog.
+    pub(crate) title: Option<String>,
+    /// Secondary text displayed below the message, in a smaller and dimmer font.
+    pub(crate) detail: Option<String>,
+    /// Text displayed in a collapsible monospace section below the message, collapsed at first.
+    pub(crate) expandable_details: Option<String>,
+    /// Single line of text displayed below the buttons, in a small and dim font.
+    pub(crate) footer: Option<String>,
+    /// Displays a text input field with the given placeholder text.
+    pub(crate) input: Option<String>,
+    /// Masks the characters typed into the input field.
+    pub(crate) password: bool,
+    /// Text the input field contains at first, with the caret at its end.
+    pub(crate) input_default: Option<String>,
+    /// Selects all of [`Options::input_default`], so that typing replaces it.
+    pub(crate) select_input_default: bool,
+    /// Number of lines the input field displays, if it is a multi-line text area.
+    pub(crate) input_rows: Option<u32>,
+    /// Maximum number of characters in the input field.
+    pub(crate) input_max_len: Option<usize>,
+    /// Characters that are rejected by this aren't inserted into the input field.
+    pub(crate) input_filter: Option<InputFilter>,
+    /// Disables the [default button][Options::default_button] (or the first button, if there is
+    /// none) while this rejects the text of the input field.
+    pub(crate) input_validate: Option<InputValidator>,
+    /// Reserves an area between the message and the buttons, drawn into by this.
+    pub(crate) custom_draw: Option<CustomDraw>,
+    /// Minimum width and height of the area reserved for [`Options::custom_draw`], in logical
+    /// pixels.
+    pub(crate) custom_draw_size: Option<(u32, u32)>,
+    /// Displays a checkbox with the given label above the buttons.
+    pub(crate) checkbox: Option<String>,
+    /// Displays a list of options below the message, of which the user selects one.
+    pub(crate) choices: Vec<String>,
+    /// Displays the choices as checkboxes, of which the user selects any number.
+    pub(crate) multi_select: bool,
+    /// Displays a progress indicator below the message, in the given initial state.
+    pub(crate) progress: Option<Indicator>,
+    /// Adds a "Copy" button after the regular buttons, which copies the message.
+    pub(crate) copy_button: bool,
+    /// Makes Ctrl+Shift+C copy the message and close the dialog with [`Answer::CopiedAndClosed`].
+    pub(crate) copy_shortcut: bool,
+    /// Indices of the buttons that don't close the dialog, but are reported by
+    /// [`Ui::take_callback_button`] instead.
+    pub(crate) callback_buttons: Vec<usize>,
+    /// Buttons that only take effect when activated twice, with the label displayed in between
+    /// (see [`Ui::armed_button`]).
+    pub(crate) confirm_buttons: Vec<(usize, String)>,
+    /// Gives the buttons whose labels don't mark a mnemonic the first free letter of their label.
+    pub(crate) auto_mnemonics: bool,
+    /// Fill color of the default button, as `0xAARRGGBB` (the alpha channel is ignored).
+    pub(crate) accent_color: Option<u32>,
+    /// Maximum window height.
+    ///
+    /// If the message doesn't fit, it becomes scrollable.
+    pub(crate) max_height: Option<i32>,
+    /// Window size to lay out the dialog for, instead of fitting the window to the contents.
+    pub(crate) size: Option<(u32, u32)>,
+    /// Maximum number of message lines to display, with the rest replaced by an ellipsis.
+    pub(crate) max_lines: Option<usize>,
+    /// Maximum window width, instead of the default.
+    ///
+    /// Long words are broken between characters to keep the message within the limit.
+    pub(crate) max_width: Option<i32>,
+    /// Factor to scale all sizes by, for HiDPI displays. `None` means 1.
+    pub(crate) scale: Option<f32>,
+    /// Family of the system font to use instead of the built-in font.
+    pub(crate) font_family: Option<String>,
+    /// Font size in pixels, before scaling.
+    pub(crate) font_size: Option<f32>,
+    /// Displays the message in the system's monospace font, without soft wrapping.
+    pub(crate) monospace: bool,
+    /// Distance between tab stops in widths of a space, instead of [`font::DEFAULT_TAB_WIDTH`].
+    pub(crate) tab_width: Option<u32>,
+    /// Interprets `**bold**` and `*italic*` markup in the message.
+    pub(crate) markup: bool,
+    pub(crate) ansi: AnsiMode,
+    /// Makes the URLs in the message clickable (see [`Ui::take_opened_link`]).
+    pub(crate) detect_links: bool,
+    pub(crate) text_direction: TextDirection,
+    pub(crate) wrap: WrapMode,
+    /// Lays out the buttons from right to left.
+    pub(crate) mirror_buttons: bool,
+    /// Minimum width of a button in pixels, before scaling.
+    pub(crate) min_button_width: Option<u32>,
+    /// Distance between the window edges and its contents, and between adjacent buttons, in
+    /// pixels before scaling.
+    pub(crate) padding: Option<(u32, u32)>,
+    /// Makes all buttons as wide as the widest one, instead of stretching them across the row.
+    pub(crate) equal_button_widths: bool,
+    /// Radius of the window corners in pixels, before scaling. 0 means square corners.
+    pub(crate) corner_radius: u32,
+}
+
+/// The options that determine the layout, kept for laying out the dialog again when it is resized.
+struct LayoutOptions {
+    icon_position: IconPosition,
+    wrap: WrapMode,
+    text_direction: TextDirection,
+    mirror_buttons: bool,
+    equal_button_widths: bool,
+    /// Whether messages passed to [`Ui::set_message`] are parsed as [`Markup`].
+    markup: bool,
+    ansi: AnsiMode,
+    detect_links: bool,
+    max_width: i32,
+    max_height: Option<i32>,
+    /// Window size used when the dialog isn't laid out for a specific size.
+    size: Option<IntSize<i32>>,
+    max_lines: Option<usize>,
+    /// Distance between tab stops, in widths of a space.
+    tab_width: u32,
+    /// Font of the message, if it differs from the regular font.
+    message_font: Option<Font>,
+    /// Styles of the message text (see [`Markup`]).
+    message_runs: Vec<(Range<usize>, font::Style)>,
+    /// The title displayed as a header.
+    title: Option<String>,
+    /// The detail text, and the font it is displayed in.
+    detail: Option<(String, Font)>,
+    /// The footer text, and the font it is displayed in.
+    footer: Option<(String, Font)>,
+}
 
 pub(crate) struct Ui {
-    colors: &'static Colors,
+    metrics: Metrics,
+    theme: Theme,
+    colors: Colors,
+    accent_color: Option<u32>,
+    /// Radius of the window corners in physical pixels.
+    corner_radius: f32,
+    layout: LayoutOptions,
+    font: Font,
     pub(crate) canvas: DrawTarget,
-    icon: DrawTarget,
+    /// The pixels of the canvas when [`Ui::take_damage`] was last called, and its width then.
+    presented: (i32, Vec<u32>),
+    /// Whether the canvas is out of date (see [`Ui::needs_redraw`]).
+    needs_redraw: bool,
+    min_size: IntSize<i32>,
+    /// The icon, or `None` if it shouldn't be drawn or is animated.
+    icon: Option<DrawTarget>,
+    animated_icon: Option<AnimatedIcon>,
     icon_pos: IntPoint,
+    message_text: String,
+    /// Byte ranges of the URLs in the message, if they are clickable.
+    links: Vec<Range<usize>>,
     message: DrawTarget,
+    message_layout: TextLayout,
     message_pos: IntPoint,
+    /// Size of the visible part of the message.
+    message_view_size: IntSize<i32>,
+    /// Scroll offset into the message, in pixels.
+    message_scroll: IntVector<i32>,
+    /// The rendered header above the icon and the message, and its position.
+    title: Option<(DrawTarget, IntPoint)>,
+    /// The rendered detail text below the message, and its position.
+    detail: Option<(DrawTarget, IntPoint)>,
+    /// The rendered footer below the buttons, and its position.
+    footer: Option<(DrawTarget, IntPoint)>,
+    /// The dialog buttons, followed by the "Copy" button if enabled.
     buttons: Vec<Button>,
+    copy_button: bool,
+    copy_shortcut: bool,
+    callback_buttons: Vec<usize>,
+    /// The callback button that was pressed (see [`Options::callback_buttons`]).
+    pressed_callback: Option<usize>,
+    confirm_buttons: Vec<ConfirmButton>,
+    /// Index of the confirm button that was activated once.
+    armed: Option<usize>,
+    default_button: Option<usize>,
+    /// Index of the button that has keyboard focus.
+    focused: Option<usize>,
+    /// Whether the focus was moved with Tab.
+    ///
+    /// Until then, Enter and Space only activate the focused button if it is the default button
+    /// (or there is an input field), and the focus ring is only drawn on the default button.
+    focus_visible: bool,
+    input: Option<Input>,
+    checkbox: Option<Checkbox>,
+    choices: Option<Choices>,
+    progress: Option<Progress>,
+    details: Option<Details>,
+    custom: Option<Custom>,
+    /// New window size, after expanding or collapsing the details.
+    resize_request: Option<IntSize<i32>>,
+    /// The context menu of the message, while it is open.
+    menu: Option<Menu>,
+    /// Selected part of the message, from the position where the selection was started to the
+    /// current end of the selection (see [`TextLayout`]).
+    selection: Option<(usize, usize)>,
+    /// Whether the selection is currently being extended by dragging the mouse.
+    selecting: bool,
+    /// Text that was copied, and should be placed in the clipboard.
+    copied: Option<String>,
+    /// The selection that should be pasted into the input field.
+    paste_requested: Option<Selection>,
+    /// Whether the bell should be rung, because a character was rejected by the input field.
+    bell_requested: bool,
+    /// URL that was clicked, and should be opened.
+    opened_link: Option<String>,
+    /// Whether the button mnemonics are underlined, which they are while Alt is held.
+    mnemonics_visible: bool,
     cursor_pos: Option<IntPoint>,
     mouse_pressed: bool,
+    /// The button that the left mouse button was pressed on, which is drawn pressed in while the
+    /// cursor is over it.
+    pressed_button: Option<usize>,
     mouse_dragging: bool,
 }
 
+/// A button that has to be activated twice (see [`Options::confirm_buttons`]).
+struct ConfirmButton {
+    index: usize,
+    label: String,
+    /// The label displayed after the first activation.
+    confirm_label: String,
+}
+
 struct Button {
     /// Text size + padding.
     min_size: Size2D<i32, ()>,
     size: Size2D<i32, ()>,
     pos: IntPoint,
     text: DrawTarget,
+    /// Image displayed left of the text.
+    icon: Option<DrawTarget>,
+    /// Lowercase character that activates the button when typed while holding Alt.
+    mnemonic: Option<char>,
+    /// Where to underline the mnemonic, relative to `text`.
+    underline: Option<Box2D<f32>>,
+}
+
+/// A text input field, with a single line or several.
+struct Input {
+    rect: IntRect,
+    placeholder: DrawTarget,
+    text: String,
+    password: bool,
+    /// Number of visible lines, which is 1 unless the field is a text area.
+    rows: usize,
+    /// Index of the topmost visible line.
+    scroll_row: usize,
+    /// Byte offset of the caret in `text`.
+    caret: usize,
+    /// Whether the caret is drawn, which alternates while it blinks.
+    caret_visible: bool,
+    /// Whether the caret was moved since [`Ui::take_caret_reset`] was last called.
+    caret_reset: bool,
+    /// Whether all of the text is selected, and is replaced by typing.
+    selected: bool,
+    max_len: Option<usize>,
+    filter: Option<InputFilter>,
+    validate: Option<InputValidator>,
+}
+
+/// A checkbox with a label to its right.
+struct Checkbox {
+    /// Clickable area, covering both the box and the label.
+    rect: IntRect,
+    label: DrawTarget,
+    checked: bool,
+}
+
+/// A list of options, displayed as rows with a radio button or a checkbox each.
+///
+/// At most [`MAX_VISIBLE_CHOICES`] rows are visible, and longer lists scroll.
+struct Choices {
+    /// Area covered by the visible rows, not including the scrollbar.
+    rect: IntRect,
+    labels: Vec<DrawTarget>,
+    row_height: i32,
+    /// Index of the selected row. In a checklist, this is the row that Space toggles.
+    selected: usize,
+    /// Whether each row is checked, if the rows are checkboxes instead of radio buttons.
+    checked: Option<Vec<bool>>,
+    /// Index of the topmost visible row.
+    scroll: usize,
+}
+
+impl Choices {
+    fn is_scrollable(&self) -> bool {
+        self.labels.len() > MAX_VISIBLE_CHOICES
+    }
+
+    fn visible_rows(&self) -> Range<usize> {
+        self.scroll..cmp::min(self.scroll + MAX_VISIBLE_CHOICES, self.labels.len())
+    }
+
+    /// Returns the area of the row at `index`, which may be outside of [`Choices::rect`] if the
+    /// row is scrolled out of view.
+    fn row(&self, index: usize) -> IntRect {
+        IntRect::from_origin_and_size(
+            self.rect.min + vec2(0, (index as i32 - self.scroll as i32) * self.row_height),
+            size2(self.rect.width(), self.row_height),
+        )
+    }
+
+    /// Returns the index of the visible row at `point`, if any.
+    fn row_at(&self, point: IntPoint) -> Option<usize> {
+        self.visible_rows().find(|&i| self.row(i).contains(point))
+    }
+
+    /// Selects the row at `index`, and scrolls it into view.
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        if index < self.scroll {
+            self.scroll = index;
+        } else if index >= self.scroll + MAX_VISIBLE_CHOICES {
+            self.scroll = index + 1 - MAX_VISIBLE_CHOICES;
+        }
+    }
+
+    /// Selects the row at `index`, and toggles it if it is a checkbox.
+    fn toggle(&mut self, index: usize) {
+        self.select(index);
+        if let Some(checked) = &mut self.checked {
+            checked[index] = !checked[index];
+        }
+    }
+
+    /// Scrolls the list by `rows`, which scrolls down if positive.
+    fn scroll_by(&mut self, rows: isize) {
+        let max_scroll = self.labels.len().saturating_sub(MAX_VISIBLE_CHOICES);
+        self.scroll = self.scroll.saturating_add_signed(rows).min(max_scroll);
+    }
+}
+
+/// A popup menu with actions for the message, opened by right-clicking it.
+struct Menu {
+    rect: IntRect,
+    item_height: i32,
+    items: Vec<(MenuAction, DrawTarget)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MenuAction {
+    Copy,
+    SelectAll,
+}
+
+impl Menu {
+    /// Returns the item under `point`, if any.
+    fn item_at(&self, point: IntPoint) -> Option<usize> {
+        if !self.rect.contains(point) {
+            return None;
+        }
+        let i = ((point.y - self.rect.min.y) / self.item_height) as usize;
+        (i < self.items.len()).then_some(i)
+    }
+}
+
+/// A section below the message with a longer text, like a traceback, which is hidden behind a
+/// "Show details" toggle until the user expands it.
+struct Details {
+    /// Clickable area of the toggle, covering the label and the arrow.
+    toggle: IntRect,
+    /// Labels of the toggle while the details are collapsed, and while they are expanded.
+    labels: [DrawTarget; 2],
+    text: String,
+    /// The monospace font the text is displayed in.
+    font: Font,
+    expanded: bool,
+    /// Box the text is displayed in while expanded, including its padding.
+    rect: IntRect,
+    /// The rendered text, while expanded.
+    rendered: DrawTarget,
+    /// Scroll offset into the text, in pixels.
+    scroll: i32,
+}
+
+impl Details {
+    fn label(&self) -> &DrawTarget {
+        &self.labels[usize::from(self.expanded)]
+    }
+
+    /// Returns the area of [`Details::rect`] the text is visible in, not including the padding
+    /// and the scrollbar.
+    fn view(&self, m: &Metrics) -> IntRect {
+        let mut view = self.rect.inflate(-m.input_padding, -m.input_padding);
+        if self.rendered.height() > view.height() {
+            view.max.x -= m.scrollbar_spacing + m.scrollbar_width;
+        }
+        view
+    }
+
+    fn max_scroll(&self, m: &Metrics) -> i32 {
+        cmp::max(0, self.rendered.height() - self.view(m).height())
+    }
+}
+
+/// An area below the message that the application draws into.
+struct Custom {
+    rect: IntRect,
+    /// Width the area needs at least, and its height.
+    min_size: IntSize<i32>,
+    draw: CustomDraw,
+}
+
+/// A progress indicator below the message.
+struct Progress {
+    /// The row the indicator is drawn in, spanning the width of the window.
+    rect: IntRect,
+    indicator: Indicator,
+}
+
+/// An icon made of several images that are displayed one after another, in a loop.
+struct AnimatedIcon {
+    /// The images, with how long each of them is displayed. Never empty.
+    frames: Vec<(Duration, DrawTarget)>,
+    /// Index of the displayed frame.
+    current: usize,
+    /// How long the current frame has been displayed.
+    elapsed: Duration,
+}
+
+impl AnimatedIcon {
+    fn current(&self) -> &DrawTarget {
+        &self.frames[self.current].1
+    }
+
+    fn advance(&mut self, elapsed: Duration) {
+        self.elapsed += elapsed;
+        // Frames can be shorter than the time between redraws, and are skipped then.
+        while self.elapsed >= self.frames[self.current].0 {
+            self.elapsed -= self.frames[self.current].0;
+            self.current = (self.current + 1) % self.frames.len();
+        }
+    }
+}
+
+/// The mouse cursor to display, depending on what is under it (see [`Ui::cursor_shape`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CursorShape {
+    Default,
+    /// A pointing hand, over elements that can be clicked.
+    Pointer,
+}
+
+/// A selection whose text can be pasted into the input field (see [`Ui::take_paste_request`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Selection {
+    /// The text selected in some window, pasted with the middle mouse button.
+    Primary,
+    /// The clipboard, pasted with Ctrl+V.
+    Clipboard,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Indicator {
+    /// A progress bar with the completed fraction, from 0 to 1.
+    Bar(f32),
+    /// An animated spinner for operations of unknown duration, with its rotation in radians.
+    Spinner(f32),
+}
+
+impl Input {
+    /// Character displayed in place of every character of a password.
+    const MASK: char = '\u{2022}';
+
+    /// Inserts `c` at the caret, and moves the caret past it.
+    fn push(&mut self, c: char) {
+        if self.password && self.text.len() + c.len_utf8() > self.text.capacity() {
+            // Reallocate manually, so that the old buffer can be erased instead of being freed
+            // with the secret still in it.
+            let mut text = String::with_capacity(cmp::max(32, self.text.capacity() * 2));
+            text.push_str(&self.text);
+            erase(&mut self.text);
+            self.text = text;
+        }
+        self.text.insert(self.caret, c);
+        self.move_caret(self.caret + c.len_utf8());
+    }
+
+    /// Inserts `c` at the caret like [`Input::push`], unless the text is at its maximum length
+    /// or the filter rejects `c`. Returns whether `c` was inserted.
+    fn insert(&mut self, c: char) -> bool {
+        let full = self
+            .max_len
+            .is_some_and(|max_len| self.text.chars().count() >= max_len);
+        if (full && !self.selected) || self.filter.as_ref().is_some_and(|filter| !filter(c)) {
+            return false;
+        }
+        if self.selected {
+            self.remove(0..self.text.len());
+        }
+        self.push(c);
+        true
+    }
+
+    /// Returns whether the text may be submitted.
+    fn is_valid(&self) -> bool {
+        self.validate
+            .as_ref()
+            .is_none_or(|validate| validate(&self.text))
+    }
+
+    /// Removes the character before the caret.
+    fn pop(&mut self) {
+        if self.selected {
+            self.remove(0..self.text.len());
+        } else if let Some((i, _)) = self.text[..self.caret].char_indices().next_back() {
+            self.remove(i..self.caret);
+        }
+    }
+
+    /// Removes the grapheme cluster after the caret.
+    fn delete(&mut self) {
+        if self.selected {
+            self.remove(0..self.text.len());
+        } else {
+            self.remove(self.caret..self.next_boundary());
+        }
+    }
+
+    /// Removes `range` of the text, and moves the caret to its start.
+    fn remove(&mut self, range: Range<usize>) {
+        let old_len = self.text.len();
+        self.text.replace_range(range.clone(), "");
+        if self.password {
+            let new_len = self.text.len();
+            // The text after `range` was moved to the front, so its old copy is at the end.
+            // SAFETY: the bytes up to `old_len` are still initialized, and overwriting them with
+            // NUL bytes keeps the string valid UTF-8.
+            unsafe {
+                let bytes = self.text.as_mut_vec();
+                bytes.set_len(old_len);
+                bytes[new_len..].fill(0);
+                bytes.set_len(new_len);
+            }
+        }
+        self.move_caret(range.start);
+    }
+
+    /// Moves the caret to the byte offset `caret`, makes it visible, and deselects the text.
+    ///
+    /// The lines are scrolled so that the caret stays visible.
+    fn move_caret(&mut self, caret: usize) {
+        self.caret = caret;
+        let row = self.caret_row();
+        self.scroll_row = self
+            .scroll_row
+            .clamp((row + 1).saturating_sub(self.rows), row);
+        self.selected = false;
+        self.caret_visible = true;
+        self.caret_reset = true;
+    }
+
+    /// Returns the start of the grapheme cluster before the caret.
+    fn prev_boundary(&self) -> usize {
+        grapheme::cluster_starts(&self.text[..self.caret])
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// Returns the end of the grapheme cluster after the caret.
+    fn next_boundary(&self) -> usize {
+        grapheme::cluster_starts(&self.text[self.caret..])
+            .nth(1)
+            .map_or(self.text.len(), |i| self.caret + i)
+    }
+
+    /// Returns the offset in [`Input::display`] that corresponds to the byte offset `i` of the
+    /// text.
+    fn display_offset(&self, i: usize) -> usize {
+        if self.password {
+            self.text[..i].chars().count() * Self::MASK.len_utf8()
+        } else {
+            i
+        }
+    }
+
+    /// Returns the byte range of the line at `row`, without its line break. Rows past the end
+    /// refer to the last line.
+    fn line(&self, row: usize) -> Range<usize> {
+        let start = iter::once(0)
+            .chain(self.text.match_indices('\n').map(|(i, _)| i + 1))
+            .take(row + 1)
+            .last()
+            .unwrap_or(0);
+        let end = self.text[start..]
+            .find('\n')
+            .map_or(self.text.len(), |i| start + i);
+        start..end
+    }
+
+    /// Returns the index of the last line.
+    fn last_row(&self) -> usize {
+        self.text.matches('\n').count()
+    }
+
+    /// Returns the index of the line the caret is on.
+    fn caret_row(&self) -> usize {
+        self.text[..self.caret].matches('\n').count()
+    }
+
+    /// Returns the position of the caret relative to the start of the text, in pixels.
+    fn caret_offset(&self, font: &Font) -> IntPoint {
+        let display = self.display();
+        let row = self.caret_row();
+        let line_start = self.display_offset(self.line(row).start);
+        let x = font
+            .advance(&display[line_start..self.display_offset(self.caret)])
+            .round() as i32;
+        point2(x, (row as f32 * font.line_height()).round() as i32)
+    }
+
+    /// Returns how far the text is scrolled to keep the caret within `text_space`, in pixels.
+    fn scroll(&self, font: &Font, text_space: i32) -> IntVector<i32> {
+        let caret = self.caret_offset(font);
+        vec2(
+            cmp::max(0, caret.x + 1 - text_space),
+            (self.scroll_row as f32 * font.line_height()).round() as i32,
+        )
+    }
+
+    /// Returns the grapheme cluster boundary on the line at `row` that is closest to `x` pixels
+    /// from the start of the line.
+    fn caret_at(&self, font: &Font, x: i32, row: usize) -> usize {
+        let display = self.display();
+        let line = self.line(row);
+        let line_start = self.display_offset(line.start);
+        grapheme::cluster_starts(&self.text[line.clone()])
+            .map(|i| line.start + i)
+            .chain([line.end])
+            .min_by_key(|&i| {
+                let advance = font.advance(&display[line_start..self.display_offset(i)]);
+                (advance.round() as i32 - x).abs()
+            })
+            .unwrap_or(line.start)
+    }
+
+    /// Returns the entered text, erasing it from the input field if it is a password.
+    fn take(&mut self) -> String {
+        if self.password {
+            let text = self.text.clone();
+            erase(&mut self.text);
+            text
+        } else {
+            self.text.clone()
+        }
+    }
+
+    /// Returns the text to display in the input field.
+    fn display(&self) -> Cow<'_, str> {
+        if self.password {
+            Cow::Owned(iter::repeat_n(Self::MASK, self.text.chars().count()).collect())
+        } else {
+            Cow::Borrowed(&self.text)
+        }
+    }
+}
+
+impl Drop for Input {
+    fn drop(&mut self) {
+        if self.password {
+            erase(&mut self.text);
+        }
+    }
+}
+
+/// Overwrites the contents of `s` with zeroes and clears it.
+fn erase(s: &mut String) {
+    // SAFETY: NUL bytes are valid UTF-8.
+    let bytes = unsafe { s.as_mut_vec() };
+    bytes.fill(0);
+    // Make sure the writes aren't optimized out because the buffer is never read again.
+    hint::black_box(&mut *bytes);
+    bytes.clear();
 }
 
 impl Button {
@@ -98,163 +1017,2524 @@ impl Button {
 }
 
 impl Ui {
-    pub(crate) fn new(icon: Icon, theme: Theme, text: &str, buttons: &[&str]) -> Self {
-        const MIN_WIDTH: i32 = 400;
-        const MIN_HEIGHT: i32 = 100;
+    pub(crate) fn new(
+        icon: Icon,
+        theme: Theme,
+        text: &str,
+        buttons: &[&str],
+        options: Options,
+    ) -> Self {
+        let mut m = Metrics::new(options.scale.unwrap_or(1.0));
+        if let Some((content, btn_gap)) = options.padding {
+            m.window_padding = m.scale(content as f32).round() as i32;
+            m.btn_gap = m.scale(btn_gap as f32).round() as i32;
+        }
+        let colors = Colors::new(theme, options.accent_color);
 
-        let colors = match theme {
-            Theme::Light => &THEME_LIGHT,
-            Theme::Dark => &THEME_DARK,
+        let animated_icon = (!options.icon_frames.is_empty()
+            && options.icon_position != IconPosition::None)
+            .then(|| AnimatedIcon {
+                frames: options
+                    .icon_frames
+                    .into_iter()
+                    .map(|(duration, image)| {
+                        (
+                            duration.max(MIN_ICON_FRAME_DURATION),
+                            scale_image(image, m.scale),
+                        )
+                    })
+                    .collect(),
+                current: 0,
+                elapsed: Duration::ZERO,
+            });
+        let icon = match options.icon_position {
+            IconPosition::None => None,
+            _ if animated_icon.is_some() => None,
+            _ => options
+                .custom_icon
+                .or_else(|| icon.get())
+                .map(|image| scale_image(image, m.scale)),
+        };
+        // Without an image, the message takes up the space of the icon.
+        let icon_position = if icon.is_some() || animated_icon.is_some() {
+            options.icon_position
+        } else {
+            IconPosition::None
         };
+        let font_size = options.font_size.unwrap_or(font::DEFAULT_SIZE) * m.scale;
+        let font = Font::load(options.font_family.as_deref(), font_size);
+        let message_font = options.monospace.then(|| Font::load_monospace(font_size));
+        let detail = options.detail.map(|text| {
+            let font = Font::load(
+                options.font_family.as_deref(),
+                font_size * DETAIL_FONT_SCALE,
+            );
+            (text, font)
+        });
+        let footer = options.footer.map(|text| {
+            let font = Font::load(
+                options.font_family.as_deref(),
+                font_size * FOOTER_FONT_SCALE,
+            );
+            (text, font)
+        });
 
-        let icon = icon.get();
-        let font = Font::load();
+        let (markup, links) =
+            parse_message(text, options.markup, options.ansi, options.detect_links);
 
-        // Compute sizes of the individual components first.
-        let message_pos_x = icon.width() + WINDOW_PADDING + SPACING;
-        let message_space = MIN_WIDTH - message_pos_x - WINDOW_PADDING;
-        let message = font
-            .render(text)
-            .with_max_width(message_space as f32)
-            .with_color(colors.text)
-            .finish();
-
-        let mut btn_height = 0;
-        let mut buttons = buttons
+        let mut labels = buttons
+            .iter()
+            .copied()
+            .chain(options.copy_button.then_some("Copy"))
+            .map(mnemonic::parse)
+            .collect::<Vec<_>>();
+        if options.auto_mnemonics {
+            mnemonic::assign(&mut labels);
+        }
+        let confirm_buttons = options
+            .confirm_buttons
+            .into_iter()
+            .filter_map(|(index, confirm_label)| {
+                let label = buttons.get(index)?.to_string();
+                Some(ConfirmButton {
+                    index,
+                    label,
+                    confirm_label,
+                })
+            })
+            .collect::<Vec<_>>();
+        let min_btn_width = options
+            .min_button_width
+            .map_or(m.min_btn_width, |px| m.scale(px as f32).round() as i32);
+        let mut button_icons = options.button_icons;
+        let buttons = labels
             .iter()
-            .map(|txt| {
-                let text = font.render(txt).with_color(colors.text).finish();
-                let w = text.width() + 2 * BTN_PADDING;
-                let h = text.height() + 2 * BTN_PADDING;
-                btn_height = cmp::max(btn_height, h);
+            .enumerate()
+            .map(|(i, label)| {
+                let color = colors.button_text(i, options.default_button);
+                let (text, underline) = render_label(&font, &m, label, color);
+                let icon = button_icons
+                    .iter()
+                    .rposition(|(index, _)| *index == i)
+                    .map(|pos| {
+                        let image = button_icons.swap_remove(pos).1;
+                        let factor = text.height() as f32 / image.height() as f32;
+                        scale_image(image, factor)
+                    });
+                let icon_width = icon
+                    .as_ref()
+                    .map_or(0, |icon| icon.width() + m.btn_icon_spacing);
+                // Confirm buttons are wide enough for both of their labels.
+                let confirm_width = confirm_buttons
+                    .iter()
+                    .filter(|confirm| confirm.index == i)
+                    .map(|confirm| {
+                        let label = mnemonic::parse(&confirm.confirm_label);
+                        render_label(&font, &m, &label, color).0.width()
+                    })
+                    .max()
+                    .unwrap_or(0);
+                let w = cmp::max(
+                    icon_width + cmp::max(text.width(), confirm_width) + 2 * m.btn_padding,
+                    min_btn_width,
+                );
+                let h = text.height() + 2 * m.btn_padding;
                 Button {
                     min_size: size2(w, h),
                     size: Size2D::zero(),
                     pos: IntPoint::zero(),
                     text,
+                    icon,
+                    mnemonic: label.key(),
+                    underline,
                 }
             })
             .collect::<Vec<_>>();
 
-        // Now we can compute the required window size.
+        let input = options.input.map(|placeholder| {
+            let placeholder = font
+                .render(&placeholder)
+                .with_color(colors.placeholder)
+                .with_line_box()
+                .finish();
+            let mut input = Input {
+                rect: IntRect::zero(),
+                placeholder,
+                text: String::new(),
+                password: options.password,
+                rows: options.input_rows.map_or(1, |rows| rows as usize),
+                scroll_row: 0,
+                caret: 0,
+                caret_visible: true,
+                caret_reset: true,
+                selected: false,
+                max_len: options.input_max_len,
+                filter: options.input_filter,
+                validate: options.input_validate,
+            };
+            // The default text isn't subject to the length limit or the filter.
+            for c in options.input_default.iter().flat_map(|text| text.chars()) {
+                input.push(c);
+            }
+            input.selected = options.select_input_default && !input.text.is_empty();
+            input
+        });
 
-        let mut win_width = cmp::max(
-            MIN_WIDTH,
-            icon.width() + SPACING + message.width() + 2 * WINDOW_PADDING,
-        );
-        let win_height_message = message.height() + btn_height + SPACING + 2 * WINDOW_PADDING;
-        let win_height_icon = icon.height() + btn_height + SPACING * 2 + WINDOW_PADDING;
-        let win_height = cmp::max(MIN_HEIGHT, cmp::max(win_height_message, win_height_icon));
+        let checkbox = options.checkbox.map(|label| Checkbox {
+            rect: IntRect::zero(),
+            label: font.render(&label).with_color(colors.text).finish(),
+            checked: false,
+        });
 
-        let message_space_y = win_height - btn_height - WINDOW_PADDING * 2 - SPACING;
-        let message_pos_y = (message_space_y - message.height()) / 2 + WINDOW_PADDING;
+        let choices = (!options.choices.is_empty()).then(|| {
+            let labels = options
+                .choices
+                .iter()
+                .map(|label| {
+                    font.render(label)
+                        .with_color(colors.text)
+                        .with_line_box()
+                        .finish()
+                })
+                .collect::<Vec<_>>();
+            let label_height = labels.iter().map(DrawTarget::height).max().unwrap_or(0);
+            Choices {
+                rect: IntRect::zero(),
+                row_height: cmp::max(m.checkbox_size, label_height) + 2 * m.input_padding,
+                checked: options.multi_select.then(|| vec![false; labels.len()]),
+                labels,
+                selected: 0,
+                scroll: 0,
+            }
+        });
 
-        // Absolute minimum required width of the button row.
-        let width_sum = buttons.iter().map(|btn| btn.min_size.width).sum::<i32>();
-        let required_width =
-            width_sum + 2 * WINDOW_PADDING + (buttons.len().saturating_sub(1) as i32) * SPACING;
-        win_width = cmp::max(win_width, required_width);
+        let progress = options.progress.map(|indicator| Progress {
+            rect: IntRect::zero(),
+            indicator,
+        });
 
-        let mut x = WINDOW_PADDING;
-        let btn_width = (win_width - WINDOW_PADDING * 2 - SPACING * (buttons.len() as i32 - 1))
-            / buttons.len() as i32;
-        for btn in &mut buttons {
-            btn.size = size2(btn_width, btn_height);
-            btn.pos = point2(x, win_height - WINDOW_PADDING - btn_height);
+        let details = options.expandable_details.map(|text| Details {
+            toggle: IntRect::zero(),
+            labels: ["Show details", "Hide details"].map(|label| {
+                font.render(label)
+                    .with_color(colors.link)
+                    .with_line_box()
+                    .finish()
+            }),
+            text,
+            font: Font::load(Some("monospace"), font_size),
+            expanded: false,
+            rect: IntRect::zero(),
+            rendered: DrawTarget::new(0, 0),
+            scroll: 0,
+        });
 
-            x += btn.size.width + SPACING;
-        }
+        let custom = options.custom_draw.map(|draw| {
+            let (width, height) = options
+                .custom_draw_size
+                .unwrap_or((0, CUSTOM_DRAW_HEIGHT as u32));
+            Custom {
+                rect: IntRect::zero(),
+                min_size: size2(
+                    m.scale(width as f32).round() as i32,
+                    m.scale(height as f32).round() as i32,
+                ),
+                draw,
+            }
+        });
 
         let mut this = Self {
+            metrics: m,
+            theme,
             colors,
-            canvas: DrawTarget::new(win_width, win_height),
+            accent_color: options.accent_color,
+            corner_radius: m.scale(options.corner_radius as f32).round(),
+            layout: LayoutOptions {
+                icon_position,
+                // Monospace text is preformatted, so it must not be wrapped.
+                wrap: if options.monospace {
+                    WrapMode::None
+                } else {
+                    options.wrap
+                },
+                text_direction: options.text_direction,
+                mirror_buttons: options.mirror_buttons,
+                equal_button_widths: options.equal_button_widths,
+                markup: options.markup,
+                ansi: options.ansi,
+                detect_links: options.detect_links,
+                max_width: options.max_width.unwrap_or(m.max_width),
+                max_height: options.max_height,
+                size: options.size.map(|(width, height)| {
+                    size2(
+                        i32::try_from(width).unwrap_or(i32::MAX),
+                        i32::try_from(height).unwrap_or(i32::MAX),
+                    )
+                }),
+                max_lines: options.max_lines,
+                tab_width: options.tab_width.unwrap_or(font::DEFAULT_TAB_WIDTH),
+                message_font,
+                message_runs: markup.runs,
+                title: options.title,
+                detail,
+                footer,
+            },
+            font,
+            canvas: DrawTarget::new(0, 0),
+            presented: (0, Vec::new()),
+            needs_redraw: true,
+            min_size: Size2D::zero(),
             icon,
-            icon_pos: point2(WINDOW_PADDING, WINDOW_PADDING),
-            message,
-            message_pos: point2(message_pos_x, message_pos_y),
+            animated_icon,
+            icon_pos: IntPoint::zero(),
+            message_text: markup.text,
+            links,
+            message: DrawTarget::new(0, 0),
+            message_layout: TextLayout::default(),
+            message_pos: IntPoint::zero(),
+            message_view_size: IntSize::zero(),
+            message_scroll: IntVector::zero(),
+            title: None,
+            detail: None,
+            footer: None,
+            focused: options
+                .default_button
+                .or((!buttons.is_empty()).then_some(0)),
             buttons,
+            copy_button: options.copy_button,
+            copy_shortcut: options.copy_shortcut,
+            callback_buttons: options.callback_buttons,
+            pressed_callback: None,
+            confirm_buttons,
+            armed: None,
+            default_button: options.default_button,
+            focus_visible: false,
+            input,
+            checkbox,
+            choices,
+            progress,
+            details,
+            custom,
+            resize_request: None,
+            menu: None,
+            selection: None,
+            selecting: false,
+            copied: None,
+            paste_requested: None,
+            bell_requested: false,
+            opened_link: None,
+            mnemonics_visible: false,
             cursor_pos: None,
             mouse_pressed: false,
+            pressed_button: None,
             mouse_dragging: false,
         };
+        this.layout(None);
         this.redraw();
         this
     }
 
-    pub(crate) fn process_event(&mut self, event: WindowEvent) -> Option<Answer> {
-        match event {
-            WindowEvent::CloseRequested => return Some(Answer::Closed),
-            WindowEvent::CursorEnter(pos) | WindowEvent::CursorMove(pos) => {
-                self.cursor_pos = Some(point2(pos.x.into(), pos.y.into()));
-                self.mouse_dragging = self.mouse_pressed;
-            }
-            WindowEvent::CursorLeave => self.cursor_pos = None,
-            WindowEvent::ButtonPress(MouseButton::Left) => self.mouse_pressed = true,
-            WindowEvent::ButtonRelease(MouseButton::Left) => {
-                if let Some(p) = self.cursor_pos
-                    && let Some(i) = self.buttons.iter().position(|btn| btn.contains(p))
-                    && !self.mouse_dragging
-                {
-                    return Some(Answer::Button(i));
-                }
-                self.mouse_pressed = false;
-                self.mouse_dragging = false;
-            }
-            _ => {}
+    /// Lays out the dialog for a window of the given size, after it was resized.
+    ///
+    /// The message is wrapped to the new width, and becomes scrollable if it doesn't fit the new
+    /// height. The window is never laid out smaller than [`Ui::min_size`].
+    fn resize(&mut self, width: i32, height: i32) {
+        self.menu = None;
+        let size = size2(width, height).max(self.min_size);
+        if size != size2(self.canvas.width(), self.canvas.height()) {
+            self.layout(Some(size));
         }
+    }
 
-        None
+    /// Returns the smallest size the window can be resized to without cutting off the buttons.
+    pub(crate) fn min_size(&self) -> IntSize<i32> {
+        self.min_size
     }
 
-    pub(crate) fn redraw(&mut self) {
-        self.canvas.clear(self.colors.window_bg.into());
+    /// Computes the positions of all elements, and the window size.
+    ///
+    /// Without a `size`, the window is sized to fit the contents. Otherwise, the contents are laid
+    /// out to fit into a window of that size.
+    fn layout(&mut self, size: Option<IntSize<i32>>) {
+        let m = self.metrics;
+        let options = &self.layout;
+        let size = size.or(options.size);
+        let font = &self.font;
+        let message_font = options.message_font.as_ref().unwrap_or(font);
+        let wrap = options.wrap;
 
-        self.canvas.place_surface(&self.icon, self.icon_pos);
-        self.canvas.place_surface(&self.message, self.message_pos);
+        let (icon_width, icon_height) = self
+            .icon_image()
+            .map_or((0, 0), |icon| (icon.width(), icon.height()));
+        // Whether the icon shares a row with the message (and thus takes away horizontal space).
+        let icon_beside = matches!(
+            options.icon_position,
+            IconPosition::Left | IconPosition::Right
+        );
 
-        for btn in &self.buttons {
-            let mut color = self.colors.button;
-            if let Some(pos) = self.cursor_pos
-                && btn.contains(pos)
-            {
-                color = if self.mouse_pressed {
-                    self.colors.button_pressed
-                } else {
-                    self.colors.button_hover
-                };
+        // Compute sizes of the individual components first.
+        let max_width = size.map_or(options.max_width, |size| size.width);
+        let min_width = match size {
+            Some(size) => size.width,
+            None => cmp::min(m.min_width, max_width),
+        };
+        let mut message_space = min_width - 2 * m.window_padding;
+        let mut max_message_space = max_width - 2 * m.window_padding;
+        if icon_beside {
+            message_space -= icon_width + m.spacing;
+            max_message_space -= icon_width + m.spacing;
+        }
+        // Lines are wrapped at `wrap_width`, unless that would leave a single word wider than
+        // `max_width` on a line.
+        let render_message = |wrap_width: i32, max_width: i32| {
+            message_font
+                .render(&self.message_text)
+                .with_runs(&options.message_runs)
+                .with_direction(options.text_direction)
+                .with_max_width(wrap_width as f32)
+                .with_max_word_width(max_width as f32)
+                .with_wrap(wrap)
+                .with_max_lines(options.max_lines.unwrap_or(usize::MAX))
+                .with_tab_width(options.tab_width)
+                .with_color(self.colors.text)
+                .with_link_color(self.colors.link)
+                .finish_with_layout()
+        };
+        let (mut message, mut message_layout) = render_message(message_space, max_message_space);
+        // The title is at most two lines, and may make the window as wide as it can be.
+        let title_space_x = max_width - 2 * m.window_padding;
+        let title = options.title.as_ref().map(|title| {
+            font.render(title)
+                .with_runs(&[(
+                    0..title.len(),
+                    font::Style {
+                        bold: true,
+                        ..Default::default()
+                    },
+                )])
+                .with_direction(options.text_direction)
+                .with_max_width(title_space_x as f32)
+                .with_max_word_width(title_space_x as f32)
+                .with_max_lines(2)
+                .with_color(self.colors.text)
+                .with_line_box()
+                .finish()
+        });
+        // Vertical space taken up by the title, including the spacing below it.
+        let title_space = title.as_ref().map_or(0, |title| title.height() + m.spacing);
+        let title_width = title.as_ref().map_or(0, DrawTarget::width);
+        // The detail text is always wrapped, and never scrolls.
+        let detail = options.detail.as_ref().map(|(text, font)| {
+            font.render(text)
+                .with_direction(options.text_direction)
+                .with_max_width(message_space as f32)
+                .with_max_word_width(max_message_space as f32)
+                .with_tab_width(options.tab_width)
+                .with_color(self.colors.placeholder)
+                .finish()
+        });
+        // Vertical space taken up by the detail text, including the spacing above it.
+        let detail_space = detail
+            .as_ref()
+            .map_or(0, |detail| m.spacing / 2 + detail.height());
+        let detail_width = detail.as_ref().map_or(0, DrawTarget::width);
+        // Vertical space taken up by the footer, including the spacing above it. The footer is
+        // rendered once the window width is known, since it is cut off to fit.
+        let footer_height = options
+            .footer
+            .as_ref()
+            .map_or(0, |(_, font)| font.line_height().ceil() as i32);
+        let footer_space = if options.footer.is_some() {
+            m.spacing / 2 + footer_height
+        } else {
+            0
+        };
+
+        let btn_height = self
+            .buttons
+            .iter()
+            .map(|btn| btn.min_size.height)
+            .max()
+            .unwrap_or(0);
+
+        // Vertical space taken up by the input field, including the spacing above it.
+        let rows = self.input.as_ref().map_or(1, |input| input.rows);
+        let input_height = (font.line_height() * rows as f32).ceil() as i32 + 2 * m.input_padding;
+        let input_space = if self.input.is_some() {
+            input_height + m.spacing
+        } else {
+            0
+        };
+
+        let checkbox_size = self.checkbox.as_ref().map_or(Size2D::zero(), |cb| {
+            size2(
+                m.checkbox_size + m.spacing / 2 + cb.label.width(),
+                cmp::max(m.checkbox_size, cb.label.height()),
+            )
+        });
+        let checkbox_space = if self.checkbox.is_some() {
+            checkbox_size.height + m.spacing
+        } else {
+            0
+        };
+
+        let choices_scrollbar_space = match &self.choices {
+            Some(choices) if choices.is_scrollable() => m.scrollbar_spacing + m.scrollbar_width,
+            _ => 0,
+        };
+        let choices_size: IntSize<i32> = self.choices.as_ref().map_or(Size2D::zero(), |choices| {
+            let label_width = choices.labels.iter().map(DrawTarget::width).max();
+            size2(
+                m.checkbox_size
+                    + m.spacing / 2
+                    + label_width.unwrap_or(0)
+                    + 2 * m.input_padding
+                    + choices_scrollbar_space,
+                choices.visible_rows().len() as i32 * choices.row_height,
+            )
+        });
+        let choices_space = if self.choices.is_some() {
+            choices_size.height + m.spacing
+        } else {
+            0
+        };
+
+        let progress_height = match self.progress.as_ref().map(|p| p.indicator) {
+            Some(Indicator::Bar(_)) => m.progress_height,
+            Some(Indicator::Spinner(_)) => m.spinner_size,
+            None => 0,
+        };
+        let progress_space = if self.progress.is_some() {
+            progress_height + m.spacing
+        } else {
+            0
+        };
+
+        // The arrow of the details toggle is a triangle in a square of this size.
+        let arrow_size = m.checkbox_size / 2;
+        let details_toggle_size = self.details.as_ref().map_or(Size2D::zero(), |details| {
+            let labels = &details.labels;
+            size2(
+                cmp::max(labels[0].width(), labels[1].width()) + m.spacing / 2 + arrow_size,
+                cmp::max(labels[0].height(), labels[1].height()),
+            )
+        });
+        // The expanded box has room for all lines of the text, up to a limit. Wrapped lines may
+        // need more room, and are scrolled then.
+        let details_box_height = match &self.details {
+            Some(details) if details.expanded => {
+                let lines = details
+                    .text
+                    .lines()
+                    .count()
+                    .clamp(1, MAX_VISIBLE_DETAILS_LINES);
+                (details.font.line_height() * lines as f32).ceil() as i32 + 2 * m.input_padding
+            }
+            _ => 0,
+        };
+        let details_space = match &self.details {
+            Some(details) if details.expanded => {
+                details_toggle_size.height + m.spacing / 2 + details_box_height + m.spacing
             }
+            Some(_) => details_toggle_size.height + m.spacing,
+            None => 0,
+        };
 
-            let mut pb = PathBuilder::new();
-            pb.rect(
-                btn.pos.x as f32,
-                btn.pos.y as f32,
-                btn.size.width as f32,
-                btn.size.height as f32,
-            );
-            let path = rounded_rect(btn.size, BTN_RADIUS).transform(&Transform2D::translation(
-                btn.pos.x as f32,
-                btn.pos.y as f32,
-            ));
-            self.canvas.fill(&path, &color.into(), &Default::default());
-            self.canvas.stroke(
-                &path,
-                &self.colors.button_outline.into(),
-                &StrokeStyle::default(),
-                &Default::default(),
-            );
+        let custom_size = self
+            .custom
+            .as_ref()
+            .map_or(Size2D::zero(), |custom| custom.min_size);
+        let custom_space = if self.custom.is_some() {
+            custom_size.height + m.spacing
+        } else {
+            0
+        };
 
-            let text_x = btn.pos.x + btn.size.width / 2 - btn.text.width() / 2;
-            self.canvas
-                .place_surface(&btn.text, point2(text_x, btn.pos.y + BTN_PADDING));
-        }
-    }
-}
+        // Now we can compute the required window size.
+
+        let content_size = |message_width: i32, message_height: i32| {
+            let message_width = cmp::max(message_width, detail_width);
+            let message_height = message_height + detail_space;
+            match options.icon_position {
+                IconPosition::Left | IconPosition::Right => (
+                    icon_width + m.spacing + message_width,
+                    cmp::max(icon_height, message_height),
+                ),
+                IconPosition::Top => (
+                    cmp::max(icon_width, message_width),
+                    icon_height + m.spacing + message_height,
+                ),
+                IconPosition::None => (message_width, message_height),
+            }
+        };
+        let window_height = |content_height: i32| {
+            cmp::max(
+                m.min_height,
+                content_height
+                    + title_space
+                    + details_space
+                    + custom_space
+                    + choices_space
+                    + progress_space
+                    + checkbox_space
+                    + input_space
+                    + btn_height
+                    + footer_space
+                    + m.spacing
+                    + 2 * m.window_padding,
+            )
+        };
+
+        // Unwrapped lines that are too long are cut off, and the message scrolls horizontally.
+        let scrollbar_space = m.scrollbar_spacing + m.scrollbar_width;
+        let max_view_width = match wrap {
+            WrapMode::None => max_message_space,
+            WrapMode::Word | WrapMode::Char => i32::MAX,
+        };
+        let mut message_view_size =
+            size2(cmp::min(message.width(), max_view_width), message.height());
+        let mut message_width = message_view_size.width;
+        // Height of the horizontal scrollbar below the message, including the spacing above it.
+        let hscroll_space = |view_width: i32, message: &DrawTarget| {
+            if view_width < message.width() {
+                scrollbar_space
+            } else {
+                0
+            }
+        };
+        let mut hscroll_height = hscroll_space(message_view_size.width, &message);
+        // Always show at least one line of text.
+        let min_view_height = cmp::min(message_font.line_height().ceil() as i32, message.height());
+        let max_height = size.map(|size| size.height).or(options.max_height);
+        if let Some(max_height) = max_height {
+            let overflow =
+                window_height(content_size(message_width, message.height() + hscroll_height).1)
+                    - max_height;
+            if overflow > 0 {
+                // Make room for the scrollbar next to the message.
+                if wrap != WrapMode::None {
+                    (message, message_layout) = render_message(
+                        message_space - scrollbar_space,
+                        max_message_space - scrollbar_space,
+                    );
+                }
+                message_view_size.width =
+                    cmp::min(message.width(), max_view_width - scrollbar_space);
+                message_width = message_view_size.width + scrollbar_space;
+                // The narrower message may have more lines, or need a horizontal scrollbar now.
+                hscroll_height = hscroll_space(message_view_size.width, &message);
+                let overflow =
+                    window_height(content_size(message_width, message.height() + hscroll_height).1)
+                        - max_height;
+                message_view_size.height =
+                    (message.height() - overflow).clamp(min_view_height, message.height());
+            }
+        }
+        let message_view_height = message_view_size.height + hscroll_height;
+
+        let (content_width, content_height) = content_size(message_width, message_view_height);
+        let mut win_width = cmp::max(
+            min_width,
+            cmp::max(
+                content_width,
+                checkbox_size
+                    .width
+                    .max(choices_size.width)
+                    .max(details_toggle_size.width)
+                    .max(custom_size.width)
+                    .max(title_width),
+            ) + 2 * m.window_padding,
+        );
+        let mut win_height = window_height(content_height);
+        if let Some(size) = size {
+            win_height = cmp::max(win_height, size.height);
+        }
+
+        // Absolute minimum required width of the button row.
+        let buttons = &mut self.buttons;
+        let widest = buttons
+            .iter()
+            .map(|btn| btn.min_size.width)
+            .max()
+            .unwrap_or(0);
+        let width_sum = if options.equal_button_widths {
+            widest * buttons.len() as i32
+        } else {
+            buttons.iter().map(|btn| btn.min_size.width).sum::<i32>()
+        };
+        let required_width =
+            width_sum + 2 * m.window_padding + (buttons.len().saturating_sub(1) as i32) * m.btn_gap;
+        win_width = cmp::max(win_width, required_width);
+        self.min_size = size2(
+            cmp::max(
+                required_width,
+                checkbox_size
+                    .width
+                    .max(choices_size.width)
+                    .max(details_toggle_size.width)
+                    .max(custom_size.width)
+                    + 2 * m.window_padding,
+            ),
+            window_height(content_size(0, min_view_height).1),
+        );
+
+        // Buttons of equal width are aligned to the end of the row, and otherwise fill it.
+        let spacing_sum = m.btn_gap * (buttons.len() as i32 - 1);
+        let (mut x, btn_width) = if options.equal_button_widths {
+            (
+                win_width - m.window_padding - width_sum - spacing_sum,
+                widest,
+            )
+        } else {
+            let btn_width = (win_width - m.window_padding * 2 - spacing_sum) / buttons.len() as i32;
+            (m.window_padding, btn_width)
+        };
+        for btn in buttons {
+            btn.size = size2(btn_width, btn_height);
+            btn.pos = point2(x, win_height - m.window_padding - footer_space - btn_height);
+            if options.mirror_buttons {
+                btn.pos.x = win_width - x - btn_width;
+            }
+
+            x += btn.size.width + m.btn_gap;
+        }
+
+        // The message is vertically centered in the space between the icon (when placed on top)
+        // and the button row.
+        let content_top = m.window_padding + title_space;
+        let message_top = match options.icon_position {
+            IconPosition::Top => content_top + icon_height + m.spacing,
+            _ => content_top,
+        };
+        let message_space_y = win_height
+            - btn_height
+            - input_space
+            - checkbox_space
+            - progress_space
+            - choices_space
+            - details_space
+            - custom_space
+            - footer_space
+            - m.window_padding
+            - m.spacing
+            - message_top;
+        let message_pos_y =
+            (message_space_y - message_view_height - detail_space) / 2 + message_top;
+
+        let (icon_pos, message_pos) = match options.icon_position {
+            IconPosition::Left => (
+                point2(m.window_padding, content_top),
+                point2(m.window_padding + icon_width + m.spacing, message_pos_y),
+            ),
+            IconPosition::Right => (
+                point2(win_width - m.window_padding - icon_width, content_top),
+                point2(m.window_padding, message_pos_y),
+            ),
+            IconPosition::Top => (
+                point2((win_width - icon_width) / 2, content_top),
+                point2((win_width - message_width) / 2, message_pos_y),
+            ),
+            IconPosition::None => (IntPoint::zero(), point2(m.window_padding, message_pos_y)),
+        };
+
+        if let Some(input) = &mut self.input {
+            let y = win_height
+                - m.window_padding
+                - footer_space
+                - btn_height
+                - m.spacing
+                - input_height;
+            input.rect = IntRect::from_origin_and_size(
+                point2(m.window_padding, y),
+                size2(win_width - 2 * m.window_padding, input_height),
+            );
+        }
+
+        if let Some(checkbox) = &mut self.checkbox {
+            let y = win_height
+                - m.window_padding
+                - footer_space
+                - btn_height
+                - m.spacing
+                - input_space
+                - checkbox_size.height;
+            checkbox.rect =
+                IntRect::from_origin_and_size(point2(m.window_padding, y), checkbox_size);
+        }
+
+        if let Some(progress) = &mut self.progress {
+            let y = win_height
+                - m.window_padding
+                - footer_space
+                - btn_height
+                - m.spacing
+                - input_space
+                - checkbox_space
+                - progress_height;
+            progress.rect = IntRect::from_origin_and_size(
+                point2(m.window_padding, y),
+                size2(win_width - 2 * m.window_padding, progress_height),
+            );
+        }
+
+        if let Some(choices) = &mut self.choices {
+            let y = win_height
+                - m.window_padding
+                - footer_space
+                - btn_height
+                - m.spacing
+                - input_space
+                - checkbox_space
+                - progress_space
+                - choices_size.height;
+            choices.rect = IntRect::from_origin_and_size(
+                point2(m.window_padding, y),
+                size2(
+                    win_width - 2 * m.window_padding - choices_scrollbar_space,
+                    choices_size.height,
+                ),
+            );
+        }
+
+        if let Some(details) = &mut self.details {
+            let y = win_height
+                - m.window_padding
+                - footer_space
+                - btn_height
+                - m.spacing
+                - input_space
+                - checkbox_space
+                - progress_space
+                - choices_space
+                - details_space;
+            details.toggle =
+                IntRect::from_origin_and_size(point2(m.window_padding, y), details_toggle_size);
+            details.rect = IntRect::from_origin_and_size(
+                point2(
+                    m.window_padding,
+                    y + details_toggle_size.height + m.spacing / 2,
+                ),
+                size2(win_width - 2 * m.window_padding, details_box_height),
+            );
+            details.rendered = DrawTarget::new(0, 0);
+            if details.expanded {
+                let render = |width: i32| {
+                    details
+                        .font
+                        .render(&details.text)
+                        .with_max_width(width as f32)
+                        .with_max_word_width(width as f32)
+                        .with_tab_width(options.tab_width)
+                        .with_color(self.colors.text)
+                        .finish()
+                };
+                let view = details.view(&m);
+                let mut rendered = render(view.width());
+                if rendered.height() > view.height() {
+                    // Make room for the scrollbar.
+                    rendered = render(view.width() - m.scrollbar_spacing - m.scrollbar_width);
+                }
+                details.rendered = rendered;
+                details.scroll = details.scroll.clamp(0, details.max_scroll(&m));
+            }
+        }
+
+        if let Some(custom) = &mut self.custom {
+            let y = win_height
+                - m.window_padding
+                - footer_space
+                - btn_height
+                - m.spacing
+                - input_space
+                - checkbox_space
+                - progress_space
+                - choices_space
+                - details_space
+                - custom_size.height;
+            custom.rect = IntRect::from_origin_and_size(
+                point2(m.window_padding, y),
+                size2(win_width - 2 * m.window_padding, custom_size.height),
+            );
+        }
+
+        if (win_width, win_height) != (self.canvas.width(), self.canvas.height()) {
+            self.canvas = DrawTarget::new(win_width, win_height);
+        }
+        self.icon_pos = icon_pos;
+        self.message_scroll = self.message_scroll.min(
+            size2(message.width(), message.height()).to_vector() - message_view_size.to_vector(),
+        );
+        self.message = message;
+        self.message_layout = message_layout;
+        self.message_pos = message_pos;
+        self.message_view_size = message_view_size;
+        self.title = title.map(|title| {
+            let x = match options.icon_position {
+                IconPosition::Top => (win_width - title.width()) / 2,
+                _ => m.window_padding,
+            };
+            (title, point2(x, m.window_padding))
+        });
+        self.detail = detail.map(|detail| {
+            let x = match options.icon_position {
+                IconPosition::Top => (win_width - detail.width()) / 2,
+                _ => message_pos.x,
+            };
+            let y = message_pos_y + message_view_height + m.spacing / 2;
+            (detail, point2(x, y))
+        });
+        // The footer is a single line, ending with an ellipsis if it doesn't fit.
+        let footer_space_x = win_width - 2 * m.window_padding;
+        self.footer = options.footer.as_ref().map(|(text, font)| {
+            let footer = font
+                .render(text)
+                .with_direction(options.text_direction)
+                .with_max_width(footer_space_x as f32)
+                .with_max_word_width(footer_space_x as f32)
+                .with_max_lines(1)
+                .with_color(self.colors.placeholder)
+                .with_line_box()
+                .finish();
+            let y = win_height - m.window_padding - footer_height;
+            (footer, point2(m.window_padding, y))
+        });
+    }
+
+    /// Switches to the colors of `theme`, without changing the layout.
+    pub(crate) fn set_theme(&mut self, theme: Theme) {
+        // The menu is only open briefly, so closing it is simpler than recoloring it.
+        self.menu = None;
+        self.theme = theme;
+        self.colors = Colors::new(theme, self.accent_color);
+        let colored = |style: &font::Style| style.link || style.color.is_some();
+        if self
+            .layout
+            .message_runs
+            .iter()
+            .any(|(_, style)| colored(style))
+        {
+            // Text in several colors can't be recolored, so it is rendered again.
+            self.layout(Some(size2(self.canvas.width(), self.canvas.height())));
+        } else {
+            recolor(&mut self.message, self.colors.text);
+        }
+        for (i, btn) in self.buttons.iter_mut().enumerate() {
+            recolor(
+                &mut btn.text,
+                self.colors.button_text(i, self.default_button),
+            );
+        }
+        if let Some((title, _)) = &mut self.title {
+            recolor(title, self.colors.text);
+        }
+        if let Some((detail, _)) = &mut self.detail {
+            recolor(detail, self.colors.placeholder);
+        }
+        if let Some((footer, _)) = &mut self.footer {
+            recolor(footer, self.colors.placeholder);
+        }
+        if let Some(input) = &mut self.input {
+            recolor(&mut input.placeholder, self.colors.placeholder);
+        }
+        if let Some(checkbox) = &mut self.checkbox {
+            recolor(&mut checkbox.label, self.colors.text);
+        }
+        if let Some(choices) = &mut self.choices {
+            for label in &mut choices.labels {
+                recolor(label, self.colors.text);
+            }
+        }
+        if let Some(details) = &mut self.details {
+            for label in &mut details.labels {
+                recolor(label, self.colors.link);
+            }
+            recolor(&mut details.rendered, self.colors.text);
+        }
+        self.redraw();
+    }
+
+    /// Replaces the label of the button at `index`.
+    ///
+    /// The button keeps its size, so the new label should be of similar length.
+    ///
+    /// If the new label doesn't mark a mnemonic, the button keeps its previous one.
+    pub(crate) fn set_button_label(&mut self, index: usize, label: &str) {
+        let mut label = mnemonic::parse(label);
+        let btn = &mut self.buttons[index];
+        if let Some(key) = btn.mnemonic {
+            label.set_key(key);
+        }
+        let color = self.colors.button_text(index, self.default_button);
+        (btn.text, btn.underline) = render_label(&self.font, &self.metrics, &label, color);
+        btn.mnemonic = label.key().or(btn.mnemonic);
+    }
+
+    /// Returns the button that was activated once and has to be activated again to take effect,
+    /// if any (see [`Options::confirm_buttons`]).
+    pub(crate) fn armed_button(&self) -> Option<usize> {
+        self.armed
+    }
+
+    /// Restores the label of the [armed button][Ui::armed_button], so that activating it asks for
+    /// confirmation again.
+    pub(crate) fn disarm(&mut self) {
+        if let Some(index) = self.armed.take()
+            && let Some(confirm) = self.confirm_buttons.iter().find(|c| c.index == index)
+        {
+            let label = confirm.label.clone();
+            self.set_button_label(index, &label);
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Replaces the message, keeping the window size.
+    ///
+    /// The new message is wrapped to the current width, and becomes scrollable if it doesn't fit.
+    pub(crate) fn set_message(&mut self, text: &str) {
+        let options = &self.layout;
+        let (markup, links) =
+            parse_message(text, options.markup, options.ansi, options.detect_links);
+        self.message_text = markup.text;
+        self.links = links;
+        self.layout.message_runs = markup.runs;
+        self.selection = None;
+        self.selecting = false;
+        self.message_scroll = IntVector::zero();
+        self.layout(Some(size2(self.canvas.width(), self.canvas.height())));
+    }
+
+    /// Returns the theme whose colors are displayed.
+    pub(crate) fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Returns the message, without markup.
+    #[cfg_attr(not(feature = "atspi"), allow(dead_code))]
+    pub(crate) fn message_text(&self) -> &str {
+        &self.message_text
+    }
+
+    /// Returns the button that has the keyboard focus, if any.
+    #[cfg_attr(not(feature = "atspi"), allow(dead_code))]
+    pub(crate) fn focused_button(&self) -> Option<usize> {
+        self.focused
+    }
+
+    /// Takes over what changed in `old` since it was created, like the message, the entered text
+    /// and the checked options. `old` must have been created with the same options as this dialog,
+    /// except for the scale factor.
+    ///
+    /// The dialog is laid out again, at the size of `old` converted to the new scale if it is
+    /// resizable, and the new window size is requested through [`Ui::take_resize_request`].
+    pub(crate) fn carry_over(&mut self, old: Ui, resizable: bool) {
+        let ratio = self.metrics.scale / old.metrics.scale;
+        let scaled = |px: i32| (px as f32 * ratio).round() as i32;
+
+        self.message_text = old.message_text;
+        self.links = old.links;
+        self.layout.message_runs = old.layout.message_runs;
+        self.selection = old.selection;
+        self.message_scroll = old.message_scroll.map(scaled);
+        self.focused = old.focused;
+        self.focus_visible = old.focus_visible;
+        self.mnemonics_visible = old.mnemonics_visible;
+        if let (Some(input), Some(mut old)) = (&mut self.input, old.input) {
+            // Swapped rather than moved, so that a password is still erased when `old` is dropped.
+            mem::swap(&mut input.text, &mut old.text);
+            input.caret = old.caret;
+            input.scroll_row = old.scroll_row;
+            input.selected = old.selected;
+        }
+        if let (Some(checkbox), Some(old)) = (&mut self.checkbox, old.checkbox) {
+            checkbox.checked = old.checked;
+        }
+        if let (Some(choices), Some(old)) = (&mut self.choices, old.choices) {
+            choices.selected = old.selected;
+            choices.checked = old.checked;
+            choices.scroll = old.scroll;
+        }
+        if let (Some(progress), Some(old)) = (&mut self.progress, old.progress) {
+            progress.indicator = old.indicator;
+        }
+        if let (Some(details), Some(old)) = (&mut self.details, old.details) {
+            details.expanded = old.expanded;
+            details.scroll = scaled(old.scroll);
+        }
+        if let (Some(animated), Some(old)) = (&mut self.animated_icon, old.animated_icon) {
+            animated.current = old.current;
+            animated.elapsed = old.elapsed;
+        }
+        if let Some(index) = old.armed
+            && let Some(confirm) = self.confirm_buttons.iter().find(|c| c.index == index)
+        {
+            let label = confirm.confirm_label.clone();
+            self.set_button_label(index, &label);
+            self.armed = Some(index);
+        }
+
+        let size = resizable.then(|| {
+            size2(scaled(old.canvas.width()), scaled(old.canvas.height())).max(self.min_size)
+        });
+        self.layout(size);
+        self.redraw();
+        self.resize_request = Some(size2(self.canvas.width(), self.canvas.height()));
+    }
+
+    /// Sets the completed fraction of the progress bar, clamped to the range from 0 to 1.
+    ///
+    /// Does nothing if the dialog has no progress bar.
+    pub(crate) fn set_progress(&mut self, fraction: f32) {
+        if let Some(Progress {
+            indicator: Indicator::Bar(bar),
+            ..
+        }) = &mut self.progress
+        {
+            *bar = if fraction.is_nan() {
+                0.0
+            } else {
+                fraction.clamp(0.0, 1.0)
+            };
+        }
+    }
+
+    /// Returns the image of the icon that is currently displayed, if any.
+    fn icon_image(&self) -> Option<&DrawTarget> {
+        let animated = self.animated_icon.as_ref().map(AnimatedIcon::current);
+        animated.or(self.icon.as_ref())
+    }
+
+    /// Returns whether the dialog contains an animation, and has to be redrawn periodically.
+    pub(crate) fn is_animated(&self) -> bool {
+        matches!(
+            self.progress,
+            Some(Progress {
+                indicator: Indicator::Spinner(_),
+                ..
+            })
+        ) || self
+            .animated_icon
+            .as_ref()
+            .is_some_and(|animated| animated.frames.len() > 1)
+    }
+
+    /// Advances the animation by `elapsed`.
+    pub(crate) fn animate(&mut self, elapsed: Duration) {
+        if let Some(Progress {
+            indicator: Indicator::Spinner(angle),
+            ..
+        }) = &mut self.progress
+        {
+            let turns = elapsed.as_secs_f32() / SPINNER_PERIOD.as_secs_f32();
+            *angle = (*angle + turns * TAU) % TAU;
+        }
+        if let Some(animated) = &mut self.animated_icon {
+            animated.advance(elapsed);
+        }
+    }
+
+    /// Updates the state of the dialog for an input event, and returns the [`Answer`] if the event
+    /// answered the dialog.
+    ///
+    /// This doesn't redraw the canvas, and has no effect outside of the `Ui`: requests to the
+    /// window (like copying text) are collected until they are taken by the caller. Whether the
+    /// canvas has to be redrawn afterwards is reported by [`Ui::needs_redraw`].
+    pub(crate) fn process_event(&mut self, event: WindowEvent) -> Option<Answer> {
+        // Mouse movement is the most frequent event, but only changes the canvas if it changes
+        // what is hovered, or extends the text selection.
+        let moved = matches!(
+            event,
+            WindowEvent::CursorEnter(_) | WindowEvent::CursorMove(_) | WindowEvent::CursorLeave
+        );
+        let invisible = matches!(
+            event,
+            WindowEvent::RedrawRequested
+                | WindowEvent::Wakeup
+                | WindowEvent::SelectionRequest(_)
+                | WindowEvent::Moved { .. }
+        );
+        let (hovered, selecting) = (self.hovered(), self.selecting);
+        let answer = self.handle_event(event);
+        self.needs_redraw |= if moved {
+            selecting || self.hovered() != hovered
+        } else {
+            !invisible
+        };
+        answer
+    }
+
+    /// Returns whether the state changed in a way that is visible since the last
+    /// [`Ui::redraw`].
+    pub(crate) fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    /// Returns the indices of the button and the menu item that are drawn highlighted because the
+    /// mouse cursor is over them.
+    fn hovered(&self) -> (Option<usize>, Option<usize>) {
+        let Some(p) = self.cursor_pos else {
+            return (None, None);
+        };
+        (
+            self.buttons.iter().position(|btn| btn.contains(p)),
+            self.menu.as_ref().and_then(|menu| menu.item_at(p)),
+        )
+    }
+
+    fn handle_event(&mut self, event: WindowEvent) -> Option<Answer> {
+        if let Some(menu) = &self.menu {
+            match event {
+                WindowEvent::KeyPress(Keysym::ESCAPE, _) => {
+                    self.menu = None;
+                    return None;
+                }
+                WindowEvent::ButtonPress(MouseButton::Left) => {
+                    let item = self.cursor_pos.and_then(|p| menu.item_at(p));
+                    let action = item.map(|i| menu.items[i].0);
+                    self.menu = None;
+                    if let Some(action) = action {
+                        self.run_menu_action(action);
+                        return None;
+                    }
+                    // A click outside of the menu closes it, and is handled as usual.
+                }
+                _ => {}
+            }
+        }
+
+        // Other keys hide the mnemonics when Alt is no longer held (the release of Alt may have
+        // gone to another window).
+        if let WindowEvent::KeyPress(sym, mods) = &event
+            && !matches!(*sym, Keysym::ALT_L | Keysym::ALT_R)
+        {
+            self.mnemonics_visible = mods.alt();
+        }
+
+        match event {
+            WindowEvent::CloseRequested => {
+                return Some(Answer::Closed(CloseReason::WmCloseButton));
+            }
+            // Escape closes the dialog regardless of any held modifiers.
+            WindowEvent::KeyPress(Keysym::ESCAPE, _) => {
+                return Some(Answer::Closed(CloseReason::Keyboard));
+            }
+            WindowEvent::CursorEnter(pos) | WindowEvent::CursorMove(pos) => {
+                let pos = point2(pos.x.into(), pos.y.into());
+                self.cursor_pos = Some(pos);
+                self.mouse_dragging = self.mouse_pressed;
+                // Moving on to another button cancels the confirmation.
+                if let Some(armed) = self.armed
+                    && self.hovered().0.is_some_and(|i| i != armed)
+                {
+                    self.disarm();
+                }
+                if self.selecting
+                    && let Some((start, _)) = self.selection
+                {
+                    self.selection = Some((start, self.message_position_at(pos)));
+                }
+            }
+            WindowEvent::CursorLeave => self.cursor_pos = None,
+            // The window contents were lost, so all of the canvas has to be presented again.
+            WindowEvent::RedrawRequested => self.presented.1.clear(),
+            WindowEvent::Resized { width, height } => self.resize(width.into(), height.into()),
+            WindowEvent::ButtonPress(MouseButton::Left) => {
+                self.mouse_pressed = true;
+                self.pressed_button = self.hovered().0;
+                if let Some(p) = self.cursor_pos
+                    && self.message_view().contains(p)
+                {
+                    let pos = self.message_position_at(p);
+                    self.selection = Some((pos, pos));
+                    self.selecting = true;
+                } else {
+                    self.selection = None;
+                }
+                if let Some(p) = self.cursor_pos
+                    && let Some(input) = &mut self.input
+                    && input.rect.contains(p)
+                {
+                    let m = &self.metrics;
+                    let text_space = input.rect.width() - 2 * m.input_padding;
+                    let offset = p - input.rect.min - vec2(m.input_padding, m.input_padding)
+                        + input.scroll(&self.font, text_space);
+                    let row = (offset.y as f32 / self.font.line_height()).max(0.0) as usize;
+                    input.move_caret(input.caret_at(&self.font, offset.x, row));
+                }
+            }
+            // Double-clicking the message selects the word under the cursor.
+            WindowEvent::DoubleClick(MouseButton::Left) => {
+                if let Some(p) = self.cursor_pos
+                    && self.message_view().contains(p)
+                {
+                    self.selection = Some(self.word_at(self.message_position_at(p)));
+                    self.selecting = false;
+                }
+            }
+            WindowEvent::Scroll { delta_y, .. }
+                if let Some(p) = self.cursor_pos
+                    && let Some(choices) = &mut self.choices
+                    && choices.is_scrollable()
+                    && choices.rect.min.y <= p.y
+                    && p.y < choices.rect.max.y =>
+            {
+                choices.scroll_by(-isize::from(delta_y) * SCROLL_LINES as isize);
+            }
+            WindowEvent::Scroll { delta_y, .. }
+                if let Some(p) = self.cursor_pos
+                    && let Some(details) = &mut self.details
+                    && details.expanded
+                    && details.rect.contains(p) =>
+            {
+                let step = (details.font.line_height() * SCROLL_LINES).round() as i32;
+                details.scroll = (details.scroll - i32::from(delta_y) * step)
+                    .clamp(0, details.max_scroll(&self.metrics));
+            }
+            WindowEvent::Scroll {
+                mut delta_x,
+                mut delta_y,
+            } => {
+                let step = (self.font.line_height() * SCROLL_LINES).round() as i32;
+                let max_scroll =
+                    size2(self.message.width(), self.message.height()) - self.message_view_size;
+                // The wheel scrolls horizontally if the message can only be scrolled that way.
+                if max_scroll.height == 0 && delta_x == 0 {
+                    (delta_x, delta_y) = (-delta_y, 0);
+                }
+                self.message_scroll = vec2(
+                    (self.message_scroll.x + i32::from(delta_x) * step).clamp(0, max_scroll.width),
+                    (self.message_scroll.y - i32::from(delta_y) * step).clamp(0, max_scroll.height),
+                );
+            }
+            WindowEvent::ButtonPress(MouseButton::Right) => {
+                self.menu = None;
+                if let Some(p) = self.cursor_pos
+                    && self.message_view().contains(p)
+                {
+                    self.open_menu(p);
+                }
+            }
+            WindowEvent::ButtonPress(MouseButton::Middle) => {
+                self.paste_requested = self.input.is_some().then_some(Selection::Primary);
+            }
+            WindowEvent::Paste(text) => {
+                if let Some(input) = &mut self.input {
+                    // Line breaks are dropped, unless the input field is a text area.
+                    let multiline = input.rows > 1;
+                    for c in text
+                        .chars()
+                        .filter(|&c| !c.is_control() || (multiline && c == '\n'))
+                    {
+                        self.bell_requested |= !input.insert(c);
+                    }
+                }
+            }
+            WindowEvent::ButtonRelease(MouseButton::Left) => {
+                // Only complete clicks activate buttons, not presses that closed the menu.
+                if let Some(p) = self.cursor_pos
+                    && let Some(i) = self.buttons.iter().position(|btn| btn.contains(p))
+                    && self.mouse_pressed
+                    && !self.mouse_dragging
+                    && let Some(answer) = self.activate(i)
+                {
+                    return Some(answer);
+                }
+                if let Some(p) = self.cursor_pos
+                    && let Some(checkbox) = &mut self.checkbox
+                    && checkbox.rect.contains(p)
+                    && self.mouse_pressed
+                    && !self.mouse_dragging
+                {
+                    checkbox.checked = !checkbox.checked;
+                }
+                if let Some(p) = self.cursor_pos
+                    && let Some(choices) = &mut self.choices
+                    && let Some(i) = choices.row_at(p)
+                    && self.mouse_pressed
+                    && !self.mouse_dragging
+                {
+                    choices.toggle(i);
+                }
+                if let Some(p) = self.cursor_pos
+                    && let Some(details) = &self.details
+                    && details.toggle.contains(p)
+                    && self.mouse_pressed
+                    && !self.mouse_dragging
+                {
+                    self.toggle_details();
+                }
+                if let Some(p) = self.cursor_pos
+                    && self.mouse_pressed
+                    && !self.mouse_dragging
+                    && let Some(link) = self.link_at(p)
+                {
+                    self.opened_link = Some(self.message_text[link.clone()].to_string());
+                }
+                self.mouse_pressed = false;
+                self.pressed_button = None;
+                self.mouse_dragging = false;
+                self.selecting = false;
+            }
+            WindowEvent::KeyPress(sym @ (Keysym::TAB | Keysym::ISO_LEFT_TAB), mods) => {
+                if let Some(i) = self.focused {
+                    let n = self.buttons.len();
+                    let backwards = mods.shift() || sym == Keysym::ISO_LEFT_TAB;
+                    self.focused = Some(if backwards {
+                        (i + n - 1) % n
+                    } else {
+                        (i + 1) % n
+                    });
+                    self.focus_visible = true;
+                }
+            }
+            // In a text area, Enter inserts a line break, and Ctrl+Enter answers the dialog.
+            WindowEvent::KeyPress(Keysym::RETURN | Keysym::KP_ENTER, mods)
+                if let Some(input) = &mut self.input
+                    && input.rows > 1
+                    && !mods.control() =>
+            {
+                self.bell_requested |= !input.insert('\n');
+            }
+            WindowEvent::KeyPress(Keysym::RETURN | Keysym::KP_ENTER, _) => {
+                if let Some(i) = self.keyboard_target() {
+                    return self.activate(i);
+                }
+            }
+            // In a checklist, Space toggles the selected choice instead.
+            WindowEvent::KeyPress(Keysym::SPACE, _)
+                if let Some(choices) = &mut self.choices
+                    && choices.checked.is_some() =>
+            {
+                choices.toggle(choices.selected);
+            }
+            // With an input field, Space types a space instead.
+            WindowEvent::KeyPress(Keysym::SPACE, _) if self.input.is_none() => {
+                if let Some(i) = self.keyboard_target() {
+                    return self.activate(i);
+                }
+            }
+            // Holding Backspace keeps deleting, since the X server repeats the `KeyPress`.
+            WindowEvent::KeyPress(Keysym::BACKSPACE, _) => {
+                if let Some(input) = &mut self.input {
+                    input.pop();
+                }
+            }
+            WindowEvent::KeyPress(Keysym::DELETE | Keysym::KP_DELETE, _) => {
+                if let Some(input) = &mut self.input {
+                    input.delete();
+                }
+            }
+            WindowEvent::KeyPress(Keysym::LEFT | Keysym::KP_LEFT, _) => {
+                if let Some(input) = &mut self.input {
+                    input.move_caret(input.prev_boundary());
+                }
+            }
+            WindowEvent::KeyPress(Keysym::RIGHT | Keysym::KP_RIGHT, _) => {
+                if let Some(input) = &mut self.input {
+                    input.move_caret(input.next_boundary());
+                }
+            }
+            WindowEvent::KeyPress(Keysym::HOME | Keysym::KP_HOME, _) => {
+                if let Some(input) = &mut self.input {
+                    input.move_caret(input.line(input.caret_row()).start);
+                }
+            }
+            WindowEvent::KeyPress(Keysym::END | Keysym::KP_END, _) => {
+                if let Some(input) = &mut self.input {
+                    input.move_caret(input.line(input.caret_row()).end);
+                }
+            }
+            // In a text area, Up and Down move the caret between lines instead. Past the first and
+            // the last line, they move it to the start and the end of the text.
+            WindowEvent::KeyPress(
+                sym @ (Keysym::UP | Keysym::KP_UP | Keysym::DOWN | Keysym::KP_DOWN),
+                _,
+            ) if let Some(input) = &mut self.input
+                && input.rows > 1 =>
+            {
+                let (x, row) = (input.caret_offset(&self.font).x, input.caret_row());
+                let caret = if matches!(sym, Keysym::UP | Keysym::KP_UP) {
+                    match row.checked_sub(1) {
+                        Some(row) => input.caret_at(&self.font, x, row),
+                        None => 0,
+                    }
+                } else if row < input.last_row() {
+                    input.caret_at(&self.font, x, row + 1)
+                } else {
+                    input.text.len()
+                };
+                input.move_caret(caret);
+            }
+            WindowEvent::KeyPress(Keysym::UP | Keysym::KP_UP, _) => {
+                if let Some(choices) = &mut self.choices {
+                    choices.select(choices.selected.saturating_sub(1));
+                }
+            }
+            WindowEvent::KeyPress(Keysym::DOWN | Keysym::KP_DOWN, _) => {
+                if let Some(choices) = &mut self.choices {
+                    choices.select(cmp::min(choices.selected + 1, choices.labels.len() - 1));
+                }
+            }
+            WindowEvent::KeyPress(Keysym::ALT_L | Keysym::ALT_R, _) => {
+                self.mnemonics_visible = true;
+            }
+            WindowEvent::KeyRelease(Keysym::ALT_L | Keysym::ALT_R) => {
+                self.mnemonics_visible = false;
+            }
+            WindowEvent::KeyPress(sym, mods) if mods.alt() => {
+                if let Some(c) = sym.to_char()
+                    && let Some(i) = self
+                        .buttons
+                        .iter()
+                        .position(|btn| btn.mnemonic == Some(mnemonic::fold(c)))
+                {
+                    return self.activate(i);
+                }
+            }
+            WindowEvent::KeyPress(sym, mods)
+                if self.copy_shortcut
+                    && mods.control()
+                    && mods.shift()
+                    && matches!(sym.to_char(), Some('c' | 'C')) =>
+            {
+                self.copied = Some(self.message_text.clone());
+                return Some(Answer::CopiedAndClosed);
+            }
+            WindowEvent::KeyPress(sym, mods)
+                if mods.control() && matches!(sym.to_char(), Some('v' | 'V')) =>
+            {
+                self.paste_requested = self.input.is_some().then_some(Selection::Clipboard);
+            }
+            WindowEvent::KeyPress(sym, mods)
+                if mods.control() && matches!(sym.to_char(), Some('c' | 'C')) =>
+            {
+                self.copy_selection();
+            }
+            WindowEvent::KeyPress(sym, mods) if !mods.control() && !mods.alt() => {
+                if let Some(input) = &mut self.input
+                    && let Some(c) = sym.to_char()
+                {
+                    self.bell_requested |= !input.insert(c);
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    /// Returns the area of every button that reacts to clicks, in window coordinates.
+    ///
+    /// The buttons are in the order they were passed to [`Ui::new`], followed by the "Copy"
+    /// button if enabled.
+    pub(crate) fn button_bounds(&self) -> Vec<IntRect> {
+        self.buttons
+            .iter()
+            .map(|btn| IntRect::from_origin_and_size(btn.pos, btn.size.cast_unit()))
+            .collect()
+    }
+
+    /// Returns the clickable area of the details toggle, and the box of the details while they
+    /// are expanded.
+    #[cfg(test)]
+    pub(crate) fn details_bounds(&self) -> Option<(IntRect, Option<IntRect>)> {
+        let details = self.details.as_ref()?;
+        Some((details.toggle, details.expanded.then_some(details.rect)))
+    }
+
+    /// Returns the area of the input field, if there is one.
+    #[cfg(test)]
+    pub(crate) fn input_rect(&self) -> Option<IntRect> {
+        self.input.as_ref().map(|input| input.rect)
+    }
+
+    /// Returns whether a text selection is being made with the mouse.
+    ///
+    /// While this is the case, moving the mouse must not drag the window.
+    pub(crate) fn is_selecting(&self) -> bool {
+        self.selecting
+    }
+
+    /// Returns whether the mouse cursor is in the strip at the top of the window, above the
+    /// message.
+    pub(crate) fn cursor_in_title_area(&self) -> bool {
+        self.cursor_pos
+            .is_some_and(|pos| pos.y >= 0 && pos.y < self.message_pos.y)
+    }
+
+    /// Returns the mouse cursor to display at the current cursor position.
+    ///
+    /// Buttons, links and the toggle of the details are clickable. Nothing is while the context
+    /// menu covers them.
+    pub(crate) fn cursor_shape(&self) -> CursorShape {
+        let Some(p) = self.cursor_pos else {
+            return CursorShape::Default;
+        };
+        let clickable = !self.menu.as_ref().is_some_and(|menu| menu.rect.contains(p))
+            && (self.buttons.iter().any(|btn| btn.contains(p))
+                || self.link_at(p).is_some()
+                || self.details.as_ref().is_some_and(|d| d.toggle.contains(p)));
+        if clickable {
+            CursorShape::Pointer
+        } else {
+            CursorShape::Default
+        }
+    }
+
+    /// Returns the text that was copied since the last call, if any.
+    pub(crate) fn take_copied_text(&mut self) -> Option<String> {
+        self.copied.take()
+    }
+
+    /// Returns the callback button that was pressed since the last call, if any.
+    pub(crate) fn take_callback_button(&mut self) -> Option<usize> {
+        self.pressed_callback.take()
+    }
+
+    /// Returns the URL that was clicked since the last call, if any.
+    pub(crate) fn take_opened_link(&mut self) -> Option<String> {
+        self.opened_link.take()
+    }
+
+    /// Returns the selection that should be pasted into the input field, and resets the request.
+    pub(crate) fn take_paste_request(&mut self) -> Option<Selection> {
+        self.paste_requested.take()
+    }
+
+    /// Returns whether the bell should be rung since the last call, and resets the request.
+    pub(crate) fn take_bell_request(&mut self) -> bool {
+        mem::take(&mut self.bell_requested)
+    }
+
+    /// Returns whether the caret of the input field was moved since the last call, which restarts
+    /// its blinking with the caret visible.
+    pub(crate) fn take_caret_reset(&mut self) -> bool {
+        self.input
+            .as_mut()
+            .is_some_and(|input| mem::take(&mut input.caret_reset))
+    }
+
+    /// Hides the caret of the input field if it is visible, and shows it otherwise.
+    ///
+    /// The caret blinks by calling this every [`CARET_BLINK_INTERVAL`].
+    pub(crate) fn blink_caret(&mut self) {
+        if let Some(input) = &mut self.input {
+            input.caret_visible = !input.caret_visible;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Returns the size the window should be resized to since the last call, if it changed.
+    pub(crate) fn take_resize_request(&mut self) -> Option<IntSize<i32>> {
+        self.resize_request.take()
+    }
+
+    /// Expands the details if they are collapsed, and collapses them otherwise.
+    ///
+    /// The window grows or shrinks to fit, which is requested through [`Ui::take_resize_request`].
+    fn toggle_details(&mut self) {
+        let Some(details) = &mut self.details else {
+            return;
+        };
+        details.expanded = !details.expanded;
+        details.scroll = 0;
+        self.layout(None);
+        self.resize_request = Some(size2(self.canvas.width(), self.canvas.height()));
+    }
+
+    /// Opens the context menu of the message at `point`, keeping it inside of the window.
+    fn open_menu(&mut self, point: IntPoint) {
+        let m = self.metrics;
+        let items = [
+            (MenuAction::Copy, "Copy"),
+            (MenuAction::SelectAll, "Select All"),
+        ]
+        .map(|(action, label)| {
+            let label = self
+                .font
+                .render(label)
+                .with_color(self.colors.text)
+                .with_line_box()
+                .finish();
+            (action, label)
+        });
+        let item_height = self.font.line_height().ceil() as i32 + 2 * m.input_padding;
+        let width = items.iter().map(|(_, l)| l.width()).max().unwrap_or(0) + 4 * m.input_padding;
+        let size = size2(width, item_height * items.len() as i32);
+        let max = size2(self.canvas.width(), self.canvas.height()) - size;
+        let origin = point2(
+            point.x.clamp(0, cmp::max(0, max.width)),
+            point.y.clamp(0, cmp::max(0, max.height)),
+        );
+        self.menu = Some(Menu {
+            rect: IntRect::from_origin_and_size(origin, size),
+            item_height,
+            items: items.into(),
+        });
+    }
+
+    fn run_menu_action(&mut self, action: MenuAction) {
+        match action {
+            MenuAction::Copy => self.copy_selection(),
+            MenuAction::SelectAll => {
+                self.selection = Some((0, self.message_layout.len()));
+            }
+        }
+    }
+
+    /// Copies the selected part of the message, or the whole message if nothing is selected.
+    fn copy_selection(&mut self) {
+        let range = self.selection_range();
+        let text = if range.is_empty() {
+            &self.message_text[..]
+        } else {
+            &self.message_text[self.message_layout.byte_range(range)]
+        };
+        self.copied = Some(text.to_string());
+    }
+
+    /// Returns the range of selected characters in the message.
+    fn selection_range(&self) -> Range<usize> {
+        match self.selection {
+            Some((a, b)) => cmp::min(a, b)..cmp::max(a, b),
+            None => 0..0,
+        }
+    }
+
+    /// Returns the start and end positions of the word around the position `pos` in the message.
+    ///
+    /// If `pos` isn't next to a word character, the range is empty.
+    fn word_at(&self, pos: usize) -> (usize, usize) {
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let chars = self.message_text.chars().collect::<Vec<_>>();
+        let pos = cmp::min(pos, chars.len());
+        let start = pos - chars[..pos].iter().rev().take_while(|c| is_word(c)).count();
+        let end = pos + chars[pos..].iter().take_while(|c| is_word(c)).count();
+        (start, end)
+    }
+
+    /// Returns the visible area of the message, in window coordinates.
+    pub(crate) fn message_view(&self) -> IntRect {
+        IntRect::from_origin_and_size(self.message_pos, self.message_view_size)
+    }
+
+    /// Returns the position between two characters of the message closest to `point` (in window
+    /// coordinates).
+    fn message_position_at(&self, point: IntPoint) -> usize {
+        let p = point - self.message_pos + self.message_scroll;
+        self.message_layout.position_at(p.to_point().to_f32())
+    }
+
+    /// Returns the byte range of the link at `point` (in window coordinates), if there is one.
+    fn link_at(&self, point: IntPoint) -> Option<&Range<usize>> {
+        if self.links.is_empty() || !self.message_view().contains(point) {
+            return None;
+        }
+        let p = point - self.message_pos + self.message_scroll;
+        let byte = self.message_layout.byte_at(p.to_point().to_f32())?;
+        self.links.iter().find(|link| link.contains(&byte))
+    }
+
+    /// Returns whether the checkbox is checked, or `None` if there is no checkbox.
+    pub(crate) fn checkbox_checked(&self) -> Option<bool> {
+        self.checkbox.as_ref().map(|checkbox| checkbox.checked)
+    }
+
+    /// Returns the button that Enter and Space activate, if any.
+    fn keyboard_target(&self) -> Option<usize> {
+        self.focused
+            .filter(|_| self.focus_visible || self.default_button.is_some() || self.input.is_some())
+    }
+
+    /// Returns whether the button at `index` is disabled, because the text of the input field
+    /// isn't valid (see [`Options::input_validate`]).
+    fn is_disabled(&self, index: usize) -> bool {
+        index == self.default_button.unwrap_or(0)
+            && self.input.as_ref().is_some_and(|input| !input.is_valid())
+    }
+
+    /// Activates the button at `index`, returning the [`Answer`] if that closes the dialog.
+    fn activate(&mut self, index: usize) -> Option<Answer> {
+        if self.is_disabled(index) {
+            return None;
+        }
+        if self.armed == Some(index) {
+            self.disarm();
+        } else if let Some(confirm) = self.confirm_buttons.iter().find(|c| c.index == index) {
+            // The first activation only asks for confirmation.
+            let label = confirm.confirm_label.clone();
+            self.disarm();
+            self.armed = Some(index);
+            self.set_button_label(index, &label);
+            return None;
+        }
+
+        if self.copy_button && index == self.buttons.len() - 1 {
+            // Copying doesn't close the dialog.
+            self.copied = Some(self.message_text.clone());
+            None
+        } else if self.callback_buttons.contains(&index) {
+            self.pressed_callback = Some(index);
+            None
+        } else {
+            Some(self.answer(index))
+        }
+    }
+
+    /// Returns the [`Answer`] for pressing the button at `index`.
+    fn answer(&mut self, index: usize) -> Answer {
+        if let Some(input) = &mut self.input {
+            Answer::Input {
+                button: index,
+                text: input.take(),
+            }
+        } else if let Some(choices) = &self.choices {
+            match &choices.checked {
+                Some(checked) => Answer::MultiSelected {
+                    indices: (0..checked.len()).filter(|&i| checked[i]).collect(),
+                    button: index,
+                },
+                None => Answer::Selected {
+                    index: choices.selected,
+                    button: index,
+                },
+            }
+        } else {
+            Answer::Button(index)
+        }
+    }
+
+    /// Returns the opaque parts of the canvas as rectangles, for shaping a window with rounded
+    /// corners.
+    ///
+    /// Returns `None` if the corners are square, so that the window doesn't need a shape.
+    pub(crate) fn window_shape(&self) -> Option<Vec<IntRect>> {
+        let (width, height) = (self.canvas.width(), self.canvas.height());
+        let radius = self.corner_radius.min(width.min(height) as f32 / 2.0);
+        let rows = radius.ceil() as i32;
+        if rows == 0 {
+            return None;
+        }
+        // Each row of the corners is inset by the distance from the edge to the circle, measured
+        // at the center of the row. Rows close to the middle are merged into one rectangle.
+        let mut rects = Vec::new();
+        for y in 0..rows {
+            let dy = radius - (y as f32 + 0.5);
+            let inset = (radius - (radius * radius - dy * dy).max(0.0).sqrt()).round() as i32;
+            for y in [y, height - 1 - y] {
+                rects.push(IntRect::new(point2(inset, y), point2(width - inset, y + 1)));
+            }
+        }
+        rects.push(IntRect::new(point2(0, rows), point2(width, height - rows)));
+        Some(rects)
+    }
+
+    /// Returns the area of the canvas that changed since the last call, and has to be presented
+    /// again.
+    ///
+    /// The first call, and the first call after a [`WindowEvent::RedrawRequested`], returns the
+    /// whole canvas.
+    pub(crate) fn take_damage(&mut self) -> Option<IntRect> {
+        let (width, pixels) = (self.canvas.width(), self.canvas.get_data());
+        let (presented_width, presented) = &mut self.presented;
+        let damage = if *presented_width != width || presented.len() != pixels.len() {
+            Some(IntRect::new(
+                IntPoint::zero(),
+                point2(width, self.canvas.height()),
+            ))
+        } else {
+            let rows = pixels
+                .chunks_exact(width as usize)
+                .zip(presented.chunks_exact(width as usize));
+            rows.enumerate()
+                .filter_map(|(y, (new, old))| {
+                    let differs = |(a, b): (&u32, &u32)| a != b;
+                    let first = new.iter().zip(old).position(differs)?;
+                    let last = new.iter().zip(old).rposition(differs)?;
+                    Some(IntRect::new(
+                        point2(first as i32, y as i32),
+                        point2(last as i32 + 1, y as i32 + 1),
+                    ))
+                })
+                .reduce(|a, b| a.union(&b))
+        };
+        if damage.is_some() {
+            *presented_width = width;
+            presented.clear();
+            presented.extend_from_slice(pixels);
+        }
+        damage
+    }
+
+    pub(crate) fn redraw(&mut self) {
+        self.needs_redraw = false;
+        let m = self.metrics;
+        if self.corner_radius > 0.0 {
+            // The corners are cut off by the window shape, so they stay transparent.
+            self.canvas
+                .clear(SolidSource::from_unpremultiplied_argb(0, 0, 0, 0));
+            let size = size2(self.canvas.width(), self.canvas.height());
+            self.canvas.fill(
+                &rounded_rect(size, self.corner_radius),
+                &self.colors.window_bg.into(),
+                &Default::default(),
+            );
+        } else {
+            self.canvas.clear(self.colors.window_bg.into());
+        }
+
+        // Not `icon_image`, which would borrow all of `self`.
+        let icon = self.animated_icon.as_ref().map(AnimatedIcon::current);
+        if let Some(icon) = icon.or(self.icon.as_ref()) {
+            self.canvas.place_surface(icon, self.icon_pos);
+        }
+        self.draw_message();
+        if let Some((title, pos)) = &self.title {
+            self.canvas.place_surface(title, *pos);
+        }
+        if let Some((detail, pos)) = &self.detail {
+            self.canvas.place_surface(detail, *pos);
+        }
+        if let Some((footer, pos)) = &self.footer {
+            self.canvas.place_surface(footer, *pos);
+        }
+
+        if let Some(input) = &self.input {
+            draw_input(&mut self.canvas, &m, &self.colors, &self.font, input);
+        }
+        if let Some(checkbox) = &self.checkbox {
+            draw_checkbox(&mut self.canvas, &m, &self.colors, checkbox);
+        }
+        if let Some(choices) = &self.choices {
+            draw_choices(&mut self.canvas, &m, &self.colors, choices);
+        }
+        if let Some(progress) = &self.progress {
+            draw_progress(&mut self.canvas, &m, &self.colors, progress);
+        }
+        if let Some(details) = &self.details {
+            draw_details(&mut self.canvas, &m, &self.colors, details);
+        }
+        if let Some(custom) = &self.custom {
+            let rect = custom.rect;
+            self.canvas.push_clip_rect(rect);
+            (custom.draw)(
+                &mut self.canvas,
+                crate::Rect {
+                    x: rect.min.x,
+                    y: rect.min.y,
+                    width: rect.width() as u32,
+                    height: rect.height() as u32,
+                },
+            );
+            self.canvas.pop_clip();
+        }
+
+        for (i, btn) in self.buttons.iter().enumerate() {
+            let [mut color, hover, pressed] = self.colors.button_fill(i, self.default_button);
+            let disabled = self.is_disabled(i);
+            let is_hovered = !disabled && self.cursor_pos.is_some_and(|pos| btn.contains(pos));
+            // Pressing the mouse button elsewhere and moving onto the button only highlights it.
+            let is_pressed = is_hovered && self.pressed_button == Some(i);
+            if is_pressed {
+                color = pressed;
+            } else if is_hovered {
+                color = hover;
+            }
+
+            let mut pb = PathBuilder::new();
+            pb.rect(
+                btn.pos.x as f32,
+                btn.pos.y as f32,
+                btn.size.width as f32,
+                btn.size.height as f32,
+            );
+            let path = rounded_rect(btn.size, m.btn_radius).transform(&Transform2D::translation(
+                btn.pos.x as f32,
+                btn.pos.y as f32,
+            ));
+            self.canvas.fill(&path, &color.into(), &Default::default());
+            self.canvas.stroke(
+                &path,
+                &self.colors.button_outline.into(),
+                &StrokeStyle {
+                    width: m.line_width,
+                    ..Default::default()
+                },
+                &Default::default(),
+            );
+            let ring = if self.focus_visible {
+                self.focused == Some(i)
+            } else {
+                self.default_button == Some(i) && self.colors.accent.is_none()
+            };
+            if ring {
+                let inset = m.focus_ring_inset;
+                let ring = rounded_rect(
+                    btn.size - size2(inset, inset) * 2,
+                    m.btn_radius - m.line_width,
+                )
+                .transform(&Transform2D::translation(
+                    (btn.pos.x + inset) as f32,
+                    (btn.pos.y + inset) as f32,
+                ));
+                self.canvas.stroke(
+                    &ring,
+                    &self.colors.focus_ring.into(),
+                    &StrokeStyle {
+                        width: m.thick_line_width,
+                        ..Default::default()
+                    },
+                    &Default::default(),
+                );
+            }
+
+            // The label of a pressed button moves down, as if the button was pushed in.
+            let icon_width = btn
+                .icon
+                .as_ref()
+                .map_or(0, |icon| icon.width() + m.btn_icon_spacing);
+            let icon_x = btn.pos.x + btn.size.width / 2 - (icon_width + btn.text.width()) / 2;
+            let text_x = icon_x + icon_width;
+            let text_y =
+                btn.pos.y + m.btn_padding + if is_pressed { m.line_width as i32 } else { 0 };
+            if let Some(icon) = &btn.icon {
+                let icon_y = text_y + (btn.text.height() - icon.height()) / 2;
+                self.canvas.place_surface(icon, point2(icon_x, icon_y));
+            }
+            self.canvas.place_surface(&btn.text, point2(text_x, text_y));
+            if self.mnemonics_visible
+                && let Some(underline) = btn.underline
+            {
+                self.canvas.fill_rect(
+                    text_x as f32 + underline.min.x,
+                    text_y as f32 + underline.min.y,
+                    underline.width(),
+                    underline.height(),
+                    &self.colors.button_text(i, self.default_button).into(),
+                    &Default::default(),
+                );
+            }
+            // Disabled buttons are faded into the background.
+            if disabled {
+                let Rgb(r, g, b) = self.colors.window_bg;
+                self.canvas.fill(
+                    &path,
+                    &SolidSource::from_unpremultiplied_argb(160, r, g, b).into(),
+                    &Default::default(),
+                );
+            }
+        }
+
+        if let Some(menu) = &self.menu {
+            draw_menu(&mut self.canvas, &m, &self.colors, menu, self.cursor_pos);
+        }
+    }
+}
+
+/// Renders the label of a button, and returns where to underline its mnemonic in the image.
+fn render_label(
+    font: &Font,
+    m: &Metrics,
+    label: &Label,
+    color: Rgb,
+) -> (DrawTarget, Option<Box2D<f32>>) {
+    let (text, layout) = font
+        .render(&label.text)
+        .with_color(color)
+        .finish_with_layout();
+    let underline = label.mnemonic.and_then(|i| {
+        let index = label.text[..i].chars().count();
+        let rect = *layout.boxes(index..index + 1).first()?;
+        let y = (rect.min.y + font.ascent() + m.line_width).round();
+        Some(Box2D::new(
+            point2(rect.min.x.round(), y),
+            point2(rect.max.x.round(), y + m.line_width),
+        ))
+    });
+    (text, underline)
+}
+
+fn draw_menu(
+    canvas: &mut DrawTarget,
+    m: &Metrics,
+    colors: &Colors,
+    menu: &Menu,
+    cursor_pos: Option<IntPoint>,
+) {
+    let rect = menu.rect;
+    let path = rounded_rect(rect.size().cast_unit(), m.btn_radius).transform(
+        &Transform2D::translation(rect.min.x as f32, rect.min.y as f32),
+    );
+    canvas.fill(&path, &colors.input_bg.into(), &Default::default());
+    canvas.stroke(
+        &path,
+        &colors.button_outline.into(),
+        &StrokeStyle {
+            width: m.line_width,
+            ..Default::default()
+        },
+        &Default::default(),
+    );
+
+    let hovered = cursor_pos.and_then(|p| menu.item_at(p));
+    for (i, (_, label)) in menu.items.iter().enumerate() {
+        let y = rect.min.y + i as i32 * menu.item_height;
+        if hovered == Some(i) {
+            let inset = m.focus_ring_inset;
+            let highlight = rounded_rect(
+                size2(rect.width(), menu.item_height) - size2(inset, inset) * 2,
+                m.btn_radius - m.line_width,
+            )
+            .transform(&Transform2D::translation(
+                (rect.min.x + inset) as f32,
+                (y + inset) as f32,
+            ));
+            canvas.fill(&highlight, &colors.selection.into(), &Default::default());
+        }
+        canvas.place_surface(
+            label,
+            point2(rect.min.x + 2 * m.input_padding, y + m.input_padding),
+        );
+    }
+}
+
+fn draw_checkbox(canvas: &mut DrawTarget, m: &Metrics, colors: &Colors, checkbox: &Checkbox) {
+    let rect = checkbox.rect;
+    let box_y = rect.min.y + (rect.height() - m.checkbox_size) / 2;
+    draw_check_box(
+        canvas,
+        m,
+        colors,
+        point2(rect.min.x, box_y),
+        checkbox.checked,
+    );
+
+    let label_y = rect.min.y + (rect.height() - checkbox.label.height()) / 2;
+    canvas.place_surface(
+        &checkbox.label,
+        point2(rect.min.x + m.checkbox_size + m.spacing / 2, label_y),
+    );
+}
+
+/// Draws the box of a checkbox, with its top-left corner at `pos`.
+fn draw_check_box(
+    canvas: &mut DrawTarget,
+    m: &Metrics,
+    colors: &Colors,
+    pos: IntPoint,
+    checked: bool,
+) {
+    let path = rounded_rect(size2(m.checkbox_size, m.checkbox_size), m.scale(3.0))
+        .transform(&Transform2D::translation(pos.x as f32, pos.y as f32));
+    let fill = if checked {
+        colors.focus_ring
+    } else {
+        colors.input_bg
+    };
+    canvas.fill(&path, &fill.into(), &Default::default());
+    canvas.stroke(
+        &path,
+        &colors.button_outline.into(),
+        &StrokeStyle {
+            width: m.line_width,
+            ..Default::default()
+        },
+        &Default::default(),
+    );
+
+    if checked {
+        let (x, y, size) = (pos.x as f32, pos.y as f32, m.checkbox_size as f32);
+        let mut pb = PathBuilder::new();
+        pb.move_to(x + size * 0.25, y + size * 0.5);
+        pb.line_to(x + size * 0.42, y + size * 0.7);
+        pb.line_to(x + size * 0.75, y + size * 0.3);
+        canvas.stroke(
+            &pb.finish(),
+            &colors.window_bg.into(),
+            &StrokeStyle {
+                width: m.thick_line_width,
+                cap: LineCap::Round,
+                join: LineJoin::Round,
+                ..Default::default()
+            },
+            &Default::default(),
+        );
+    }
+}
+
+fn draw_choices(canvas: &mut DrawTarget, m: &Metrics, colors: &Colors, choices: &Choices) {
+    let radius = m.checkbox_size as f32 / 2.0;
+    for i in choices.visible_rows() {
+        let (label, rect) = (&choices.labels[i], choices.row(i));
+        let selected = i == choices.selected;
+        if selected {
+            let highlight = rounded_rect(rect.size().cast_unit(), m.btn_radius).transform(
+                &Transform2D::translation(rect.min.x as f32, rect.min.y as f32),
+            );
+            canvas.fill(&highlight, &colors.selection.into(), &Default::default());
+        }
+
+        let label_x = rect.min.x + m.input_padding + m.checkbox_size + m.spacing / 2;
+        let label_y = rect.min.y + (rect.height() - label.height()) / 2;
+        canvas.place_surface(label, point2(label_x, label_y));
+
+        if let Some(checked) = &choices.checked {
+            let pos = point2(
+                rect.min.x + m.input_padding,
+                rect.min.y + (rect.height() - m.checkbox_size) / 2,
+            );
+            draw_check_box(canvas, m, colors, pos, checked[i]);
+            continue;
+        }
+        let (x, y) = (
+            (rect.min.x + m.input_padding) as f32 + radius,
+            rect.min.y as f32 + rect.height() as f32 / 2.0,
+        );
+        let mut pb = PathBuilder::new();
+        pb.arc(x, y, radius, 0.0, TAU);
+        let circle = pb.finish();
+        canvas.fill(&circle, &colors.input_bg.into(), &Default::default());
+        canvas.stroke(
+            &circle,
+            &colors.button_outline.into(),
+            &StrokeStyle {
+                width: m.line_width,
+                ..Default::default()
+            },
+            &Default::default(),
+        );
+        if selected {
+            let mut pb = PathBuilder::new();
+            pb.arc(x, y, radius / 2.0, 0.0, TAU);
+            canvas.fill(&pb.finish(), &colors.focus_ring.into(), &Default::default());
+        }
+    }
+
+    if choices.is_scrollable() {
+        let (track, rows) = (choices.rect.height() as f32, choices.labels.len() as f32);
+        let visible = MAX_VISIBLE_CHOICES as f32;
+        let length = track * visible / rows;
+        let offset = choices.scroll as f32 / (rows - visible) * (track - length);
+        let thumb = rounded_rect(
+            size2(m.scrollbar_width, length.round() as i32),
+            m.scrollbar_width as f32 / 2.0,
+        )
+        .transform(&Transform2D::translation(
+            (choices.rect.max.x + m.scrollbar_spacing) as f32,
+            choices.rect.min.y as f32 + offset,
+        ));
+        canvas.fill(&thumb, &colors.button_hover.into(), &Default::default());
+    }
+}
+
+fn draw_details(canvas: &mut DrawTarget, m: &Metrics, colors: &Colors, details: &Details) {
+    let toggle = details.toggle;
+    let label = details.label();
+    canvas.place_surface(
+        label,
+        point2(
+            toggle.min.x,
+            toggle.min.y + (toggle.height() - label.height()) / 2,
+        ),
+    );
+
+    // The arrow points right while collapsed, and down while expanded.
+    let size = (m.checkbox_size / 2) as f32;
+    let (x, y) = (
+        (toggle.max.x as f32) - size,
+        toggle.min.y as f32 + (toggle.height() as f32 - size) / 2.0,
+    );
+    let mut pb = PathBuilder::new();
+    if details.expanded {
+        pb.move_to(x, y + size / 4.0);
+        pb.line_to(x + size, y + size / 4.0);
+        pb.line_to(x + size / 2.0, y + size * 3.0 / 4.0);
+    } else {
+        pb.move_to(x + size / 4.0, y);
+        pb.line_to(x + size * 3.0 / 4.0, y + size / 2.0);
+        pb.line_to(x + size / 4.0, y + size);
+    }
+    pb.close();
+    canvas.fill(&pb.finish(), &colors.link.into(), &Default::default());
+
+    if !details.expanded {
+        return;
+    }
+    let rect = details.rect;
+    let path = rounded_rect(rect.size().cast_unit(), m.btn_radius).transform(
+        &Transform2D::translation(rect.min.x as f32, rect.min.y as f32),
+    );
+    canvas.fill(&path, &colors.input_bg.into(), &Default::default());
+    canvas.stroke(
+        &path,
+        &colors.button_outline.into(),
+        &StrokeStyle {
+            width: m.line_width,
+            ..Default::default()
+        },
+        &Default::default(),
+    );
+
+    let view = details.view(m);
+    let visible = cmp::min(view.height(), details.rendered.height());
+    canvas.place_surface_region(
+        &details.rendered,
+        IntRect::from_origin_and_size(
+            point2(0, details.scroll),
+            size2(details.rendered.width(), visible),
+        ),
+        view.min,
+    );
+
+    let max_scroll = details.max_scroll(m);
+    if max_scroll > 0 {
+        let (track, content) = (view.height() as f32, details.rendered.height() as f32);
+        let length = f32::max(track * track / content, m.scale(20.0));
+        let offset = details.scroll as f32 / max_scroll as f32 * (track - length);
+        let thumb = rounded_rect(
+            size2(m.scrollbar_width, length.round() as i32),
+            m.scrollbar_width as f32 / 2.0,
+        )
+        .transform(&Transform2D::translation(
+            (view.max.x + m.scrollbar_spacing) as f32,
+            view.min.y as f32 + offset,
+        ));
+        canvas.fill(&thumb, &colors.button_hover.into(), &Default::default());
+    }
+}
+
+fn draw_progress(canvas: &mut DrawTarget, m: &Metrics, colors: &Colors, progress: &Progress) {
+    let rect = progress.rect;
+    let fraction = match progress.indicator {
+        Indicator::Bar(fraction) => fraction,
+        Indicator::Spinner(angle) => return draw_spinner(canvas, m, colors, rect, angle),
+    };
+    let radius = rect.height() as f32 / 2.0;
+    let translation = Transform2D::translation(rect.min.x as f32, rect.min.y as f32);
+    let track = rounded_rect(rect.size().cast_unit(), radius).transform(&translation);
+    canvas.fill(&track, &colors.input_bg.into(), &Default::default());
+    canvas.stroke(
+        &track,
+        &colors.button_outline.into(),
+        &StrokeStyle {
+            width: m.line_width,
+            ..Default::default()
+        },
+        &Default::default(),
+    );
+
+    if fraction > 0.0 {
+        // The filled part is never narrower than it is tall, so that its ends stay rounded.
+        let width = (rect.width() as f32 * fraction).round() as i32;
+        let width = cmp::max(width, rect.height());
+        let fill = rounded_rect(size2(width, rect.height()), radius).transform(&translation);
+        canvas.fill(&fill, &colors.focus_ring.into(), &Default::default());
+    }
+}
+
+/// Draws a circle with a highlighted arc at `angle`, centered in `rect`.
+fn draw_spinner(canvas: &mut DrawTarget, m: &Metrics, colors: &Colors, rect: IntRect, angle: f32) {
+    let center = rect.to_f32().center();
+    let radius = (rect.height() as f32 - m.thick_line_width) / 2.0;
+    let style = StrokeStyle {
+        width: m.thick_line_width,
+        cap: LineCap::Round,
+        ..Default::default()
+    };
+
+    let mut pb = PathBuilder::new();
+    pb.arc(center.x, center.y, radius, 0.0, TAU);
+    canvas.stroke(
+        &pb.finish(),
+        &colors.button_outline.into(),
+        &style,
+        &Default::default(),
+    );
+
+    let mut pb = PathBuilder::new();
+    pb.arc(center.x, center.y, radius, angle, PI / 2.0);
+    canvas.stroke(
+        &pb.finish(),
+        &colors.focus_ring.into(),
+        &style,
+        &Default::default(),
+    );
+}
+
+impl Ui {
+    fn draw_message(&mut self) {
+        let m = self.metrics;
+        let range = self.selection_range();
+        if !range.is_empty() {
+            let origin = self.message_pos - self.message_scroll;
+            self.canvas.push_clip_rect(self.message_view());
+            for rect in self.message_layout.boxes(range) {
+                self.canvas.fill_rect(
+                    origin.x as f32 + rect.min.x,
+                    origin.y as f32 + rect.min.y,
+                    rect.width(),
+                    rect.height(),
+                    &self.colors.selection.into(),
+                    &Default::default(),
+                );
+            }
+            self.canvas.pop_clip();
+        }
+
+        let view =
+            IntRect::from_origin_and_size(self.message_scroll.to_point(), self.message_view_size);
+        self.canvas
+            .place_surface_region(&self.message, view, self.message_pos);
+
+        if !self.links.is_empty() {
+            let origin = self.message_pos - self.message_scroll;
+            let ascent = self
+                .layout
+                .message_font
+                .as_ref()
+                .unwrap_or(&self.font)
+                .ascent();
+            self.canvas.push_clip_rect(self.message_view());
+            for link in &self.links {
+                for rect in self
+                    .message_layout
+                    .boxes(self.message_layout.chars_in(link.clone()))
+                {
+                    let y = (rect.min.y + ascent + m.line_width).round();
+                    self.canvas.fill_rect(
+                        origin.x as f32 + rect.min.x.round(),
+                        origin.y as f32 + y,
+                        rect.width().round(),
+                        m.line_width,
+                        &self.colors.link.into(),
+                        &Default::default(),
+                    );
+                }
+            }
+            self.canvas.pop_clip();
+        }
+
+        let view = self.message_view_size;
+        let content: IntSize<i32> = size2(self.message.width(), self.message.height());
+        let scroll = self.message_scroll;
+        let thumb = |track: i32, content: i32, scroll: i32| {
+            let track = track as f32;
+            let length = f32::max(track * track / content as f32, m.scale(20.0));
+            let offset = scroll as f32 / (content as f32 - track) * (track - length);
+            (length.round() as i32, offset)
+        };
+        let mut thumbs = Vec::new();
+        if view.height < content.height {
+            let (height, y) = thumb(view.height, content.height, scroll.y);
+            thumbs.push((
+                size2(m.scrollbar_width, height),
+                ((view.width + m.scrollbar_spacing) as f32, y),
+            ));
+        }
+        if view.width < content.width {
+            let (width, x) = thumb(view.width, content.width, scroll.x);
+            thumbs.push((
+                size2(width, m.scrollbar_width),
+                (x, (view.height + m.scrollbar_spacing) as f32),
+            ));
+        }
+        for (size, (x, y)) in thumbs {
+            let thumb = rounded_rect(size, m.scrollbar_width as f32 / 2.0).transform(
+                &Transform2D::translation(
+                    self.message_pos.x as f32 + x,
+                    self.message_pos.y as f32 + y,
+                ),
+            );
+            self.canvas.fill(
+                &thumb,
+                &self.colors.button_hover.into(),
+                &Default::default(),
+            );
+        }
+    }
+}
+
+fn draw_input(canvas: &mut DrawTarget, m: &Metrics, colors: &Colors, font: &Font, input: &Input) {
+    let rect = input.rect;
+    let path = rounded_rect(rect.size().cast_unit(), m.btn_radius).transform(
+        &Transform2D::translation(rect.min.x as f32, rect.min.y as f32),
+    );
+    canvas.fill(&path, &colors.input_bg.into(), &Default::default());
+    // The input field always has keyboard focus.
+    canvas.stroke(
+        &path,
+        &colors.focus_ring.into(),
+        &StrokeStyle {
+            width: m.thick_line_width,
+            ..Default::default()
+        },
+        &Default::default(),
+    );
+
+    let text_pos = rect.min + size2(m.input_padding, m.input_padding);
+    let text_space = rect.width() - 2 * m.input_padding;
+    if input.text.is_empty() {
+        canvas.place_surface_clipped(
+            &input.placeholder,
+            text_pos,
+            size2(text_space, input.placeholder.height()),
+        );
+    }
+
+    let display = input.display();
+    let text = font
+        .render(&display)
+        .with_color(colors.text)
+        .with_line_box()
+        .finish();
+    // If the text doesn't fit, scroll it so that the caret stays visible.
+    let view = size2(text_space, rect.height() - 2 * m.input_padding);
+    let caret = input.caret_offset(font);
+    let scroll = input.scroll(font, text_space);
+    if input.selected {
+        canvas.fill_rect(
+            text_pos.x as f32,
+            text_pos.y as f32,
+            cmp::min(text.width() - scroll.x, view.width) as f32,
+            cmp::min(text.height() - scroll.y, view.height) as f32,
+            &colors.selection.into(),
+            &Default::default(),
+        );
+    }
+    canvas.place_surface_region(
+        &text,
+        IntRect::from_origin_and_size(scroll.to_point(), view),
+        text_pos,
+    );
+
+    if input.caret_visible {
+        let caret = text_pos + (caret - scroll).to_vector();
+        canvas.fill_rect(
+            caret.x as f32,
+            caret.y as f32,
+            m.line_width,
+            font.line_height(),
+            &colors.text.into(),
+            &Default::default(),
+        );
+    }
+}
 
 trait DrawTargetExt {
     /// Renders `surface` at `position` in `self`.
     ///
     /// The entire surface will be rendered, and this will use the standard `SrcOver` blend mode.
     fn place_surface(&mut self, surface: &DrawTarget, position: IntPoint);
+
+    /// Renders at most `size` pixels of `surface` at `position` in `self`.
+    fn place_surface_clipped(
+        &mut self,
+        surface: &DrawTarget,
+        position: IntPoint,
+        size: Size2D<i32, ()>,
+    );
+
+    /// Renders the `region` of `surface` at `position` in `self`.
+    fn place_surface_region(&mut self, surface: &DrawTarget, region: IntRect, position: IntPoint);
 }
 impl DrawTargetExt for DrawTarget {
     fn place_surface(&mut self, surface: &DrawTarget, position: IntPoint) {
@@ -265,6 +3545,133 @@ impl DrawTargetExt for DrawTarget {
             BlendMode::SrcOver,
         );
     }
+
+    fn place_surface_clipped(
+        &mut self,
+        surface: &DrawTarget,
+        position: IntPoint,
+        size: Size2D<i32, ()>,
+    ) {
+        self.blend_surface(
+            surface,
+            IntRect::from_size(
+                size.min(size2(surface.width(), surface.height()))
+                    .cast_unit(),
+            ),
+            position,
+            BlendMode::SrcOver,
+        );
+    }
+
+    fn place_surface_region(&mut self, surface: &DrawTarget, region: IntRect, position: IntPoint) {
+        // `blend_surface` clips incorrectly when the source rectangle doesn't start at the origin,
+        // so copy the region into its own surface first.
+        let region = region.intersection_unchecked(&IntRect::from_size(size2(
+            surface.width(),
+            surface.height(),
+        )));
+        if region.is_empty() {
+            return;
+        }
+        let mut data = Vec::with_capacity(region.area() as usize);
+        for y in region.min.y..region.max.y {
+            let row = (y * surface.width()) as usize;
+            data.extend_from_slice(
+                &surface.get_data()[row + region.min.x as usize..row + region.max.x as usize],
+            );
+        }
+        let copy = DrawTarget::from_vec(region.width(), region.height(), data);
+        self.place_surface(&copy, position);
+    }
+}
+
+/// Replaces the color of every pixel in a single-colored `surface` (like rendered text) with
+/// `color`, keeping its alpha.
+fn recolor(surface: &mut DrawTarget, color: Rgb) {
+    let Rgb(r, g, b) = color;
+    for px in surface.get_data_mut() {
+        let a = (*px >> 24) as u8;
+        let c = SolidSource::from_unpremultiplied_argb(a, r, g, b);
+        *px = u32::from_be_bytes([c.a, c.r, c.g, c.b]);
+    }
+}
+
+/// Breaks `text` into lines at `max_width` pixels, like the message (see [`crate::wrap_text`]).
+pub(crate) fn wrap_text(text: &str, max_width: f32, font_size: f32) -> Vec<String> {
+    let font = Font::load(None, font_size);
+    font.render(text)
+        .with_max_width(max_width)
+        .with_max_word_width(max_width)
+        .lines()
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Scales `image` by `factor`, rounding its size to whole pixels.
+pub(crate) fn scale_image(image: DrawTarget, factor: f32) -> DrawTarget {
+    let width = cmp::max(1, (image.width() as f32 * factor).round() as i32);
+    let height = cmp::max(1, (image.height() as f32 * factor).round() as i32);
+    if (width, height) == (image.width(), image.height()) {
+        return image;
+    }
+
+    let mut scaled = DrawTarget::new(width, height);
+    scaled.draw_image_with_size_at(
+        width as f32,
+        height as f32,
+        0.0,
+        0.0,
+        &Image {
+            width: image.width(),
+            height: image.height(),
+            data: image.get_data(),
+        },
+        &Default::default(),
+    );
+    scaled
+}
+
+/// Parses `text` as [`Markup`] if `markup` is enabled, and returns it unstyled otherwise.
+///
+/// If `detect_links` is enabled, the URLs in the text are styled as links, and their byte ranges
+/// are returned as well.
+fn parse_message(
+    text: &str,
+    markup: bool,
+    ansi: AnsiMode,
+    detect_links: bool,
+) -> (Markup, Vec<Range<usize>>) {
+    let text = normalize_line_breaks(text);
+    let text = &*text;
+    let message = if markup {
+        markup::parse(text)
+    } else {
+        Markup {
+            text: text.to_string(),
+            runs: Vec::new(),
+        }
+    };
+    // Escape sequences contain no markup characters, so they are removed afterwards.
+    let mut message = ansi::apply(message, ansi == AnsiMode::Render);
+    let links = if detect_links {
+        link::find(&message.text)
+    } else {
+        Vec::new()
+    };
+    if !links.is_empty() {
+        message.runs = link::style(&message.runs, &links);
+    }
+    (message, links)
+}
+
+/// Replaces Windows (`\r\n`) and classic Mac OS (`\r`) line breaks with `\n`.
+fn normalize_line_breaks(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
 }
 
 fn rounded_rect(size: Size2D<i32, ()>, radius: f32) -> Path {
@@ -0,0 +1,245 @@
+//! Removes ANSI escape sequences from messages, and optionally renders their colors.
+//!
+//! Of the SGR ("Select Graphic Rendition") sequences, bold, italic and the foreground colors (the
+//! 16 basic colors, the 256-color palette and 24-bit colors) are rendered. Background colors,
+//! underlines and all other sequences are removed without effect.
+
+use std::ops::Range;
+
+use crate::ui::{
+    Rgb,
+    markup::{Markup, Style},
+    rgb,
+};
+
+const ESC: char = '\x1b';
+
+/// The 16 basic colors, followed by their bright variants.
+const BASIC_COLORS: [Rgb; 16] = [
+    rgb(0, 0, 0),
+    rgb(205, 49, 49),
+    rgb(13, 188, 121),
+    rgb(229, 229, 16),
+    rgb(36, 114, 200),
+    rgb(188, 63, 188),
+    rgb(17, 168, 205),
+    rgb(229, 229, 229),
+    rgb(102, 102, 102),
+    rgb(241, 76, 76),
+    rgb(35, 209, 139),
+    rgb(245, 245, 67),
+    rgb(59, 142, 234),
+    rgb(214, 112, 214),
+    rgb(41, 184, 219),
+    rgb(255, 255, 255),
+];
+
+/// Removes the escape sequences from `message`.
+///
+/// If `render` is set, the styles selected by SGR sequences are added to the runs of `message`.
+/// Runs that were already there keep their style, and get the color of the overlapping text.
+pub(crate) fn apply(message: Markup, render: bool) -> Markup {
+    if !message.text.contains(ESC) {
+        return message;
+    }
+
+    let src = &message.text;
+    let mut text = String::with_capacity(src.len());
+    // Byte ranges of `src` that were removed.
+    let mut removed: Vec<Range<usize>> = Vec::new();
+    let mut runs = Vec::new();
+    let (mut style, mut run_start) = (Style::default(), 0);
+    let mut pos = 0;
+    while let Some(offset) = src[pos..].find(ESC) {
+        let start = pos + offset;
+        text.push_str(&src[pos..start]);
+        let (len, params) = sequence(&src[start..]);
+        removed.push(start..start + len);
+        pos = start + len;
+
+        let Some(params) = params.filter(|_| render) else {
+            continue;
+        };
+        let new_style = sgr(style, params);
+        if new_style != style {
+            if style != Style::default() && run_start < text.len() {
+                runs.push((run_start..text.len(), style));
+            }
+            (style, run_start) = (new_style, text.len());
+        }
+    }
+    text.push_str(&src[pos..]);
+    if style != Style::default() && run_start < text.len() {
+        runs.push((run_start..text.len(), style));
+    }
+
+    // Where a byte offset of `src` ends up in `text`.
+    let map = |offset: usize| {
+        let before = removed
+            .iter()
+            .map(|range| range.end.min(offset).saturating_sub(range.start))
+            .sum::<usize>();
+        offset - before
+    };
+    let markup = message
+        .runs
+        .iter()
+        .map(|(range, style)| (map(range.start)..map(range.end), *style))
+        .filter(|(range, _)| !range.is_empty())
+        .collect::<Vec<_>>();
+
+    Markup {
+        runs: merge(&markup, &runs),
+        text,
+    }
+}
+
+/// Returns the length in bytes of the escape sequence at the start of `s`, and its parameters if
+/// it is an SGR sequence.
+///
+/// Unterminated sequences extend to the end of `s`.
+fn sequence(s: &str) -> (usize, Option<&str>) {
+    let bytes = s.as_bytes();
+    match bytes.get(1) {
+        // Control Sequence Introducer: parameter bytes, intermediate bytes, and a final byte.
+        Some(b'[') => {
+            let params = bytes[2..]
+                .iter()
+                .position(|b| !(0x30..=0x3f).contains(b))
+                .map_or(bytes.len(), |i| i + 2);
+            let end = bytes[params..]
+                .iter()
+                .position(|b| !(0x20..=0x2f).contains(b))
+                .map_or(bytes.len(), |i| i + params);
+            match bytes.get(end) {
+                Some(b'm') if end == params => (end + 1, Some(&s[2..params])),
+                Some(0x40..=0x7e) => (end + 1, None),
+                // Not a valid sequence, so only the escape character is removed.
+                Some(_) => (1, None),
+                None => (s.len(), None),
+            }
+        }
+        // Operating System Command and other strings, terminated by BEL or ST (`ESC \`).
+        Some(b']' | b'P' | b'X' | b'^' | b'_') => {
+            let end = s[2..].find(['\x07', ESC]).map_or(s.len(), |i| i + 2);
+            match bytes.get(end) {
+                Some(b'\x07') => (end + 1, None),
+                Some(_) if bytes.get(end + 1) == Some(&b'\\') => (end + 2, None),
+                Some(_) => (end, None),
+                None => (s.len(), None),
+            }
+        }
+        // Other escape sequences: intermediate bytes and a final byte.
+        Some(_) => {
+            let end = bytes[1..]
+                .iter()
+                .position(|b| !(0x20..=0x2f).contains(b))
+                .map_or(bytes.len(), |i| i + 1);
+            match bytes.get(end) {
+                Some(0x30..=0x7e) => (end + 1, None),
+                _ => (end, None),
+            }
+        }
+        None => (1, None),
+    }
+}
+
+/// Returns `style` with the SGR parameters `params` applied.
+fn sgr(mut style: Style, params: &str) -> Style {
+    let mut params = params.split([';', ':']).map(|p| {
+        if p.is_empty() {
+            Some(0)
+        } else {
+            p.parse::<u16>().ok()
+        }
+    });
+    while let Some(param) = params.next() {
+        match param {
+            Some(0) => style = Style::default(),
+            Some(1) => style.bold = true,
+            Some(3) => style.italic = true,
+            Some(22) => style.bold = false,
+            Some(23) => style.italic = false,
+            Some(n @ 30..=37) => style.color = Some(BASIC_COLORS[usize::from(n - 30)]),
+            Some(n @ 90..=97) => style.color = Some(BASIC_COLORS[usize::from(n - 90 + 8)]),
+            Some(39) => style.color = None,
+            Some(code @ (38 | 48)) => {
+                let color = match params.next().flatten() {
+                    Some(5) => params.next().flatten().map(palette),
+                    Some(2) => {
+                        let mut channel = || params.next().flatten().map(|c| c.min(255) as u8);
+                        match (channel(), channel(), channel()) {
+                            (Some(r), Some(g), Some(b)) => Some(rgb(r, g, b)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                // Background colors are not rendered.
+                if code == 38 && color.is_some() {
+                    style.color = color;
+                }
+            }
+            _ => {}
+        }
+    }
+    style
+}
+
+/// Returns the color at `index` in the 256-color palette.
+fn palette(index: u16) -> Rgb {
+    match index {
+        0..=15 => BASIC_COLORS[usize::from(index)],
+        // A 6x6x6 color cube.
+        16..=231 => {
+            let level = |i: u16| if i == 0 { 0 } else { (55 + i * 40) as u8 };
+            let i = index - 16;
+            rgb(level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        // A ramp of grays, without black and white.
+        232..=255 => {
+            let gray = (8 + (index - 232) * 10) as u8;
+            rgb(gray, gray, gray)
+        }
+        _ => BASIC_COLORS[7],
+    }
+}
+
+/// Combines the styles of the markup `runs` with those of the escape sequences.
+fn merge(
+    runs: &[(Range<usize>, Style)],
+    ansi: &[(Range<usize>, Style)],
+) -> Vec<(Range<usize>, Style)> {
+    let mut bounds = runs
+        .iter()
+        .chain(ansi)
+        .flat_map(|(range, _)| [range.start, range.end])
+        .collect::<Vec<_>>();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut merged: Vec<(Range<usize>, Style)> = Vec::new();
+    for pair in bounds.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let style_of = |runs: &[(Range<usize>, Style)]| {
+            runs.iter()
+                .find(|(run, _)| run.start <= start && end <= run.end)
+                .map_or(Style::default(), |(_, style)| *style)
+        };
+        let (markup, ansi) = (style_of(runs), style_of(ansi));
+        let style = Style {
+            bold: markup.bold || ansi.bold,
+            italic: markup.italic || ansi.italic,
+            color: ansi.color,
+            ..markup
+        };
+        if style == Style::default() {
+            continue;
+        }
+        match merged.last_mut() {
+            Some((run, s)) if run.end == start && *s == style => run.end = end,
+            _ => merged.push((start..end, style)),
+        }
+    }
+    merged
+}
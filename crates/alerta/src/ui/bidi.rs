@@ -0,0 +1,143 @@
+//! A simplified version of the Unicode Bidirectional Algorithm.
+//!
+//! This handles the common cases of right-to-left text (Hebrew, Arabic) with embedded
+//! left-to-right words and numbers, but not explicit directional formatting characters. Arabic
+//! letters are not shaped into their joined forms.
+
+/// Bidirectional character type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Class {
+    /// Strong left-to-right.
+    L,
+    /// Strong right-to-left.
+    R,
+    /// European (ASCII) digit.
+    En,
+    /// Whitespace, punctuation and everything else.
+    Neutral,
+}
+
+pub(crate) fn class(c: char) -> Class {
+    match c {
+        '0'..='9' => Class::En,
+        // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, Mandaic and their extensions.
+        '\u{0590}'..='\u{08ff}'
+        | '\u{fb1d}'..='\u{fdff}'
+        | '\u{fe70}'..='\u{feff}'
+        | '\u{10800}'..='\u{10fff}'
+        | '\u{1e800}'..='\u{1efff}' => Class::R,
+        c if c.is_alphabetic() => Class::L,
+        _ => Class::Neutral,
+    }
+}
+
+/// Returns whether the first strong character in `text` is right-to-left.
+pub(crate) fn starts_rtl(text: &str) -> bool {
+    text.chars()
+        .map(class)
+        .find(|c| matches!(c, Class::L | Class::R))
+        == Some(Class::R)
+}
+
+/// Computes the embedding level of every character of a line.
+///
+/// Odd levels are right-to-left.
+pub(crate) fn levels(classes: &[Class], rtl: bool) -> Vec<u8> {
+    let base = if rtl { Class::R } else { Class::L };
+
+    // Digits take the direction of the preceding strong character (W7), and otherwise behave like
+    // right-to-left text when resolving neutrals.
+    let mut resolved = classes.to_vec();
+    let mut last_strong = base;
+    for c in &mut resolved {
+        match *c {
+            Class::L | Class::R => last_strong = *c,
+            Class::En if last_strong == Class::L => *c = Class::L,
+            _ => {}
+        }
+    }
+    let direction = |c: Class| match c {
+        Class::L => Some(Class::L),
+        Class::R | Class::En => Some(Class::R),
+        Class::Neutral => None,
+    };
+
+    // Neutrals between characters of the same direction take that direction, and the base
+    // direction otherwise (N1, N2).
+    let mut levels = Vec::with_capacity(resolved.len());
+    for (i, &c) in resolved.iter().enumerate() {
+        let c = match c {
+            Class::Neutral => {
+                let before = resolved[..i].iter().rev().find_map(|&c| direction(c));
+                let after = resolved[i + 1..].iter().find_map(|&c| direction(c));
+                match (before.unwrap_or(base), after.unwrap_or(base)) {
+                    (a, b) if a == b => a,
+                    _ => base,
+                }
+            }
+            c => c,
+        };
+        levels.push(match (rtl, c) {
+            (false, Class::L) | (false, Class::Neutral) => 0,
+            (false, Class::R) | (true, Class::R) | (true, Class::Neutral) => 1,
+            (false, Class::En) | (true, Class::L) | (true, Class::En) => 2,
+        });
+    }
+
+    // Trailing whitespace is at the base level (L1).
+    let base_level = u8::from(rtl);
+    for (level, _) in levels
+        .iter_mut()
+        .zip(classes)
+        .rev()
+        .take_while(|(_, c)| **c == Class::Neutral)
+    {
+        *level = base_level;
+    }
+    levels
+}
+
+/// Returns the indices of the characters with the given embedding `levels` in visual order, from
+/// left to right (L2).
+pub(crate) fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order = (0..levels.len()).collect::<Vec<_>>();
+    let max = levels.iter().copied().max().unwrap_or(0);
+    let min_odd = levels
+        .iter()
+        .copied()
+        .filter(|l| l % 2 == 1)
+        .min()
+        .unwrap_or(max + 1);
+    for level in (min_odd..=max).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+    order
+}
+
+/// Returns the mirrored counterpart of `c`, for display in right-to-left text.
+pub(crate) fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        c => c,
+    }
+}
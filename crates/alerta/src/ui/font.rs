@@ -1,25 +1,178 @@
-use ab_glyph::{Font as _, Glyph, OutlinedGlyph, PxScaleFont, ScaleFont, point};
-use raqote::DrawTarget;
+use std::{cell::RefCell, cmp, collections::HashMap, fs, ops::Range, process::Command};
 
-use crate::ui::{Rgb, rgb};
+use ab_glyph::{
+    Font as _, FontArc, FontVec, Glyph, GlyphImageFormat, OutlinedGlyph, PxScaleFont, ScaleFont,
+    point,
+};
+use euclid::default::{Box2D, Point2D};
+use log::warn;
+use raqote::{DrawTarget, Image};
+
+use crate::{
+    TextDirection, WrapMode,
+    ui::{Rgb, bidi, grapheme, rgb},
+};
 
 const FALLBACK_FONT: &[u8] = include_bytes!("../../3rdparty/fonts/Cantarell-Regular.ttf");
 
 pub(crate) struct Font {
-    font: PxScaleFont<ab_glyph::FontRef<'static>>,
+    font: PxScaleFont<FontArc>,
+    /// System fonts for the characters that `font` has no glyph for, looked up as needed.
+    fallbacks: RefCell<Fallbacks>,
+}
+
+#[derive(Default)]
+struct Fallbacks {
+    /// The fallback fonts loaded so far, with the file and face index they were loaded from.
+    fonts: Vec<(String, PxScaleFont<FontArc>)>,
+    /// The face of every character looked up so far (see [`Font::face_of`]).
+    chars: HashMap<char, usize>,
 }
 
-const FONT_SIZE: f32 = 18.0;
+/// Size of the built-in font, in pixels.
+pub(crate) const DEFAULT_SIZE: f32 = 18.0;
+
+/// Horizontal shift per pixel of height above the baseline, for synthesized italics.
+const ITALIC_SKEW: f32 = 0.2;
+
+/// Default distance between tab stops, in widths of a space.
+pub(crate) const DEFAULT_TAB_WIDTH: u32 = 4;
+
+/// Emphasis applied to a piece of text.
+///
+/// Only a single face of each font is loaded, so these are synthesized: bold text is drawn
+/// several times with a horizontal offset, and italic text is slanted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Style {
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+    /// Draws the text in the link color (see [`Renderer::with_link_color`]).
+    pub(crate) link: bool,
+    /// Draws the text in this color instead of the regular one. The link color takes precedence.
+    pub(crate) color: Option<Rgb>,
+}
 
 impl Font {
-    /// Loads the font to use for the dialog contents.
-    pub(crate) fn load() -> Self {
-        // TODO: invoke `fc-match` to find a system font
-        let inner = ab_glyph::FontRef::try_from_slice(FALLBACK_FONT).unwrap();
+    /// Loads the font to use for the dialog contents, at a size of `size` pixels.
+    ///
+    /// If `family` is given, the system font of that family is used. If it can't be found, the
+    /// built-in font is used instead.
+    pub(crate) fn load(family: Option<&str>, size: f32) -> Self {
+        let system = family.and_then(|family| {
+            let font = find_system_font(family);
+            if font.is_none() {
+                warn!("font family '{family}' not found, using the built-in font");
+            }
+            font
+        });
+        let inner = system.unwrap_or_else(|| {
+            FontArc::new(ab_glyph::FontRef::try_from_slice(FALLBACK_FONT).unwrap())
+        });
 
         Self {
-            font: inner.into_scaled(FONT_SIZE),
+            font: inner.into_scaled(size),
+            fallbacks: RefCell::default(),
+        }
+    }
+
+    /// Loads the system monospace font, at a size of `size` pixels.
+    ///
+    /// Without one, the built-in font is used, which isn't monospace, so a warning is logged.
+    pub(crate) fn load_monospace(size: f32) -> Self {
+        let font = Self::load(Some("monospace"), size);
+        if !font.is_monospace() {
+            warn!("no monospace font found, columns in the message won't line up");
         }
+        font
+    }
+
+    /// Returns whether all characters of the font are equally wide, judging by a few of them.
+    pub(crate) fn is_monospace(&self) -> bool {
+        let advance = |c| self.font.h_advance(self.font.glyph_id(c));
+        ['i', 'M', '0', ' ']
+            .iter()
+            .all(|&c| advance(c) == advance('m'))
+    }
+
+    /// Returns the index of the face to draw `c` with.
+    ///
+    /// Face 0 is the primary font. Characters it has no glyph for (like emoji) are looked up in
+    /// the system fonts with `fc-match`, and drawn with the primary font's replacement glyph if no
+    /// font has them.
+    pub(crate) fn face_of(&self, c: char) -> usize {
+        if self.font.glyph_id(c).0 != 0 || c.is_whitespace() || c.is_control() || c == ZWSP {
+            return 0;
+        }
+        let mut fallbacks = self.fallbacks.borrow_mut();
+        if let Some(&face) = fallbacks.chars.get(&c) {
+            return face;
+        }
+
+        let face = find_fallback_font(c)
+            .and_then(|key| {
+                if let Some(i) = fallbacks.fonts.iter().position(|(k, _)| *k == key) {
+                    return Some(i + 1);
+                }
+                let (path, index) = key.rsplit_once('\n')?;
+                let data = fs::read(path).ok()?;
+                let font =
+                    FontVec::try_from_vec_and_index(data, index.parse().unwrap_or(0)).ok()?;
+                fallbacks
+                    .fonts
+                    .push((key, FontArc::new(font).into_scaled(self.font.scale)));
+                Some(fallbacks.fonts.len())
+            })
+            .filter(|&face| fallbacks.fonts[face - 1].1.glyph_id(c).0 != 0)
+            .unwrap_or(0);
+        fallbacks.chars.insert(c, face);
+        face
+    }
+
+    /// Looks up the faces needed for all characters of `text`, and returns the list of faces to
+    /// index with [`Font::face_of`].
+    pub(crate) fn faces_for(&self, text: &str) -> Vec<PxScaleFont<FontArc>> {
+        for c in text.chars() {
+            self.face_of(c);
+        }
+        let fallbacks = self.fallbacks.borrow();
+        let mut faces = vec![self.font.clone()];
+        faces.extend(fallbacks.fonts.iter().map(|(_, font)| font.clone()));
+        faces
+    }
+
+    /// Returns the height of a line of text, from the highest ascender to the lowest descender.
+    pub(crate) fn line_height(&self) -> f32 {
+        self.font.height()
+    }
+
+    /// Returns the distance from the top of a line to its baseline.
+    pub(crate) fn ascent(&self) -> f32 {
+        self.font.ascent()
+    }
+
+    /// Returns how many pixels wider bold glyphs are drawn.
+    fn embolden(&self) -> f32 {
+        f32::max(1.0, (self.font.height() / 24.0).round())
+    }
+
+    /// Returns the horizontal advance of a single-line `text`, including kerning.
+    pub(crate) fn advance(&self, text: &str) -> f32 {
+        let faces = self.faces_for(text);
+        let mut x = 0.0;
+        let mut last = None;
+        for c in text.chars() {
+            let face = self.face_of(c);
+            let font = &faces[face];
+            let id = font.glyph_id(c);
+            if let Some((last_face, last)) = last
+                && last_face == face
+            {
+                x += font.kern(last, id);
+            }
+            x += font.h_advance(id);
+            last = Some((face, id));
+        }
+        x
     }
 
     /// Returns a [`Renderer`] for rendering `text`.
@@ -30,16 +183,140 @@ impl Font {
             font: self,
             text,
             color: rgb(255, 255, 255),
+            link_color: None,
             max_width: f32::MAX,
+            max_word_width: f32::MAX,
+            line_box: false,
+            runs: &[],
+            direction: TextDirection::Auto,
+            wrap: WrapMode::Word,
+            max_lines: usize::MAX,
+            tab_width: DEFAULT_TAB_WIDTH,
         }
     }
 }
 
+/// Generic font families, which fontconfig resolves to a concrete family.
+const GENERIC_FAMILIES: &[&str] = &["sans-serif", "sans", "serif", "monospace", "mono"];
+
+/// Looks up a font of the given `family` with `fc-match`, and loads it.
+///
+/// `family` may also be a fontconfig pattern like `Noto Sans:bold`, or a generic family like
+/// `monospace`.
+fn find_system_font(family: &str) -> Option<FontArc> {
+    let output = Command::new("fc-match")
+        .args(["--format=%{family}\n%{file}\n%{index}", family])
+        .output()
+        .inspect_err(|e| warn!("failed to run fc-match: {e}"))
+        .ok()
+        .filter(|output| output.status.success())?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    let mut lines = output.lines();
+    let (families, path, index) = (lines.next()?, lines.next()?, lines.next()?);
+
+    // fc-match always returns *some* font, so check that we got the family that was asked for.
+    let wanted = family.split(':').next().unwrap_or_default().trim();
+    let generic = GENERIC_FAMILIES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(wanted));
+    if !generic
+        && !families
+            .split(',')
+            .any(|name| name.trim().eq_ignore_ascii_case(wanted))
+    {
+        return None;
+    }
+
+    let data = fs::read(path).ok()?;
+    let font = FontVec::try_from_vec_and_index(data, index.parse().unwrap_or(0)).ok()?;
+    Some(FontArc::new(font))
+}
+
+/// Asks fontconfig for a system font containing `c`.
+///
+/// Returns the path of the font file and the index of the face in it, separated by a newline.
+fn find_fallback_font(c: char) -> Option<String> {
+    let output = Command::new("fc-match")
+        .args([
+            "--format=%{file}\n%{index}".to_string(),
+            format!(":charset={:x}", u32::from(c)),
+        ])
+        .output()
+        .inspect_err(|e| warn!("failed to run fc-match: {e}"))
+        .ok()
+        .filter(|output| output.status.success())?;
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Returns the color bitmap of `glyph` and the area to draw it into, if `font` has one.
+///
+/// Emoji fonts like Noto Color Emoji contain PNG images instead of (or in addition to) outlines.
+fn color_glyph(font: &PxScaleFont<FontArc>, glyph: &Glyph) -> Option<(DrawTarget, Box2D<f32>)> {
+    let ppem = font.font().units_per_em()? * font.v_scale_factor();
+    let image = font
+        .font()
+        .glyph_raster_image2(glyph.id, ppem.round() as u16)
+        .filter(|image| matches!(image.format, GlyphImageFormat::Png))?;
+    let bitmap = crate::image::decode(image.data).ok()?;
+
+    // The origin is the bottom-left corner of the image, relative to the pen position.
+    let scale = ppem / f32::from(image.pixels_per_em);
+    let min = Point2D::new(
+        glyph.position.x + image.origin.x * scale,
+        glyph.position.y - (image.origin.y + bitmap.height() as f32) * scale,
+    );
+    let size = euclid::size2(bitmap.width() as f32, bitmap.height() as f32) * scale;
+    Some((bitmap, Box2D::from_origin_and_size(min, size).round_out()))
+}
+
+/// Returns the pixel bounds of `glyph` when it is drawn with `style`.
+fn styled_bounds(glyph: &OutlinedGlyph, style: Style, embolden: f32) -> ab_glyph::Rect {
+    let mut bounds = glyph.px_bounds();
+    if style.bold {
+        bounds.max.x += embolden;
+    }
+    if style.italic {
+        let baseline = glyph.glyph().position.y;
+        bounds.max.x += ((baseline - bounds.min.y) * ITALIC_SKEW).round().max(0.0);
+        bounds.min.x += ((baseline - bounds.max.y) * ITALIC_SKEW).round().min(0.0);
+    }
+    bounds
+}
+
 pub(crate) struct Renderer<'a> {
     font: &'a Font,
     text: &'a str,
     color: Rgb,
+    link_color: Option<Rgb>,
     max_width: f32,
+    max_word_width: f32,
+    line_box: bool,
+    runs: &'a [(Range<usize>, Style)],
+    direction: TextDirection,
+    wrap: WrapMode,
+    max_lines: usize,
+    tab_width: u32,
+}
+
+/// How a visible character is drawn.
+enum Drawn {
+    Outline(OutlinedGlyph, Style),
+    /// A color bitmap glyph, scaled to fit the given area.
+    Image(DrawTarget, Box2D<f32>),
+}
+
+/// A character of the text, placed on its line.
+struct Placed {
+    /// Byte range of the character in the rendered text.
+    bytes: Range<usize>,
+    /// The face the glyph is from (see [`Font::face_of`]).
+    face: usize,
+    glyph: Glyph,
+    style: Style,
+    /// Horizontal distance to the next character, including the extra width of bold text.
+    advance: f32,
+    /// Whether the character is drawn (whitespace isn't).
+    visible: bool,
 }
 
 impl<'a> Renderer<'a> {
@@ -47,16 +324,101 @@ impl<'a> Renderer<'a> {
         Self { max_width, ..self }
     }
 
+    /// Breaks words that are wider than `max_word_width` between characters, in
+    /// [`WrapMode::Word`].
+    ///
+    /// By default, words that don't fit on a line by themselves exceed the max width.
+    pub(crate) fn with_max_word_width(self, max_word_width: f32) -> Self {
+        Self {
+            max_word_width,
+            ..self
+        }
+    }
+
     pub(crate) fn with_color(self, color: Rgb) -> Self {
         Self { color, ..self }
     }
 
+    /// Sets the color of text with the [`Style::link`] style, which otherwise uses the regular
+    /// color.
+    pub(crate) fn with_link_color(self, color: Rgb) -> Self {
+        Self {
+            link_color: Some(color),
+            ..self
+        }
+    }
+
+    /// Makes the rendered image cover the whole line box, instead of only the inked area.
+    ///
+    /// The resulting image starts at the pen origin of the first line and always has the full line
+    /// height, so that text in it doesn't shift around depending on which characters it contains.
+    pub(crate) fn with_line_box(self) -> Self {
+        Self {
+            line_box: true,
+            ..self
+        }
+    }
+
+    /// Applies `runs` of styling to the text.
+    ///
+    /// The ranges are byte ranges in the text, and must be sorted and non-overlapping. Text that
+    /// isn't covered by any run uses the default [`Style`].
+    pub(crate) fn with_runs(self, runs: &'a [(Range<usize>, Style)]) -> Self {
+        Self { runs, ..self }
+    }
+
+    /// Sets the base direction of the text.
+    ///
+    /// Right-to-left text is aligned to the right edge of the rendered image.
+    pub(crate) fn with_direction(self, direction: TextDirection) -> Self {
+        Self { direction, ..self }
+    }
+
+    /// Sets where lines may be broken when they exceed the max width.
+    ///
+    /// With [`WrapMode::None`], the max width is ignored.
+    pub(crate) fn with_wrap(self, wrap: WrapMode) -> Self {
+        Self { wrap, ..self }
+    }
+
+    /// Limits the text to `max_lines` visual lines (at least one).
+    ///
+    /// If the text has more lines, the rest is dropped and the last line ends with an ellipsis.
+    pub(crate) fn with_max_lines(self, max_lines: usize) -> Self {
+        Self {
+            max_lines: max_lines.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the distance between tab stops, in widths of a space (at least one).
+    ///
+    /// Tab characters advance to the next tab stop, counted from the start of the line.
+    pub(crate) fn with_tab_width(self, tab_width: u32) -> Self {
+        Self {
+            tab_width: tab_width.max(1),
+            ..self
+        }
+    }
+
     pub(crate) fn finish(self) -> DrawTarget {
-        let glyphs = self.layout();
+        self.finish_with_layout().0
+    }
+
+    /// Renders the text, and also returns the positions of its characters in the rendered image.
+    pub(crate) fn finish_with_layout(self) -> (DrawTarget, TextLayout) {
+        let (glyphs, mut chars, last_baseline, _) = self.layout();
 
-        let bounds = glyphs
+        let embolden = self.font.embolden();
+        let mut bounds = glyphs
             .iter()
-            .map(|g| g.px_bounds())
+            .map(|drawn| match drawn {
+                Drawn::Outline(g, style) => styled_bounds(g, *style, embolden),
+                Drawn::Image(_, rect) => ab_glyph::Rect {
+                    min: point(rect.min.x, rect.min.y),
+                    max: point(rect.max.x, rect.max.y),
+                },
+            })
             .reduce(|mut sum, next| {
                 sum.min.x = f32::min(sum.min.x, next.min.x);
                 sum.min.y = f32::min(sum.min.y, next.min.y);
@@ -67,78 +429,210 @@ impl<'a> Renderer<'a> {
                 sum
             })
             .unwrap_or_default();
+        if self.line_box {
+            let font = &self.font.font;
+            bounds.min.x = f32::min(bounds.min.x, 0.0);
+            bounds.min.y = f32::min(bounds.min.y, -font.ascent());
+            bounds.max.y = f32::max(bounds.max.y, last_baseline - font.descent());
+        }
 
         let width = bounds.width() as u16;
         let height = bounds.height() as u16;
         let mut target = DrawTarget::new(width.into(), height.into());
         let pixels = target.get_data_mut();
 
-        for g in glyphs {
+        let mut images = Vec::new();
+        for drawn in glyphs {
+            let (g, style) = match drawn {
+                Drawn::Outline(g, style) => (g, style),
+                Drawn::Image(image, rect) => {
+                    images.push((image, rect));
+                    continue;
+                }
+            };
             let glyph_bounds = g.px_bounds();
-            let offset = glyph_bounds.min - bounds.min;
-            let (off_x, off_y) = (offset.x as u32, offset.y as u32);
-            g.draw(|x, y, c| {
-                let idx = (off_y + y) * width as u32 + off_x + x;
-                let Some(pix) = pixels.get_mut(idx as usize) else {
-                    return;
-                };
+            let baseline = g.glyph().position.y;
+            let passes = if style.bold { embolden as i32 + 1 } else { 1 };
+            for pass in 0..passes {
+                let offset = glyph_bounds.min - bounds.min;
+                let (off_x, off_y) = (offset.x as i32 + pass, offset.y as i32);
+                g.draw(|x, y, c| {
+                    let mut x = off_x + x as i32;
+                    if style.italic {
+                        let height = baseline - (glyph_bounds.min.y + y as f32);
+                        x += (height * ITALIC_SKEW).round() as i32;
+                    }
+                    let idx = (off_y + y as i32) * i32::from(width) + x;
+                    let Some(pix) = usize::try_from(idx).ok().and_then(|i| pixels.get_mut(i))
+                    else {
+                        return;
+                    };
 
-                // `DrawTarget` expects pre-multiplied alpha.
-                let Rgb(r, g, b) = self.color;
-                let a = (c * 255.0).round() as u32;
-                let r = r as u32 * a / 255;
-                let g = g as u32 * a / 255;
-                let b = b as u32 * a / 255;
-                *pix = (a << 24) | (r << 16) | (g << 8) | b;
-            });
+                    // `DrawTarget` expects pre-multiplied alpha.
+                    let Rgb(r, g, b) = self
+                        .link_color
+                        .filter(|_| style.link)
+                        .or(style.color)
+                        .unwrap_or(self.color);
+                    let a = (c * 255.0).round() as u32;
+                    // The extra passes of bold text must not erase what the previous ones drew.
+                    if pass > 0 && a <= *pix >> 24 {
+                        return;
+                    }
+                    let r = r as u32 * a / 255;
+                    let g = g as u32 * a / 255;
+                    let b = b as u32 * a / 255;
+                    *pix = (a << 24) | (r << 16) | (g << 8) | b;
+                });
+            }
+        }
+        for (image, rect) in images {
+            target.draw_image_with_size_at(
+                rect.width(),
+                rect.height(),
+                rect.min.x - bounds.min.x,
+                rect.min.y - bounds.min.y,
+                &Image {
+                    width: image.width(),
+                    height: image.height(),
+                    data: image.get_data(),
+                },
+                &Default::default(),
+            );
+        }
+
+        for c in &mut chars {
+            c.rect = c.rect.translate(euclid::vec2(-bounds.min.x, -bounds.min.y));
         }
 
-        target
+        (target, TextLayout { chars })
+    }
+
+    /// Returns the lines the text is broken into, without rendering it.
+    ///
+    /// Whitespace at the end of the lines is removed, including the whitespace that soft-wrapped
+    /// lines were broken at.
+    pub(crate) fn lines(self) -> Vec<&'a str> {
+        let (_, _, _, mut line_offsets) = self.layout();
+        line_offsets.truncate(self.max_lines);
+        line_offsets
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = line_offsets.get(i + 1).copied().unwrap_or(self.text.len());
+                self.text[start..end].trim_end()
+            })
+            .collect()
     }
 
     /// Calculates the text layout and computes glyph outlines.
     ///
-    /// This will respect hard line breaks (`\n`) and attempt to perform soft wrapping when a line
-    /// exceeds the configured max width.
+    /// This will respect hard line breaks (`\n`) and attempt to perform soft wrapping (as
+    /// configured by [`Renderer::with_wrap`]) when a line exceeds the configured max width.
     /// Soft-wrapping may fail if the rendered text contains no (or insufficient) permissible line
     /// break opportunities, in which case the text will exceed the intended width.
-    fn layout(&self) -> Vec<OutlinedGlyph> {
-        let mut glyphs: Vec<Glyph> = Vec::new();
+    ///
+    /// Returns the glyphs, the boxes of all characters (including whitespace), the y coordinate of
+    /// the last line's baseline (the first line's baseline is at 0), and the byte offsets at which
+    /// the visual lines begin.
+    fn layout(&self) -> (Vec<Drawn>, Vec<CharBox>, f32, Vec<usize>) {
+        if self.max_lines != usize::MAX {
+            // Make sure that `faces` includes the face of the ellipsis.
+            self.font.face_of(ELLIPSIS);
+        }
+        let faces = self.font.faces_for(self.text);
+        // All characters, along with their glyph. Whitespace is included so that it can be
+        // selected, but is not drawn.
+        let mut placed: Vec<Placed> = Vec::new();
+        let embolden = self.font.embolden();
+        let mut runs = self.runs.iter().peekable();
+        // Indices in `placed` at which the visual lines begin, and their byte offsets in the text.
+        let mut line_starts = Vec::new();
+        let mut line_offsets = Vec::new();
 
         let mut y = 0.0;
+        let mut last_baseline = 0.0;
         for line in self.text.lines() {
+            let line_start = line.as_ptr() as usize - self.text.as_ptr() as usize;
+            line_starts.push(placed.len());
+            line_offsets.push(line_start);
+            last_baseline = y;
             let mut x = 0.0;
 
             let mut last_softbreak: Option<usize> = None;
 
-            // ID of the last glyph we placed; used to apply kerning.
+            // Face and ID of the last glyph we placed; used to apply kerning.
             let mut last = None;
 
-            for c in line.chars() {
-                let mut glyph = self.font.font.scaled_glyph(c);
-                if let Some(last) = last {
-                    x += self.font.font.kern(last, glyph.id);
+            // Lines are only broken between characters at the start of a grapheme cluster, so that
+            // combining marks and emoji sequences aren't split up.
+            let mut cluster_starts = grapheme::cluster_starts(line).peekable();
+            // Index in `placed` of the first character of the current grapheme cluster.
+            let mut cluster_start = placed.len();
+
+            for (i, c) in line.char_indices() {
+                if cluster_starts.next_if_eq(&i).is_some() {
+                    cluster_start = placed.len();
+                }
+                let bytes = line_start + i..line_start + i + c.len_utf8();
+                while runs.next_if(|(run, _)| run.end <= bytes.start).is_some() {}
+                let style = match runs.peek() {
+                    Some((run, style)) if run.start <= bytes.start => *style,
+                    _ => Style::default(),
+                };
+                let face = self.font.face_of(c);
+                let font = &faces[face];
+                let mut glyph = font.scaled_glyph(c);
+                if let Some((last_face, last)) = last
+                    && last_face == face
+                {
+                    x += font.kern(last, glyph.id);
                 }
                 glyph.position = point(x, y);
-                last = Some(glyph.id);
+                last = Some((face, glyph.id));
 
-                x += self.font.font.h_advance(glyph.id);
+                let mut advance = font.h_advance(glyph.id);
+                if c == '\t' {
+                    advance = self.tab_stop(x) - x;
+                } else if style.bold {
+                    advance += embolden;
+                }
+                x += advance;
 
-                if c == ' ' || c == ZWSP {
-                    last_softbreak = Some(glyphs.len());
+                let visible = !(c == ' ' || c == '\t' || c == ZWSP);
+                placed.push(Placed {
+                    bytes,
+                    face,
+                    glyph,
+                    style,
+                    advance,
+                    visible,
+                });
+                if !visible {
+                    last_softbreak = Some(placed.len());
                 } else {
-                    glyphs.push(glyph);
-
+                    let line_start = line_starts.last().copied().unwrap_or(0);
+                    // Moves the grapheme cluster that doesn't fit to the next line.
+                    let char_break = Some(cluster_start).filter(|&i| i > line_start);
+                    let break_at = match self.wrap {
+                        WrapMode::Word if x > self.max_word_width => last_softbreak.or(char_break),
+                        WrapMode::Word => last_softbreak,
+                        WrapMode::Char => char_break,
+                        WrapMode::None => None,
+                    };
                     if x > self.max_width
-                        && let Some(i) = last_softbreak
+                        && let Some(i) = break_at
                     {
                         // Out of space on this line. Perform a soft line break.
                         // Glyph at index `i` and later will be moved to the next line.
                         y += self.font.font.height() + self.font.font.line_gap();
-                        let x_diff = glyphs.get(i).map(|g| g.position.x).unwrap_or(0.0);
-                        for glyph in &mut glyphs[i..] {
-                            glyph.position.x -= x_diff;
-                            glyph.position.y = y;
+                        last_baseline = y;
+                        line_starts.push(i);
+                        line_offsets.push(placed[i].bytes.start);
+                        let x_diff = placed.get(i).map(|p| p.glyph.position.x).unwrap_or(0.0);
+                        for p in &mut placed[i..] {
+                            p.glyph.position.x -= x_diff;
+                            p.glyph.position.y = y;
                         }
                         x -= x_diff;
 
@@ -148,11 +642,252 @@ impl<'a> Renderer<'a> {
             }
             y += self.font.font.height() + self.font.font.line_gap();
         }
-        glyphs
+        if line_starts.len() > self.max_lines {
+            self.truncate(&mut placed, &mut line_starts, &faces);
+            last_baseline =
+                (self.max_lines - 1) as f32 * (self.font.font.height() + self.font.font.line_gap());
+        }
+        self.reorder(&mut placed, &line_starts, &faces);
+
+        let font = &self.font.font;
+        let chars = placed
+            .iter()
+            .map(|p| {
+                let g = &p.glyph;
+                CharBox {
+                    bytes: p.bytes.clone(),
+                    rect: Box2D::new(
+                        Point2D::new(g.position.x, g.position.y - font.ascent()),
+                        Point2D::new(g.position.x + p.advance, g.position.y - font.descent()),
+                    ),
+                }
+            })
+            .collect();
+        let glyphs = placed
             .into_iter()
-            .filter_map(|g| self.font.font.outline_glyph(g))
-            .collect()
+            .filter(|p| p.visible)
+            .filter_map(|p| {
+                let font = &faces[p.face];
+                if let Some((image, rect)) = color_glyph(font, &p.glyph) {
+                    return Some(Drawn::Image(image, rect));
+                }
+                Some(Drawn::Outline(font.outline_glyph(p.glyph)?, p.style))
+            })
+            .collect();
+        (glyphs, chars, last_baseline, line_offsets)
+    }
+}
+
+impl Renderer<'_> {
+    /// Returns the position of the first tab stop after `x`.
+    fn tab_stop(&self, x: f32) -> f32 {
+        let font = &self.font.font;
+        let width = font.h_advance(font.glyph_id(' ')) * self.tab_width as f32;
+        ((x / width).floor() + 1.0) * width
+    }
+
+    /// Drops the visual lines after the first `max_lines`, and ends the last remaining line with
+    /// an ellipsis.
+    ///
+    /// Characters are removed from the end of that line until the ellipsis fits within the max
+    /// width.
+    fn truncate(
+        &self,
+        placed: &mut Vec<Placed>,
+        line_starts: &mut Vec<usize>,
+        faces: &[PxScaleFont<FontArc>],
+    ) {
+        let mut cut = placed
+            .get(line_starts[self.max_lines])
+            .map_or(self.text.len(), |p| p.bytes.start);
+        placed.truncate(line_starts[self.max_lines]);
+        line_starts.truncate(self.max_lines);
+        let start = line_starts[self.max_lines - 1];
+
+        let end_of = |p: &Placed| p.glyph.position.x + p.advance;
+        let face = self.font.face_of(ELLIPSIS);
+        let mut glyph = faces[face].scaled_glyph(ELLIPSIS);
+        let width = faces[face].h_advance(glyph.id);
+        let max_width = match self.wrap {
+            WrapMode::None => f32::MAX,
+            WrapMode::Word | WrapMode::Char => self.max_width,
+        };
+        while let Some(last) = placed[start..].last() {
+            if last.visible && end_of(last) + width <= max_width {
+                break;
+            }
+            cut = last.bytes.start;
+            placed.pop();
+        }
+
+        let y = (self.max_lines - 1) as f32 * (self.font.font.height() + self.font.font.line_gap());
+        glyph.position = point(placed[start..].last().map_or(0.0, end_of), y);
+        placed.push(Placed {
+            // The ellipsis isn't part of the text, so it covers no bytes.
+            bytes: cut..cut,
+            face,
+            glyph,
+            style: Style::default(),
+            advance: width,
+            visible: true,
+        });
+    }
+
+    /// Reorders the characters of every visual line for display, if they contain right-to-left
+    /// text.
+    fn reorder(
+        &self,
+        placed: &mut [Placed],
+        line_starts: &[usize],
+        faces: &[PxScaleFont<FontArc>],
+    ) {
+        let rtl = match self.direction {
+            TextDirection::Auto => bidi::starts_rtl(self.text),
+            TextDirection::Ltr => false,
+            TextDirection::Rtl => true,
+        };
+        let embolden = self.font.embolden();
+
+        let mut line_ends = Vec::with_capacity(line_starts.len());
+        for (i, &start) in line_starts.iter().enumerate() {
+            let end = line_starts.get(i + 1).copied().unwrap_or(placed.len());
+            let line = &mut placed[start..end];
+            let chars = line
+                .iter()
+                .map(|p| {
+                    self.text[p.bytes.clone()]
+                        .chars()
+                        .next()
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>();
+            let classes = chars.iter().map(|&c| bidi::class(c)).collect::<Vec<_>>();
+            if rtl || classes.contains(&bidi::Class::R) {
+                // Lay out the line again in visual order.
+                let levels = bidi::levels(&classes, rtl);
+                let mut x = 0.0;
+                for i in bidi::visual_order(&levels) {
+                    let p = &mut line[i];
+                    let font = &faces[p.face];
+                    if levels[i] % 2 == 1 && bidi::mirror(chars[i]) != chars[i] {
+                        p.glyph.id = font.glyph_id(bidi::mirror(chars[i]));
+                        p.advance = font.h_advance(p.glyph.id);
+                        if p.style.bold {
+                            p.advance += embolden;
+                        }
+                    } else if chars[i] == '\t' {
+                        p.advance = self.tab_stop(x) - x;
+                    }
+                    p.glyph.position.x = x;
+                    x += p.advance;
+                }
+            }
+
+            // Where the ink of the line ends.
+            let line_end = line
+                .iter()
+                .filter(|p| p.visible)
+                .map(|p| p.glyph.position.x + faces[p.face].h_advance(p.glyph.id))
+                .fold(0.0, f32::max);
+            line_ends.push(line_end);
+        }
+
+        if rtl {
+            // Align all lines to the right.
+            let right = line_ends.iter().copied().fold(0.0, f32::max);
+            for (i, &start) in line_starts.iter().enumerate() {
+                let end = line_starts.get(i + 1).copied().unwrap_or(placed.len());
+                for p in &mut placed[start..end] {
+                    p.glyph.position.x += right - line_ends[i];
+                }
+            }
+        }
+    }
+}
+
+/// The positions of the characters of some rendered text.
+///
+/// Positions between characters (like the ends of a selection) are identified by the index of the
+/// character following them, from 0 to the number of characters (excluding line breaks).
+#[derive(Default)]
+pub(crate) struct TextLayout {
+    chars: Vec<CharBox>,
+}
+
+struct CharBox {
+    /// Byte range of the character in the rendered text.
+    bytes: Range<usize>,
+    /// Area covered by the character, in the coordinate space of the rendered image.
+    rect: Box2D<f32>,
+}
+
+impl TextLayout {
+    /// Returns the position between characters that is closest to `point`.
+    pub(crate) fn position_at(&self, point: Point2D<f32>) -> usize {
+        // Find the line containing `point`, or the last line if it is below the text.
+        let Some(line_top) = self
+            .chars
+            .iter()
+            .find(|c| point.y < c.rect.max.y)
+            .or(self.chars.last())
+            .map(|c| c.rect.min.y)
+        else {
+            return 0;
+        };
+        let line = self
+            .chars
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.rect.min.y == line_top);
+        let mut pos = 0;
+        for (i, c) in line {
+            if point.x < c.rect.center().x {
+                return i;
+            }
+            pos = i + 1;
+        }
+        pos
+    }
+
+    /// Returns the byte offset in the rendered text of the character at `point`, if any.
+    pub(crate) fn byte_at(&self, point: Point2D<f32>) -> Option<usize> {
+        let c = self.chars.iter().find(|c| c.rect.contains(point))?;
+        Some(c.bytes.start)
+    }
+
+    /// Returns the range of characters that lie within the byte range `bytes` of the rendered
+    /// text.
+    pub(crate) fn chars_in(&self, bytes: Range<usize>) -> Range<usize> {
+        let start = self.chars.partition_point(|c| c.bytes.start < bytes.start);
+        let end = self.chars.partition_point(|c| c.bytes.end <= bytes.end);
+        start..cmp::max(start, end)
+    }
+
+    /// Returns the number of characters in the layout, which is the last position.
+    pub(crate) fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Returns the byte range in the rendered text covered by the characters in `range`.
+    pub(crate) fn byte_range(&self, range: Range<usize>) -> Range<usize> {
+        if range.is_empty() {
+            return 0..0;
+        }
+        self.chars[range.start].bytes.start..self.chars[range.end - 1].bytes.end
+    }
+
+    /// Returns the areas covered by the characters in `range`, merged into one box per line.
+    pub(crate) fn boxes(&self, range: Range<usize>) -> Vec<Box2D<f32>> {
+        let mut boxes: Vec<Box2D<f32>> = Vec::new();
+        for c in &self.chars[range] {
+            match boxes.last_mut() {
+                Some(last) if last.min.y == c.rect.min.y => *last = last.union(&c.rect),
+                _ => boxes.push(c.rect),
+            }
+        }
+        boxes
     }
 }
 
 const ZWSP: char = '\u{200b}';
+const ELLIPSIS: char = '…';
@@ -0,0 +1,11 @@
+//! Extended grapheme cluster boundaries (UAX #29), for moving the caret and wrapping text
+//! without splitting characters that are displayed as one.
+
+use unicode_segmentation::UnicodeSegmentation as _;
+
+/// Returns the byte offsets at which the grapheme clusters of `text` start, in ascending order.
+///
+/// Lines are expected to be segmented separately, so line breaks get no special treatment.
+pub(crate) fn cluster_starts(text: &str) -> impl Iterator<Item = usize> + '_ {
+    text.grapheme_indices(true).map(|(i, _)| i)
+}
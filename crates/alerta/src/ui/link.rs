@@ -0,0 +1,92 @@
+//! Finds the URLs in dialog messages, so that they can be opened by clicking them.
+//!
+//! Only `http://` and `https://` URLs are detected. A URL ends at whitespace or an angle bracket,
+//! and punctuation at its end is assumed to belong to the surrounding sentence.
+
+use std::ops::Range;
+
+use crate::ui::font::Style;
+
+/// Characters that aren't part of a URL when they are its last character.
+const TRAILING: &[char] = &['.', ',', ':', ';', '!', '?', '\'', '"'];
+
+/// Returns the byte ranges of the URLs in `text`.
+pub(crate) fn find(text: &str) -> Vec<Range<usize>> {
+    let mut links = Vec::new();
+    let mut offset = 0;
+    while let Some(i) = text[offset..].find("http") {
+        let start = offset + i;
+        let rest = &text[start..];
+        let Some(scheme) = ["https://", "http://"]
+            .into_iter()
+            .find(|scheme| rest.starts_with(scheme))
+        else {
+            offset = start + "http".len();
+            continue;
+        };
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>'))
+            .unwrap_or(rest.len());
+        offset = start + len;
+        // A scheme in the middle of a word doesn't start a URL.
+        if text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+        {
+            continue;
+        }
+
+        let mut url = &rest[..len];
+        loop {
+            if let Some(trimmed) = url.strip_suffix(TRAILING) {
+                url = trimmed;
+            } else if url.ends_with(')') && url.matches('(').count() < url.matches(')').count() {
+                // The URL is in parentheses.
+                url = &url[..url.len() - 1];
+            } else {
+                break;
+            }
+        }
+        if url.len() > scheme.len() {
+            links.push(start..start + url.len());
+        }
+    }
+    links
+}
+
+/// Adds the link style to the parts of the styled `runs` that are covered by `links`.
+///
+/// Both lists must be sorted and non-overlapping, and so is the result.
+pub(crate) fn style(
+    runs: &[(Range<usize>, Style)],
+    links: &[Range<usize>],
+) -> Vec<(Range<usize>, Style)> {
+    let mut bounds = runs
+        .iter()
+        .map(|(run, _)| run)
+        .chain(links)
+        .flat_map(|range| [range.start, range.end])
+        .collect::<Vec<_>>();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut styled: Vec<(Range<usize>, Style)> = Vec::new();
+    for pair in bounds.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let covers = |range: &Range<usize>| range.start <= start && end <= range.end;
+        let mut style = runs
+            .iter()
+            .find(|(run, _)| covers(run))
+            .map_or(Style::default(), |(_, style)| *style);
+        style.link = links.iter().any(covers);
+        if style == Style::default() {
+            continue;
+        }
+        match styled.last_mut() {
+            Some((run, s)) if run.end == start && *s == style => run.end = end,
+            _ => styled.push((start..end, style)),
+        }
+    }
+    styled
+}
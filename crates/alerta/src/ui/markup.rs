@@ -0,0 +1,97 @@
+//! Parses the minimal inline markup supported in dialog messages.
+//!
+//! `**text**` is bold and `*text*` is italic. A backslash escapes an asterisk or another
+//! backslash, and markers without a matching closing marker are displayed as-is.
+
+use std::ops::Range;
+
+pub(crate) use crate::ui::font::Style;
+
+/// Message text with the markup removed, and the styles to apply to it.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Markup {
+    pub(crate) text: String,
+    /// Sorted, non-overlapping byte ranges of `text` and their styles.
+    pub(crate) runs: Vec<(Range<usize>, Style)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    Bold,
+    Italic,
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Marker(Marker),
+}
+
+pub(crate) fn parse(src: &str) -> Markup {
+    let mut tokens = Vec::new();
+    let mut rest = src;
+    while !rest.is_empty() {
+        let end = rest.find(['*', '\\']).unwrap_or(rest.len());
+        if end > 0 {
+            tokens.push(Token::Text(&rest[..end]));
+            rest = &rest[end..];
+        } else if let Some(r) = rest.strip_prefix("**") {
+            tokens.push(Token::Marker(Marker::Bold));
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('*') {
+            tokens.push(Token::Marker(Marker::Italic));
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("\\*").or(rest.strip_prefix("\\\\")) {
+            tokens.push(Token::Text(&rest[1..2]));
+            rest = r;
+        } else {
+            // A backslash that doesn't escape anything.
+            tokens.push(Token::Text(&rest[..1]));
+            rest = &rest[1..];
+        }
+    }
+
+    // A marker without a partner is literal text; that's the last one if the count is odd.
+    let literal = [Marker::Bold, Marker::Italic].map(|kind| {
+        let positions = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| matches!(t, Token::Marker(m) if *m == kind))
+            .map(|(i, _)| i);
+        positions
+            .clone()
+            .next_back()
+            .filter(|_| positions.count() % 2 == 1)
+    });
+
+    let mut markup = Markup {
+        text: String::with_capacity(src.len()),
+        runs: Vec::new(),
+    };
+    let mut style = Style::default();
+    for (i, token) in tokens.into_iter().enumerate() {
+        let text = match token {
+            Token::Marker(Marker::Bold) if literal.contains(&Some(i)) => "**",
+            Token::Marker(Marker::Italic) if literal.contains(&Some(i)) => "*",
+            Token::Marker(Marker::Bold) => {
+                style.bold = !style.bold;
+                continue;
+            }
+            Token::Marker(Marker::Italic) => {
+                style.italic = !style.italic;
+                continue;
+            }
+            Token::Text(text) => text,
+        };
+
+        let start = markup.text.len();
+        markup.text.push_str(text);
+        if style == Style::default() {
+            continue;
+        }
+        match markup.runs.last_mut() {
+            Some((run, s)) if run.end == start && *s == style => run.end = markup.text.len(),
+            _ => markup.runs.push((start..markup.text.len(), style)),
+        }
+    }
+    markup
+}
@@ -0,0 +1,76 @@
+//! Parses the mnemonics (access keys) of button labels.
+//!
+//! In a label, `&` marks the following letter or digit as the mnemonic, which activates the button
+//! when typed while holding Alt. `&&` is a literal ampersand, and an `&` that isn't followed by a
+//! letter or digit is displayed as-is.
+
+/// A button label with the mnemonic marker removed.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Label {
+    pub(crate) text: String,
+    /// Byte index of the mnemonic character in `text`.
+    pub(crate) mnemonic: Option<usize>,
+}
+
+impl Label {
+    /// Returns the mnemonic character, in lowercase.
+    pub(crate) fn key(&self) -> Option<char> {
+        self.text[self.mnemonic?..].chars().next().map(fold)
+    }
+
+    /// Makes the first occurrence of `key` in the label its mnemonic, if it doesn't have one yet.
+    pub(crate) fn set_key(&mut self, key: char) {
+        if self.mnemonic.is_none() {
+            self.mnemonic = self
+                .text
+                .char_indices()
+                .find(|(_, c)| fold(*c) == key)
+                .map(|(i, _)| i);
+        }
+    }
+}
+
+pub(crate) fn parse(src: &str) -> Label {
+    let mut label = Label {
+        text: String::with_capacity(src.len()),
+        mnemonic: None,
+    };
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '&' {
+            match chars.peek() {
+                Some('&') => {
+                    chars.next();
+                }
+                // Only the first marker counts, but all of them are removed.
+                Some(next) if next.is_alphanumeric() => {
+                    label.mnemonic.get_or_insert(label.text.len());
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        label.text.push(c);
+    }
+    label
+}
+
+/// Gives every label without a mnemonic the first letter or digit that no other label uses.
+pub(crate) fn assign(labels: &mut [Label]) {
+    let mut used = labels.iter().filter_map(Label::key).collect::<Vec<_>>();
+    for label in labels.iter_mut().filter(|label| label.mnemonic.is_none()) {
+        let free = label
+            .text
+            .char_indices()
+            .find(|(_, c)| c.is_alphanumeric() && !used.contains(&fold(*c)));
+        if let Some((i, c)) = free {
+            label.mnemonic = Some(i);
+            used.push(fold(c));
+        }
+    }
+}
+
+/// Folds `c` to lowercase, so that mnemonics match regardless of Shift and Caps Lock.
+pub(crate) fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
@@ -1,58 +1,376 @@
-use std::{ops::Deref, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    env, fs,
+    ops::Deref,
+    os::fd::AsFd as _,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
-use raqote::DrawTarget;
+use log::{debug, warn};
+use raqote::{DrawTarget, IntRect};
+use rustix::{
+    event::{PollFd, PollFlags, Timespec, poll},
+    io::Errno,
+};
 use x11rb::{
-    connection::Connection as _,
-    properties::WmSizeHints,
+    connection::{Connection as _, RequestConnection as _},
+    errors::{ConnectError, ConnectionError, ReplyError},
+    properties::{WmHints, WmSizeHints, WmSizeHintsSpecification},
     protocol::{
         Event,
+        randr::ConnectionExt as _,
+        shape::{self, ConnectionExt as _},
+        shm::ConnectionExt as _,
         xproto::{
-            self, AtomEnum, ClientMessageEvent, ConfigureWindowAux, ConnectionExt as _,
-            CreateWindowAux, EventMask, ImageFormat, KeyButMask, PropMode, StackMode, VisualClass,
-            WindowClass,
+            self, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ClipOrdering,
+            ConfigureWindowAux, ConnectionExt as _, CreateWindowAux, EventMask, Gravity,
+            ImageFormat, InputFocus, KeyButMask, MapState, PropMode, Rectangle,
+            SelectionNotifyEvent, SelectionRequestEvent, StackMode, VisualClass, WindowClass,
         },
     },
     rust_connection::RustConnection,
     wrapper::ConnectionExt as _,
 };
 
-use crate::{CursorPos, Error, MouseButton, WindowEvent, error::err};
+use crate::{
+    CursorPos, Error, ErrorKind, Keysym, Modifiers, MouseButton, SelectionRequest, WindowEvent,
+    WindowType,
+    error::err,
+    ui::{CursorShape, Selection},
+};
+
+mod shm;
 
 x11rb::atom_manager! {
     pub Atoms: AtomCookie {
         UTF8_STRING,
+        CLIPBOARD,
+        CLIPBOARD_MANAGER,
+        SAVE_TARGETS,
+        TARGETS,
+        _ALERTA_SELECTION,
+        _ALERTA_WAKEUP,
+        _ALERTA_TIMESTAMP,
 
         WM_PROTOCOLS,
         WM_DELETE_WINDOW,
 
+        WM_WINDOW_ROLE,
+
         _NET_WM_NAME,
+        _NET_WM_ICON,
+        _NET_WM_PID,
+        _NET_WM_WINDOW_OPACITY,
         _NET_WM_WINDOW_TYPE,
         _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_NORMAL,
+        _NET_WM_WINDOW_TYPE_UTILITY,
+        _NET_WM_WINDOW_TYPE_NOTIFICATION,
 
         _NET_WM_MOVERESIZE,
-    }
-}
+        _NET_ACTIVE_WINDOW,
+
+        _NET_WM_STATE,
+        _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_MODAL,
+        _NET_WM_STATE_DEMANDS_ATTENTION,
 
-enum WindowType {
-    Dialog,
+        _MOTIF_WM_HINTS,
+    }
 }
 
 #[derive(Clone)]
 pub(crate) struct Connection {
-    inner: Rc<RustConnection>,
+    inner: Arc<RustConnection>,
     screen: usize,
+    /// Whether images can be uploaded through shared memory (see [`shm::is_supported`]).
+    shm: bool,
+    /// Whether the X server supports the Shape extension, for windows with rounded corners.
+    pub(crate) shape: bool,
+    /// The contents of the `RESOURCE_MANAGER` property (the X resource database) when the
+    /// connection was established.
+    resources: Option<Arc<str>>,
+    /// How often the X server was waited for, see [`Connection::round_trip`].
+    round_trips: Arc<AtomicUsize>,
 }
 
 impl Connection {
-    pub(crate) fn connect() -> Result<Self, Error> {
-        let (conn, screen) = x11rb::connect(None).map_err(err)?;
-        Ok(Self {
-            inner: Rc::new(conn),
+    /// Connects to the X server `display`, or the one in the `DISPLAY` environment variable.
+    ///
+    /// In Wayland sessions without `DISPLAY`, this looks for a local XWayland server instead.
+    pub(crate) fn connect(display: Option<&str>) -> Result<Self, Error> {
+        let display = display
+            .map(str::to_string)
+            .or_else(|| env::var("DISPLAY").ok().filter(|name| !name.is_empty()));
+        let wayland = env::var_os("WAYLAND_DISPLAY").is_some_and(|name| !name.is_empty());
+        let name = match display {
+            Some(name) => name,
+            // XWayland may be running even if `DISPLAY` wasn't passed on to us.
+            None if wayland => local_display().ok_or_else(|| {
+                Error::with_kind(
+                    ErrorKind::NoDisplay,
+                    "no X server found: this is a Wayland session without XWayland, which is \
+                    needed for displaying dialogs",
+                )
+            })?,
+            None => {
+                return Err(Error::with_kind(
+                    ErrorKind::NoDisplay,
+                    "no X server found: the DISPLAY environment variable is not set",
+                ));
+            }
+        };
+        let (conn, screen) = x11rb::connect(Some(&name)).map_err(|e| {
+            let kind = match e {
+                ConnectError::DisplayParsingError(_) => ErrorKind::NoDisplay,
+                _ => ErrorKind::ConnectionFailed,
+            };
+            Error::with_kind(
+                kind,
+                format!("failed to connect to X server at '{name}': {e}"),
+            )
+        })?;
+        let mut conn = Self {
+            inner: Arc::new(conn),
             screen,
+            shm: false,
+            shape: false,
+            resources: None,
+            round_trips: Arc::default(),
+        };
+
+        // Everything that is needed from the server is requested at once, so that the replies
+        // arrive together.
+        conn.prefetch_extension_information(x11rb::protocol::shm::X11_EXTENSION_NAME)
+            .map_err(err)?;
+        conn.prefetch_extension_information(shape::X11_EXTENSION_NAME)
+            .map_err(err)?;
+        conn.prefetch_maximum_request_bytes();
+        let root = conn.setup().roots[screen].root;
+        let resources = conn.get_property(
+            false,
+            root,
+            AtomEnum::RESOURCE_MANAGER,
+            AtomEnum::STRING,
+            0,
+            u32::MAX,
+        );
+        let (shape, resources) = conn.round_trip(|| {
+            let shape = conn
+                .extension_information(shape::X11_EXTENSION_NAME)
+                .is_ok_and(|info| info.is_some());
+            let resources = resources.ok().and_then(|cookie| cookie.reply().ok());
+            (shape, resources)
+        });
+        conn.shape = shape;
+        debug!("window shapes supported: {shape}");
+        conn.resources = resources.map(|reply| String::from_utf8_lossy(&reply.value).into());
+        conn.shm = shm::is_supported(&conn);
+        debug!("shared memory uploads supported: {}", conn.shm);
+        Ok(conn)
+    }
+
+    /// Runs `wait`, which waits for the X server to answer one or more requests, and counts it as
+    /// a round-trip.
+    ///
+    /// Requests whose replies are waited for together should all be sent before `wait` is called,
+    /// so that the server answers them at once.
+    pub(crate) fn round_trip<T>(&self, wait: impl FnOnce() -> T) -> T {
+        self.round_trips.fetch_add(1, Ordering::Relaxed);
+        wait()
+    }
+
+    /// Returns how often this connection (or one of its clones) waited for the X server.
+    pub(crate) fn round_trips(&self) -> usize {
+        self.round_trips.load(Ordering::Relaxed)
+    }
+
+    /// Returns the scale factor configured for the screen, based on the `Xft.dpi` resource.
+    ///
+    /// This is the setting that GTK and Qt use on X11, and is how desktop environments expose their
+    /// scaling setting. Returns `None` if it isn't set.
+    pub(crate) fn scale_factor(&self) -> Option<f32> {
+        let dpi = xft_dpi(self.resources.as_deref()?)?;
+        Some(dpi / 96.0)
+    }
+
+    /// Returns the maximum time between the clicks of a double-click, in milliseconds.
+    ///
+    /// This is read from the `*doubleClickTime` resource, and defaults to 400 ms.
+    fn double_click_time(&self) -> u32 {
+        self.resources
+            .as_deref()
+            .and_then(double_click_time)
+            .unwrap_or(400)
+    }
+
+    /// Returns the monitors of the screen, as `(x, y, width, height)` rectangles in root window
+    /// coordinates.
+    ///
+    /// Without RandR, the whole screen counts as a single monitor.
+    fn monitors(&self) -> Vec<(i32, i32, i32, i32)> {
+        let screen = &self.setup().roots[self.screen];
+        let whole = (
+            0,
+            0,
+            screen.width_in_pixels.into(),
+            screen.height_in_pixels.into(),
+        );
+        let Ok(resources) = self.round_trip(|| {
+            self.randr_get_screen_resources_current(screen.root)?
+                .reply()
+        }) else {
+            return vec![whole];
+        };
+        let cookies: Vec<_> = resources
+            .crtcs
+            .iter()
+            .filter_map(|&crtc| {
+                self.randr_get_crtc_info(crtc, resources.config_timestamp)
+                    .ok()
+            })
+            .collect();
+        let monitors: Vec<_> = self.round_trip(|| {
+            cookies
+                .into_iter()
+                .filter_map(|cookie| cookie.reply().ok())
+                // Disabled CRTCs have no size.
+                .filter(|info| info.width > 0 && info.height > 0)
+                .map(|info| {
+                    (
+                        info.x.into(),
+                        info.y.into(),
+                        info.width.into(),
+                        info.height.into(),
+                    )
+                })
+                .collect()
+        });
+        if monitors.is_empty() {
+            vec![whole]
+        } else {
+            monitors
+        }
+    }
+}
+
+/// Returns the name of the local X server with the lowest display number, found by its socket.
+fn local_display() -> Option<String> {
+    fs::read_dir("/tmp/.X11-unix")
+        .ok()?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?.strip_prefix('X')?.parse::<u32>().ok()
         })
+        .min()
+        .map(|n| format!(":{n}"))
+}
+
+/// Moves a window of the given size at `pos` so that it is completely on a monitor, if possible.
+///
+/// The window stays on the monitor that contains its center. If no monitor does (for example
+/// because the one it was on has been disconnected), it is moved onto the first one.
+pub(crate) fn clamp_to_monitors(
+    monitors: &[(i32, i32, i32, i32)],
+    (x, y): (i32, i32),
+    (width, height): (i32, i32),
+) -> (i32, i32) {
+    let (cx, cy) = (x + width / 2, y + height / 2);
+    let Some(&(left, top, w, h)) = monitors
+        .iter()
+        .find(|(left, top, w, h)| (*left..left + w).contains(&cx) && (*top..top + h).contains(&cy))
+        .or(monitors.first())
+    else {
+        return (x, y);
+    };
+    // Windows larger than the monitor are aligned with its top-left corner.
+    (
+        x.min(left + w - width).max(left),
+        y.min(top + h - height).max(top),
+    )
+}
+
+/// Returns the scale factor suited to the pixel density of a monitor, from its size in pixels and
+/// in millimeters.
+///
+/// Like [`Connection::scale_factor`], 96 DPI corresponds to a factor of 1. The factor is rounded
+/// to a multiple of 0.5, and is at least 1. Returns `None` if the physical size is unknown, or too
+/// small to be plausible (like that of projectors, which often report a tiny one).
+pub(crate) fn monitor_scale(size_px: (u32, u32), size_mm: (u32, u32)) -> Option<f32> {
+    let mm = size_mm.0.max(size_mm.1);
+    if mm < 100 {
+        return None;
+    }
+    // The longer sides are compared, which works for rotated monitors too.
+    let dpi = size_px.0.max(size_px.1) as f32 / (mm as f32 / 25.4);
+    Some(((dpi / 96.0 * 2.0).round() / 2.0).max(1.0))
+}
+
+/// A monitor as an `(x, y, width, height)` rectangle in root window coordinates, and its scale
+/// factor if known.
+type MonitorScale = ((i32, i32, i32, i32), Option<f32>);
+
+/// Tracks the monitor that the window is on, to adapt the scale factor to its pixel density.
+pub(crate) struct MonitorScales {
+    monitors: Vec<MonitorScale>,
+    /// The scale factor of the monitor that the window was first on.
+    initial: Option<Option<f32>>,
+}
+
+impl MonitorScales {
+    pub(crate) fn new(monitors: Vec<MonitorScale>) -> Self {
+        Self {
+            monitors,
+            initial: None,
+        }
+    }
+
+    /// Returns the scale factor of the monitor at `(x, y)`, relative to that of the monitor at the
+    /// first point this was called with.
+    ///
+    /// Returns `None` if the scale factor of either monitor is unknown, in which case the current
+    /// scale factor should be kept.
+    pub(crate) fn relative_scale(&mut self, (x, y): (i32, i32)) -> Option<f32> {
+        let scale = self
+            .monitors
+            .iter()
+            .find(|((left, top, w, h), _)| {
+                (*left..left + w).contains(&x) && (*top..top + h).contains(&y)
+            })
+            .and_then(|(_, scale)| *scale);
+        let initial = (*self.initial.get_or_insert(scale))?;
+        Some(scale? / initial)
     }
 }
 
+/// Extracts the value of `Xft.dpi` from the contents of the `RESOURCE_MANAGER` property.
+pub(crate) fn xft_dpi(resources: &str) -> Option<f32> {
+    resources.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim() != "Xft.dpi" {
+            return None;
+        }
+        value.trim().parse::<f32>().ok().filter(|dpi| *dpi > 0.0)
+    })
+}
+
+/// Extracts the value of `*doubleClickTime` from the contents of the `RESOURCE_MANAGER` property.
+pub(crate) fn double_click_time(resources: &str) -> Option<u32> {
+    resources.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        let name = name.trim();
+        if name != "*doubleClickTime" && !name.ends_with(".doubleClickTime") {
+            return None;
+        }
+        value.trim().parse::<u32>().ok().filter(|ms| *ms > 0)
+    })
+}
+
 impl Deref for Connection {
     type Target = RustConnection;
 
@@ -61,23 +379,209 @@ impl Deref for Connection {
     }
 }
 
+/// Window setup settings.
+///
+/// The [`Default`] value lets the window manager decide everything.
+#[derive(Default)]
+pub(crate) struct WindowOptions {
+    pub(crate) position: Position,
+    /// Ask the window manager to keep the window above other windows.
+    pub(crate) always_on_top: bool,
+    /// The window this one is a transient (eg. a dialog) for. Ignored if 0.
+    pub(crate) transient_for: xproto::Window,
+    /// Ask the window manager to make the window modal for its parent.
+    pub(crate) modal: bool,
+    /// Ask the window manager and taskbars to draw attention to the window until it is focused.
+    pub(crate) urgent: bool,
+    /// Allow the window to be resized, down to the given minimum size.
+    pub(crate) resizable: Option<(u16, u16)>,
+    /// The icon for the taskbar and window switcher, in one or more sizes.
+    pub(crate) icons: Vec<DrawTarget>,
+    /// Let the window manager draw a title bar and border around the window.
+    pub(crate) decorated: bool,
+    /// The instance and class name of the window, instead of [`WM_CLASS`].
+    pub(crate) class: Option<(String, String)>,
+    /// The `WM_WINDOW_ROLE` of the window, which tells it apart from other windows of the same
+    /// class.
+    pub(crate) role: Option<String>,
+    pub(crate) window_type: WindowType,
+    /// Ask the window manager to give the window the keyboard focus when it is shown.
+    pub(crate) grab_focus: bool,
+    /// Opacity of the window between 0 and 1, applied by compositing managers. `None` means
+    /// opaque.
+    pub(crate) opacity: Option<f32>,
+}
+
+/// Initial placement of the window.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) enum Position {
+    /// Let the window manager place the window.
+    #[default]
+    Auto,
+    /// Place the window at the given horizontal and vertical offsets from the screen edges.
+    At(Offset, Offset),
+    /// Center the window on the screen.
+    Centered,
+    /// Place the top-left corner of the window contents (without the frame drawn by the window
+    /// manager) at the given root window coordinates, moved onto a monitor if it would be off
+    /// screen.
+    Restored(i32, i32),
+}
+
+/// Position of a window along one axis of the screen, as in X11 geometry specifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Offset {
+    /// Distance of the left or top edge of the window from that of the screen.
+    Start(i32),
+    /// Distance of the right or bottom edge of the window from that of the screen.
+    End(i32),
+}
+
+impl Offset {
+    /// Returns the coordinate of the left or top edge of a window that is `size` pixels long
+    /// along this axis, on a screen that is `screen` pixels long.
+    fn resolve(self, screen: u16, size: u16) -> i32 {
+        match self {
+            Offset::Start(offset) => offset,
+            Offset::End(offset) => i32::from(screen) - i32::from(size) - offset,
+        }
+    }
+}
+
 const MOVERESIZE_MOVE: u32 = 8;
 
-const KEYCODE_ESC: u8 = 9;
+const NET_WM_STATE_ADD: u32 = 1;
+/// Source indication for requests from normal applications.
+const SOURCE_APPLICATION: u32 = 1;
+
+/// The `decorations` field of `_MOTIF_WM_HINTS` is set.
+const MWM_HINTS_DECORATIONS: u32 = 1 << 1;
 
 const WM_CLASS: &[u8] = b"alerta\0alerta\0"; // instance, class
 
 pub(crate) struct X11Window {
     atoms: Atoms,
     conn: Connection,
+    root: xproto::Window,
     window: xproto::Window,
     gc: xproto::Gcontext,
+    /// The `_NET_WM_STATE` atoms requested for this window.
+    states: Vec<xproto::Atom>,
+    /// Whether to request the keyboard focus in [`X11Window::show`].
+    grab_focus: bool,
+    /// Modifier state reported by the most recent input event.
+    modifiers: Cell<KeyButMask>,
+    keymap: Keymap,
+    /// The text we offer as the `CLIPBOARD` selection, while we own it.
+    clipboard: RefCell<Option<String>>,
+    clicks: Cell<Clicks>,
+    /// Events to return before reading the next one from the connection.
+    pending: RefCell<VecDeque<WindowEvent>>,
+    /// Whether to upload the contents through shared memory. This is turned off if it fails.
+    use_shm: Cell<bool>,
+    /// The shared memory for uploading the contents, once allocated.
+    shm: RefCell<Option<shm::Segment>>,
+    /// The cursor currently displayed over the window.
+    cursor_shape: Cell<CursorShape>,
+    /// The pointing hand cursor, once created.
+    pointer_cursor: Cell<Option<xproto::Cursor>>,
+    /// The window that had the keyboard focus when this one was created.
+    previous_focus: xproto::Window,
+    /// Whether the window manager reparented the window into a frame, so that the position in
+    /// real `ConfigureNotify` events is relative to that frame.
+    reparented: Cell<bool>,
 }
 
-impl X11Window {
-    pub(crate) fn create(conn: Connection, width: u16, height: u16) -> Result<Self, Error> {
-        let atoms = Atoms::new(&conn.inner).map_err(err)?.reply().map_err(err)?;
+/// Glyph of the pointing hand (`hand2`) in the X cursor font. The glyph after it is its mask.
+const XC_HAND2: u16 = 60;
+
+/// Maximum distance in pixels between the clicks of a double-click.
+const DOUBLE_CLICK_DISTANCE: i16 = 4;
+
+/// Detects double-clicks from the timing and position of mouse button presses.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Clicks {
+    /// Maximum time between the presses of a double-click, in milliseconds.
+    interval: u32,
+    /// Button, time and position of the press that may start a double-click.
+    last: Option<(xproto::Button, xproto::Timestamp, i16, i16)>,
+}
+
+impl Clicks {
+    pub(crate) fn new(interval: u32) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Records a button press, and returns whether it completes a double-click.
+    pub(crate) fn press(&mut self, e: &xproto::ButtonPressEvent) -> bool {
+        let double = self.last.is_some_and(|(button, time, x, y)| {
+            button == e.detail
+                && e.time.wrapping_sub(time) <= self.interval
+                && (e.event_x - x).abs() <= DOUBLE_CLICK_DISTANCE
+                && (e.event_y - y).abs() <= DOUBLE_CLICK_DISTANCE
+        });
+        // The press completing a double-click doesn't start another one.
+        self.last = (!double).then_some((e.detail, e.time, e.event_x, e.event_y));
+        double
+    }
+}
+
+/// The keycode-to-keysym mapping of the X server.
+struct Keymap {
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: Vec<u32>,
+}
 
+impl Keymap {
+    /// Requests the mapping, and returns a function that waits for it.
+    fn request(
+        conn: &RustConnection,
+    ) -> Result<impl FnOnce() -> Result<Self, ReplyError> + '_, ConnectionError> {
+        let setup = conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let cookie = conn.get_keyboard_mapping(min, max - min + 1)?;
+        Ok(move || {
+            let reply = cookie.reply()?;
+            Ok(Self {
+                min_keycode: min,
+                keysyms_per_keycode: reply.keysyms_per_keycode,
+                keysyms: reply.keysyms,
+            })
+        })
+    }
+
+    /// Looks up the keysym produced by `keycode` when the modifiers in `state` are held.
+    fn lookup(&self, keycode: u8, state: KeyButMask) -> Keysym {
+        let per = usize::from(self.keysyms_per_keycode);
+        let start = usize::from(keycode.wrapping_sub(self.min_keycode)) * per;
+        let syms = self.keysyms.get(start..start + per).unwrap_or_default();
+        let unshifted = syms.first().copied().unwrap_or(0);
+        let shifted = syms.get(1).copied().unwrap_or(0);
+
+        // Caps Lock only affects letters, and is inverted by Shift.
+        let mut use_shifted = state.contains(KeyButMask::SHIFT);
+        if matches!(unshifted, 0x61..=0x7a | 0xe0..=0xfe) {
+            use_shifted ^= state.contains(KeyButMask::LOCK);
+        }
+        if use_shifted && shifted != 0 {
+            Keysym(shifted)
+        } else {
+            Keysym(unshifted)
+        }
+    }
+}
+
+impl X11Window {
+    pub(crate) fn create(
+        conn: Connection,
+        width: u16,
+        height: u16,
+        options: &WindowOptions,
+    ) -> Result<Self, Error> {
         let screen = conn.inner.setup().roots.get(conn.screen).ok_or_else(|| {
             Error::new(format!("screen '{}' does not exist on server", conn.screen))
         })?;
@@ -120,23 +624,59 @@ impl X11Window {
             .border_pixel(0)
             .colormap(0);
 
+        let position = match options.position {
+            Position::Auto => None,
+            Position::At(x, y) => Some((
+                x.resolve(screen.width_in_pixels, width),
+                y.resolve(screen.height_in_pixels, height),
+            )),
+            Position::Centered => Some((
+                (i32::from(screen.width_in_pixels) - i32::from(width)) / 2,
+                (i32::from(screen.height_in_pixels) - i32::from(height)) / 2,
+            )),
+            Position::Restored(x, y) => Some(clamp_to_monitors(
+                &conn.monitors(),
+                (x, y),
+                (width.into(), height.into()),
+            )),
+        };
+        // Positions are 16-bit in the X11 protocol, so ones outside of that range are moved to its
+        // edge instead of wrapping around to the other side of the screen.
+        let position = position.map(|(x, y)| {
+            let clamp = |c: i32| c.clamp(i16::MIN.into(), i16::MAX.into());
+            (clamp(x), clamp(y))
+        });
+        let (x, y) = position.unwrap_or_default();
+
+        // The replies that are needed for setting up the window are waited for together, and the
+        // other requests are sent without waiting for them to complete. They can only fail if the
+        // window doesn't exist, which is checked along with the replies.
         let window = conn.generate_id().map_err(err)?;
-        conn.create_window(
-            24,
-            window,
-            screen.root,
-            0,
-            0,
-            width,
-            height,
-            0,
-            WindowClass::INPUT_OUTPUT,
-            vid,
-            &attrs,
-        )
-        .map_err(err)?
-        .check()
-        .map_err(err)?;
+        let created = conn
+            .create_window(
+                24,
+                window,
+                screen.root,
+                x as i16,
+                y as i16,
+                width,
+                height,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                vid,
+                &attrs,
+            )
+            .map_err(err)?;
+        let atoms = Atoms::new(&conn.inner).map_err(err)?;
+        let keymap = Keymap::request(&conn).map_err(err)?;
+        // The window isn't visible yet, so the focus is still where the user left it.
+        let focus = conn.get_input_focus().map_err(err)?;
+        let (atoms, keymap, previous_focus) = conn
+            .round_trip(|| -> Result<_, ReplyError> {
+                created.check()?;
+                Ok((atoms.reply()?, keymap()?, focus.reply()?.focus))
+            })
+            .map_err(err)?;
 
         let gc = conn.generate_id().map_err(err)?;
         conn.create_gc(
@@ -158,26 +698,175 @@ impl X11Window {
         )
         .map_err(err)?;
 
-        // Configure size hints to prevent resizing the window.
+        // Configure size hints to prevent resizing the window, unless it is resizable.
+        // The position is marked as user-specified, since window managers tend to ignore
+        // program-specified positions.
+        let (min_size, max_size) = match options.resizable {
+            Some((w, h)) => ((w.into(), h.into()), None),
+            None => (
+                (width.into(), height.into()),
+                Some((width.into(), height.into())),
+            ),
+        };
+        // A restored position is that of the window contents, which static gravity keeps the
+        // window manager from offsetting by the size of its frame. Offsets from the right or the
+        // bottom edge of the screen apply to that edge of the frame, like for other X clients.
+        let win_gravity = match options.position {
+            Position::Restored(..) => Some(Gravity::STATIC),
+            Position::At(Offset::End(_), Offset::End(_)) => Some(Gravity::SOUTH_EAST),
+            Position::At(Offset::End(_), Offset::Start(_)) => Some(Gravity::NORTH_EAST),
+            Position::At(Offset::Start(_), Offset::End(_)) => Some(Gravity::SOUTH_WEST),
+            _ => None,
+        };
         WmSizeHints {
-            max_size: Some((width.into(), height.into())),
-            min_size: Some((width.into(), height.into())),
+            position: position.map(|(x, y)| (WmSizeHintsSpecification::UserSpecified, x, y)),
+            max_size,
+            min_size: Some(min_size),
+            win_gravity,
             ..Default::default()
         }
         .set_normal_hints(&conn.inner, window)
-        .map_err(err)?
-        .check()
         .map_err(err)?;
 
+        let mut states = Vec::new();
+        if options.always_on_top {
+            states.push(atoms._NET_WM_STATE_ABOVE);
+        }
+        if options.modal {
+            states.push(atoms._NET_WM_STATE_MODAL);
+        }
+        if options.urgent {
+            states.push(atoms._NET_WM_STATE_DEMANDS_ATTENTION);
+            // Window managers that predate the EWMH state look at the ICCCM urgency hint.
+            WmHints {
+                input: Some(true),
+                urgent: true,
+                ..WmHints::new()
+            }
+            .set(&conn.inner, window)
+            .map_err(err)?;
+        }
+        // Setting the property before mapping the window is enough for most window managers.
+        // Some of them only react to client messages though, which are sent in `show()`.
+        if !states.is_empty() {
+            conn.change_property32(
+                PropMode::REPLACE,
+                window,
+                atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                &states,
+            )
+            .map_err(err)?;
+        }
+
+        if !options.decorated {
+            // Window managers that don't support the Motif hints just keep the decorations.
+            conn.change_property32(
+                PropMode::REPLACE,
+                window,
+                atoms._MOTIF_WM_HINTS,
+                atoms._MOTIF_WM_HINTS,
+                // flags, functions, decorations, input mode, status
+                &[MWM_HINTS_DECORATIONS, 0, 0, 0, 0],
+            )
+            .map_err(err)?;
+        }
+
+        if !options.icons.is_empty() {
+            conn.change_property32(
+                PropMode::REPLACE,
+                window,
+                atoms._NET_WM_ICON,
+                AtomEnum::CARDINAL,
+                &net_wm_icon(&options.icons),
+            )
+            .map_err(err)?;
+        }
+
+        if options.transient_for != x11rb::NONE {
+            // The window manager will ignore this if the window doesn't exist.
+            conn.change_property32(
+                PropMode::REPLACE,
+                window,
+                AtomEnum::WM_TRANSIENT_FOR,
+                AtomEnum::WINDOW,
+                &[options.transient_for],
+            )
+            .map_err(err)?;
+        }
+
+        // The PID is only meaningful together with the machine the process runs on.
+        let uname = rustix::system::uname();
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_CLIENT_MACHINE,
+            AtomEnum::STRING,
+            uname.nodename().to_bytes(),
+        )
+        .map_err(err)?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            atoms._NET_WM_PID,
+            AtomEnum::CARDINAL,
+            &[std::process::id()],
+        )
+        .map_err(err)?;
+
+        if let Some(role) = &options.role {
+            conn.change_property8(
+                PropMode::REPLACE,
+                window,
+                atoms.WM_WINDOW_ROLE,
+                AtomEnum::STRING,
+                role.as_bytes(),
+            )
+            .map_err(err)?;
+        }
+
+        if let Some(opacity) = options.opacity {
+            conn.change_property32(
+                PropMode::REPLACE,
+                window,
+                atoms._NET_WM_WINDOW_OPACITY,
+                AtomEnum::CARDINAL,
+                &[(f64::from(opacity) * f64::from(u32::MAX)).round() as u32],
+            )
+            .map_err(err)?;
+        }
+
+        let root = screen.root;
+        let clicks = Clicks::new(conn.double_click_time());
+        let use_shm = Cell::new(conn.shm);
+
         let mut win = X11Window {
             atoms,
             conn,
+            root,
             window,
             gc,
+            states,
+            grab_focus: options.grab_focus,
+            modifiers: Cell::new(KeyButMask::default()),
+            keymap,
+            clipboard: RefCell::new(None),
+            clicks: Cell::new(clicks),
+            pending: RefCell::new(VecDeque::new()),
+            use_shm,
+            shm: RefCell::new(None),
+            cursor_shape: Cell::new(CursorShape::Default),
+            pointer_cursor: Cell::new(None),
+            previous_focus,
+            reparented: Cell::new(false),
+        };
+        let class = match &options.class {
+            Some((instance, class)) => format!("{instance}\0{class}\0").into_bytes(),
+            None => WM_CLASS.to_vec(),
         };
         win = win
-            .with_class(WM_CLASS)?
-            .with_window_type(WindowType::Dialog)?;
+            .with_class(&class)?
+            .with_window_type(options.window_type)?;
 
         Ok(win)
     }
@@ -191,8 +880,6 @@ impl X11Window {
                 AtomEnum::STRING,
                 cls,
             )
-            .map_err(err)?
-            .check()
             .map_err(err)?;
         Ok(self)
     }
@@ -200,6 +887,9 @@ impl X11Window {
     fn with_window_type(self, ty: WindowType) -> Result<Self, Error> {
         let atom = match ty {
             WindowType::Dialog => self.atoms._NET_WM_WINDOW_TYPE_DIALOG,
+            WindowType::Normal => self.atoms._NET_WM_WINDOW_TYPE_NORMAL,
+            WindowType::Utility => self.atoms._NET_WM_WINDOW_TYPE_UTILITY,
+            WindowType::Notification => self.atoms._NET_WM_WINDOW_TYPE_NOTIFICATION,
         };
         self.conn
             .change_property32(
@@ -209,8 +899,6 @@ impl X11Window {
                 AtomEnum::ATOM,
                 &[atom],
             )
-            .map_err(err)?
-            .check()
             .map_err(err)?;
         Ok(self)
     }
@@ -228,8 +916,6 @@ impl X11Window {
                 AtomEnum::STRING,
                 title.as_bytes(),
             )
-            .map_err(err)?
-            .check()
             .map_err(err)?;
         self.conn
             .change_property8(
@@ -239,34 +925,179 @@ impl X11Window {
                 self.atoms.UTF8_STRING,
                 title.as_bytes(),
             )
-            .map_err(err)?
-            .check()
             .map_err(err)?;
 
         Ok(self)
     }
 
-    pub(crate) fn set_contents(&self, pixmap: &DrawTarget) -> Result<(), Error> {
+    /// Resizes the window, and updates its size hints to match.
+    ///
+    /// `resizable` is the minimum size of a resizable window, like in [`WindowOptions`]. Otherwise,
+    /// the window is kept from being resized by the user at its new size.
+    pub(crate) fn resize(
+        &self,
+        width: u16,
+        height: u16,
+        resizable: Option<(u16, u16)>,
+    ) -> Result<(), Error> {
+        let mut hints = self
+            .conn
+            .round_trip(|| WmSizeHints::get_normal_hints(&self.conn.inner, self.window)?.reply())
+            .map_err(err)?
+            .unwrap_or_default();
+        (hints.min_size, hints.max_size) = match resizable {
+            Some((w, h)) => (Some((w.into(), h.into())), None),
+            None => (
+                Some((width.into(), height.into())),
+                Some((width.into(), height.into())),
+            ),
+        };
+        hints
+            .set_normal_hints(&self.conn.inner, self.window)
+            .map_err(err)?;
+        self.conn
+            .round_trip(|| {
+                self.conn
+                    .configure_window(
+                        self.window,
+                        &ConfigureWindowAux::new()
+                            .width(u32::from(width))
+                            .height(u32::from(height)),
+                    )?
+                    .check()
+            })
+            .map_err(err)?;
+        Ok(())
+    }
+
+    /// Restricts the window to the union of `rects`, making the rest of it transparent.
+    ///
+    /// Does nothing if the X server doesn't support the Shape extension.
+    pub(crate) fn set_shape(&self, rects: &[IntRect]) -> Result<(), Error> {
+        if !self.conn.shape {
+            return Ok(());
+        }
+        let rects: Vec<_> = rects
+            .iter()
+            .map(|rect| Rectangle {
+                x: rect.min.x as i16,
+                y: rect.min.y as i16,
+                width: rect.width() as u16,
+                height: rect.height() as u16,
+            })
+            .collect();
+        self.conn
+            .shape_rectangles(
+                shape::SO::SET,
+                shape::SK::BOUNDING,
+                ClipOrdering::UNSORTED,
+                self.window,
+                0,
+                0,
+                &rects,
+            )
+            .map_err(err)?;
+        Ok(())
+    }
+
+    /// Copies the area `rect` of `pixmap` to the same position in the window.
+    pub(crate) fn set_contents_region(
+        &self,
+        pixmap: &DrawTarget,
+        rect: IntRect,
+    ) -> Result<(), Error> {
+        if self.use_shm.get() {
+            match self.set_contents_shm(pixmap, rect) {
+                Ok(()) => return Ok(()),
+                Err(e) if e.is_disconnect() => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "failed to upload through shared memory, sending the pixels instead: {e}"
+                    );
+                    self.use_shm.set(false);
+                    *self.shm.borrow_mut() = None;
+                }
+            }
+        }
+
+        let stride = pixmap.width() as usize * 4;
+        let columns = rect.min.x as usize * 4..rect.max.x as usize * 4;
+        let data = pixmap
+            .get_data_u8()
+            .chunks_exact(stride)
+            .take(rect.max.y as usize)
+            .skip(rect.min.y as usize)
+            .flat_map(|row| &row[columns.clone()])
+            .copied()
+            .collect::<Vec<_>>();
         self.conn
             .put_image(
                 ImageFormat::Z_PIXMAP,
+                self.window,
+                self.gc,
+                rect.width().try_into().unwrap(),
+                rect.height().try_into().unwrap(),
+                rect.min.x.try_into().unwrap(),
+                rect.min.y.try_into().unwrap(),
+                0,
+                24,
+                &data,
+            )
+            .map_err(err)?;
+        // Unlike with shared memory, the pixels were copied, so there is no need to wait for the
+        // request to complete.
+        self.conn.flush().map_err(err)?;
+        Ok(())
+    }
+
+    /// Like [`X11Window::set_contents_region`], but through shared memory.
+    fn set_contents_shm(&self, pixmap: &DrawTarget, rect: IntRect) -> Result<(), Error> {
+        let data = pixmap.get_data_u8();
+        let mut shm = self.shm.borrow_mut();
+        if shm
+            .as_ref()
+            .is_some_and(|segment| segment.len() < data.len())
+        {
+            // Detach the old segment first, so that the server doesn't hold on to both.
+            *shm = None;
+        }
+        let segment = match &mut *shm {
+            Some(segment) => segment,
+            None => shm.insert(shm::Segment::new(&self.conn, data.len())?),
+        };
+
+        let stride = pixmap.width() as usize * 4;
+        let rows = rect.min.y as usize * stride..rect.max.y as usize * stride;
+        segment.data_mut()[rows.clone()].copy_from_slice(&data[rows]);
+        // Waiting for the request to complete makes sure that the server is done reading the
+        // segment before it is modified again.
+        let cookie = self
+            .conn
+            .shm_put_image(
                 self.window,
                 self.gc,
                 pixmap.width().try_into().unwrap(),
                 pixmap.height().try_into().unwrap(),
-                0,
-                0,
-                0,
+                rect.min.x.try_into().unwrap(),
+                rect.min.y.try_into().unwrap(),
+                rect.width().try_into().unwrap(),
+                rect.height().try_into().unwrap(),
+                rect.min.x.try_into().unwrap(),
+                rect.min.y.try_into().unwrap(),
                 24,
-                pixmap.get_data_u8(),
+                ImageFormat::Z_PIXMAP.into(),
+                false,
+                segment.id(),
+                0,
             )
-            .map_err(err)?
-            .check()
             .map_err(err)?;
+        self.conn.round_trip(|| cookie.check()).map_err(err)?;
         Ok(())
     }
 
     /// Makes the window visible and raises it to the foreground.
+    ///
+    /// If [`WindowOptions::grab_focus`] was set, this also asks the window manager to focus it.
     pub(crate) fn show(&self) -> Result<(), Error> {
         self.conn.map_window(self.window).map_err(err)?;
         self.conn
@@ -275,12 +1106,263 @@ impl X11Window {
                 &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
             )
             .map_err(err)?;
+        for &state in &self.states {
+            self.send_to_root(
+                self.atoms._NET_WM_STATE,
+                [NET_WM_STATE_ADD, state, 0, SOURCE_APPLICATION, 0],
+            )?;
+        }
+        if self.grab_focus {
+            self.request_activation()?;
+        }
+        self.conn.flush().map_err(err)?;
+
+        Ok(())
+    }
+
+    /// Raises the window to the foreground, and asks the window manager to focus it.
+    pub(crate) fn activate(&self) -> Result<(), Error> {
+        self.conn
+            .configure_window(
+                self.window,
+                &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+            )
+            .map_err(err)?;
+        self.request_activation()?;
+        self.conn.flush().map_err(err)
+    }
+
+    fn request_activation(&self) -> Result<(), Error> {
+        // Window managers with focus stealing prevention ignore activation requests with
+        // `CURRENT_TIME`, so this needs the real time of the request.
+        let time = self.server_time()?;
+        self.send_to_root(
+            self.atoms._NET_ACTIVE_WINDOW,
+            [SOURCE_APPLICATION, time, 0, 0, 0],
+        )
+    }
+
+    /// Returns the window that had the keyboard focus when this window was created, unless the
+    /// focus was on no window at all.
+    pub(crate) fn previous_focus(&self) -> Option<xproto::Window> {
+        self.other_window(self.previous_focus)
+    }
+
+    /// Returns `focus`, unless it is no window at all, the root window or this window.
+    fn other_window(&self, focus: xproto::Window) -> Option<xproto::Window> {
+        // 0 and 1 stand for `None` and `PointerRoot`.
+        (focus > 1 && focus != self.root && focus != self.window).then_some(focus)
+    }
+
+    /// Gives the keyboard focus to `window`, if the focus is still on this window (or nowhere) and
+    /// `window` is still visible.
+    pub(crate) fn restore_focus(&self, window: xproto::Window) -> Result<(), Error> {
+        let focus = self.conn.get_input_focus().map_err(err)?;
+        let attributes = self.conn.get_window_attributes(window).map_err(err)?;
+        let (focus, attributes) = self.conn.round_trip(|| (focus.reply(), attributes.reply()));
+        if self.other_window(focus.map_err(err)?.focus).is_some() {
+            return Ok(());
+        }
+        // The window may have been destroyed or unmapped while the dialog was open.
+        if attributes.is_err() || attributes.is_ok_and(|a| a.map_state != MapState::VIEWABLE) {
+            return Ok(());
+        }
+        self.conn
+            .set_input_focus(InputFocus::PARENT, window, x11rb::CURRENT_TIME)
+            .map_err(err)?;
+        self.conn.flush().map_err(err)?;
+        Ok(())
+    }
+
+    /// Returns the X11 resource ID of the window.
+    pub(crate) fn id(&self) -> xproto::Window {
+        self.window
+    }
+
+    /// Returns the position and size of the window, as `(x, y, width, height)` in root window
+    /// coordinates.
+    pub(crate) fn geometry(&self) -> Result<(i32, i32, u32, u32), Error> {
+        let geometry = self.conn.get_geometry(self.window).map_err(err)?;
+        // The window manager may have reparented the window into a frame, so its coordinates are
+        // relative to that.
+        let origin = self
+            .conn
+            .translate_coordinates(self.window, self.root, 0, 0)
+            .map_err(err)?;
+        let (geometry, origin) = self
+            .conn
+            .round_trip(|| Ok::<_, ReplyError>((geometry.reply()?, origin.reply()?)))
+            .map_err(err)?;
+        Ok((
+            origin.dst_x.into(),
+            origin.dst_y.into(),
+            geometry.width.into(),
+            geometry.height.into(),
+        ))
+    }
+
+    /// Returns the name of the RandR output that displays the point `(x, y)` of the root window.
+    ///
+    /// Returns `None` if there is no such output, or if the X server doesn't support RandR 1.3.
+    pub(crate) fn output_at(&self, x: i32, y: i32) -> Option<String> {
+        let resources = self
+            .conn
+            .round_trip(|| {
+                self.conn
+                    .randr_get_screen_resources_current(self.root)?
+                    .reply()
+            })
+            .ok()?;
+        let time = resources.config_timestamp;
+        let cookies: Vec<_> = resources
+            .crtcs
+            .iter()
+            .filter_map(|&crtc| self.conn.randr_get_crtc_info(crtc, time).ok())
+            .collect();
+        let output = self.conn.round_trip(|| {
+            cookies.into_iter().find_map(|cookie| {
+                let crtc = cookie.reply().ok()?;
+                let (left, top) = (i32::from(crtc.x), i32::from(crtc.y));
+                let contains = (left..left + i32::from(crtc.width)).contains(&x)
+                    && (top..top + i32::from(crtc.height)).contains(&y);
+                crtc.outputs.first().copied().filter(|_| contains)
+            })
+        })?;
+        let info = self
+            .conn
+            .round_trip(|| self.conn.randr_get_output_info(output, time)?.reply())
+            .ok()?;
+        Some(String::from_utf8_lossy(&info.name).into_owned())
+    }
+
+    /// Returns the monitors of the screen along with their scale factors, for following the
+    /// window across monitors.
+    ///
+    /// Without RandR 1.3, no monitors are known.
+    pub(crate) fn monitor_scales(&self) -> MonitorScales {
+        let Ok(resources) = self.conn.round_trip(|| {
+            self.conn
+                .randr_get_screen_resources_current(self.root)?
+                .reply()
+        }) else {
+            return MonitorScales::new(Vec::new());
+        };
+        let time = resources.config_timestamp;
+        let cookies: Vec<_> = resources
+            .crtcs
+            .iter()
+            .filter_map(|&crtc| self.conn.randr_get_crtc_info(crtc, time).ok())
+            .collect();
+        let crtcs: Vec<_> = self.conn.round_trip(|| {
+            cookies
+                .into_iter()
+                .filter_map(|cookie| cookie.reply().ok())
+                // Disabled CRTCs have no size.
+                .filter(|crtc| crtc.width > 0 && crtc.height > 0)
+                .collect()
+        });
+        let cookies: Vec<_> = crtcs
+            .iter()
+            .map(|crtc| {
+                let output = crtc.outputs.first()?;
+                self.conn.randr_get_output_info(*output, time).ok()
+            })
+            .collect();
+        let monitors = self.conn.round_trip(|| {
+            crtcs
+                .iter()
+                .zip(cookies)
+                .map(|(crtc, cookie)| {
+                    let rect = (
+                        crtc.x.into(),
+                        crtc.y.into(),
+                        crtc.width.into(),
+                        crtc.height.into(),
+                    );
+                    let scale = cookie
+                        .and_then(|cookie| cookie.reply().ok())
+                        .and_then(|info| {
+                            monitor_scale(
+                                (crtc.width.into(), crtc.height.into()),
+                                (info.mm_width, info.mm_height),
+                            )
+                        });
+                    (rect, scale)
+                })
+                .collect()
+        });
+        MonitorScales::new(monitors)
+    }
+
+    /// Rings the bell of the X server.
+    pub(crate) fn bell(&self) -> Result<(), Error> {
+        self.conn.bell(0).map_err(err)?;
+        self.conn.flush().map_err(err)?;
+        Ok(())
+    }
+
+    /// Returns a [`Waker`] for interrupting [`X11Window::wait_for_event`] from another thread.
+    pub(crate) fn waker(&self) -> Waker {
+        Waker {
+            conn: self.conn.clone(),
+            window: self.window,
+            atom: self.atoms._ALERTA_WAKEUP,
+        }
+    }
+
+    /// Returns the current X server time.
+    ///
+    /// Like ICCCM suggests, this appends nothing to a property of the window and takes the time
+    /// of the resulting `PropertyNotify` event. Other events received in the meantime are kept
+    /// for [`X11Window::wait_for_event`].
+    fn server_time(&self) -> Result<xproto::Timestamp, Error> {
+        self.conn
+            .change_property8(
+                PropMode::APPEND,
+                self.window,
+                self.atoms._ALERTA_TIMESTAMP,
+                AtomEnum::STRING,
+                &[],
+            )
+            .map_err(err)?;
         self.conn.flush().map_err(err)?;
+        self.conn.round_trip(|| {
+            loop {
+                match self.conn.wait_for_event().map_err(err)? {
+                    Event::PropertyNotify(e)
+                        if e.window == self.window && e.atom == self.atoms._ALERTA_TIMESTAMP =>
+                    {
+                        return Ok(e.time);
+                    }
+                    ev => {
+                        let len = self.pending.borrow().len();
+                        if let Some(ev) = self.cvt_event(ev) {
+                            // Goes before a `DoubleClick` that `cvt_event` may have queued.
+                            self.pending.borrow_mut().insert(len, ev);
+                        }
+                    }
+                }
+            }
+        })
+    }
 
+    /// Sends a client message about this window to the root window, for the window manager.
+    fn send_to_root(&self, ty: xproto::Atom, data: [u32; 5]) -> Result<(), Error> {
+        self.conn
+            .send_event(
+                false,
+                self.root,
+                EventMask::SUBSTRUCTURE_NOTIFY | EventMask::SUBSTRUCTURE_REDIRECT,
+                ClientMessageEvent::new(32, self.window, ty, data),
+            )
+            .map_err(err)?;
         Ok(())
     }
 
     pub(crate) fn wait_for_event(&self) -> Result<WindowEvent, Error> {
+        if let Some(ev) = self.pending.borrow_mut().pop_front() {
+            return Ok(ev);
+        }
         loop {
             let ev = self.conn.wait_for_event().map_err(err)?;
             if let Some(ev) = self.cvt_event(ev) {
@@ -289,7 +1371,40 @@ impl X11Window {
         }
     }
 
+    /// Waits for the next event, or until `deadline` has passed.
+    ///
+    /// Returns `Ok(None)` if no event arrived before the deadline.
+    /// A `deadline` of `None` waits forever, like [`X11Window::wait_for_event`].
+    pub(crate) fn wait_for_event_until(
+        &self,
+        deadline: Option<Instant>,
+    ) -> Result<Option<WindowEvent>, Error> {
+        let Some(deadline) = deadline else {
+            return self.wait_for_event().map(Some);
+        };
+        loop {
+            if let Some(ev) = self.poll_for_event()? {
+                return Ok(Some(ev));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            self.conn.flush().map_err(err)?;
+            let timeout = Timespec::try_from(deadline - now).map_err(err)?;
+            let fd = self.conn.stream().as_fd();
+            match poll(&mut [PollFd::new(&fd, PollFlags::IN)], Some(&timeout)) {
+                Ok(_) | Err(Errno::INTR) => {}
+                Err(e) => return Err(err(e)),
+            }
+        }
+    }
+
     pub(crate) fn poll_for_event(&self) -> Result<Option<WindowEvent>, Error> {
+        if let Some(ev) = self.pending.borrow_mut().pop_front() {
+            return Ok(Some(ev));
+        }
         loop {
             match self.conn.poll_for_event().map_err(err)? {
                 Some(ev) => {
@@ -302,22 +1417,64 @@ impl X11Window {
         }
     }
 
+    /// Returns the keyboard modifiers that were held down during the most recent input event.
+    pub(crate) fn modifiers(&self) -> Modifiers {
+        let state = self.modifiers.get();
+        let mut bits = 0;
+        for (mask, bit) in [
+            (KeyButMask::SHIFT, Modifiers::SHIFT),
+            (KeyButMask::CONTROL, Modifiers::CONTROL),
+            (KeyButMask::MOD1, Modifiers::ALT),
+            (KeyButMask::MOD4, Modifiers::LOGO),
+        ] {
+            if state.contains(mask) {
+                bits |= bit;
+            }
+        }
+        Modifiers { bits }
+    }
+
     fn cvt_event(&self, ev: Event) -> Option<WindowEvent> {
+        match &ev {
+            Event::KeyPress(e) | Event::KeyRelease(e) => self.modifiers.set(e.state),
+            Event::ButtonPress(e) | Event::ButtonRelease(e) => self.modifiers.set(e.state),
+            Event::MotionNotify(e) => self.modifiers.set(e.state),
+            _ => {}
+        }
+
         Some(match ev {
+            Event::ClientMessage(msg) if msg.type_ == self.atoms._ALERTA_WAKEUP => {
+                WindowEvent::Wakeup
+            }
             Event::ClientMessage(msg) if msg.data.as_data32()[0] == self.atoms.WM_DELETE_WINDOW => {
                 WindowEvent::CloseRequested
             }
-            Event::KeyPress(press)
-                if press.event == self.window
-                    && press.detail == KEYCODE_ESC
-                    && !press
-                        .state
-                        .intersects(KeyButMask::CONTROL | KeyButMask::SHIFT | KeyButMask::MOD1) =>
-            {
-                // ESC closes the dialog.
-                WindowEvent::CloseRequested
+            Event::KeyPress(press) if press.event == self.window => WindowEvent::KeyPress(
+                self.keymap.lookup(press.detail, press.state),
+                self.modifiers(),
+            ),
+            Event::KeyRelease(release) if release.event == self.window => {
+                WindowEvent::KeyRelease(self.keymap.lookup(release.detail, release.state))
             }
             Event::Expose(ex) if ex.count == 0 => WindowEvent::RedrawRequested,
+            Event::ReparentNotify(e) if e.window == self.window => {
+                self.reparented.set(e.parent != self.root);
+                return None;
+            }
+            Event::ConfigureNotify(e) if e.window == self.window => {
+                // Window managers that reparent send synthetic events with the position on the
+                // screen, like ICCCM requires.
+                if e.response_type & 0x80 != 0 || !self.reparented.get() {
+                    self.pending.borrow_mut().push_back(WindowEvent::Moved {
+                        x: e.x.into(),
+                        y: e.y.into(),
+                    });
+                }
+                WindowEvent::Resized {
+                    width: e.width,
+                    height: e.height,
+                }
+            }
             Event::EnterNotify(e) => WindowEvent::CursorEnter(CursorPos {
                 x: e.event_x,
                 y: e.event_y,
@@ -327,26 +1484,268 @@ impl X11Window {
                 x: e.event_x,
                 y: e.event_y,
             }),
-            Event::ButtonPress(e) => mouse_button(e.detail).map(WindowEvent::ButtonPress)?,
+            Event::ButtonPress(e) => {
+                let ev = button_press(&e)?;
+                if let WindowEvent::ButtonPress(button) = ev {
+                    let mut clicks = self.clicks.get();
+                    if clicks.press(&e) {
+                        self.pending
+                            .borrow_mut()
+                            .push_back(WindowEvent::DoubleClick(button));
+                    }
+                    self.clicks.set(clicks);
+                }
+                ev
+            }
+            Event::SelectionRequest(req) => WindowEvent::SelectionRequest(SelectionRequest(req)),
+            Event::SelectionClear(e) if e.selection == self.atoms.CLIPBOARD => {
+                // Somebody else copied something.
+                self.clipboard.take();
+                return None;
+            }
             Event::ButtonRelease(e) => mouse_button(e.detail).map(WindowEvent::ButtonRelease)?,
+            Event::SelectionNotify(e) if e.selection == self.atoms.CLIPBOARD_MANAGER => {
+                // The clipboard manager has saved the clipboard (see `X11Window::save_clipboard`).
+                self.clipboard.take();
+                return None;
+            }
+            Event::SelectionNotify(e) if e.requestor == self.window => {
+                WindowEvent::Paste(self.read_selection(&e)?)
+            }
             _ => return None,
         })
     }
 
+    /// Places `text` in the clipboard.
+    ///
+    /// The text is served to other clients in response to [`WindowEvent::SelectionRequest`]s, so
+    /// it is only available while the window exists.
+    pub(crate) fn set_clipboard(&self, text: String) -> Result<(), Error> {
+        *self.clipboard.borrow_mut() = Some(text);
+        self.conn
+            .set_selection_owner(self.window, self.atoms.CLIPBOARD, x11rb::CURRENT_TIME)
+            .map_err(err)?;
+        self.conn.flush().map_err(err)?;
+        Ok(())
+    }
+
+    /// Asks the clipboard manager to take over the contents of the clipboard, if we still own it,
+    /// so that they stay available after the window is destroyed.
+    ///
+    /// This follows the freedesktop.org clipboard manager specification. While the manager
+    /// requests the contents, other events are discarded. Gives up after `timeout`, and does
+    /// nothing if no clipboard manager is running.
+    pub(crate) fn save_clipboard(&self, timeout: Duration) -> Result<(), Error> {
+        if self.clipboard.borrow().is_none() {
+            return Ok(());
+        }
+        let owner = self
+            .conn
+            .round_trip(|| {
+                self.conn
+                    .get_selection_owner(self.atoms.CLIPBOARD_MANAGER)?
+                    .reply()
+            })
+            .map_err(err)?
+            .owner;
+        if owner == x11rb::NONE {
+            return Ok(());
+        }
+        self.conn
+            .convert_selection(
+                self.window,
+                self.atoms.CLIPBOARD_MANAGER,
+                self.atoms.SAVE_TARGETS,
+                self.atoms._ALERTA_SELECTION,
+                x11rb::CURRENT_TIME,
+            )
+            .map_err(err)?;
+        self.conn.flush().map_err(err)?;
+
+        let deadline = Instant::now() + timeout;
+        while self.clipboard.borrow().is_some() {
+            match self.wait_for_event_until(Some(deadline))? {
+                Some(WindowEvent::SelectionRequest(SelectionRequest(req))) => {
+                    self.answer_selection_request(&req)?;
+                }
+                Some(_) => {}
+                None => {
+                    debug!("the clipboard manager didn't save the clipboard in time");
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Asks the owner of `selection` for its contents.
+    ///
+    /// The text arrives later, as a [`WindowEvent::Paste`]. If the selection has no owner, or
+    /// doesn't contain text, nothing arrives.
+    pub(crate) fn request_selection(&self, selection: Selection) -> Result<(), Error> {
+        let selection = match selection {
+            Selection::Primary => AtomEnum::PRIMARY.into(),
+            Selection::Clipboard => self.atoms.CLIPBOARD,
+        };
+        self.conn
+            .convert_selection(
+                self.window,
+                selection,
+                self.atoms.UTF8_STRING,
+                self.atoms._ALERTA_SELECTION,
+                x11rb::CURRENT_TIME,
+            )
+            .map_err(err)?;
+        self.conn.flush().map_err(err)?;
+        Ok(())
+    }
+
+    /// Reads the text that the owner of a selection stored on our window.
+    fn read_selection(&self, e: &SelectionNotifyEvent) -> Option<String> {
+        // The property is `NONE` if the selection is empty, or can't be converted to text.
+        // Otherwise, the text was stored in `_ALERTA_SELECTION`.
+        if e.property == x11rb::NONE {
+            return None;
+        }
+        let reply = self
+            .conn
+            .round_trip(|| {
+                self.conn
+                    .get_property(true, self.window, e.property, AtomEnum::ANY, 0, u32::MAX)?
+                    .reply()
+            })
+            .ok()?;
+        // Large selections are transferred incrementally (`INCR`, with format 32), which isn't
+        // supported.
+        (reply.format == 8).then(|| String::from_utf8_lossy(&reply.value).into_owned())
+    }
+
+    /// Responds to another client's request for the contents of the clipboard.
+    pub(crate) fn answer_selection_request(
+        &self,
+        req: &SelectionRequestEvent,
+    ) -> Result<(), Error> {
+        // Obsolete clients don't specify a property, and expect us to use the target atom.
+        let property = if req.property == x11rb::NONE {
+            req.target
+        } else {
+            req.property
+        };
+
+        let clipboard = self.clipboard.borrow();
+        let text = clipboard
+            .as_deref()
+            .filter(|_| req.selection == self.atoms.CLIPBOARD);
+        let stored = match text {
+            Some(_) if req.target == self.atoms.TARGETS => {
+                let targets = [self.atoms.TARGETS, self.atoms.UTF8_STRING];
+                self.conn
+                    .change_property32(
+                        PropMode::REPLACE,
+                        req.requestor,
+                        property,
+                        AtomEnum::ATOM,
+                        &targets,
+                    )
+                    .map_err(err)?;
+                true
+            }
+            Some(text) if req.target == self.atoms.UTF8_STRING => {
+                self.conn
+                    .change_property8(
+                        PropMode::REPLACE,
+                        req.requestor,
+                        property,
+                        self.atoms.UTF8_STRING,
+                        text.as_bytes(),
+                    )
+                    .map_err(err)?;
+                true
+            }
+            _ => false,
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: xproto::SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: req.time,
+            requestor: req.requestor,
+            selection: req.selection,
+            target: req.target,
+            // Signals that the request was refused.
+            property: if stored { property } else { x11rb::NONE },
+        };
+        self.conn
+            .send_event(false, req.requestor, EventMask::NO_EVENT, notify)
+            .map_err(err)?;
+        self.conn.flush().map_err(err)?;
+        Ok(())
+    }
+
+    /// Changes the mouse cursor displayed over the window.
+    ///
+    /// The pointing hand is taken from the X cursor font, which X servers translate to the
+    /// cursor theme if they support one.
+    pub(crate) fn set_cursor(&self, shape: CursorShape) -> Result<(), Error> {
+        if self.cursor_shape.get() == shape {
+            return Ok(());
+        }
+        let cursor = match shape {
+            CursorShape::Default => x11rb::NONE,
+            CursorShape::Pointer => self.pointer_cursor()?,
+        };
+        self.conn
+            .change_window_attributes(
+                self.window,
+                &ChangeWindowAttributesAux::new().cursor(cursor),
+            )
+            .map_err(err)?;
+        self.conn.flush().map_err(err)?;
+        self.cursor_shape.set(shape);
+        Ok(())
+    }
+
+    fn pointer_cursor(&self) -> Result<xproto::Cursor, Error> {
+        if let Some(cursor) = self.pointer_cursor.get() {
+            return Ok(cursor);
+        }
+        let font = self.conn.generate_id().map_err(err)?;
+        self.conn.open_font(font, b"cursor").map_err(err)?;
+        let cursor = self.conn.generate_id().map_err(err)?;
+        self.conn
+            .create_glyph_cursor(
+                cursor,
+                font,
+                font,
+                XC_HAND2,
+                XC_HAND2 + 1,
+                0,
+                0,
+                0,
+                0xffff,
+                0xffff,
+                0xffff,
+            )
+            .map_err(err)?;
+        self.conn.close_font(font).map_err(err)?;
+        self.pointer_cursor.set(Some(cursor));
+        Ok(cursor)
+    }
+
     /// Initiates window dragging.
     pub(crate) fn start_drag(&self) -> Result<(), Error> {
         let pointer = self
             .conn
-            .query_pointer(self.window)
-            .map_err(err)?
-            .reply()
+            .round_trip(|| self.conn.query_pointer(self.window)?.reply())
             .map_err(err)?;
 
         let window_pos = self
             .conn
-            .translate_coordinates(self.window, pointer.root, 0, 0)
-            .map_err(err)?
-            .reply()
+            .round_trip(|| {
+                self.conn
+                    .translate_coordinates(self.window, pointer.root, 0, 0)?
+                    .reply()
+            })
             .map_err(err)?;
 
         let x = (window_pos.dst_x + pointer.win_x) as u32;
@@ -364,14 +1763,75 @@ impl X11Window {
                     [x, y, MOVERESIZE_MOVE, 1, 1],
                 ),
             )
-            .map_err(err)?
-            .check()
             .map_err(err)?;
+        self.conn.flush().map_err(err)?;
 
         Ok(())
     }
 }
 
+/// Wakes up the event loop of an [`X11Window`] by sending a client message to the window.
+pub(crate) struct Waker {
+    conn: Connection,
+    window: xproto::Window,
+    atom: xproto::Atom,
+}
+
+impl Waker {
+    /// Makes the window receive a [`WindowEvent::Wakeup`].
+    pub(crate) fn wake(&self) {
+        let event = ClientMessageEvent::new(32, self.window, self.atom, [0; 5]);
+        self.conn
+            .send_event(false, self.window, EventMask::NO_EVENT, event)
+            .ok();
+        self.conn.flush().ok();
+    }
+}
+
+impl Drop for X11Window {
+    fn drop(&mut self) {
+        // The connection may outlive the window if it is shared (see `Session`).
+        self.conn.free_gc(self.gc).ok();
+        if let Some(cursor) = self.pointer_cursor.get() {
+            self.conn.free_cursor(cursor).ok();
+        }
+        self.conn.destroy_window(self.window).ok();
+        self.conn.flush().ok();
+    }
+}
+
+/// Packs `icons` into the format of the `_NET_WM_ICON` property: the width and height of each
+/// image, followed by its non-premultiplied ARGB pixels.
+pub(crate) fn net_wm_icon(icons: &[DrawTarget]) -> Vec<u32> {
+    let mut data = Vec::new();
+    for icon in icons {
+        data.extend([icon.width() as u32, icon.height() as u32]);
+        data.extend(icon.get_data().iter().map(|&px| {
+            let [a, r, g, b] = px.to_be_bytes().map(u32::from);
+            if a == 0 {
+                return 0;
+            }
+            let [r, g, b] = [r, g, b].map(|c| c * 255 / a);
+            (a << 24) | (r << 16) | (g << 8) | b
+        }));
+    }
+    data
+}
+
+/// Converts a button press into either a mouse button or mouse wheel event.
+pub(crate) fn button_press(e: &xproto::ButtonPressEvent) -> Option<WindowEvent> {
+    // Buttons 4 to 7 are the vertical and horizontal mouse wheels, which report each step as a
+    // press immediately followed by a release.
+    let (delta_x, delta_y) = match e.detail {
+        4 => (0, 1),
+        5 => (0, -1),
+        6 => (-1, 0),
+        7 => (1, 0),
+        _ => return mouse_button(e.detail).map(WindowEvent::ButtonPress),
+    };
+    Some(WindowEvent::Scroll { delta_x, delta_y })
+}
+
 fn mouse_button(detail: u8) -> Option<MouseButton> {
     Some(match detail {
         1 => MouseButton::Left,
@@ -0,0 +1,114 @@
+//! Uploading images through shared memory (the MIT-SHM extension), instead of sending their pixels
+//! over the connection.
+
+use std::{ffi::c_void, ptr, slice};
+
+use rustix::{
+    fs::{self, MemfdFlags},
+    mm::{self, MapFlags, ProtFlags},
+    net::{self, AddressFamily},
+};
+use x11rb::{
+    connection::{Connection as _, RequestConnection as _},
+    protocol::shm::{self, ConnectionExt as _},
+};
+
+use crate::{Error, error::err, x11::Connection};
+
+/// Returns whether images can be uploaded to the server of `conn` through shared memory.
+///
+/// This needs version 1.2 of the extension, which takes the memory as a file descriptor, and a
+/// connection over a Unix socket to pass it over. Such a connection may still lead to a server
+/// that can't access our memory (through a forwarding proxy), so attaching a segment can fail even
+/// if this returns `true`.
+pub(crate) fn is_supported(conn: &Connection) -> bool {
+    let local = net::getsockname(conn.stream())
+        .is_ok_and(|addr| addr.address_family() == AddressFamily::UNIX);
+    local
+        && conn
+            .extension_information(shm::X11_EXTENSION_NAME)
+            .is_ok_and(|info| info.is_some())
+        && conn
+            .round_trip(|| conn.shm_query_version()?.reply())
+            .is_ok_and(|version| (version.major_version, version.minor_version) >= (1, 2))
+}
+
+/// A shared memory segment that is attached to the X server.
+///
+/// It is detached and unmapped when this is dropped.
+pub(crate) struct Segment {
+    conn: Connection,
+    id: shm::Seg,
+    map: Mapping,
+}
+
+impl Segment {
+    /// Allocates a segment of `len` bytes, and attaches it to the X server as read-only.
+    pub(crate) fn new(conn: &Connection, len: usize) -> Result<Self, Error> {
+        let fd = fs::memfd_create("alerta-shm", MemfdFlags::CLOEXEC).map_err(err)?;
+        fs::ftruncate(&fd, len as u64).map_err(err)?;
+        // SAFETY: the new mapping doesn't overlap any memory that is in use.
+        let ptr = unsafe {
+            mm::mmap(
+                ptr::null_mut(),
+                len,
+                ProtFlags::READ | ProtFlags::WRITE,
+                MapFlags::SHARED,
+                &fd,
+                0,
+            )
+        }
+        .map_err(err)?;
+        let map = Mapping { ptr, len };
+
+        let id = conn.generate_id().map_err(err)?;
+        conn.round_trip(|| conn.shm_attach_fd(id, fd, true)?.check())
+            .map_err(err)?;
+        Ok(Self {
+            conn: conn.clone(),
+            id,
+            map,
+        })
+    }
+
+    pub(crate) fn id(&self) -> shm::Seg {
+        self.id
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.map.len
+    }
+
+    /// Returns the memory of the segment.
+    ///
+    /// The X server reads it while it processes a request that uses the segment, so it must not
+    /// be modified until that request has been completed.
+    pub(crate) fn data_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the mapping is `len` bytes long, readable and writable, and only accessed
+        // through `self`. The X server only reads it.
+        unsafe { slice::from_raw_parts_mut(self.map.ptr.cast::<u8>(), self.map.len) }
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        self.conn.shm_detach(self.id).ok();
+        self.conn.flush().ok();
+    }
+}
+
+/// A shared memory mapping, which is unmapped when dropped.
+struct Mapping {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+// SAFETY: the mapping is owned memory, like a `Box<[u8]>`.
+unsafe impl Send for Mapping {}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created with this address and length, and isn't used anymore.
+        unsafe { mm::munmap(self.ptr, self.len) }.ok();
+    }
+}